@@ -0,0 +1,86 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use fundot::evaluator::Evaluator;
+use fundot::object::Object;
+use std::hint::black_box;
+
+fn large_program(n: usize) -> String {
+    let mut s = String::from("(get [");
+    for i in 0..n {
+        if i > 0 {
+            s.push_str(", ");
+        }
+        s.push_str(&format!(
+            "{{id: {}, name: \"item{}\", tags: (a b c)}}",
+            i, i
+        ));
+    }
+    s.push_str(&format!("] {})", n / 2));
+    s
+}
+
+fn string_program(n: usize) -> String {
+    let mut s = String::from("(get [");
+    for i in 0..n {
+        if i > 0 {
+            s.push_str(", ");
+        }
+        s.push_str(&format!(
+            "\"{}\"",
+            "lorem ipsum dolor sit amet ".repeat(i % 8 + 1)
+        ));
+    }
+    s.push_str(&format!("] {})", n / 2));
+    s
+}
+
+fn nested_calls(depth: usize) -> String {
+    let mut s = String::from("[0]");
+    for _ in 0..depth {
+        s = format!("(get [{}] 0)", s);
+    }
+    s
+}
+
+fn bench_eval(c: &mut Criterion) {
+    let evaluator = Evaluator::new();
+    let program = large_program(1000).parse::<Object>().unwrap();
+    c.bench_function("eval large program", |b| {
+        b.iter(|| evaluator.eval(black_box(&program)))
+    });
+    let strings = string_program(1000).parse::<Object>().unwrap();
+    c.bench_function("eval string values", |b| {
+        b.iter(|| evaluator.eval(black_box(&strings)))
+    });
+}
+
+fn bench_calls(c: &mut Criterion) {
+    let evaluator = Evaluator::new();
+    let call = "(get [1, 2, 3] 1)".parse::<Object>().unwrap();
+    c.bench_function("primitive call overhead", |b| {
+        b.iter(|| evaluator.eval(black_box(&call)))
+    });
+    let calls = nested_calls(100).parse::<Object>().unwrap();
+    c.bench_function("eval nested calls", |b| {
+        b.iter(|| evaluator.eval(black_box(&calls)))
+    });
+}
+
+fn bench_recursion(c: &mut Criterion) {
+    let evaluator = Evaluator::new();
+    let definitions = [
+        "(set fib (fn (n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))))",
+        "(set ack (fn (m n) (if (= m 0) (+ n 1) (if (= n 0) (ack (- m 1) 1) (ack (- m 1) (ack m (- n 1)))))))",
+    ];
+    for definition in &definitions {
+        evaluator.eval(&definition.parse::<Object>().unwrap());
+    }
+    let fib = "(fib 15)".parse::<Object>().unwrap();
+    c.bench_function("fib 15", |b| b.iter(|| evaluator.eval(black_box(&fib))));
+    let ack = "(ack 2 3)".parse::<Object>().unwrap();
+    c.bench_function("ackermann 2 3", |b| {
+        b.iter(|| evaluator.eval(black_box(&ack)))
+    });
+}
+
+criterion_group!(benches, bench_eval, bench_calls, bench_recursion);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use fundot::object::Object;
+use std::hint::black_box;
+
+fn flat_atoms(n: usize) -> String {
+    let mut s = String::from("(");
+    for i in 0..n {
+        s.push_str(&format!("sym{} {} {}.5 \"str{}\" ", i % 16, i, i, i));
+    }
+    s.push(')');
+    s
+}
+
+fn large_program(n: usize) -> String {
+    let mut s = String::from("(get [");
+    for i in 0..n {
+        if i > 0 {
+            s.push_str(", ");
+        }
+        s.push_str(&format!(
+            "{{id: {}, name: \"item{}\", tags: (a b c)}}",
+            i, i
+        ));
+    }
+    s.push_str(&format!("] {})", n / 2));
+    s
+}
+
+fn deeply_nested(depth: usize) -> String {
+    "[".repeat(depth) + "0" + &"]".repeat(depth)
+}
+
+fn bench_tokenize(c: &mut Criterion) {
+    let atoms = flat_atoms(1000);
+    c.bench_function("tokenize flat atoms", |b| {
+        b.iter(|| black_box(&atoms).parse::<Object>().unwrap())
+    });
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let program = large_program(1000);
+    c.bench_function("parse large program", |b| {
+        b.iter(|| black_box(&program).parse::<Object>().unwrap())
+    });
+    let nested = deeply_nested(200);
+    c.bench_function("parse deeply nested", |b| {
+        b.iter(|| black_box(&nested).parse::<Object>().unwrap())
+    });
+}
+
+criterion_group!(benches, bench_tokenize, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+#![no_main]
+
+use fundot::object::Object;
+use libfuzzer_sys::fuzz_target;
+
+// Decoding never panics, and whatever decodes encodes to bytes that decode
+// again. Neither the objects, as functions are compared by identity, nor
+// the bytes, as maps have no fixed order, are compared.
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(obj) = Object::from_bytes(bytes) {
+        let encoded = obj.to_bytes().expect("decoded objects encode");
+        Object::from_bytes(&encoded).expect("encoded objects decode");
+    }
+});
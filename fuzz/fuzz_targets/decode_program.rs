@@ -0,0 +1,19 @@
+#![no_main]
+
+use fundot::compiler::CompiledProgram;
+use fundot::evaluator::{Evaluator, Sandbox};
+use libfuzzer_sys::fuzz_target;
+
+// Compiled programs come from caches on disk, so a corrupt one must be
+// rejected by the decoder, or else be one that runs without crashing.
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(program) = CompiledProgram::from_bytes(bytes) {
+        program.to_bytes().expect("decoded programs encode");
+        let evaluator = Evaluator::builder()
+            .modules(&[])
+            .sandbox(Sandbox::pure())
+            .fuel(10_000)
+            .build();
+        let _ = evaluator.execute(&program);
+    }
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+use fundot::object::Object;
+use libfuzzer_sys::fuzz_target;
+
+// Parsing and formatting never panic, and whatever parses prints as text
+// that parses back as the same object.
+fuzz_target!(|source: &str| {
+    Object::is_incomplete(source);
+    let _ = fundot::fmt::format(source);
+    let _ = Object::parse_all(source);
+    if let Ok(obj) = source.parse::<Object>() {
+        let printed = obj.to_string();
+        assert_eq!(printed.parse::<Object>().ok(), Some(obj), "{}", printed);
+    }
+});
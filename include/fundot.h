@@ -0,0 +1,75 @@
+/*
+ * C interface to the Fundot interpreter, available when the crate is built
+ * with the fundot-capi feature:
+ *
+ *     cargo build --release --features fundot-capi
+ *
+ * and linked against target/release/libfundot.so (or .dylib, or .dll).
+ *
+ * Values cross as JSON text. Every pointer the library gives out is freed
+ * with the matching fundot_*_free function, and passing a null pointer
+ * where one is expected is an error rather than undefined behaviour.
+ */
+
+#ifndef FUNDOT_H
+#define FUNDOT_H
+
+#include <stdbool.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Evaluator fundot;
+typedef struct FundotResult fundot_result;
+typedef struct FundotCall fundot_call;
+
+/*
+ * A function registered with fundot_register_fn. It is called with its
+ * arguments as a JSON array and the user_data it was registered with, and
+ * sets its return value with fundot_call_return_json. It may be called
+ * from any thread the evaluator is used on.
+ */
+typedef void (*fundot_fn)(fundot_call *call, const char *args_json, void *user_data);
+
+/* A new evaluator with every builtin. Free it with fundot_free. */
+fundot *fundot_new(void);
+void fundot_free(fundot *fundot);
+
+/*
+ * Evaluates every form in source, a NUL-terminated UTF-8 string, and gives
+ * the last value or the first error. Free the result with
+ * fundot_result_free.
+ */
+fundot_result *fundot_eval_str(const fundot *fundot, const char *source);
+bool fundot_result_is_error(const fundot_result *result);
+
+/* The value as JSON, or NULL for an error or a value with no JSON form. */
+char *fundot_result_to_json(const fundot_result *result);
+
+/* The value as Fundot prints it, or the error message. */
+char *fundot_result_to_string(const fundot_result *result);
+
+void fundot_result_free(fundot_result *result);
+
+/* Frees a string given by fundot_result_to_json or fundot_result_to_string. */
+void fundot_string_free(char *s);
+
+/*
+ * Binds name to a function that calls function. Gives 0, or -1 when a
+ * pointer is null or name is not UTF-8.
+ */
+int fundot_register_fn(const fundot *fundot, const char *name, fundot_fn function,
+                       void *user_data);
+
+/*
+ * Sets what the call returns from JSON text. Invalid JSON, like never
+ * calling this, returns null.
+ */
+void fundot_call_return_json(fundot_call *call, const char *json);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
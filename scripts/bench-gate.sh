@@ -0,0 +1,13 @@
+#!/bin/sh
+# Compare the benchmark suite against a saved baseline and fail on regressions.
+#
+#   git checkout main && cargo bench --bench '*' -- --save-baseline main
+#   git checkout my-branch && scripts/bench-gate.sh main
+set -e
+baseline="${1:-main}"
+output=$(cargo bench --bench '*' -- --baseline "$baseline" 2>&1)
+echo "$output"
+if echo "$output" | grep -q "Performance has regressed"; then
+    echo "benchmark regression against baseline '$baseline'" >&2
+    exit 1
+fi
@@ -0,0 +1,175 @@
+use crate::object::Object;
+use crate::symbol::Symbol;
+#[cfg(feature = "proptest")]
+use proptest::prelude::*;
+#[cfg(feature = "quickcheck")]
+use quickcheck::{Arbitrary, Gen};
+#[cfg(feature = "quickcheck")]
+use std::collections::HashMap;
+
+// Generated objects are data: null, booleans, numbers, including nan and
+// the infinities, strings and symbols with any characters, and lists,
+// vectors and maps of them. Anything generated prints as text that reads
+// back as an equal object.
+#[derive(Clone, Copy, Debug)]
+pub struct ArbitraryLimits {
+    // How deeply collections nest.
+    pub max_depth: u32,
+    // How many items one collection holds at most.
+    pub max_elements: usize,
+    // About how many objects one generated object holds in all.
+    pub max_size: u32,
+}
+
+impl Default for ArbitraryLimits {
+    fn default() -> Self {
+        ArbitraryLimits {
+            max_depth: 4,
+            max_elements: 8,
+            max_size: 64,
+        }
+    }
+}
+
+#[cfg(feature = "proptest")]
+fn text(max_len: usize) -> impl Strategy<Value = String> {
+    prop::collection::vec(any::<char>(), 0..max_len).prop_map(|chars| chars.into_iter().collect())
+}
+
+// Names that need no escaping are the common case in real data, so
+// symbols are mostly those, with the occasional arbitrary one.
+#[cfg(feature = "proptest")]
+pub fn symbol() -> BoxedStrategy<Object> {
+    prop_oneof![
+        3 => "[a-z][a-z0-9?!*-]{0,8}",
+        1 => text(8),
+    ]
+    .prop_map(|name| Object::Symbol(Symbol::new(&name)))
+    .boxed()
+}
+
+#[cfg(feature = "proptest")]
+pub fn atom() -> BoxedStrategy<Object> {
+    prop_oneof![
+        Just(Object::Null),
+        any::<bool>().prop_map(Object::Bool),
+        any::<i64>().prop_map(Object::Integer),
+        prop::num::f64::ANY.prop_map(Object::Float),
+        text(16).prop_map(|s| Object::String(s.into())),
+        symbol(),
+    ]
+    .boxed()
+}
+
+// Objects built from leaf, which gives the atoms, within limits.
+#[cfg(feature = "proptest")]
+pub fn object_from(leaf: BoxedStrategy<Object>, limits: ArbitraryLimits) -> BoxedStrategy<Object> {
+    let elements = 0..=limits.max_elements;
+    leaf.prop_recursive(
+        limits.max_depth,
+        limits.max_size,
+        limits.max_elements as u32,
+        move |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), elements.clone()).prop_map(Object::List),
+                prop::collection::vec(inner.clone(), elements.clone()).prop_map(Object::Vector),
+                prop::collection::hash_map(inner.clone(), inner, elements.clone())
+                    .prop_map(Object::Map),
+            ]
+        },
+    )
+    .boxed()
+}
+
+#[cfg(feature = "proptest")]
+pub fn object(limits: ArbitraryLimits) -> BoxedStrategy<Object> {
+    object_from(atom(), limits)
+}
+
+#[cfg(feature = "proptest")]
+impl proptest::arbitrary::Arbitrary for Object {
+    type Parameters = ArbitraryLimits;
+    type Strategy = BoxedStrategy<Object>;
+
+    fn arbitrary_with(limits: ArbitraryLimits) -> Self::Strategy {
+        object(limits)
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+fn count(g: &mut Gen, max: usize) -> usize {
+    usize::arbitrary(g) % (max.min(g.size()) + 1)
+}
+
+#[cfg(feature = "quickcheck")]
+fn generate_atom(g: &mut Gen) -> Object {
+    match u8::arbitrary(g) % 6 {
+        0 => Object::Null,
+        1 => Object::Bool(bool::arbitrary(g)),
+        2 => Object::Integer(i64::arbitrary(g)),
+        3 => Object::Float(f64::arbitrary(g)),
+        4 => Object::String(String::arbitrary(g).into()),
+        _ => Object::Symbol(Symbol::new(&String::arbitrary(g))),
+    }
+}
+
+// An object within limits, with collections no longer than the size of g.
+#[cfg(feature = "quickcheck")]
+pub fn generate(g: &mut Gen, limits: &ArbitraryLimits) -> Object {
+    let kind = match limits.max_depth {
+        0 => 0,
+        _ => u8::arbitrary(g) % 4,
+    };
+    let inner = ArbitraryLimits {
+        max_depth: limits.max_depth.saturating_sub(1),
+        ..*limits
+    };
+    let len = count(g, limits.max_elements);
+    match kind {
+        1 => Object::List((0..len).map(|_| generate(g, &inner)).collect()),
+        2 => Object::Vector((0..len).map(|_| generate(g, &inner)).collect()),
+        3 => Object::Map(
+            (0..len)
+                .map(|_| (generate(g, &inner), generate(g, &inner)))
+                .collect(),
+        ),
+        _ => generate_atom(g),
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl Arbitrary for Object {
+    fn arbitrary(g: &mut Gen) -> Self {
+        generate(g, &ArbitraryLimits::default())
+    }
+
+    // Collections shrink to each of their items and then to shorter
+    // collections, atoms as quickcheck shrinks the values they hold.
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        match self.without_meta() {
+            Object::Bool(b) => Box::new(b.shrink().map(Object::Bool)),
+            Object::Integer(n) => Box::new(n.shrink().map(Object::Integer)),
+            Object::Float(n) => Box::new(n.shrink().map(Object::Float)),
+            Object::String(s) => Box::new(s.to_string().shrink().map(|s| Object::String(s.into()))),
+            Object::List(list) => {
+                let items = list.clone().into_iter();
+                Box::new(items.chain(list.shrink().map(Object::List)))
+            }
+            Object::Vector(vector) => {
+                let items = vector.clone().into_iter();
+                Box::new(items.chain(vector.shrink().map(Object::Vector)))
+            }
+            Object::Map(map) => {
+                let values: Vec<Object> = map.values().cloned().collect();
+                let shorter = map
+                    .clone()
+                    .into_iter()
+                    .collect::<Vec<_>>()
+                    .shrink()
+                    .map(|pairs| Object::Map(pairs.into_iter().collect::<HashMap<_, _>>()));
+                Box::new(values.into_iter().chain(shorter))
+            }
+            _ => quickcheck::empty_shrinker(),
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use crate::evaluator::{args, EvalError, NativeFunction, Overflow, PrimitiveFunction};
+use crate::interpreter::Interpreter;
+use crate::object::Object;
+#[cfg(feature = "bigint")]
+use crate::object::{big_integer, is_big};
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+#[cfg(feature = "bigint")]
+use num_traits::{ToPrimitive, Zero};
+
+// What + - * / % are under each overflow mode. Only checked can fail, so
+// it is the only one made of natives.
+pub(crate) const WRAPPING: [(&str, PrimitiveFunction); 5] =
+    [("+", add), ("-", sub), ("*", mul), ("/", div), ("%", rem)];
+
+pub(crate) const SATURATING: [(&str, PrimitiveFunction); 5] = [
+    ("+", saturating_add),
+    ("-", saturating_sub),
+    ("*", saturating_mul),
+    ("/", saturating_div),
+    ("%", saturating_rem),
+];
+
+pub(crate) const CHECKED: [(&str, NativeFunction); 5] = [
+    ("+", strict_add),
+    ("-", strict_sub),
+    ("*", strict_mul),
+    ("/", strict_div),
+    ("%", strict_rem),
+];
+
+#[cfg(feature = "bigint")]
+pub(crate) const PROMOTING: [(&str, PrimitiveFunction); 5] = [
+    ("+", promoting_add),
+    ("-", promoting_sub),
+    ("*", promoting_mul),
+    ("/", promoting_div),
+    ("%", promoting_rem),
+];
+
+// The same operations with a chosen mode, whatever the evaluator's is.
+// Checked ones give null on overflow rather than failing.
+pub(crate) const PRIMITIVES: [(&str, PrimitiveFunction); 9] = [
+    ("checked-add", checked_add),
+    ("checked-sub", checked_sub),
+    ("checked-mul", checked_mul),
+    ("wrapping-add", add),
+    ("wrapping-sub", sub),
+    ("wrapping-mul", mul),
+    ("saturating-add", saturating_add),
+    ("saturating-sub", saturating_sub),
+    ("saturating-mul", saturating_mul),
+];
+
+pub(crate) fn is_pure(primitive: PrimitiveFunction) -> bool {
+    let same = |(_, f): &(&str, PrimitiveFunction)| std::ptr::fn_addr_eq(*f, primitive);
+    #[cfg(feature = "bigint")]
+    if PROMOTING.iter().any(same) {
+        return true;
+    }
+    WRAPPING
+        .iter()
+        .chain(&SATURATING)
+        .chain(&PRIMITIVES)
+        .any(same)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl Operator {
+    fn checked(self, x: i64, y: i64) -> Option<i64> {
+        match self {
+            Operator::Add => x.checked_add(y),
+            Operator::Sub => x.checked_sub(y),
+            Operator::Mul => x.checked_mul(y),
+            Operator::Div => x.checked_div(y),
+            Operator::Rem => x.checked_rem(y),
+        }
+    }
+
+    fn wrapping(self, x: i64, y: i64) -> i64 {
+        match self {
+            Operator::Add => x.wrapping_add(y),
+            Operator::Sub => x.wrapping_sub(y),
+            Operator::Mul => x.wrapping_mul(y),
+            Operator::Div => x.wrapping_div(y),
+            Operator::Rem => x.wrapping_rem(y),
+        }
+    }
+
+    // The only remainder that overflows, i64::MIN % -1, is 0.
+    fn saturating(self, x: i64, y: i64) -> i64 {
+        match self {
+            Operator::Add => x.saturating_add(y),
+            Operator::Sub => x.saturating_sub(y),
+            Operator::Mul => x.saturating_mul(y),
+            Operator::Div => x.saturating_div(y),
+            Operator::Rem => 0,
+        }
+    }
+
+    fn float(self, x: f64, y: f64) -> f64 {
+        match self {
+            Operator::Add => x + y,
+            Operator::Sub => x - y,
+            Operator::Mul => x * y,
+            Operator::Div => x / y,
+            Operator::Rem => x % y,
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    fn big(self, x: &BigInt, y: &BigInt) -> BigInt {
+        match self {
+            Operator::Add => x + y,
+            Operator::Sub => x - y,
+            Operator::Mul => x * y,
+            Operator::Div => x / y,
+            Operator::Rem => x % y,
+        }
+    }
+}
+
+// Dividing an integer by zero gives null in every mode.
+fn integer(operator: Operator, x: i64, y: i64, overflow: Overflow) -> Result<Object, EvalError> {
+    if y == 0 && matches!(operator, Operator::Div | Operator::Rem) {
+        return Ok(Object::Null);
+    }
+    if let Some(n) = operator.checked(x, y) {
+        return Ok(Object::Integer(n));
+    }
+    match overflow {
+        Overflow::Wrap => Ok(Object::Integer(operator.wrapping(x, y))),
+        Overflow::Saturate => Ok(Object::Integer(operator.saturating(x, y))),
+        Overflow::Checked => Err(EvalError::Overflow),
+        #[cfg(feature = "bigint")]
+        Overflow::Promote => Ok(Object::from(
+            operator.big(&BigInt::from(x), &BigInt::from(y)),
+        )),
+    }
+}
+
+// Big integers only come from promoting, but once there they are used
+// with any integer in every mode, and with floats as floats.
+#[cfg(feature = "bigint")]
+fn big(operator: Operator, x: &Object, y: &Object) -> Option<Object> {
+    if !is_big(x) && !is_big(y) {
+        return None;
+    }
+    match (big_integer(x), big_integer(y)) {
+        (Some(_), Some(y)) if y.is_zero() && matches!(operator, Operator::Div | Operator::Rem) => {
+            Some(Object::Null)
+        }
+        (Some(x), Some(y)) => Some(Object::from(operator.big(&x, &y))),
+        (Some(x), None) => match y {
+            Object::Float(y) => Some(Object::Float(operator.float(x.to_f64()?, *y))),
+            _ => Some(Object::Null),
+        },
+        (None, Some(y)) => match x {
+            Object::Float(x) => Some(Object::Float(operator.float(*x, y.to_f64()?))),
+            _ => Some(Object::Null),
+        },
+        (None, None) => None,
+    }
+}
+
+fn arithmetic(
+    args: &[Object],
+    operator: Operator,
+    overflow: Overflow,
+) -> Result<Object, EvalError> {
+    let mut iter = args.iter();
+    let mut result = match iter.next() {
+        Some(first) => first.without_meta().clone(),
+        None => return Ok(Object::Null),
+    };
+    for obj in iter {
+        #[cfg(feature = "bigint")]
+        if let Some(value) = big(operator, &result, obj.without_meta()) {
+            result = value;
+            continue;
+        }
+        result = match (&result, obj.without_meta()) {
+            (Object::Integer(x), Object::Integer(y)) => integer(operator, *x, *y, overflow)?,
+            (Object::Integer(x), Object::Float(y)) => Object::Float(operator.float(*x as f64, *y)),
+            (Object::Float(x), Object::Integer(y)) => Object::Float(operator.float(*x, *y as f64)),
+            (Object::Float(x), Object::Float(y)) => Object::Float(operator.float(*x, *y)),
+            _ => return Ok(Object::Null),
+        };
+    }
+    Ok(result)
+}
+
+// + also joins strings when its first argument is one, and - alone
+// negates.
+fn operate(obj: &Object, operator: Operator, overflow: Overflow) -> Result<Object, EvalError> {
+    let args = args(obj);
+    match (operator, args) {
+        (Operator::Add, [first, ..]) if matches!(first.without_meta(), Object::String(_)) => {
+            let mut s = String::new();
+            for obj in args {
+                match obj.without_meta() {
+                    Object::String(string) => s.push_str(string),
+                    other => s.push_str(&other.to_string()),
+                }
+            }
+            Ok(Object::String(s.into()))
+        }
+        (Operator::Sub, [value]) => match value.without_meta() {
+            Object::Float(n) => Ok(Object::Float(-n)),
+            value => arithmetic(&[Object::Integer(0), value.clone()], operator, overflow),
+        },
+        _ => arithmetic(args, operator, overflow),
+    }
+}
+
+macro_rules! primitive {
+    ($name:ident, $operator:ident, $overflow:ident) => {
+        fn $name(obj: &Object) -> Object {
+            operate(obj, Operator::$operator, Overflow::$overflow).unwrap_or(Object::Null)
+        }
+    };
+}
+
+macro_rules! native {
+    ($name:ident, $operator:ident) => {
+        fn $name(_: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+            operate(obj, Operator::$operator, Overflow::Checked)
+        }
+    };
+}
+
+primitive!(add, Add, Wrap);
+primitive!(sub, Sub, Wrap);
+primitive!(mul, Mul, Wrap);
+primitive!(div, Div, Wrap);
+primitive!(rem, Rem, Wrap);
+primitive!(saturating_add, Add, Saturate);
+primitive!(saturating_sub, Sub, Saturate);
+primitive!(saturating_mul, Mul, Saturate);
+primitive!(saturating_div, Div, Saturate);
+primitive!(saturating_rem, Rem, Saturate);
+primitive!(checked_add, Add, Checked);
+primitive!(checked_sub, Sub, Checked);
+primitive!(checked_mul, Mul, Checked);
+native!(strict_add, Add);
+native!(strict_sub, Sub);
+native!(strict_mul, Mul);
+native!(strict_div, Div);
+native!(strict_rem, Rem);
+#[cfg(feature = "bigint")]
+primitive!(promoting_add, Add, Promote);
+#[cfg(feature = "bigint")]
+primitive!(promoting_sub, Sub, Promote);
+#[cfg(feature = "bigint")]
+primitive!(promoting_mul, Mul, Promote);
+#[cfg(feature = "bigint")]
+primitive!(promoting_div, Div, Promote);
+#[cfg(feature = "bigint")]
+primitive!(promoting_rem, Rem, Promote);
@@ -0,0 +1,591 @@
+use crate::compiler::{Chunk, Closure, CompiledProgram, Op, Proto};
+use crate::evaluator::Function;
+use crate::object::{Object, Shared};
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::result::Result;
+
+const MAGIC: &[u8] = b"FDO";
+const VERSION: u8 = 2;
+const MAX_DEPTH: usize = 512;
+
+const PROGRAM_MAGIC: &[u8] = b"FDC";
+const PROGRAM_VERSION: u8 = 3;
+
+const TAG_NULL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_INTEGER: u8 = 3;
+const TAG_FLOAT: u8 = 4;
+const TAG_STRING: u8 = 5;
+const TAG_SYMBOL: u8 = 6;
+const TAG_LIST: u8 = 7;
+const TAG_VECTOR: u8 = 8;
+const TAG_MAP: u8 = 9;
+const TAG_META: u8 = 10;
+const TAG_FUNCTION: u8 = 11;
+
+#[derive(Debug)]
+pub enum BinaryObjectError {
+    Foreign,
+    Magic,
+    Version(u8),
+    Truncated,
+    Tag(u8),
+    Utf8,
+    Depth,
+    Size,
+    Trailing,
+    Bytecode,
+    // A symbol would be new past what data may add, see Symbol::try_new.
+    Symbols,
+}
+
+impl fmt::Display for BinaryObjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for BinaryObjectError {}
+
+fn write_varint(bytes: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        bytes.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+fn write_str(bytes: &mut Vec<u8>, s: &str) {
+    write_varint(bytes, s.len() as u64);
+    bytes.extend_from_slice(s.as_bytes());
+}
+
+// Values nested deeper than the decoder accepts are refused rather than
+// encoded.
+fn encode(obj: &Object, bytes: &mut Vec<u8>, depth: usize) -> Result<(), BinaryObjectError> {
+    if depth > MAX_DEPTH {
+        return Err(BinaryObjectError::Depth);
+    }
+    match obj {
+        Object::Null => bytes.push(TAG_NULL),
+        Object::Bool(false) => bytes.push(TAG_FALSE),
+        Object::Bool(true) => bytes.push(TAG_TRUE),
+        Object::Integer(n) => {
+            bytes.push(TAG_INTEGER);
+            write_varint(bytes, ((n << 1) ^ (n >> 63)) as u64);
+        }
+        Object::Float(n) => {
+            bytes.push(TAG_FLOAT);
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        Object::String(s) => {
+            bytes.push(TAG_STRING);
+            write_str(bytes, s);
+        }
+        Object::Symbol(s) => {
+            bytes.push(TAG_SYMBOL);
+            write_str(bytes, s.as_str());
+        }
+        Object::List(list) => {
+            bytes.push(TAG_LIST);
+            write_varint(bytes, list.len() as u64);
+            for obj in list {
+                encode(obj, bytes, depth + 1)?;
+            }
+        }
+        Object::Vector(vector) => {
+            bytes.push(TAG_VECTOR);
+            write_varint(bytes, vector.len() as u64);
+            for obj in vector {
+                encode(obj, bytes, depth + 1)?;
+            }
+        }
+        Object::Map(map) => {
+            bytes.push(TAG_MAP);
+            write_varint(bytes, map.len() as u64);
+            for (key, value) in map {
+                encode(key, bytes, depth + 1)?;
+                encode(value, bytes, depth + 1)?;
+            }
+        }
+        Object::Other(other) => match obj.meta() {
+            Some(meta) => {
+                bytes.push(TAG_META);
+                encode(meta, bytes, depth + 1)?;
+                encode(obj.without_meta(), bytes, depth + 1)?;
+            }
+            None => match other.downcast_ref::<Function>() {
+                Some(function) => {
+                    bytes.push(TAG_FUNCTION);
+                    encode_proto(&function.proto, bytes, depth + 1)?;
+                    write_varint(bytes, function.captured.len() as u64);
+                    for obj in &function.captured {
+                        encode(obj, bytes, depth + 1)?;
+                    }
+                }
+                None => return Err(BinaryObjectError::Foreign),
+            },
+        },
+    }
+    Ok(())
+}
+
+fn encode_op(op: &Op, bytes: &mut Vec<u8>) {
+    let (opcode, operand) = match *op {
+        Op::Constant(n) => (0, Some(n)),
+        Op::GetLocal(n) => (1, Some(n)),
+        Op::SetLocal(n) => (2, Some(n)),
+        Op::GetGlobal(n) => (3, Some(n)),
+        Op::SetGlobal(n) => (4, Some(n)),
+        Op::Jump(n) => (5, Some(n)),
+        Op::JumpIfFalse(n) => (6, Some(n)),
+        Op::Pop => (7, None),
+        Op::Call(n) => (8, Some(n)),
+        Op::Closure(n) => (9, Some(n)),
+        Op::Return => (10, None),
+        Op::Yield => (11, None),
+        Op::Reset => (12, None),
+        Op::Shift => (13, None),
+        Op::Bind(n) => (14, Some(n)),
+        Op::Dynamic(n) => (15, Some(n)),
+        Op::Time => (16, None),
+    };
+    bytes.push(opcode);
+    if let Some(operand) = operand {
+        write_varint(bytes, u64::from(operand));
+    }
+}
+
+fn encode_proto(proto: &Proto, bytes: &mut Vec<u8>, depth: usize) -> Result<(), BinaryObjectError> {
+    if depth > MAX_DEPTH {
+        return Err(BinaryObjectError::Depth);
+    }
+    write_varint(bytes, proto.params.len() as u64);
+    for param in &proto.params {
+        write_str(bytes, param.as_str());
+    }
+    write_varint(bytes, proto.captures.len() as u64);
+    for (symbol, slot) in &proto.captures {
+        write_str(bytes, symbol.as_str());
+        write_varint(bytes, u64::from(*slot));
+    }
+    write_varint(bytes, proto.body.len() as u64);
+    for obj in &proto.body {
+        encode(obj, bytes, depth + 1)?;
+    }
+    bytes.push(proto.generator as u8);
+    match &proto.chunk {
+        Some(chunk) => {
+            bytes.push(1);
+            write_varint(bytes, u64::from(chunk.locals));
+            write_varint(bytes, chunk.code.len() as u64);
+            for op in &chunk.code {
+                encode_op(op, bytes);
+            }
+            write_varint(bytes, chunk.constants.len() as u64);
+            for obj in &chunk.constants {
+                encode(obj, bytes, depth + 1)?;
+            }
+            write_varint(bytes, chunk.closures.len() as u64);
+            for closure in &chunk.closures {
+                write_varint(bytes, closure.sources.len() as u64);
+                for source in &closure.sources {
+                    write_varint(bytes, u64::from(*source));
+                }
+                encode_proto(&closure.proto, bytes, depth + 1)?;
+            }
+            write_varint(bytes, chunk.callees.len() as u64);
+            for (offset, symbol) in &chunk.callees {
+                write_varint(bytes, u64::from(*offset));
+                write_str(bytes, symbol.as_str());
+            }
+        }
+        None => bytes.push(0),
+    }
+    Ok(())
+}
+
+fn verify_chunk(proto: &Proto, chunk: &Chunk) -> Result<(), BinaryObjectError> {
+    let locals = chunk.locals as usize;
+    let code = &chunk.code;
+    if proto.params.len() > locals
+        || proto
+            .captures
+            .iter()
+            .any(|(_, slot)| *slot as usize >= locals)
+        || chunk.closures.iter().any(|closure| {
+            closure.proto.captures.len() != closure.sources.len()
+                || closure
+                    .sources
+                    .iter()
+                    .any(|source| *source as usize >= locals)
+        })
+    {
+        return Err(BinaryObjectError::Bytecode);
+    }
+    let mut previous = None;
+    for (offset, _) in &chunk.callees {
+        if previous >= Some(*offset) || !matches!(code.get(*offset as usize), Some(Op::Call(_))) {
+            return Err(BinaryObjectError::Bytecode);
+        }
+        previous = Some(*offset);
+    }
+    let mut depths: Vec<Option<usize>> = vec![None; code.len()];
+    let mut pending = vec![(0, 0)];
+    while let Some((ip, depth)) = pending.pop() {
+        let op = code.get(ip).ok_or(BinaryObjectError::Bytecode)?;
+        match depths[ip] {
+            Some(known) if known == depth => continue,
+            Some(_) => return Err(BinaryObjectError::Bytecode),
+            None => depths[ip] = Some(depth),
+        }
+        let (needs, after, jump) = match *op {
+            Op::Constant(index)
+            | Op::GetGlobal(index)
+            | Op::SetGlobal(index)
+            | Op::Bind(index)
+            | Op::Dynamic(index)
+                if index as usize >= chunk.constants.len() =>
+            {
+                return Err(BinaryObjectError::Bytecode)
+            }
+            Op::GetGlobal(index) | Op::SetGlobal(index) | Op::Dynamic(index)
+                if !matches!(chunk.constants[index as usize], Object::Symbol(_)) =>
+            {
+                return Err(BinaryObjectError::Bytecode)
+            }
+            Op::GetLocal(slot) | Op::SetLocal(slot) if slot as usize >= locals => {
+                return Err(BinaryObjectError::Bytecode)
+            }
+            Op::Closure(index) if index as usize >= chunk.closures.len() => {
+                return Err(BinaryObjectError::Bytecode)
+            }
+            Op::Constant(_) | Op::GetLocal(_) | Op::GetGlobal(_) | Op::Closure(_) => {
+                (0, depth + 1, None)
+            }
+            Op::SetLocal(_) | Op::SetGlobal(_) => (1, depth, None),
+            Op::Pop => (1, depth.wrapping_sub(1), None),
+            Op::Jump(to) => {
+                pending.push((to as usize, depth));
+                continue;
+            }
+            Op::JumpIfFalse(to) => (1, depth.wrapping_sub(1), Some(to)),
+            Op::Call(argc) => {
+                let argc = argc as usize;
+                (argc + 1, depth.wrapping_sub(argc), None)
+            }
+            Op::Yield if !proto.generator => return Err(BinaryObjectError::Bytecode),
+            Op::Yield | Op::Reset | Op::Shift | Op::Time => (1, depth, None),
+            Op::Bind(index) => match &chunk.constants[index as usize] {
+                Object::Vector(names)
+                    if names.iter().all(|name| matches!(name, Object::Symbol(_))) =>
+                {
+                    (names.len() + 1, depth.wrapping_sub(names.len()), None)
+                }
+                _ => return Err(BinaryObjectError::Bytecode),
+            },
+            Op::Dynamic(_) => (0, depth, None),
+            Op::Return if depth >= 1 => continue,
+            Op::Return => return Err(BinaryObjectError::Bytecode),
+        };
+        if depth < needs {
+            return Err(BinaryObjectError::Bytecode);
+        }
+        if let Some(to) = jump {
+            pending.push((to as usize, after));
+        }
+        pending.push((ip + 1, after));
+    }
+    Ok(())
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn read_byte(&mut self) -> Result<u8, BinaryObjectError> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or(BinaryObjectError::Truncated)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], BinaryObjectError> {
+        if self.bytes.len() - self.pos < len {
+            return Err(BinaryObjectError::Truncated);
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn read_varint(&mut self) -> Result<u64, BinaryObjectError> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_byte()?;
+            n |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(BinaryObjectError::Size)
+    }
+
+    fn read_len(&mut self) -> Result<usize, BinaryObjectError> {
+        let len = self.read_varint()?;
+        if len > (self.bytes.len() - self.pos) as u64 {
+            return Err(BinaryObjectError::Size);
+        }
+        Ok(len as usize)
+    }
+
+    fn read_str(&mut self) -> Result<String, BinaryObjectError> {
+        let len = self.read_len()?;
+        let slice = self.read_slice(len)?;
+        String::from_utf8(slice.to_vec()).map_err(|_| BinaryObjectError::Utf8)
+    }
+
+    fn decode(&mut self, depth: usize) -> Result<Object, BinaryObjectError> {
+        if depth > MAX_DEPTH {
+            return Err(BinaryObjectError::Depth);
+        }
+        match self.read_byte()? {
+            TAG_NULL => Ok(Object::Null),
+            TAG_FALSE => Ok(Object::Bool(false)),
+            TAG_TRUE => Ok(Object::Bool(true)),
+            TAG_INTEGER => {
+                let n = self.read_varint()?;
+                Ok(Object::Integer(((n >> 1) as i64) ^ -((n & 1) as i64)))
+            }
+            TAG_FLOAT => {
+                let mut buf = [0; 8];
+                buf.copy_from_slice(self.read_slice(8)?);
+                Ok(Object::Float(f64::from_le_bytes(buf)))
+            }
+            TAG_STRING => Ok(Object::String(self.read_str()?.into())),
+            TAG_SYMBOL => Ok(Object::Symbol(self.read_symbol()?)),
+            TAG_LIST => {
+                let len = self.read_len()?;
+                let mut list = Vec::with_capacity(len);
+                for _ in 0..len {
+                    list.push(self.decode(depth + 1)?);
+                }
+                Ok(Object::List(list))
+            }
+            TAG_VECTOR => {
+                let len = self.read_len()?;
+                let mut vector = Vec::with_capacity(len);
+                for _ in 0..len {
+                    vector.push(self.decode(depth + 1)?);
+                }
+                Ok(Object::Vector(vector))
+            }
+            TAG_MAP => {
+                let len = self.read_len()?;
+                let mut map = HashMap::with_capacity(len);
+                for _ in 0..len {
+                    let key = self.decode(depth + 1)?;
+                    let value = self.decode(depth + 1)?;
+                    map.insert(key, value);
+                }
+                Ok(Object::Map(map))
+            }
+            TAG_META => {
+                let meta = self.decode(depth + 1)?;
+                let value = self.decode(depth + 1)?;
+                Ok(value.with_meta(meta))
+            }
+            TAG_FUNCTION => {
+                let proto = self.decode_proto(depth + 1)?;
+                let len = self.read_len()?;
+                if len != proto.captures.len() {
+                    return Err(BinaryObjectError::Bytecode);
+                }
+                let mut captured = Vec::with_capacity(len);
+                for _ in 0..len {
+                    captured.push(self.decode(depth + 1)?);
+                }
+                Ok(Object::Other(Shared::new(Function {
+                    proto: Shared::new(proto),
+                    captured,
+                })))
+            }
+            tag => Err(BinaryObjectError::Tag(tag)),
+        }
+    }
+
+    fn read_u32(&mut self) -> Result<u32, BinaryObjectError> {
+        let n = self.read_varint()?;
+        if n > u64::from(u32::MAX) {
+            return Err(BinaryObjectError::Size);
+        }
+        Ok(n as u32)
+    }
+
+    fn read_symbol(&mut self) -> Result<Symbol, BinaryObjectError> {
+        Symbol::try_new(&self.read_str()?).ok_or(BinaryObjectError::Symbols)
+    }
+
+    fn decode_op(&mut self) -> Result<Op, BinaryObjectError> {
+        Ok(match self.read_byte()? {
+            0 => Op::Constant(self.read_u32()?),
+            1 => Op::GetLocal(self.read_u32()?),
+            2 => Op::SetLocal(self.read_u32()?),
+            3 => Op::GetGlobal(self.read_u32()?),
+            4 => Op::SetGlobal(self.read_u32()?),
+            5 => Op::Jump(self.read_u32()?),
+            6 => Op::JumpIfFalse(self.read_u32()?),
+            7 => Op::Pop,
+            8 => Op::Call(self.read_u32()?),
+            9 => Op::Closure(self.read_u32()?),
+            10 => Op::Return,
+            11 => Op::Yield,
+            12 => Op::Reset,
+            13 => Op::Shift,
+            14 => Op::Bind(self.read_u32()?),
+            15 => Op::Dynamic(self.read_u32()?),
+            16 => Op::Time,
+            opcode => return Err(BinaryObjectError::Tag(opcode)),
+        })
+    }
+
+    fn decode_proto(&mut self, depth: usize) -> Result<Proto, BinaryObjectError> {
+        if depth > MAX_DEPTH {
+            return Err(BinaryObjectError::Depth);
+        }
+        let len = self.read_len()?;
+        let mut params = Vec::with_capacity(len);
+        for _ in 0..len {
+            params.push(self.read_symbol()?);
+        }
+        let len = self.read_len()?;
+        let mut captures = Vec::with_capacity(len);
+        for _ in 0..len {
+            captures.push((self.read_symbol()?, self.read_u32()?));
+        }
+        let len = self.read_len()?;
+        let mut body = Vec::with_capacity(len);
+        for _ in 0..len {
+            body.push(self.decode(depth + 1)?);
+        }
+        let generator = match self.read_byte()? {
+            0 => false,
+            1 => true,
+            tag => return Err(BinaryObjectError::Tag(tag)),
+        };
+        let mut proto = Proto {
+            params,
+            captures,
+            body,
+            chunk: None,
+            generator,
+        };
+        // Generators only run on the VM, so one without a chunk is useless.
+        match self.read_byte()? {
+            0 if generator => return Err(BinaryObjectError::Bytecode),
+            0 => return Ok(proto),
+            _ => {}
+        }
+        let mut chunk = Chunk {
+            locals: self.read_u32()?,
+            ..Chunk::default()
+        };
+        let len = self.read_len()?;
+        for _ in 0..len {
+            chunk.code.push(self.decode_op()?);
+        }
+        let len = self.read_len()?;
+        for _ in 0..len {
+            chunk.constants.push(self.decode(depth + 1)?);
+        }
+        let len = self.read_len()?;
+        for _ in 0..len {
+            let len = self.read_len()?;
+            let mut sources = Vec::with_capacity(len);
+            for _ in 0..len {
+                sources.push(self.read_u32()?);
+            }
+            chunk.closures.push(Closure {
+                proto: Shared::new(self.decode_proto(depth + 1)?),
+                sources,
+            });
+        }
+        let len = self.read_len()?;
+        for _ in 0..len {
+            chunk.callees.push((self.read_u32()?, self.read_symbol()?));
+        }
+        verify_chunk(&proto, &chunk)?;
+        proto.chunk = Some(chunk);
+        Ok(proto)
+    }
+}
+
+impl Object {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BinaryObjectError> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        encode(self, &mut bytes, 0)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Object, BinaryObjectError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(BinaryObjectError::Magic);
+        }
+        let mut decoder = Decoder {
+            bytes,
+            pos: MAGIC.len(),
+        };
+        let version = decoder.read_byte()?;
+        if version != VERSION {
+            return Err(BinaryObjectError::Version(version));
+        }
+        let obj = decoder.decode(0)?;
+        if decoder.pos != bytes.len() {
+            return Err(BinaryObjectError::Trailing);
+        }
+        Ok(obj)
+    }
+}
+
+impl CompiledProgram {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BinaryObjectError> {
+        let mut bytes = PROGRAM_MAGIC.to_vec();
+        bytes.push(PROGRAM_VERSION);
+        bytes.extend_from_slice(&self.hash.to_le_bytes());
+        encode_proto(&self.proto, &mut bytes, 0)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<CompiledProgram, BinaryObjectError> {
+        if !bytes.starts_with(PROGRAM_MAGIC) {
+            return Err(BinaryObjectError::Magic);
+        }
+        let mut decoder = Decoder {
+            bytes,
+            pos: PROGRAM_MAGIC.len(),
+        };
+        let version = decoder.read_byte()?;
+        if version != PROGRAM_VERSION {
+            return Err(BinaryObjectError::Version(version));
+        }
+        let mut hash = [0; 8];
+        hash.copy_from_slice(decoder.read_slice(8)?);
+        let proto = decoder.decode_proto(0)?;
+        if decoder.pos != bytes.len() {
+            return Err(BinaryObjectError::Trailing);
+        }
+        Ok(CompiledProgram {
+            hash: u64::from_le_bytes(hash),
+            proto: Shared::new(proto),
+        })
+    }
+}
@@ -0,0 +1,65 @@
+use crate::evaluator::{args, PrimitiveFunction};
+use crate::object::{Bytes, Object};
+use std::convert::TryFrom;
+
+pub(crate) const PRIMITIVES: [(&str, PrimitiveFunction); 3] = [
+    ("bytes", bytes),
+    ("bytes-to-string", bytes_to_string),
+    ("bytes-to-vector", bytes_to_vector),
+];
+
+// The contents of a Bytes object, or the UTF-8 encoding of a string, for
+// primitives that take either.
+pub(crate) fn data(obj: &Object) -> Option<&[u8]> {
+    match obj.without_meta() {
+        Object::String(string) => Some(string.as_bytes()),
+        Object::Other(other) => other.downcast_ref::<Bytes>().map(|Bytes(bytes)| &bytes[..]),
+        _ => None,
+    }
+}
+
+// Lowercase, two digits per byte.
+pub(crate) fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// (bytes "text") encodes a string as UTF-8 and (bytes [104, 105]) takes
+// integers from 0 to 255.
+fn bytes(obj: &Object) -> Object {
+    let arg = match args(obj).first() {
+        Some(arg) => arg,
+        None => return Object::from(Bytes(Vec::new())),
+    };
+    if let Some(data) = data(arg) {
+        return Object::from(Bytes(data.to_vec()));
+    }
+    match arg.without_meta() {
+        Object::Vector(values) | Object::List(values) => values
+            .iter()
+            .map(|value| match value.without_meta() {
+                Object::Integer(n) => u8::try_from(*n).ok(),
+                _ => None,
+            })
+            .collect::<Option<Vec<u8>>>()
+            .map_or(Object::Null, |bytes| Object::from(Bytes(bytes))),
+        _ => Object::Null,
+    }
+}
+
+fn bytes_to_string(obj: &Object) -> Object {
+    match args(obj).first().and_then(data) {
+        Some(data) => std::str::from_utf8(data).map_or(Object::Null, Object::from),
+        None => Object::Null,
+    }
+}
+
+fn bytes_to_vector(obj: &Object) -> Object {
+    match args(obj).first().and_then(data) {
+        Some(data) => Object::Vector(
+            data.iter()
+                .map(|&byte| Object::Integer(byte as i64))
+                .collect(),
+        ),
+        None => Object::Null,
+    }
+}
@@ -0,0 +1,185 @@
+// A C interface for embedding, declared in include/fundot.h. An evaluator
+// is handed out as an opaque pointer, values cross as JSON text, and every
+// pointer given out is freed by the matching fundot_*_free. A null
+// pointer passed in is an error rather than undefined behaviour. What each
+// function needs of the pointers it is given is said above it and in the
+// header rather than in rustdoc.
+#![allow(clippy::missing_safety_doc)]
+
+use crate::error::describe;
+use crate::evaluator::{args, Evaluator, Partial, PrimitiveFunction};
+use crate::object::{Object, Shared};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+
+// What fundot_eval_str gives: a value, or the message of what failed.
+pub struct FundotResult {
+    value: Result<Object, String>,
+}
+
+// Where a callback leaves its return value.
+pub struct FundotCall {
+    value: Object,
+}
+
+pub type FundotFn =
+    extern "C" fn(call: *mut FundotCall, args_json: *const c_char, user_data: *mut c_void);
+
+// A registered callback and the pointer it is called with. The C side
+// promises the callback may be called from any thread the evaluator is
+// used on, as the header says.
+struct Callback {
+    function: FundotFn,
+    user_data: *mut c_void,
+}
+
+unsafe impl Send for Callback {}
+unsafe impl Sync for Callback {}
+
+// Calls the callback bound as the first argument with the rest as a JSON
+// array. Arguments with no JSON form, such as functions, make it null
+// without calling back.
+fn call_back(obj: &Object) -> Object {
+    let (callback, rest) = match args(obj) {
+        [Object::Other(callback), rest @ ..] => match callback.downcast_ref::<Callback>() {
+            Some(callback) => (callback, rest),
+            None => return Object::Null,
+        },
+        _ => return Object::Null,
+    };
+    let json = serde_json::to_string(&Object::Vector(rest.to_vec()))
+        .ok()
+        .and_then(|json| CString::new(json).ok());
+    let json = match json {
+        Some(json) => json,
+        None => return Object::Null,
+    };
+    let mut call = FundotCall {
+        value: Object::Null,
+    };
+    (callback.function)(&mut call, json.as_ptr(), callback.user_data);
+    call.value
+}
+
+unsafe fn string<'a>(s: *const c_char) -> Option<&'a str> {
+    match s.is_null() {
+        true => None,
+        false => CStr::from_ptr(s).to_str().ok(),
+    }
+}
+
+fn c_string(s: String) -> *mut c_char {
+    CString::new(s).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+#[no_mangle]
+pub extern "C" fn fundot_new() -> *mut Evaluator {
+    Box::into_raw(Box::new(Evaluator::new()))
+}
+
+// fundot must be null or from fundot_new and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn fundot_free(fundot: *mut Evaluator) {
+    if !fundot.is_null() {
+        drop(Box::from_raw(fundot));
+    }
+}
+
+// Evaluates every form in source. fundot must be from fundot_new and
+// source a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fundot_eval_str(
+    fundot: *const Evaluator,
+    source: *const c_char,
+) -> *mut FundotResult {
+    let value = match (fundot.as_ref(), string(source)) {
+        (Some(evaluator), Some(source)) => {
+            evaluator.eval_str(source).map_err(|error| describe(&error))
+        }
+        (None, _) => Err("no evaluator".to_string()),
+        (_, None) => Err("source is not UTF-8".to_string()),
+    };
+    Box::into_raw(Box::new(FundotResult { value }))
+}
+
+// result, here and below, must be from fundot_eval_str and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn fundot_result_is_error(result: *const FundotResult) -> bool {
+    result.as_ref().is_none_or(|result| result.value.is_err())
+}
+
+// The value as JSON, or null for an error or a value with no JSON form.
+#[no_mangle]
+pub unsafe extern "C" fn fundot_result_to_json(result: *const FundotResult) -> *mut c_char {
+    match result.as_ref().map(|result| &result.value) {
+        Some(Ok(value)) => serde_json::to_string(value).map_or(ptr::null_mut(), c_string),
+        _ => ptr::null_mut(),
+    }
+}
+
+// The value as Fundot prints it, or the error message.
+#[no_mangle]
+pub unsafe extern "C" fn fundot_result_to_string(result: *const FundotResult) -> *mut c_char {
+    match result.as_ref().map(|result| &result.value) {
+        Some(Ok(value)) => c_string(value.to_string()),
+        Some(Err(message)) => c_string(message.clone()),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn fundot_result_free(result: *mut FundotResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+// s must be null or a string from this library and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn fundot_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+// Binds name to a function that calls function with its arguments as a
+// JSON array and user_data. Gives 0, or -1 when a pointer is null or name
+// is not UTF-8. fundot must be from fundot_new, name a NUL-terminated
+// string, and function safe to call with user_data from any thread fundot
+// is used on.
+#[no_mangle]
+pub unsafe extern "C" fn fundot_register_fn(
+    fundot: *const Evaluator,
+    name: *const c_char,
+    function: Option<FundotFn>,
+    user_data: *mut c_void,
+) -> c_int {
+    let (evaluator, name, function) = match (fundot.as_ref(), string(name), function) {
+        (Some(evaluator), Some(name), Some(function)) => (evaluator, name, function),
+        _ => return -1,
+    };
+    let call_back: PrimitiveFunction = call_back;
+    let callback = Callback {
+        function,
+        user_data,
+    };
+    let partial = Partial {
+        function: Object::Other(Shared::new(call_back)),
+        args: vec![Object::Other(Shared::new(callback))],
+    };
+    evaluator.define(name, Object::Other(Shared::new(partial)));
+    0
+}
+
+// Sets what the callback returns from the JSON text json. Invalid JSON,
+// like never setting it, returns null. call must be the one the callback
+// was given and json null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fundot_call_return_json(call: *mut FundotCall, json: *const c_char) {
+    if let Some(call) = call.as_mut() {
+        call.value = string(json)
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or(Object::Null);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::evaluator::{args, EvalError, NativeFunction};
+use crate::interpreter::Interpreter;
+use crate::object::Object;
+use crate::symbol::Symbol;
+use std::collections::{HashMap, HashSet};
+
+pub(crate) const NATIVES: [(&str, NativeFunction); 1] = [("parse-args", parse_args)];
+
+fn key(name: &str) -> Object {
+    Object::Symbol(Symbol::new(name))
+}
+
+fn failure(message: String) -> Object {
+    Object::Map(HashMap::from([(key("error"), Object::from(message))]))
+}
+
+// Reads an option's value as its default's kind: integers and floats are
+// parsed, vectors collect every value given and anything else takes the
+// text as it is. Values given replace a vector's default rather than
+// adding to it.
+fn value(default: &Object, text: &str, current: Option<Object>) -> Option<Object> {
+    match default.without_meta() {
+        Object::Integer(_) => text.parse().ok().map(Object::Integer),
+        Object::Float(_) => text.parse().ok().map(Object::Float),
+        Object::Vector(_) => {
+            let mut values = match current {
+                Some(Object::Vector(values)) => values,
+                _ => Vec::new(),
+            };
+            values.push(Object::from(text));
+            Some(Object::Vector(values))
+        }
+        _ => Some(Object::from(text)),
+    }
+}
+
+// (parse-args spec argv) parses argv, *argv* unless given, against a spec
+// mapping option names to defaults, such as {verbose: false, output:
+// "out.txt", n: 1}. A name of one letter is given as -n and a longer one
+// as --output, with its value after it or after an =. An option whose
+// default is a boolean is a flag, and --no-verbose turns it off. The
+// result maps every name to its value, with the arguments that are not
+// options under args; everything after -- is one. An unknown option or a
+// missing or malformed value gives {error: message} instead.
+fn parse_args(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let args = args(obj);
+    let spec = match args.first().map(Object::without_meta) {
+        Some(Object::Map(spec)) => spec,
+        _ => return Ok(Object::Null),
+    };
+    let argv = match args.get(1) {
+        Some(argv) => argv.clone(),
+        None => interpreter.lookup_global(Symbol::new("*argv*")),
+    };
+    let argv: Vec<String> = match argv.without_meta() {
+        Object::Vector(argv) | Object::List(argv) => argv
+            .iter()
+            .map(|arg| match arg.without_meta() {
+                Object::String(arg) => arg.to_string(),
+                arg => arg.to_string(),
+            })
+            .collect(),
+        _ => return Ok(Object::Null),
+    };
+    let mut defaults = HashMap::new();
+    for (name, default) in spec {
+        let name = match name.without_meta() {
+            Object::Symbol(name) => name.as_str().to_string(),
+            Object::String(name) => name.to_string(),
+            _ => return Ok(Object::Null),
+        };
+        defaults.insert(name, default);
+    }
+    let mut result: HashMap<Object, Object> = defaults
+        .iter()
+        .map(|(name, default)| (key(name), (*default).clone()))
+        .collect();
+    let mut positional = Vec::new();
+    let mut given = HashSet::new();
+    let mut argv = argv.into_iter();
+    while let Some(arg) = argv.next() {
+        if arg == "--" {
+            positional.extend(argv.by_ref().map(Object::from));
+            break;
+        }
+        let option = match arg.strip_prefix("--") {
+            Some(option) => option,
+            None => match arg.strip_prefix('-') {
+                Some(option) if option.len() == 1 && option.as_bytes()[0].is_ascii_alphabetic() => {
+                    option
+                }
+                _ => {
+                    positional.push(Object::from(arg));
+                    continue;
+                }
+            },
+        };
+        let (name, inline) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (option, None),
+        };
+        let negated = name.strip_prefix("no-").filter(|name| {
+            matches!(
+                defaults.get(*name).map(|default| default.without_meta()),
+                Some(Object::Bool(_))
+            )
+        });
+        if let Some(name) = negated {
+            result.insert(key(name), Object::Bool(false));
+            continue;
+        }
+        let default = match defaults.get(name) {
+            Some(default) => default,
+            None => return Ok(failure(format!("unknown option {}", arg))),
+        };
+        if let Object::Bool(_) = default.without_meta() {
+            result.insert(key(name), Object::Bool(true));
+            continue;
+        }
+        let text = match inline.or_else(|| argv.next()) {
+            Some(text) => text,
+            None => return Ok(failure(format!("{} needs a value", arg))),
+        };
+        let current = match given.insert(name.to_string()) {
+            true => None,
+            false => result.remove(&key(name)),
+        };
+        match value(default, &text, current) {
+            Some(value) => result.insert(key(name), value),
+            None => return Ok(failure(format!("bad value {} for {}", text, arg))),
+        };
+    }
+    result.insert(key("args"), Object::Vector(positional));
+    Ok(Object::Map(result))
+}
@@ -0,0 +1,6 @@
+// std has no clock on wasm32-unknown-unknown and panics when asked the
+// time, so there it is read from the browser instead.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) use std::time::{Instant, SystemTime, UNIX_EPOCH};
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) use web_time::{Instant, SystemTime, UNIX_EPOCH};
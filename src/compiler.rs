@@ -0,0 +1,633 @@
+use crate::object::{Object, Shared};
+use crate::symbol::Symbol;
+use crate::types;
+use std::error::Error;
+use std::fmt::{self, Write};
+use std::result::Result;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Constant(u32),
+    GetLocal(u32),
+    SetLocal(u32),
+    GetGlobal(u32),
+    SetGlobal(u32),
+    Jump(u32),
+    JumpIfFalse(u32),
+    Pop,
+    Call(u32),
+    Closure(u32),
+    Return,
+    Yield,
+    Reset,
+    Shift,
+    Bind(u32),
+    Dynamic(u32),
+    Time,
+}
+
+impl Op {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Op::Constant(_) => "CONSTANT",
+            Op::GetLocal(_) => "GET_LOCAL",
+            Op::SetLocal(_) => "SET_LOCAL",
+            Op::GetGlobal(_) => "GET_GLOBAL",
+            Op::SetGlobal(_) => "SET_GLOBAL",
+            Op::Jump(_) => "JUMP",
+            Op::JumpIfFalse(_) => "JUMP_IF_FALSE",
+            Op::Pop => "POP",
+            Op::Call(_) => "CALL",
+            Op::Closure(_) => "CLOSURE",
+            Op::Return => "RETURN",
+            Op::Yield => "YIELD",
+            Op::Reset => "RESET",
+            Op::Shift => "SHIFT",
+            Op::Bind(_) => "BIND",
+            Op::Dynamic(_) => "DYNAMIC",
+            Op::Time => "TIME",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub proto: Shared<Proto>,
+    pub sources: Vec<u32>,
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub constants: Vec<Object>,
+    pub closures: Vec<Closure>,
+    pub locals: u32,
+    pub callees: Vec<(u32, Symbol)>,
+}
+
+impl Chunk {
+    pub fn callee(&self, offset: usize) -> Option<Symbol> {
+        self.callees
+            .binary_search_by_key(&(offset as u32), |(offset, _)| *offset)
+            .ok()
+            .map(|index| self.callees[index].1)
+    }
+
+    pub fn disassemble(&self) -> String {
+        let mut output = String::new();
+        self.disassemble_into(&mut output, 0);
+        output
+    }
+
+    fn disassemble_into(&self, output: &mut String, indent: usize) {
+        let pad = " ".repeat(indent);
+        for (offset, op) in self.code.iter().enumerate() {
+            let _ = match op {
+                Op::Constant(index)
+                | Op::GetGlobal(index)
+                | Op::SetGlobal(index)
+                | Op::Bind(index)
+                | Op::Dynamic(index) => writeln!(
+                    output,
+                    "{}{:04} {:<14}{:>4}  ; {}",
+                    pad,
+                    offset,
+                    op.name(),
+                    index,
+                    self.constants[*index as usize]
+                ),
+                Op::Call(argc) if self.callee(offset).is_some() => writeln!(
+                    output,
+                    "{}{:04} {:<14}{:>4}  ; {}",
+                    pad,
+                    offset,
+                    op.name(),
+                    argc,
+                    self.callee(offset).unwrap()
+                ),
+                Op::GetLocal(operand)
+                | Op::SetLocal(operand)
+                | Op::Jump(operand)
+                | Op::JumpIfFalse(operand)
+                | Op::Call(operand)
+                | Op::Closure(operand) => {
+                    writeln!(
+                        output,
+                        "{}{:04} {:<14}{:>4}",
+                        pad,
+                        offset,
+                        op.name(),
+                        operand
+                    )
+                }
+                Op::Pop | Op::Return | Op::Yield | Op::Reset | Op::Shift | Op::Time => {
+                    writeln!(output, "{}{:04} {}", pad, offset, op.name())
+                }
+            };
+        }
+        for (index, closure) in self.closures.iter().enumerate() {
+            let proto = &closure.proto;
+            let params: Vec<&str> = proto.params.iter().map(|param| param.as_str()).collect();
+            let captures: Vec<&str> = proto
+                .captures
+                .iter()
+                .map(|(symbol, _)| symbol.as_str())
+                .collect();
+            let _ = writeln!(
+                output,
+                "{}{} {} ({}) captures ({}):",
+                pad,
+                if proto.generator {
+                    "generator"
+                } else {
+                    "closure"
+                },
+                index,
+                params.join(" "),
+                captures.join(" ")
+            );
+            if let Some(chunk) = &proto.chunk {
+                chunk.disassemble_into(output, indent + 2);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Proto {
+    pub params: Vec<Symbol>,
+    pub captures: Vec<(Symbol, u32)>,
+    pub body: Vec<Object>,
+    pub chunk: Option<Chunk>,
+    pub generator: bool,
+}
+
+#[derive(Debug)]
+pub struct CompiledProgram {
+    pub(crate) hash: u64,
+    pub(crate) proto: Shared<Proto>,
+}
+
+pub(crate) fn source_hash(source: &str) -> u64 {
+    source.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl CompiledProgram {
+    pub fn source_hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn matches(&self, source: &str) -> bool {
+        self.hash == source_hash(source)
+    }
+}
+
+#[derive(Debug)]
+pub struct CompileError;
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for CompileError {}
+
+#[derive(Default)]
+struct FunctionState {
+    chunk: Chunk,
+    scopes: Vec<Vec<(Symbol, u32)>>,
+    captures: Vec<(Symbol, u32)>,
+    sources: Vec<u32>,
+    generator: bool,
+}
+
+impl FunctionState {
+    fn lookup(&self, name: Symbol) -> Option<u32> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(symbol, _)| *symbol == name)
+            .map(|(_, slot)| *slot)
+    }
+
+    fn declare(&mut self, name: Symbol) -> u32 {
+        let slot = self.chunk.locals;
+        self.chunk.locals += 1;
+        self.scopes.last_mut().unwrap().push((name, slot));
+        slot
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.code.len() - 1
+    }
+
+    fn constant(&mut self, obj: Object) -> u32 {
+        self.chunk.constants.push(obj);
+        (self.chunk.constants.len() - 1) as u32
+    }
+
+    fn patch(&mut self, at: usize) {
+        let target = self.chunk.code.len() as u32;
+        match &mut self.chunk.code[at] {
+            Op::Jump(to) | Op::JumpIfFalse(to) => *to = target,
+            _ => unreachable!(),
+        }
+    }
+}
+
+const MAX_DEPTH: usize = 512;
+
+pub struct Compiler {
+    functions: Vec<FunctionState>,
+    depth: usize,
+}
+
+pub const SPECIAL_FORMS: &[&str] = &[
+    "quote",
+    "if",
+    "do",
+    "set",
+    "let",
+    "fn",
+    "fn*",
+    "while",
+    "yield",
+    "reset",
+    "shift",
+    "defdynamic",
+    "binding",
+    "time",
+];
+
+fn special_form(list: &[Object]) -> Option<&'static str> {
+    if let Some(Object::Symbol(symbol)) = list.first() {
+        let name = symbol.as_str();
+        if SPECIAL_FORMS.contains(&name) {
+            return Some(name);
+        }
+    }
+    None
+}
+
+fn params(obj: &Object) -> Result<Vec<Symbol>, CompileError> {
+    match obj {
+        Object::List(list) => list
+            .iter()
+            .map(|param| match param {
+                Object::Symbol(symbol) => Ok(*symbol),
+                _ => Err(CompileError {}),
+            })
+            .collect(),
+        _ => Err(CompileError {}),
+    }
+}
+
+// Accepts let-style ((name value) ...) as well as a flat [name, value, ...].
+pub(crate) fn dynamic_bindings(obj: &Object) -> Option<Vec<(Symbol, &Object)>> {
+    let pair = |name: &Object, value| match name {
+        Object::Symbol(symbol) => Some((*symbol, value)),
+        _ => None,
+    };
+    match obj {
+        Object::List(bindings) => bindings
+            .iter()
+            .map(|binding| match binding {
+                Object::List(list) if list.len() == 2 => pair(&list[0], &list[1]),
+                _ => None,
+            })
+            .collect(),
+        Object::Vector(flat) if flat.len() % 2 == 0 => flat
+            .chunks(2)
+            .map(|chunk| pair(&chunk[0], &chunk[1]))
+            .collect(),
+        _ => None,
+    }
+}
+
+impl Compiler {
+    pub fn compile(obj: &Object) -> Result<Proto, CompileError> {
+        let mut compiler = Compiler {
+            functions: vec![FunctionState::default()],
+            depth: 0,
+        };
+        compiler.current().scopes.push(Vec::new());
+        compiler.expr(obj)?;
+        compiler.current().emit(Op::Return);
+        let state = compiler.functions.pop().unwrap();
+        Ok(Proto {
+            params: Vec::new(),
+            captures: Vec::new(),
+            body: vec![obj.clone()],
+            chunk: Some(state.chunk),
+            generator: false,
+        })
+    }
+
+    pub fn compile_function(
+        params: &Object,
+        body: &[Object],
+        locals: &[Symbol],
+    ) -> Result<Closure, CompileError> {
+        Compiler::compile_closure(params, body, locals, false)
+    }
+
+    pub fn compile_generator(
+        params: &Object,
+        body: &[Object],
+        locals: &[Symbol],
+    ) -> Result<Closure, CompileError> {
+        Compiler::compile_closure(params, body, locals, true)
+    }
+
+    fn compile_closure(
+        params: &Object,
+        body: &[Object],
+        locals: &[Symbol],
+        generator: bool,
+    ) -> Result<Closure, CompileError> {
+        let mut compiler = Compiler {
+            functions: vec![FunctionState::default()],
+            depth: 0,
+        };
+        compiler.current().scopes.push(Vec::new());
+        for local in locals {
+            compiler.current().declare(*local);
+        }
+        compiler.function(params, body, generator)
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions.last_mut().unwrap()
+    }
+
+    fn resolve(&mut self, level: usize, name: Symbol) -> Option<u32> {
+        if let Some(slot) = self.functions[level].lookup(name) {
+            return Some(slot);
+        }
+        if let Some((_, slot)) = self.functions[level]
+            .captures
+            .iter()
+            .find(|(symbol, _)| *symbol == name)
+        {
+            return Some(*slot);
+        }
+        if level == 0 {
+            return None;
+        }
+        let source = self.resolve(level - 1, name)?;
+        let state = &mut self.functions[level];
+        let slot = state.chunk.locals;
+        state.chunk.locals += 1;
+        state.captures.push((name, slot));
+        state.sources.push(source);
+        Some(slot)
+    }
+
+    fn body(&mut self, body: &[Object]) -> Result<(), CompileError> {
+        if body.is_empty() {
+            let index = self.current().constant(Object::Null);
+            self.current().emit(Op::Constant(index));
+        }
+        for (i, obj) in body.iter().enumerate() {
+            if i > 0 {
+                self.current().emit(Op::Pop);
+            }
+            self.expr(obj)?;
+        }
+        Ok(())
+    }
+
+    fn function(
+        &mut self,
+        params_obj: &Object,
+        body: &[Object],
+        generator: bool,
+    ) -> Result<Closure, CompileError> {
+        let params = params(params_obj)?;
+        let mut state = FunctionState {
+            generator,
+            ..FunctionState::default()
+        };
+        state.scopes.push(Vec::new());
+        for param in &params {
+            state.declare(*param);
+        }
+        self.functions.push(state);
+        self.body(body)?;
+        self.current().emit(Op::Return);
+        let state = self.functions.pop().unwrap();
+        Ok(Closure {
+            proto: Shared::new(Proto {
+                params,
+                captures: state.captures,
+                body: body.to_vec(),
+                chunk: Some(state.chunk),
+                generator,
+            }),
+            sources: state.sources,
+        })
+    }
+
+    fn expr(&mut self, obj: &Object) -> Result<(), CompileError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(CompileError {});
+        }
+        self.depth += 1;
+        let result = self.lower(obj);
+        self.depth -= 1;
+        result
+    }
+
+    fn lower(&mut self, obj: &Object) -> Result<(), CompileError> {
+        match obj {
+            Object::Symbol(symbol) => {
+                let level = self.functions.len() - 1;
+                if let Some(slot) = self.resolve(level, *symbol) {
+                    self.current().emit(Op::GetLocal(slot));
+                } else {
+                    let index = self.current().constant(obj.clone());
+                    self.current().emit(Op::GetGlobal(index));
+                }
+                Ok(())
+            }
+            Object::List(list) if !list.is_empty() => match special_form(list) {
+                Some(name) => self.special(name, &list[1..]),
+                None => {
+                    for obj in list {
+                        self.expr(obj)?;
+                    }
+                    let offset = self.current().emit(Op::Call(list.len() as u32 - 1));
+                    if let Object::Symbol(symbol) = &list[0] {
+                        let chunk = &mut self.current().chunk;
+                        chunk.callees.push((offset as u32, *symbol));
+                    }
+                    Ok(())
+                }
+            },
+            Object::List(_) => {
+                let index = self.current().constant(Object::Null);
+                self.current().emit(Op::Constant(index));
+                Ok(())
+            }
+            _ => {
+                let index = self.current().constant(obj.clone());
+                self.current().emit(Op::Constant(index));
+                Ok(())
+            }
+        }
+    }
+
+    fn special(&mut self, name: &str, args: &[Object]) -> Result<(), CompileError> {
+        let unannotated = types::unannotated(name, args);
+        let args = unannotated.as_deref().unwrap_or(args);
+        match (name, args) {
+            ("quote", [obj]) => {
+                let index = self.current().constant(obj.clone());
+                self.current().emit(Op::Constant(index));
+            }
+            ("if", [condition, then]) | ("if", [condition, then, _]) => {
+                self.expr(condition)?;
+                let to_else = self.current().emit(Op::JumpIfFalse(0));
+                self.expr(then)?;
+                let to_end = self.current().emit(Op::Jump(0));
+                self.current().patch(to_else);
+                match args.get(2) {
+                    Some(otherwise) => self.expr(otherwise)?,
+                    None => {
+                        let index = self.current().constant(Object::Null);
+                        self.current().emit(Op::Constant(index));
+                    }
+                }
+                self.current().patch(to_end);
+            }
+            ("do", body) => self.body(body)?,
+            ("set", [Object::Symbol(symbol), value]) => {
+                self.expr(value)?;
+                let level = self.functions.len() - 1;
+                if let Some(slot) = self.resolve(level, *symbol) {
+                    self.current().emit(Op::SetLocal(slot));
+                } else {
+                    let index = self.current().constant(Object::Symbol(*symbol));
+                    self.current().emit(Op::SetGlobal(index));
+                }
+            }
+            ("let", [Object::List(bindings), body @ ..]) => {
+                self.current().scopes.push(Vec::new());
+                for binding in bindings {
+                    match binding {
+                        Object::List(pair) if pair.len() == 2 => {
+                            if let Object::Symbol(symbol) = pair[0] {
+                                self.expr(&pair[1])?;
+                                let slot = self.current().declare(symbol);
+                                self.current().emit(Op::SetLocal(slot));
+                                self.current().emit(Op::Pop);
+                                continue;
+                            }
+                            return Err(CompileError {});
+                        }
+                        _ => return Err(CompileError {}),
+                    }
+                }
+                self.body(body)?;
+                self.current().scopes.pop();
+            }
+            ("fn", [params, body @ ..]) | ("fn*", [params, body @ ..]) => {
+                let closure = self.function(params, body, name == "fn*")?;
+                let chunk = &mut self.current().chunk;
+                chunk.closures.push(closure);
+                let index = chunk.closures.len() as u32 - 1;
+                self.current().emit(Op::Closure(index));
+            }
+            // A yield evaluates to null when the generator resumes.
+            ("yield", []) | ("yield", [_]) if self.current().generator => {
+                match args.first() {
+                    Some(value) => self.expr(value)?,
+                    None => {
+                        let index = self.current().constant(Object::Null);
+                        self.current().emit(Op::Constant(index));
+                    }
+                }
+                self.current().emit(Op::Yield);
+            }
+            // Both bodies become closures: reset calls its body as a
+            // delimiter frame and shift hands its body the continuation up to
+            // the nearest one.
+            ("reset", body) => {
+                let closure = self.function(&Object::List(Vec::new()), body, false)?;
+                let chunk = &mut self.current().chunk;
+                chunk.closures.push(closure);
+                let index = chunk.closures.len() as u32 - 1;
+                self.current().emit(Op::Closure(index));
+                self.current().emit(Op::Reset);
+            }
+            ("shift", [Object::Symbol(continuation), body @ ..]) => {
+                let params = Object::List(vec![Object::Symbol(*continuation)]);
+                let closure = self.function(&params, body, false)?;
+                let chunk = &mut self.current().chunk;
+                chunk.closures.push(closure);
+                let index = chunk.closures.len() as u32 - 1;
+                self.current().emit(Op::Closure(index));
+                self.current().emit(Op::Shift);
+            }
+            ("defdynamic", [name @ Object::Symbol(_)]) => {
+                let index = self.current().constant(name.clone());
+                self.current().emit(Op::Dynamic(index));
+                let index = self.current().constant(Object::Null);
+                self.current().emit(Op::Constant(index));
+            }
+            ("defdynamic", [name @ Object::Symbol(_), value]) => {
+                let index = self.current().constant(name.clone());
+                self.current().emit(Op::Dynamic(index));
+                self.expr(value)?;
+                self.current().emit(Op::SetGlobal(index));
+            }
+            // The body runs as a closure so that the bindings are undone
+            // however it exits.
+            ("binding", [bindings, body @ ..]) => {
+                let bindings = dynamic_bindings(bindings).ok_or(CompileError {})?;
+                for (_, value) in &bindings {
+                    self.expr(value)?;
+                }
+                let closure = self.function(&Object::List(Vec::new()), body, false)?;
+                let chunk = &mut self.current().chunk;
+                chunk.closures.push(closure);
+                let index = chunk.closures.len() as u32 - 1;
+                self.current().emit(Op::Closure(index));
+                let names = bindings
+                    .iter()
+                    .map(|(name, _)| Object::Symbol(*name))
+                    .collect();
+                let index = self.current().constant(Object::Vector(names));
+                self.current().emit(Op::Bind(index));
+            }
+            ("time", body) => {
+                let closure = self.function(&Object::List(Vec::new()), body, false)?;
+                let chunk = &mut self.current().chunk;
+                chunk.closures.push(closure);
+                let index = chunk.closures.len() as u32 - 1;
+                self.current().emit(Op::Closure(index));
+                self.current().emit(Op::Time);
+            }
+            ("while", [condition, body @ ..]) => {
+                let start = self.current().chunk.code.len() as u32;
+                self.expr(condition)?;
+                let to_end = self.current().emit(Op::JumpIfFalse(0));
+                for obj in body {
+                    self.expr(obj)?;
+                    self.current().emit(Op::Pop);
+                }
+                self.current().emit(Op::Jump(start));
+                self.current().patch(to_end);
+                let index = self.current().constant(Object::Null);
+                self.current().emit(Op::Constant(index));
+            }
+            _ => return Err(CompileError {}),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use crate::bytes;
+use crate::evaluator::{args, EvalError, NativeFunction, PrimitiveFunction};
+use crate::interpreter::Interpreter;
+use crate::object::{Bytes, Object};
+use std::io::{self, Read};
+
+// Each takes a string, as UTF-8, or bytes and gives bytes, or null when the
+// input cannot be decompressed. Compressing takes an optional level.
+#[cfg(feature = "gzip")]
+pub(crate) const GZIP_PRIMITIVES: [(&str, PrimitiveFunction); 1] =
+    [("gzip-compress", gzip_compress)];
+
+#[cfg(feature = "gzip")]
+pub(crate) const GZIP_NATIVES: [(&str, NativeFunction); 1] = [("gzip-decompress", gzip_decompress)];
+
+#[cfg(feature = "zstd")]
+pub(crate) const ZSTD_PRIMITIVES: [(&str, PrimitiveFunction); 1] =
+    [("zstd-compress", zstd_compress)];
+
+#[cfg(feature = "zstd")]
+pub(crate) const ZSTD_NATIVES: [(&str, NativeFunction); 1] = [("zstd-decompress", zstd_decompress)];
+
+// A little compressed input can stand for far more output, so
+// decompressing gives null rather than more than this, or more than the
+// memory limit leaves.
+const MAX_OUTPUT: usize = 256 * 1024 * 1024;
+
+fn data(obj: &Object) -> Option<&[u8]> {
+    args(obj).first().and_then(bytes::data)
+}
+
+fn level(obj: &Object) -> Option<Option<i64>> {
+    match args(obj).get(1).map(Object::without_meta) {
+        Some(Object::Integer(level)) => Some(Some(*level)),
+        Some(Object::Null) | None => Some(None),
+        _ => None,
+    }
+}
+
+fn result(data: io::Result<Vec<u8>>) -> Object {
+    data.map_or(Object::Null, |data| Object::from(Bytes(data)))
+}
+
+// Reads what decoder gives up to the most an evaluation may make.
+fn decompress(interpreter: &Interpreter, decoder: impl Read) -> Object {
+    let cap = interpreter
+        .memory_left()
+        .map_or(MAX_OUTPUT, |left| left.min(MAX_OUTPUT));
+    let mut decompressed = Vec::new();
+    match decoder.take(cap as u64 + 1).read_to_end(&mut decompressed) {
+        Ok(read) if read <= cap => Object::from(Bytes(decompressed)),
+        _ => Object::Null,
+    }
+}
+
+// Levels go from 0, no compression, to 9 and default to 6.
+#[cfg(feature = "gzip")]
+fn gzip_compress(obj: &Object) -> Object {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    let (data, level) = match (data(obj), level(obj)) {
+        (Some(data), Some(None)) => (data, Compression::default()),
+        (Some(data), Some(Some(level @ 0..=9))) => (data, Compression::new(level as u32)),
+        _ => return Object::Null,
+    };
+    let mut encoder = GzEncoder::new(Vec::new(), level);
+    result(encoder.write_all(data).and_then(|()| encoder.finish()))
+}
+
+// Several gzip members one after another decompress to their
+// concatenation, as gunzip does.
+#[cfg(feature = "gzip")]
+fn gzip_decompress(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    use flate2::read::MultiGzDecoder;
+    Ok(match data(obj) {
+        Some(data) => decompress(interpreter, MultiGzDecoder::new(data)),
+        None => Object::Null,
+    })
+}
+
+// Levels go up to 22 and default to 3. Negative ones trade ratio for
+// speed.
+#[cfg(feature = "zstd")]
+fn zstd_compress(obj: &Object) -> Object {
+    use std::convert::TryFrom;
+    let level = match level(obj) {
+        Some(None) => 0,
+        Some(Some(level)) => match i32::try_from(level) {
+            Ok(level) if zstd::compression_level_range().contains(&level) => level,
+            _ => return Object::Null,
+        },
+        None => return Object::Null,
+    };
+    match data(obj) {
+        Some(data) => result(zstd::encode_all(data, level)),
+        None => Object::Null,
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decompress(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    Ok(match data(obj).map(zstd::Decoder::new) {
+        Some(Ok(decoder)) => decompress(interpreter, decoder),
+        _ => Object::Null,
+    })
+}
@@ -0,0 +1,169 @@
+use crate::de::{from_object, DeserializeObjectError};
+use crate::environment;
+use crate::evaluator::{EvalError, Evaluator, Sandbox};
+use crate::object::{Object, ParseObjectError};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// What a configuration may call besides the special forms: arithmetic, +
+// on strings, comparisons, lookups, deep-merge for layering and env for
+// reading environment variables.
+const BUILTINS: [&str; 23] = [
+    "+",
+    "-",
+    "*",
+    "/",
+    "%",
+    "<",
+    ">",
+    "=",
+    "not",
+    "get",
+    "deep-merge",
+    "env",
+    "path-join",
+    "path-parent",
+    "path-filename",
+    "path-ext",
+    "url-encode",
+    "url-decode",
+    "html-escape",
+    "base64-encode",
+    "base64-decode",
+    "hex-encode",
+    "hex-decode",
+];
+
+// Configurations should be quick, so one that is still running after this
+// many steps is stopped.
+const FUEL: u64 = 10_000_000;
+
+// Nor may one take more memory than this, which fuel alone would not stop
+// since a string doubles in size with every step.
+const CONFIG_MEMORY: usize = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(ParseObjectError),
+    Eval(EvalError),
+    // The configuration gave something other than a map.
+    NotMap(Object),
+    Deserialize(DeserializeObjectError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "{}", error),
+            ConfigError::Parse(_) => write!(f, "could not parse configuration"),
+            ConfigError::Eval(error) => write!(f, "could not evaluate configuration: {}", error),
+            ConfigError::NotMap(value) => {
+                write!(f, "configuration gives {} instead of a map", value)
+            }
+            ConfigError::Deserialize(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(error) => Some(error),
+            ConfigError::Parse(error) => Some(error),
+            ConfigError::Eval(error) => Some(error),
+            ConfigError::NotMap(_) => None,
+            ConfigError::Deserialize(error) => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+impl From<ParseObjectError> for ConfigError {
+    fn from(error: ParseObjectError) -> Self {
+        ConfigError::Parse(error)
+    }
+}
+
+impl From<EvalError> for ConfigError {
+    fn from(error: EvalError) -> Self {
+        ConfigError::Eval(error)
+    }
+}
+
+impl From<DeserializeObjectError> for ConfigError {
+    fn from(error: DeserializeObjectError) -> Self {
+        ConfigError::Deserialize(error)
+    }
+}
+
+// A pure evaluator with only the builtins above, so a configuration can
+// compute its values but not touch files, the network or processes.
+fn evaluator() -> Evaluator {
+    let evaluator = Evaluator::builder()
+        .modules(&[])
+        .sandbox(Sandbox::pure())
+        .fuel(FUEL)
+        .memory_limit(CONFIG_MEMORY)
+        .primitive("env", environment::get_env)
+        .build();
+    evaluator.retain(|name| BUILTINS.contains(&name));
+    evaluator
+}
+
+// Maps and vectors are data and are not evaluated, so the values in the
+// map a configuration gives are evaluated here, at every depth. Keys are
+// left as they are.
+fn resolve(evaluator: &Evaluator, obj: &Object) -> Result<Object, EvalError> {
+    match obj.without_meta() {
+        Object::Map(map) => {
+            let mut resolved = HashMap::with_capacity(map.len());
+            for (key, value) in map {
+                resolved.insert(key.clone(), resolve(evaluator, value)?);
+            }
+            Ok(Object::Map(resolved))
+        }
+        Object::Vector(items) => Ok(Object::Vector(
+            items
+                .iter()
+                .map(|item| resolve(evaluator, item))
+                .collect::<Result<_, _>>()?,
+        )),
+        obj => evaluator.try_eval(obj),
+    }
+}
+
+// Evaluates a configuration and deserializes the map its last form gives,
+// as in
+//
+//     (set base {port: 8080, debug: false})
+//     (deep-merge base {port: (+ (get base port) 1), user: (env "USER")})
+//
+// A symbol that names nothing is itself, so {mode: production} reads as
+// the string "production".
+pub fn from_str<T: DeserializeOwned>(source: &str) -> Result<T, ConfigError> {
+    let evaluator = evaluator();
+    let mut value = Object::Null;
+    for form in Object::parse_all(source)? {
+        value = evaluator.try_eval(&form)?;
+    }
+    match value.without_meta() {
+        Object::Map(_) => Ok(from_object(resolve(&evaluator, &value)?)?),
+        _ => Err(ConfigError::NotMap(value)),
+    }
+}
+
+// Reads the .fd file at path and loads it as from_str does.
+pub fn load<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, ConfigError> {
+    from_str(&fs::read_to_string(path)?)
+}
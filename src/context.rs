@@ -0,0 +1,69 @@
+use crate::error::Error;
+use crate::evaluator::{EvalError, Evaluator, Globals};
+use crate::interpreter::Interpreter;
+use crate::object::Object;
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+
+// A context reads globals from its evaluator but keeps its own assignments,
+// so many contexts can share one set of builtins and library definitions.
+pub struct Context {
+    evaluator: Arc<Evaluator>,
+    globals: Globals,
+}
+
+impl Context {
+    pub fn new(evaluator: Arc<Evaluator>) -> Context {
+        Context {
+            evaluator,
+            globals: RwLock::new(HashMap::new()),
+        }
+    }
+
+    #[cfg(not(feature = "rc"))]
+    pub(crate) fn with_bindings(
+        evaluator: Arc<Evaluator>,
+        bindings: HashMap<Symbol, Object>,
+    ) -> Context {
+        Context {
+            evaluator,
+            globals: RwLock::new(bindings),
+        }
+    }
+
+    pub fn evaluator(&self) -> &Arc<Evaluator> {
+        &self.evaluator
+    }
+
+    pub fn define(&self, name: &str, value: Object) {
+        self.globals
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(Symbol::new(name), value);
+    }
+
+    fn interpreter(&self) -> Interpreter<'_> {
+        self.evaluator
+            .interpreter(self.evaluator.limits)
+            .with_globals(&self.globals)
+    }
+
+    pub fn eval(&self, obj: &Object) -> Object {
+        self.try_eval(obj).unwrap_or(Object::Null)
+    }
+
+    pub fn try_eval(&self, obj: &Object) -> Result<Object, EvalError> {
+        self.interpreter().eval(obj)
+    }
+
+    pub fn eval_str(&self, source: &str) -> Result<Object, Error> {
+        let forms = Object::parse_all(source)?;
+        let interpreter = self.interpreter();
+        let mut result = Object::Null;
+        for form in &forms {
+            result = interpreter.eval(form)?;
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,77 @@
+use crate::bytes::{self, hex};
+use crate::evaluator::{args, PrimitiveFunction};
+use crate::object::{Bytes, Object};
+use crate::symbol::Symbol;
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+// Each takes a string, hashed as UTF-8, or bytes, and gives the digest as
+// a hex string, or as bytes when the options are {bytes: true}.
+pub(crate) const PRIMITIVES: [(&str, PrimitiveFunction); 5] = [
+    ("sha256", sha256),
+    ("sha1", sha1),
+    ("md5", md5),
+    ("crc32", crc32),
+    ("hmac-sha256", hmac_sha256),
+];
+
+fn data(obj: &Object, index: usize) -> Option<&[u8]> {
+    args(obj).get(index).and_then(bytes::data)
+}
+
+fn output(digest: &[u8], options: Option<&Object>) -> Object {
+    let as_bytes = match options.map(Object::without_meta) {
+        Some(Object::Map(map)) => map
+            .get(&Object::Symbol(Symbol::new("bytes")))
+            .or_else(|| map.get(&Object::from("bytes")))
+            .is_some_and(|value| matches!(value.without_meta(), Object::Bool(true))),
+        _ => false,
+    };
+    match as_bytes {
+        true => Object::from(Bytes(digest.to_vec())),
+        false => Object::from(hex(digest)),
+    }
+}
+
+fn digest<D: Digest>(obj: &Object) -> Object {
+    match data(obj, 0) {
+        Some(data) => output(&D::digest(data), args(obj).get(1)),
+        None => Object::Null,
+    }
+}
+
+fn sha256(obj: &Object) -> Object {
+    digest::<Sha256>(obj)
+}
+
+fn sha1(obj: &Object) -> Object {
+    digest::<Sha1>(obj)
+}
+
+fn md5(obj: &Object) -> Object {
+    digest::<Md5>(obj)
+}
+
+// The checksum's four bytes are big-endian, so its hex string reads as
+// the number would.
+fn crc32(obj: &Object) -> Object {
+    match data(obj, 0) {
+        Some(data) => output(&crc32fast::hash(data).to_be_bytes(), args(obj).get(1)),
+        None => Object::Null,
+    }
+}
+
+// (hmac-sha256 key message options)
+fn hmac_sha256(obj: &Object) -> Object {
+    let mac = match (data(obj, 0), data(obj, 1)) {
+        (Some(key), Some(message)) => Hmac::<Sha256>::new_from_slice(key)
+            .map(|mac| mac.chain_update(message).finalize().into_bytes()),
+        _ => return Object::Null,
+    };
+    match mac {
+        Ok(mac) => output(&mac, args(obj).get(2)),
+        Err(_) => Object::Null,
+    }
+}
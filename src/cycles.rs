@@ -0,0 +1,317 @@
+use crate::compiler::Proto;
+#[cfg(not(feature = "rc"))]
+use crate::evaluator::{Channel, Thread, ThreadState};
+use crate::evaluator::{
+    Composed, Continuation, Function, Generator, GeneratorState, MemoCache, Memoized, Partial,
+    Traced,
+};
+use crate::object::{AnyValue, Object, Shared, WithMeta};
+use std::collections::hash_map::{Entry, HashMap};
+use std::mem;
+use std::sync::MutexGuard;
+
+#[cfg(not(feature = "rc"))]
+type Weak<T> = std::sync::Weak<T>;
+#[cfg(feature = "rc")]
+type Weak<T> = std::rc::Weak<T>;
+
+// Reference counting frees everything but cycles. Closures copy what they
+// capture and most objects never change once made, so a cycle can only
+// pass through an object whose contents change later: a channel, the
+// cache of a memoized function, a generator or a finished thread. Those
+// are registered with their evaluator when made, and once enough are, the
+// ones that nothing outside them can reach are emptied, which frees the
+// whole cycle.
+//
+// As in CPython, an object is reachable from outside when it has more
+// references than the objects found from the registered ones hold, or is
+// reached from one that has. Nothing stops the world while this is worked
+// out, so contents are read under their locks, and an object whose lock is
+// held elsewhere, like a generator that is running, counts as reachable.
+
+// Fewer registered objects than this are not worth a collection.
+const MIN_THRESHOLD: usize = 1024;
+
+#[derive(Default)]
+pub(crate) struct Registry {
+    objects: Vec<Weak<AnyValue>>,
+    threshold: usize,
+}
+
+impl Registry {
+    // Registers obj and tells whether enough have been since the last
+    // collection that one is due.
+    pub(crate) fn track(&mut self, obj: &Shared<AnyValue>) -> bool {
+        self.objects.push(Shared::downgrade(obj));
+        self.objects.len() >= self.threshold.max(MIN_THRESHOLD)
+    }
+
+    // The registered objects still alive, which are where collection
+    // starts.
+    pub(crate) fn roots(&mut self) -> Vec<Shared<AnyValue>> {
+        self.objects.retain(|obj| obj.strong_count() > 0);
+        self.objects.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    // Collections come less often as more registered objects stay alive,
+    // so that their cost stays in proportion to what is made.
+    pub(crate) fn collected(&mut self) {
+        self.objects.retain(|obj| obj.strong_count() > 0);
+        self.threshold = 2 * self.objects.len();
+    }
+}
+
+enum Node {
+    Value(Shared<AnyValue>),
+    Proto(Shared<Proto>),
+}
+
+impl Node {
+    fn address(&self) -> usize {
+        match self {
+            Node::Value(value) => Shared::as_ptr(value) as *const () as usize,
+            Node::Proto(proto) => Shared::as_ptr(proto) as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::Value(value) => Shared::strong_count(value),
+            Node::Proto(proto) => Shared::strong_count(proto),
+        }
+    }
+}
+
+// The lock on a node's changing contents, or why there is none.
+enum Contents<'a> {
+    Fixed,
+    Busy,
+    Generator(MutexGuard<'a, GeneratorState>),
+    Memoized(MutexGuard<'a, MemoCache>),
+    #[cfg(not(feature = "rc"))]
+    Thread(MutexGuard<'a, ThreadState>),
+    #[cfg(not(feature = "rc"))]
+    Channel(MutexGuard<'a, HashMap<u64, Object>>),
+}
+
+fn lock(node: &Node) -> Contents<'_> {
+    let value = match node {
+        Node::Value(value) => value,
+        Node::Proto(_) => return Contents::Fixed,
+    };
+    if let Some(generator) = value.downcast_ref::<Generator>() {
+        return generator
+            .state
+            .try_lock()
+            .map_or(Contents::Busy, Contents::Generator);
+    }
+    if let Some(memoized) = value.downcast_ref::<Memoized>() {
+        return memoized
+            .cache
+            .try_lock()
+            .map_or(Contents::Busy, Contents::Memoized);
+    }
+    #[cfg(not(feature = "rc"))]
+    if let Some(thread) = value.downcast_ref::<Thread>() {
+        return thread
+            .state
+            .try_lock()
+            .map_or(Contents::Busy, Contents::Thread);
+    }
+    #[cfg(not(feature = "rc"))]
+    if let Some(channel) = value.downcast_ref::<Channel>() {
+        return channel
+            .values
+            .try_lock()
+            .map_or(Contents::Busy, Contents::Channel);
+    }
+    Contents::Fixed
+}
+
+fn push_values(obj: &Object, children: &mut Vec<Node>) {
+    match obj {
+        Object::List(items) | Object::Vector(items) => {
+            items.iter().for_each(|item| push_values(item, children))
+        }
+        Object::Map(map) => map.iter().for_each(|(key, value)| {
+            push_values(key, children);
+            push_values(value, children);
+        }),
+        Object::Other(value) => children.push(Node::Value(value.clone())),
+        _ => {}
+    }
+}
+
+// What node holds, or None when that cannot be known, as for a thread
+// still running. Objects of types not named here are taken to hold
+// nothing, which can only keep a cycle through them from being freed.
+fn children(node: &Node, contents: &Contents) -> Option<Vec<Node>> {
+    let mut children = Vec::new();
+    match contents {
+        Contents::Busy => return None,
+        Contents::Generator(state) => state
+            .stack
+            .iter()
+            .for_each(|obj| push_values(obj, &mut children)),
+        #[cfg(not(feature = "rc"))]
+        Contents::Thread(state) => match &**state {
+            ThreadState::Running(_) => return None,
+            ThreadState::Joined(result) => result
+                .iter()
+                .for_each(|obj| push_values(obj, &mut children)),
+        },
+        Contents::Memoized(cache) => cache.entries.iter().for_each(|(args, (value, _))| {
+            args.iter().for_each(|obj| push_values(obj, &mut children));
+            push_values(value, &mut children);
+        }),
+        #[cfg(not(feature = "rc"))]
+        Contents::Channel(values) => values
+            .values()
+            .for_each(|obj| push_values(obj, &mut children)),
+        Contents::Fixed => {}
+    }
+    let value = match node {
+        Node::Value(value) => value,
+        Node::Proto(proto) => {
+            proto
+                .body
+                .iter()
+                .for_each(|obj| push_values(obj, &mut children));
+            if let Some(chunk) = &proto.chunk {
+                chunk
+                    .constants
+                    .iter()
+                    .for_each(|obj| push_values(obj, &mut children));
+                for closure in &chunk.closures {
+                    children.push(Node::Proto(closure.proto.clone()));
+                }
+            }
+            return Some(children);
+        }
+    };
+    if let Some(function) = value.downcast_ref::<Function>() {
+        children.push(Node::Proto(function.proto.clone()));
+        function
+            .captured
+            .iter()
+            .for_each(|obj| push_values(obj, &mut children));
+    } else if let Some(generator) = value.downcast_ref::<Generator>() {
+        children.push(Node::Proto(generator.proto.clone()));
+    } else if let Some(continuation) = value.downcast_ref::<Continuation>() {
+        for (proto, ..) in &continuation.frames {
+            children.push(Node::Proto(proto.clone()));
+        }
+        continuation
+            .stack
+            .iter()
+            .for_each(|obj| push_values(obj, &mut children));
+    } else if let Some(partial) = value.downcast_ref::<Partial>() {
+        push_values(&partial.function, &mut children);
+        partial
+            .args
+            .iter()
+            .for_each(|obj| push_values(obj, &mut children));
+    } else if let Some(composed) = value.downcast_ref::<Composed>() {
+        composed
+            .functions
+            .iter()
+            .for_each(|obj| push_values(obj, &mut children));
+    } else if let Some(memoized) = value.downcast_ref::<Memoized>() {
+        push_values(&memoized.function, &mut children);
+    } else if let Some(traced) = value.downcast_ref::<Traced>() {
+        push_values(&traced.function, &mut children);
+    } else if let Some(with_meta) = value.downcast_ref::<WithMeta>() {
+        push_values(&with_meta.value, &mut children);
+        push_values(&with_meta.meta, &mut children);
+    }
+    Some(children)
+}
+
+// Empties what a node unreachable from outside holds. What it held is
+// returned to be dropped once no lock is held, since dropping it may free
+// other objects.
+fn clear(contents: &mut Contents, cleared: &mut Vec<Object>) {
+    match contents {
+        Contents::Generator(state) => {
+            cleared.append(&mut mem::take(&mut state.stack));
+            state.done = true;
+        }
+        Contents::Memoized(cache) => cleared.extend(cache.take()),
+        #[cfg(not(feature = "rc"))]
+        Contents::Thread(state) => {
+            if let ThreadState::Joined(result) = &mut **state {
+                cleared.extend(result.take());
+            }
+        }
+        #[cfg(not(feature = "rc"))]
+        Contents::Channel(values) => cleared.extend(values.drain().map(|(_, obj)| obj)),
+        Contents::Fixed | Contents::Busy => {}
+    }
+}
+
+// Frees the cycles among what roots reach and gives how many objects were
+// in them.
+pub(crate) fn collect(roots: Vec<Shared<AnyValue>>) -> usize {
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut index = HashMap::new();
+    let mut found: Vec<Node> = roots.into_iter().map(Node::Value).collect();
+    let mut next = 0;
+    loop {
+        for node in found {
+            if let Entry::Vacant(entry) = index.entry(node.address()) {
+                entry.insert(nodes.len());
+                nodes.push(node);
+            }
+        }
+        if next == nodes.len() {
+            break;
+        }
+        found = children(&nodes[next], &lock(&nodes[next])).unwrap_or_default();
+        next += 1;
+    }
+    // Contents may have changed since they were first read, so they are
+    // read again with every lock held until the cycles are emptied. What
+    // an object not seen the first time holds counts as referenced from
+    // outside.
+    let mut contents: Vec<Contents> = nodes.iter().map(lock).collect();
+    let mut edges = vec![Vec::new(); nodes.len()];
+    let mut inside = vec![0; nodes.len()];
+    let mut reachable = vec![false; nodes.len()];
+    for (i, node) in nodes.iter().enumerate() {
+        match children(node, &contents[i]) {
+            Some(found) => {
+                for child in found {
+                    if let Some(&j) = index.get(&child.address()) {
+                        edges[i].push(j);
+                        inside[j] += 1;
+                    }
+                }
+            }
+            None => reachable[i] = true,
+        }
+    }
+    // Each node's count includes the reference held in nodes.
+    let mut pending: Vec<usize> = (0..nodes.len())
+        .filter(|&i| reachable[i] || nodes[i].strong_count() > inside[i] + 1)
+        .collect();
+    pending.iter().for_each(|&i| reachable[i] = true);
+    while let Some(i) = pending.pop() {
+        for &j in &edges[i] {
+            if !reachable[j] {
+                reachable[j] = true;
+                pending.push(j);
+            }
+        }
+    }
+    let mut cleared = Vec::new();
+    let mut freed = 0;
+    for (i, contents) in contents.iter_mut().enumerate() {
+        if !reachable[i] {
+            clear(contents, &mut cleared);
+            freed += 1;
+        }
+    }
+    drop(contents);
+    drop(cleared);
+    freed
+}
@@ -0,0 +1,777 @@
+use crate::context::Context;
+use crate::debugger::{Breakpoint, Debugger, Pause, Step};
+use crate::evaluator::{EvalError, Evaluator};
+use crate::object::{DisplayLimits, Object};
+use crate::server::authorizes;
+use crate::symbol::Symbol;
+use crossbeam_channel::{self as channel, Receiver, Sender};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+
+// Programs run on a single thread, and the variables of a stop are the
+// locals and globals scopes followed by the containers expanded so far.
+const THREAD: u64 = 1;
+const LOCALS: u64 = 1;
+const GLOBALS: u64 = 2;
+
+// Messages longer than this are refused rather than read.
+const MAX_MESSAGE: usize = 4 * 1024 * 1024;
+
+const FRAME_LIMITS: DisplayLimits = DisplayLimits {
+    max_elements: 4,
+    max_depth: 2,
+};
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn invalid(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+// One message framed by a Content-Length header, or None at the end of the
+// input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    let length = loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            match length {
+                Some(length) => break length,
+                None => continue,
+            }
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = Some(value.trim().parse::<usize>().map_err(invalid)?);
+            }
+        }
+    };
+    if length > MAX_MESSAGE {
+        return Err(invalid(format!("message of {} bytes is too long", length)));
+    }
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(invalid)
+}
+
+// Parsed objects carry no positions, so the lines of a program are found by
+// scanning its source for lists and parsing each one on its own. A form
+// that appears more than once is placed at its first occurrence.
+struct Lines {
+    forms: Vec<(u64, Object)>,
+    index: HashMap<Object, usize>,
+}
+
+impl Lines {
+    fn new(source: &str) -> Lines {
+        let mut forms = Vec::new();
+        let mut open = Vec::new();
+        let mut line = 1;
+        let mut chars = source.char_indices();
+        if source.starts_with("#!") {
+            chars.find(|&(_, c)| c == '\n');
+            line += 1;
+        }
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\n' => line += 1,
+                '"' => {
+                    while let Some((_, c)) = chars.next() {
+                        match c {
+                            '"' => break,
+                            '\n' => line += 1,
+                            '\\' => {
+                                if let Some((_, '\n')) = chars.next() {
+                                    line += 1;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                '(' | '[' | '{' => open.push((index, line, c == '(')),
+                ')' | ']' | '}' => {
+                    if let Some((start, line, true)) = open.pop() {
+                        if let Ok(form) = source[start..=index].parse::<Object>() {
+                            forms.push((start, line, form));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        forms.sort_by_key(|&(start, _, _)| start);
+        let forms: Vec<(u64, Object)> = forms
+            .into_iter()
+            .map(|(_, line, form)| (line, form))
+            .collect();
+        let mut index = HashMap::new();
+        for (position, (_, form)) in forms.iter().enumerate() {
+            index.entry(form.clone()).or_insert(position);
+        }
+        Lines { forms, index }
+    }
+
+    fn find(&self, form: &Object) -> Option<usize> {
+        match form {
+            Object::List(_) => self.index.get(form).copied(),
+            _ => None,
+        }
+    }
+
+    // The first form starting on the line, or after it when none does.
+    fn at(&self, line: u64) -> Option<&(u64, Object)> {
+        self.forms.iter().find(|(start, _)| *start >= line)
+    }
+}
+
+struct Program {
+    path: PathBuf,
+    source: String,
+    lines: Lines,
+    globals: Vec<Symbol>,
+    args: Vec<String>,
+    stop_on_entry: bool,
+    no_debug: bool,
+}
+
+// Names the program assigns at the top level, shown as its globals.
+fn globals(source: &str) -> Vec<Symbol> {
+    let mut names = Vec::new();
+    for form in Object::parse_all(source).unwrap_or_default() {
+        if let Object::List(list) = form {
+            if let [Object::Symbol(head), Object::Symbol(name), ..] = list.as_slice() {
+                let assigns = ["set", "defdynamic"].contains(&head.as_str());
+                if assigns && !names.contains(name) {
+                    names.push(*name);
+                }
+            }
+        }
+    }
+    names
+}
+
+struct Frame {
+    name: String,
+    line: u64,
+}
+
+struct Stopped {
+    frames: Vec<Frame>,
+    locals: Vec<(String, Object)>,
+    globals: Vec<(String, Object)>,
+    references: Vec<Object>,
+}
+
+impl Stopped {
+    // A reference to expand value by, or 0 when it has nothing inside.
+    fn reference(&mut self, value: &Object) -> u64 {
+        match value.without_meta() {
+            Object::List(items) | Object::Vector(items) if !items.is_empty() => {}
+            Object::Map(map) if !map.is_empty() => {}
+            _ => return 0,
+        }
+        self.references.push(value.clone());
+        self.references.len() as u64 + GLOBALS
+    }
+}
+
+fn children(value: &Object) -> Vec<(String, Object)> {
+    match value.without_meta() {
+        Object::List(items) | Object::Vector(items) => items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (format!("[{}]", index), item.clone()))
+            .collect(),
+        Object::Map(map) => {
+            let mut entries: Vec<(String, Object)> = map
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            entries
+        }
+        _ => Vec::new(),
+    }
+}
+
+enum Command {
+    Resume(Step),
+    Evaluate(String, Sender<Result<Object, String>>),
+}
+
+// The state shared by the thread reading requests and the one running the
+// program.
+struct Session {
+    writer: Mutex<Box<dyn Write + Send>>,
+    seq: AtomicU64,
+    breakpoints: Mutex<Vec<Breakpoint>>,
+    function_breakpoints: Mutex<Vec<Breakpoint>>,
+    mode: Mutex<(Step, usize)>,
+    frames: Mutex<Vec<(usize, usize)>>,
+    stopped: Mutex<Option<Stopped>>,
+    pausing: AtomicBool,
+    aborting: AtomicBool,
+    evaluating: AtomicBool,
+    commands: (Sender<Command>, Receiver<Command>),
+}
+
+impl Session {
+    fn send(&self, mut message: Value) {
+        message["seq"] = json!(self.seq.fetch_add(1, Ordering::Relaxed) + 1);
+        let body = message.to_string();
+        let mut writer = lock(&self.writer);
+        let _ = write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+            .and_then(|_| writer.flush());
+    }
+
+    fn event(&self, event: &str, body: Value) {
+        let mut message = json!({"type": "event", "event": event});
+        if !body.is_null() {
+            message["body"] = body;
+        }
+        self.send(message);
+    }
+
+    fn respond(&self, request: &Value, result: Result<Value, String>) {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response);
+    }
+}
+
+struct Tracker {
+    session: Arc<Session>,
+    program: Arc<Program>,
+}
+
+impl Tracker {
+    // Why to stop before the form, if at all. The interpreter is always
+    // asked to step in, so breakpoints and steps are decided here and
+    // carry over from one top-level form to the next.
+    fn reason(&self, form: &Object, depth: usize) -> Option<&'static str> {
+        let session = &self.session;
+        if session.pausing.swap(false, Ordering::Relaxed) {
+            return Some("pause");
+        }
+        let matches = |breakpoints: &Mutex<Vec<Breakpoint>>| {
+            lock(breakpoints)
+                .iter()
+                .any(|breakpoint| breakpoint.matches(form))
+        };
+        if matches(&session.breakpoints) {
+            return Some("breakpoint");
+        }
+        if matches(&session.function_breakpoints) {
+            return Some("function breakpoint");
+        }
+        match *lock(&session.mode) {
+            (Step::StepIn, 0) => Some("entry"),
+            (Step::StepIn, _) => Some("step"),
+            (Step::StepOver, from) if depth <= from => Some("step"),
+            (Step::StepOut, from) if depth < from => Some("step"),
+            _ => None,
+        }
+    }
+
+    fn stopped(&self, pause: &Pause) -> Stopped {
+        let lines = &self.program.lines;
+        let mut frames: Vec<Frame> = lock(&self.session.frames)
+            .iter()
+            .rev()
+            .map(|&(_, index)| {
+                let (line, form) = &lines.forms[index];
+                Frame {
+                    name: form.display_with(FRAME_LIMITS).to_string(),
+                    line: *line,
+                }
+            })
+            .collect();
+        if lines.find(pause.form()).is_none() {
+            frames.insert(
+                0,
+                Frame {
+                    name: pause.form().display_with(FRAME_LIMITS).to_string(),
+                    line: frames.first().map_or(0, |frame| frame.line),
+                },
+            );
+        }
+        // Shadowed locals are left out.
+        let mut seen = HashSet::new();
+        let mut locals: Vec<(String, Object)> = pause
+            .locals()
+            .iter()
+            .rev()
+            .filter(|(symbol, _)| seen.insert(*symbol))
+            .map(|(symbol, value)| (symbol.as_str().to_string(), value.clone()))
+            .collect();
+        locals.reverse();
+        let globals = self
+            .program
+            .globals
+            .iter()
+            .map(|name| (name.as_str().to_string(), pause.lookup(name.as_str())))
+            .collect();
+        Stopped {
+            frames,
+            locals,
+            globals,
+            references: Vec::new(),
+        }
+    }
+}
+
+// Expressions from the client are evaluated where the program stopped,
+// seeing its locals. Stops inside them are skipped.
+fn evaluate(pause: &Pause, source: &str) -> Result<Object, String> {
+    let forms = Object::parse_all(source).map_err(|_| "could not parse input".to_string())?;
+    let mut result = Object::Null;
+    for form in &forms {
+        result = pause
+            .interpreter
+            .attempt(|| {
+                pause
+                    .interpreter
+                    .eval_with_bindings(form, pause.locals().to_vec())
+            })
+            .map_err(|error| format!("evaluation failed: {}", error))?;
+    }
+    Ok(result)
+}
+
+impl Debugger for Tracker {
+    fn pause(&self, pause: &Pause) -> Step {
+        let session = &self.session;
+        if session.evaluating.load(Ordering::Relaxed) {
+            return Step::StepIn;
+        }
+        if session.aborting.load(Ordering::Relaxed) {
+            return Step::Abort;
+        }
+        let depth = pause.depth();
+        {
+            let mut frames = lock(&session.frames);
+            while frames.last().is_some_and(|&(other, _)| other >= depth) {
+                frames.pop();
+            }
+            if let Some(index) = self.program.lines.find(pause.form()) {
+                frames.push((depth, index));
+            }
+        }
+        let reason = match self.reason(pause.form(), depth) {
+            Some(reason) => reason,
+            None => return Step::StepIn,
+        };
+        *lock(&session.stopped) = Some(self.stopped(pause));
+        session.event(
+            "stopped",
+            json!({"reason": reason, "threadId": THREAD, "allThreadsStopped": true}),
+        );
+        loop {
+            match session.commands.1.recv() {
+                Ok(Command::Resume(step)) => {
+                    *lock(&session.mode) = (step, depth);
+                    *lock(&session.stopped) = None;
+                    return match step {
+                        Step::Abort => Step::Abort,
+                        _ => Step::StepIn,
+                    };
+                }
+                Ok(Command::Evaluate(source, reply)) => {
+                    session.evaluating.store(true, Ordering::Relaxed);
+                    let result = evaluate(pause, &source);
+                    session.evaluating.store(false, Ordering::Relaxed);
+                    let _ = reply.send(result);
+                }
+                Err(_) => return Step::Abort,
+            }
+        }
+    }
+}
+
+// A Debug Adapter Protocol server for one program. The client launches a
+// .fd file, sets breakpoints on lines, which stop at the first form
+// starting there, or on function names, steps through it and inspects the
+// locals and globals where it stopped. Whatever the program prints goes to
+// the process's stdout; output is how it can be passed to the client
+// instead.
+//
+// With a token, the client must give it as the token argument of launch.
+// Until it does, initialize is answered and any other request ends the
+// session.
+#[derive(Clone)]
+pub struct Adapter {
+    session: Arc<Session>,
+    evaluator: Arc<Evaluator>,
+    token: Option<Arc<str>>,
+}
+
+impl Adapter {
+    pub fn new(evaluator: Arc<Evaluator>, output: impl Write + Send + 'static) -> Adapter {
+        Adapter {
+            session: Arc::new(Session {
+                writer: Mutex::new(Box::new(output)),
+                seq: AtomicU64::new(0),
+                breakpoints: Mutex::new(Vec::new()),
+                function_breakpoints: Mutex::new(Vec::new()),
+                mode: Mutex::new((Step::Continue, 0)),
+                frames: Mutex::new(Vec::new()),
+                stopped: Mutex::new(None),
+                pausing: AtomicBool::new(false),
+                aborting: AtomicBool::new(false),
+                evaluating: AtomicBool::new(false),
+                commands: channel::unbounded(),
+            }),
+            evaluator,
+            token: None,
+        }
+    }
+
+    pub fn token(mut self, token: &str) -> Adapter {
+        self.token = Some(token.into());
+        self
+    }
+
+    // Sends text to the client as program output of the given category,
+    // such as stdout or stderr.
+    pub fn output(&self, category: &str, text: &str) {
+        self.session
+            .event("output", json!({"category": category, "output": text}));
+    }
+
+    // Handles requests until the client disconnects or the input ends,
+    // stopping the program if it still runs.
+    pub fn serve(&self, mut input: impl BufRead) -> io::Result<()> {
+        let session = &self.session;
+        let mut program = None;
+        let mut configured = false;
+        let mut running: Option<JoinHandle<()>> = None;
+        let mut authorized = self.token.is_none();
+        while let Some(request) = read_message(&mut input)? {
+            if request["type"] != "request" {
+                continue;
+            }
+            let command = request["command"].as_str().unwrap_or_default();
+            let arguments = &request["arguments"];
+            if !authorized && command != "initialize" {
+                let token = arguments["token"].as_str().unwrap_or_default();
+                authorized = command == "launch" && authorizes(self.token.as_deref(), token);
+                if !authorized {
+                    session.respond(&request, Err("unauthorized".into()));
+                    break;
+                }
+            }
+            match command {
+                "launch" => match launch(arguments) {
+                    Ok(launched) => {
+                        program = Some(Arc::new(launched));
+                        session.respond(&request, Ok(Value::Null));
+                        session.event("initialized", Value::Null);
+                    }
+                    Err(message) => session.respond(&request, Err(message)),
+                },
+                "configurationDone" => {
+                    configured = true;
+                    session.respond(&request, Ok(Value::Null));
+                }
+                "continue" | "next" | "stepIn" | "stepOut" => {
+                    let step = match command {
+                        "continue" => Step::Continue,
+                        "next" => Step::StepOver,
+                        "stepIn" => Step::StepIn,
+                        _ => Step::StepOut,
+                    };
+                    if lock(&session.stopped).is_none() {
+                        session.respond(&request, Err("the program is not stopped".into()));
+                        continue;
+                    }
+                    let body = match step {
+                        Step::Continue => json!({"allThreadsContinued": true}),
+                        _ => Value::Null,
+                    };
+                    session.respond(&request, Ok(body));
+                    let _ = session.commands.0.send(Command::Resume(step));
+                }
+                "evaluate" => {
+                    let result = self.evaluate(arguments);
+                    session.respond(&request, result);
+                }
+                "terminate" | "disconnect" => {
+                    self.abort();
+                    session.respond(&request, Ok(Value::Null));
+                    if command == "disconnect" {
+                        break;
+                    }
+                }
+                _ => {
+                    let result = self.request(command, arguments, program.as_deref());
+                    session.respond(&request, result);
+                }
+            }
+            if let (Some(program), true, None) = (&program, configured, &running) {
+                running = Some(self.run(program.clone()));
+            }
+        }
+        self.abort();
+        if let Some(running) = running {
+            let _ = running.join();
+        }
+        Ok(())
+    }
+
+    fn request(
+        &self,
+        command: &str,
+        arguments: &Value,
+        program: Option<&Program>,
+    ) -> Result<Value, String> {
+        let session = &self.session;
+        match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsFunctionBreakpoints": true,
+                "supportsEvaluateForHovers": true,
+                "supportsTerminateRequest": true,
+            })),
+            "setBreakpoints" => {
+                let program = program.ok_or("no program has been launched")?;
+                let path = arguments["source"]["path"].as_str().unwrap_or_default();
+                let ours = same_file(Path::new(path), &program.path);
+                let mut breakpoints = Vec::new();
+                let mut verified = Vec::new();
+                for requested in arguments["breakpoints"].as_array().into_iter().flatten() {
+                    let line = requested["line"].as_u64().unwrap_or(0);
+                    match program.lines.at(line).filter(|_| ours) {
+                        Some((line, form)) => {
+                            breakpoints.push(Breakpoint::Form(form.clone()));
+                            verified.push(json!({"verified": true, "line": line}));
+                        }
+                        None => verified.push(json!({
+                            "verified": false,
+                            "line": line,
+                            "message": "no form starts on or after this line",
+                        })),
+                    }
+                }
+                if ours {
+                    *lock(&session.breakpoints) = breakpoints;
+                }
+                Ok(json!({ "breakpoints": verified }))
+            }
+            "setFunctionBreakpoints" => {
+                let names: Vec<&str> = arguments["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|requested| requested["name"].as_str())
+                    .collect();
+                *lock(&session.function_breakpoints) = names
+                    .iter()
+                    .map(|name| Breakpoint::function(name))
+                    .collect();
+                let verified: Vec<Value> =
+                    names.iter().map(|_| json!({"verified": true})).collect();
+                Ok(json!({ "breakpoints": verified }))
+            }
+            "setExceptionBreakpoints" => Ok(json!({ "breakpoints": [] })),
+            "threads" => Ok(json!({"threads": [{"id": THREAD, "name": "main"}]})),
+            "pause" => {
+                session.pausing.store(true, Ordering::Relaxed);
+                Ok(Value::Null)
+            }
+            "stackTrace" => {
+                let stopped = lock(&session.stopped);
+                let stopped = stopped.as_ref().ok_or("the program is not stopped")?;
+                let program = program.ok_or("no program has been launched")?;
+                let source = json!({
+                    "name": program.path.file_name().map(|name| name.to_string_lossy()),
+                    "path": program.path,
+                });
+                let frames: Vec<Value> = stopped
+                    .frames
+                    .iter()
+                    .enumerate()
+                    .map(|(id, frame)| {
+                        json!({
+                            "id": id,
+                            "name": frame.name,
+                            "source": source,
+                            "line": frame.line,
+                            "column": 1,
+                        })
+                    })
+                    .collect();
+                Ok(json!({"stackFrames": frames, "totalFrames": frames.len()}))
+            }
+            "scopes" => Ok(json!({"scopes": [
+                {"name": "Locals", "variablesReference": LOCALS, "expensive": false},
+                {"name": "Globals", "variablesReference": GLOBALS, "expensive": false},
+            ]})),
+            "variables" => {
+                let mut stopped = lock(&session.stopped);
+                let stopped = stopped.as_mut().ok_or("the program is not stopped")?;
+                let reference = arguments["variablesReference"].as_u64().unwrap_or(0);
+                let entries = match reference {
+                    LOCALS => stopped.locals.clone(),
+                    GLOBALS => stopped.globals.clone(),
+                    _ => reference
+                        .checked_sub(GLOBALS + 1)
+                        .and_then(|index| stopped.references.get(index as usize))
+                        .map(children)
+                        .ok_or("unknown variables reference")?,
+                };
+                let variables: Vec<Value> = entries
+                    .into_iter()
+                    .map(|(name, value)| {
+                        json!({
+                            "name": name,
+                            "value": value.display_with(DisplayLimits::default()).to_string(),
+                            "variablesReference": stopped.reference(&value),
+                        })
+                    })
+                    .collect();
+                Ok(json!({ "variables": variables }))
+            }
+            _ => Err(format!("unsupported request {}", command)),
+        }
+    }
+
+    fn evaluate(&self, arguments: &Value) -> Result<Value, String> {
+        let session = &self.session;
+        if lock(&session.stopped).is_none() {
+            return Err("expressions can only be evaluated while the program is stopped".into());
+        }
+        let expression = arguments["expression"].as_str().unwrap_or_default();
+        let (reply, result) = channel::bounded(1);
+        session
+            .commands
+            .0
+            .send(Command::Evaluate(expression.to_string(), reply))
+            .map_err(|error| error.to_string())?;
+        let value = result.recv().map_err(|error| error.to_string())??;
+        let reference = lock(&session.stopped)
+            .as_mut()
+            .map_or(0, |stopped| stopped.reference(&value));
+        Ok(json!({
+            "result": value.display_with(DisplayLimits::default()).to_string(),
+            "variablesReference": reference,
+        }))
+    }
+
+    // Stops the program wherever it is: at a stop, at the next one or at
+    // its next step.
+    fn abort(&self) {
+        self.session.aborting.store(true, Ordering::Relaxed);
+        let _ = self.session.commands.0.send(Command::Resume(Step::Abort));
+        self.evaluator.interrupt_handle().interrupt();
+    }
+
+    fn run(&self, program: Arc<Program>) -> JoinHandle<()> {
+        let session = self.session.clone();
+        let evaluator = self.evaluator.clone();
+        if !program.no_debug {
+            *lock(&session.mode) = match program.stop_on_entry {
+                true => (Step::StepIn, 0),
+                false => (Step::Continue, 0),
+            };
+            evaluator.attach_debugger(Arc::new(Tracker {
+                session: session.clone(),
+                program: program.clone(),
+            }));
+        }
+        thread::spawn(move || {
+            let context = Context::new(evaluator.clone());
+            context.define("*argv*", Object::from(program.args.clone()));
+            let path = std::path::absolute(&program.path).unwrap_or_else(|_| program.path.clone());
+            let dir = path.parent().map(|dir| dir.to_string_lossy().into_owned());
+            context.define(
+                "*script-path*",
+                Object::from(path.to_string_lossy().into_owned()),
+            );
+            context.define("*script-dir*", Object::from(dir));
+            let mut code = 0;
+            match Object::parse_all(&program.source) {
+                Ok(forms) => {
+                    for form in &forms {
+                        code = match context.try_eval(form) {
+                            Ok(_) => continue,
+                            Err(EvalError::Exit(code)) => code,
+                            Err(EvalError::Interrupted) => 1,
+                            Err(error) => {
+                                let message = format!("evaluation failed: {}\n", error);
+                                session.event(
+                                    "output",
+                                    json!({"category": "stderr", "output": message}),
+                                );
+                                1
+                            }
+                        };
+                        break;
+                    }
+                }
+                Err(_) => {
+                    session.event(
+                        "output",
+                        json!({"category": "stderr", "output": "syntax error: could not parse input\n"}),
+                    );
+                    code = 1;
+                }
+            }
+            evaluator.detach_debugger();
+            let _ = io::stdout().flush();
+            session.event("exited", json!({ "exitCode": code }));
+            session.event("terminated", Value::Null);
+        })
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn launch(arguments: &Value) -> Result<Program, String> {
+    let path = arguments["program"]
+        .as_str()
+        .ok_or("launch needs a program to run")?;
+    let source =
+        fs::read_to_string(path).map_err(|error| format!("could not read {}: {}", path, error))?;
+    let args = arguments["args"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|arg| arg.as_str().map(str::to_string))
+        .collect();
+    Ok(Program {
+        path: PathBuf::from(path),
+        lines: Lines::new(&source),
+        globals: globals(&source),
+        source,
+        args,
+        stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+        no_debug: arguments["noDebug"].as_bool().unwrap_or(false),
+    })
+}
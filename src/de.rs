@@ -0,0 +1,271 @@
+use crate::object::Object;
+use crate::symbol::Symbol;
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{
+    self, Deserialize, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess,
+    SeqAccess, VariantAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::result::Result;
+
+struct ObjectVisitor;
+
+impl<'de> Visitor<'de> for ObjectVisitor {
+    type Value = Object;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "any value representable as an object")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Object, E> {
+        Ok(Object::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Object, E> {
+        Ok(Object::Integer(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Object, E> {
+        if v > i64::MAX as u64 {
+            return Ok(Object::Float(v as f64));
+        }
+        Ok(Object::Integer(v as i64))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Object, E> {
+        Ok(Object::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Object, E> {
+        Ok(Object::String(v.into()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Object, E> {
+        Ok(Object::String(v.into()))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Object, E> {
+        Ok(Object::Null)
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Object, D::Error> {
+        Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Object, E> {
+        Ok(Object::Null)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Object, A::Error> {
+        let mut vector = Vec::new();
+        while let Some(obj) = seq.next_element()? {
+            vector.push(obj);
+        }
+        Ok(Object::Vector(vector))
+    }
+
+    // String keys become symbols, as in map literals, unless the symbol
+    // table is full; see Symbol::try_new.
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Object, A::Error> {
+        let mut map = HashMap::new();
+        while let Some((key, value)) = access.next_entry()? {
+            let key = match key {
+                Object::String(s) => Symbol::try_new(&s).map_or(Object::String(s), Object::Symbol),
+                _ => key,
+            };
+            map.insert(key, value);
+        }
+        Ok(Object::Map(map))
+    }
+}
+
+impl<'de> Deserialize<'de> for Object {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ObjectVisitor)
+    }
+}
+
+#[derive(Debug)]
+pub struct DeserializeObjectError(String);
+
+impl fmt::Display for DeserializeObjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for DeserializeObjectError {}
+
+impl de::Error for DeserializeObjectError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DeserializeObjectError(msg.to_string())
+    }
+}
+
+pub fn from_object<T: DeserializeOwned>(obj: Object) -> Result<T, DeserializeObjectError> {
+    T::deserialize(obj)
+}
+
+impl<'de> IntoDeserializer<'de, DeserializeObjectError> for Object {
+    type Deserializer = Object;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+fn visit_vector<'de, V: Visitor<'de>>(
+    vector: Vec<Object>,
+    visitor: V,
+) -> Result<V::Value, DeserializeObjectError> {
+    let mut seq = SeqDeserializer::new(vector.into_iter());
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+impl<'de> de::Deserializer<'de> for Object {
+    type Error = DeserializeObjectError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Object::Null => visitor.visit_unit(),
+            Object::Bool(b) => visitor.visit_bool(b),
+            Object::Integer(n) => visitor.visit_i64(n),
+            Object::Float(n) => visitor.visit_f64(n),
+            Object::String(s) => visitor.visit_str(&s),
+            Object::Symbol(s) => visitor.visit_str(s.as_str()),
+            Object::List(list) => visit_vector(list, visitor),
+            Object::Vector(vector) => visit_vector(vector, visitor),
+            Object::Map(map) => {
+                let mut access = MapDeserializer::new(map.into_iter());
+                let value = visitor.visit_map(&mut access)?;
+                access.end()?;
+                Ok(value)
+            }
+            Object::Other(_) => match self.meta() {
+                Some(_) => self.without_meta().clone().deserialize_any(visitor),
+                None => Err(de::Error::custom("cannot deserialize a foreign object")),
+            },
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Object::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            Object::String(variant) => visitor.visit_enum(EnumDeserializer {
+                variant: variant.to_string(),
+                value: None,
+            }),
+            Object::Symbol(variant) => visitor.visit_enum(EnumDeserializer {
+                variant: variant.to_string(),
+                value: None,
+            }),
+            Object::Map(map) if map.len() == 1 => {
+                let (variant, value) = map.into_iter().next().unwrap();
+                let variant = String::deserialize(variant)?;
+                visitor.visit_enum(EnumDeserializer {
+                    variant,
+                    value: Some(value),
+                })
+            }
+            _ => Err(de::Error::custom(
+                "expected a string or a map with a single key",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: Option<Object>,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = DeserializeObjectError;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, VariantDeserializer { value: self.value }))
+    }
+}
+
+struct VariantDeserializer {
+    value: Option<Object>,
+}
+
+impl<'de> VariantAccess<'de> for VariantDeserializer {
+    type Error = DeserializeObjectError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        match self.value {
+            None | Some(Object::Null) => Ok(()),
+            Some(_) => Err(de::Error::custom("expected a unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        match self.value {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::custom("expected a newtype variant")),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Some(Object::Vector(vector)) => visit_vector(vector, visitor),
+            Some(Object::List(list)) => visit_vector(list, visitor),
+            _ => Err(de::Error::custom("expected a tuple variant")),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Some(value @ Object::Map(_)) => de::Deserializer::deserialize_any(value, visitor),
+            _ => Err(de::Error::custom("expected a struct variant")),
+        }
+    }
+}
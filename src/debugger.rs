@@ -0,0 +1,74 @@
+use crate::interpreter::Interpreter;
+use crate::object::Object;
+use crate::symbol::Symbol;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    Continue,
+    StepIn,
+    StepOver,
+    StepOut,
+    Abort,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Breakpoint {
+    Function(Symbol),
+    Form(Object),
+}
+
+impl Breakpoint {
+    pub fn function(name: &str) -> Breakpoint {
+        Breakpoint::Function(Symbol::new(name))
+    }
+
+    pub(crate) fn matches(&self, obj: &Object) -> bool {
+        match (self, obj) {
+            (Breakpoint::Function(name), Object::List(list)) => {
+                matches!(list.first(), Some(Object::Symbol(head)) if head == name)
+            }
+            (Breakpoint::Form(form), _) => form == obj,
+            _ => false,
+        }
+    }
+}
+
+// Called before each form is evaluated while a debugger is attached and
+// the current step mode or a breakpoint asks for a stop. Evaluation starts
+// in StepIn mode; the returned Step decides where the next stop is.
+pub trait Debugger: Send + Sync {
+    fn pause(&self, pause: &Pause) -> Step;
+}
+
+pub struct Pause<'a> {
+    pub(crate) interpreter: &'a Interpreter<'a>,
+    pub(crate) form: &'a Object,
+    pub(crate) scope: &'a [(Symbol, Object)],
+    pub(crate) depth: usize,
+}
+
+impl<'a> Pause<'a> {
+    pub fn form(&self) -> &Object {
+        self.form
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // Local bindings from outermost to innermost: captured values first,
+    // then parameters and let bindings in the order they were introduced.
+    pub fn locals(&self) -> &[(Symbol, Object)] {
+        self.scope
+    }
+
+    // Resolves a name the way the paused form would: locals, then the
+    // context's globals, then the evaluator's globals.
+    pub fn lookup(&self, name: &str) -> Object {
+        let symbol = Symbol::new(name);
+        match self.scope.iter().rev().find(|(local, _)| *local == symbol) {
+            Some((_, value)) => value.clone(),
+            None => self.interpreter.lookup_global(symbol),
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use crate::evaluator::{args, PrimitiveFunction};
+use crate::object::{FromObject, FromObjectError, Object};
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+pub(crate) const PRIMITIVES: [(&str, PrimitiveFunction); 2] = [("diff", diff), ("patch", patch)];
+
+// One difference between two values. A path leads to it from the root
+// through map keys and vector indices; an empty path is the root.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Added {
+        path: Vec<Object>,
+        value: Object,
+    },
+    Removed {
+        path: Vec<Object>,
+        value: Object,
+    },
+    Changed {
+        path: Vec<Object>,
+        from: Object,
+        to: Object,
+    },
+}
+
+impl Change {
+    pub fn path(&self) -> &[Object] {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Changed { path, .. } => path,
+        }
+    }
+}
+
+// A path such as .servers[0].port, or . for the root.
+pub fn path_string(path: &[Object]) -> String {
+    if path.is_empty() {
+        return ".".to_string();
+    }
+    path.iter()
+        .map(|segment| match segment.without_meta() {
+            Object::Integer(index) => format!("[{}]", index),
+            key => format!(".{}", key),
+        })
+        .collect()
+}
+
+// Lines such as "+ .a: 1", "- .b: 2" and "~ .c: 1 -> 2".
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { path, value } => write!(f, "+ {}: {}", path_string(path), value),
+            Change::Removed { path, value } => write!(f, "- {}: {}", path_string(path), value),
+            Change::Changed { path, from, to } => {
+                write!(f, "~ {}: {} -> {}", path_string(path), from, to)
+            }
+        }
+    }
+}
+
+// What turns one value into another, as Object::diff finds it: maps are
+// compared key by key in the order their keys print and vectors and lists
+// index by index, so items past the end of the shorter one are added or
+// removed. Anything else that differs, including a vector against a list,
+// is changed as a whole.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Patch {
+    pub changes: Vec<Change>,
+}
+
+impl Patch {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, change) in self.changes.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+// A change whose path leads nowhere or whose old value is not there.
+#[derive(Debug)]
+pub struct PatchError {
+    pub path: Vec<Object>,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "patch does not apply at {}", path_string(&self.path))
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+fn key(name: &str) -> Object {
+    Object::Symbol(Symbol::new(name))
+}
+
+// {op: changed, path: [port], from: 80, to: 8080}, and value in place of
+// from and to for added and removed.
+impl From<Change> for Object {
+    fn from(change: Change) -> Self {
+        let (op, path, values) = match change {
+            Change::Added { path, value } => ("added", path, vec![("value", value)]),
+            Change::Removed { path, value } => ("removed", path, vec![("value", value)]),
+            Change::Changed { path, from, to } => {
+                ("changed", path, vec![("from", from), ("to", to)])
+            }
+        };
+        let mut map = HashMap::from([(key("op"), key(op)), (key("path"), Object::Vector(path))]);
+        map.extend(values.into_iter().map(|(name, value)| (key(name), value)));
+        Object::Map(map)
+    }
+}
+
+impl From<Patch> for Object {
+    fn from(patch: Patch) -> Self {
+        Object::Vector(patch.changes.into_iter().map(Object::from).collect())
+    }
+}
+
+// Reads the maps above back, with op given as a symbol or a string.
+impl FromObject for Change {
+    fn from_object(obj: &Object) -> Result<Self, FromObjectError> {
+        let map = match obj.without_meta() {
+            Object::Map(map) => map,
+            _ => return Err(FromObjectError {}),
+        };
+        let field = |name: &str| {
+            map.get(&key(name))
+                .or_else(|| map.get(&Object::from(name)))
+                .cloned()
+        };
+        let path = match field("path").as_ref().map(Object::without_meta) {
+            Some(Object::Vector(path)) | Some(Object::List(path)) => path.clone(),
+            _ => return Err(FromObjectError {}),
+        };
+        let op = match field("op").as_ref().map(Object::without_meta) {
+            Some(Object::Symbol(op)) => op.as_str().to_string(),
+            Some(Object::String(op)) => op.to_string(),
+            _ => return Err(FromObjectError {}),
+        };
+        let value = || field("value").ok_or(FromObjectError {});
+        match op.as_str() {
+            "added" => Ok(Change::Added {
+                path,
+                value: value()?,
+            }),
+            "removed" => Ok(Change::Removed {
+                path,
+                value: value()?,
+            }),
+            "changed" => Ok(Change::Changed {
+                path,
+                from: field("from").ok_or(FromObjectError {})?,
+                to: field("to").ok_or(FromObjectError {})?,
+            }),
+            _ => Err(FromObjectError {}),
+        }
+    }
+}
+
+impl FromObject for Patch {
+    fn from_object(obj: &Object) -> Result<Self, FromObjectError> {
+        Ok(Patch {
+            changes: Vec::from_object(obj)?,
+        })
+    }
+}
+
+impl TryFrom<Object> for Patch {
+    type Error = FromObjectError;
+
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        Patch::from_object(&obj)
+    }
+}
+
+pub(crate) fn changes(path: &mut Vec<Object>, from: &Object, to: &Object, out: &mut Vec<Change>) {
+    match (from.without_meta(), to.without_meta()) {
+        (Object::List(from), Object::List(to)) | (Object::Vector(from), Object::Vector(to)) => {
+            for index in 0..from.len().max(to.len()) {
+                path.push(Object::Integer(index as i64));
+                match (from.get(index), to.get(index)) {
+                    (Some(from), Some(to)) => changes(path, from, to, out),
+                    (Some(value), None) => out.push(Change::Removed {
+                        path: path.clone(),
+                        value: value.clone(),
+                    }),
+                    (None, Some(value)) => out.push(Change::Added {
+                        path: path.clone(),
+                        value: value.clone(),
+                    }),
+                    (None, None) => {}
+                }
+                path.pop();
+            }
+        }
+        (Object::Map(from), Object::Map(to)) => {
+            let mut keys: Vec<&Object> = from.keys().chain(to.keys()).collect();
+            keys.sort_by_key(|key| key.to_string());
+            keys.dedup();
+            for key in keys {
+                path.push(key.clone());
+                match (from.get(key), to.get(key)) {
+                    (Some(from), Some(to)) => changes(path, from, to, out),
+                    (Some(value), None) => out.push(Change::Removed {
+                        path: path.clone(),
+                        value: value.clone(),
+                    }),
+                    (None, Some(value)) => out.push(Change::Added {
+                        path: path.clone(),
+                        value: value.clone(),
+                    }),
+                    (None, None) => {}
+                }
+                path.pop();
+            }
+        }
+        (from, to) if from != to => out.push(Change::Changed {
+            path: path.clone(),
+            from: from.clone(),
+            to: to.clone(),
+        }),
+        _ => {}
+    }
+}
+
+// The value at path inside target, made editable: a value with metadata on
+// the way is replaced by the value alone.
+fn locate<'a>(target: &'a mut Object, path: &[Object]) -> Option<&'a mut Object> {
+    let mut target = target;
+    for segment in path {
+        if let Object::Other(_) = target {
+            *target = target.without_meta().clone();
+        }
+        target = match (target, segment.without_meta()) {
+            (Object::Map(map), _) => map.get_mut(segment)?,
+            (Object::Vector(items) | Object::List(items), Object::Integer(index)) => {
+                items.get_mut(usize::try_from(*index).ok()?)?
+            }
+            _ => return None,
+        };
+    }
+    if let Object::Other(_) = target {
+        *target = target.without_meta().clone();
+    }
+    Some(target)
+}
+
+fn apply(target: &mut Object, change: &Change) -> Option<()> {
+    let (parent, last) = match change {
+        Change::Changed { path, from, to } => {
+            let value = locate(target, path)?;
+            if value != from {
+                return None;
+            }
+            *value = to.clone();
+            return Some(());
+        }
+        change => match change.path().split_last() {
+            Some((last, parent)) => (locate(target, parent)?, last),
+            None => return None,
+        },
+    };
+    match (parent, last.without_meta(), change) {
+        (Object::Map(map), _, Change::Added { value, .. }) if !map.contains_key(last) => {
+            map.insert(last.clone(), value.clone());
+        }
+        (Object::Map(map), _, Change::Removed { value, .. }) if map.get(last) == Some(value) => {
+            map.remove(last);
+        }
+        (
+            Object::Vector(items) | Object::List(items),
+            Object::Integer(index),
+            Change::Added { value, .. },
+        ) if usize::try_from(*index).is_ok_and(|index| index <= items.len()) => {
+            items.insert(*index as usize, value.clone());
+        }
+        (
+            Object::Vector(items) | Object::List(items),
+            Object::Integer(index),
+            Change::Removed { value, .. },
+        ) if usize::try_from(*index)
+            .ok()
+            .and_then(|index| items.get(index))
+            == Some(value) =>
+        {
+            items.remove(*index as usize);
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+// Removals are made last and in reverse, so the items a patch removes from
+// the end of a vector are still where it says when each is removed.
+pub(crate) fn apply_patch(value: &Object, patch: &Patch) -> Result<Object, PatchError> {
+    let mut value = value.clone();
+    let (removals, others): (Vec<&Change>, Vec<&Change>) = patch
+        .changes
+        .iter()
+        .partition(|change| matches!(change, Change::Removed { .. }));
+    for change in others.into_iter().chain(removals.into_iter().rev()) {
+        if apply(&mut value, change).is_none() {
+            return Err(PatchError {
+                path: change.path().to_vec(),
+            });
+        }
+    }
+    Ok(value)
+}
+
+// (diff a b) gives what turns a into b as a vector of changes, such as
+// [{op: changed, path: [port], from: 80, to: 8080}], empty when they are
+// equal.
+fn diff(obj: &Object) -> Object {
+    match args(obj) {
+        [from, to] => Object::from(from.diff(to)),
+        _ => Object::Null,
+    }
+}
+
+// (patch a d) applies changes d, as diff gives them, to a. It gives null
+// when they do not apply, such as when a value they change or remove is
+// not what they say it was.
+fn patch(obj: &Object) -> Object {
+    match args(obj) {
+        [value, changes] => match Patch::from_object(changes) {
+            Ok(patch) => value.apply_patch(&patch).unwrap_or(Object::Null),
+            Err(_) => Object::Null,
+        },
+        _ => Object::Null,
+    }
+}
@@ -0,0 +1,239 @@
+use crate::object::{Object, ParseObjectError};
+use crate::symbol::Symbol;
+use crate::types;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Function(Vec<String>),
+    Generator(Vec<String>),
+    Dynamic,
+    Variable,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Item {
+    pub name: String,
+    pub kind: Kind,
+    pub doc: Option<String>,
+}
+
+impl Item {
+    pub fn signature(&self) -> String {
+        match &self.kind {
+            Kind::Function(params) | Kind::Generator(params) if !params.is_empty() => {
+                format!("({} {})", self.name, params.join(" "))
+            }
+            Kind::Function(_) | Kind::Generator(_) => format!("({})", self.name),
+            Kind::Dynamic | Kind::Variable => self.name.clone(),
+        }
+    }
+
+    fn kind_name(&self) -> &'static str {
+        match self.kind {
+            Kind::Function(_) => "function",
+            Kind::Generator(_) => "generator",
+            Kind::Dynamic => "dynamic variable",
+            Kind::Variable => "variable",
+        }
+    }
+}
+
+// The documentation of one file: a string literal as its first form
+// describes the file, and top-level definitions are its items.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Module {
+    pub doc: Option<String>,
+    pub items: Vec<Item>,
+}
+
+fn is_head(obj: &Object, names: &[&str]) -> bool {
+    matches!(obj, Object::Symbol(symbol) if names.contains(&symbol.as_str()))
+}
+
+// The doc entry of a metadata map, keyed by the symbol or the string.
+fn doc_entry(meta: &Object) -> Option<String> {
+    let map = match meta {
+        Object::Map(map) => map,
+        _ => return None,
+    };
+    let doc = map
+        .get(&Object::Symbol(Symbol::new("doc")))
+        .or_else(|| map.get(&Object::String("doc".into())))?;
+    match doc {
+        Object::String(doc) => Some(doc.to_string()),
+        _ => None,
+    }
+}
+
+// Documentation is attached with (with-meta value {doc: "..."}), which is
+// looked through to find what the value is.
+fn item(name: Symbol, value: Option<&Object>, dynamic: bool) -> Item {
+    let mut doc = None;
+    let mut value = value;
+    if let Some(Object::List(list)) = value {
+        if let [head, inner, meta] = list.as_slice() {
+            if is_head(head, &["with-meta"]) {
+                doc = doc_entry(meta);
+                value = Some(inner);
+            }
+        }
+    }
+    let kind = match value {
+        _ if dynamic => Kind::Dynamic,
+        Some(Object::List(list)) => match list.as_slice() {
+            [head, Object::List(params), ..] if is_head(head, &["fn", "fn*"]) => {
+                let params = types::params(params)
+                    .into_iter()
+                    .map(|(param, annotation)| match annotation {
+                        Some(annotation) => format!("{}: {}", param, annotation),
+                        None => param.to_string(),
+                    })
+                    .collect();
+                if is_head(head, &["fn*"]) {
+                    Kind::Generator(params)
+                } else {
+                    Kind::Function(params)
+                }
+            }
+            _ => Kind::Variable,
+        },
+        _ => Kind::Variable,
+    };
+    Item {
+        name: name.as_str().to_string(),
+        kind,
+        doc,
+    }
+}
+
+// Reads the documentation without evaluating anything. Later definitions
+// of a name replace earlier ones, keeping the first one's place.
+pub fn extract(source: &str) -> Result<Module, ParseObjectError> {
+    let forms = Object::parse_all(source)?;
+    let mut module = Module::default();
+    if let Some(Object::String(doc)) = forms.first() {
+        module.doc = Some(doc.to_string());
+    }
+    for form in &forms {
+        let list = match form {
+            Object::List(list) => list,
+            _ => continue,
+        };
+        let item = match list.as_slice() {
+            [head, Object::Symbol(name), value] | [head, Object::Symbol(name), _, _, value]
+                if is_head(head, &["set"]) =>
+            {
+                item(*name, Some(value), false)
+            }
+            [head, Object::Symbol(name), value @ ..] if is_head(head, &["defdynamic"]) => {
+                item(*name, value.first(), true)
+            }
+            _ => continue,
+        };
+        match module
+            .items
+            .iter_mut()
+            .find(|other| other.name == item.name)
+        {
+            Some(other) => *other = item,
+            None => module.items.push(item),
+        }
+    }
+    Ok(module)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Blank lines separate paragraphs.
+fn paragraphs(doc: &str) -> String {
+    doc.trim()
+        .split("\n\n")
+        .map(|paragraph| format!("<p>{}</p>\n", escape(paragraph.trim())))
+        .collect()
+}
+
+impl Module {
+    pub fn to_markdown(&self, title: &str) -> String {
+        let mut output = format!("# {}\n\n", title);
+        if let Some(doc) = &self.doc {
+            output.push_str(doc.trim());
+            output.push_str("\n\n");
+        }
+        for item in &self.items {
+            output.push_str(&format!("## `{}`\n\n", item.signature()));
+            output.push_str(&format!("*{}*\n\n", item.kind_name()));
+            if let Some(doc) = &item.doc {
+                output.push_str(doc.trim());
+                output.push_str("\n\n");
+            }
+        }
+        output
+    }
+
+    pub fn to_html(&self, title: &str) -> String {
+        let mut output = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n",
+            escape(title)
+        );
+        if let Some(doc) = &self.doc {
+            output.push_str(&paragraphs(doc));
+        }
+        for item in &self.items {
+            output.push_str(&format!(
+                "<h2 id=\"{}\"><code>{}</code></h2>\n<p><em>{}</em></p>\n",
+                escape(&item.name),
+                escape(&item.signature()),
+                item.kind_name()
+            ));
+            if let Some(doc) = &item.doc {
+                output.push_str(&paragraphs(doc));
+            }
+        }
+        output.push_str("</body>\n</html>\n");
+        output
+    }
+}
+
+// A page linking the pages of several modules, given as title, path
+// relative to the index and module doc, with the first line of each doc as
+// its summary.
+pub fn index(pages: &[(String, PathBuf, Option<String>)], html: bool) -> String {
+    let mut output = if html {
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Index</title>\n</head>\n<body>\n<h1>Index</h1>\n<ul>\n".to_string()
+    } else {
+        "# Index\n\n".to_string()
+    };
+    for (title, path, doc) in pages {
+        let link: Vec<String> = path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let link = link.join("/");
+        let summary = doc
+            .as_deref()
+            .and_then(|doc| doc.trim().lines().next())
+            .unwrap_or("");
+        if html {
+            output.push_str(&format!(
+                "<li><a href=\"{}\">{}</a> {}</li>\n",
+                escape(&link),
+                escape(title),
+                escape(summary)
+            ));
+        } else if summary.is_empty() {
+            output.push_str(&format!("- [{}]({})\n", title, link));
+        } else {
+            output.push_str(&format!("- [{}]({}): {}\n", title, link, summary));
+        }
+    }
+    if html {
+        output.push_str("</ul>\n</body>\n</html>\n");
+    }
+    output
+}
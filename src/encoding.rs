@@ -0,0 +1,178 @@
+use crate::bytes::{self, hex};
+use crate::evaluator::{args, PrimitiveFunction};
+use crate::object::{Bytes, Object};
+
+// Encoders take strings, as UTF-8, or bytes and give strings. Decoders of
+// base64 and hex give bytes, which bytes-to-string turns back into text,
+// and null when the input is malformed.
+pub(crate) const PRIMITIVES: [(&str, PrimitiveFunction); 7] = [
+    ("base64-encode", base64_encode),
+    ("base64-decode", base64_decode),
+    ("hex-encode", hex_encode),
+    ("hex-decode", hex_decode),
+    ("url-encode", url_encode),
+    ("url-decode", url_decode),
+    ("html-escape", html_escape),
+];
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn data(obj: &Object) -> Option<&[u8]> {
+    args(obj).first().and_then(bytes::data)
+}
+
+fn string(obj: &Object) -> Option<&str> {
+    match args(obj).first().map(Object::without_meta) {
+        Some(Object::String(string)) => Some(string),
+        _ => None,
+    }
+}
+
+fn encode(obj: &Object, f: fn(&[u8]) -> String) -> Object {
+    data(obj).map_or(Object::Null, |data| Object::from(f(data)))
+}
+
+fn decode(obj: &Object, f: fn(&str) -> Option<Vec<u8>>) -> Object {
+    string(obj)
+        .and_then(f)
+        .map_or(Object::Null, |data| Object::from(Bytes(data)))
+}
+
+// Standard base64 with padding.
+fn base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+// Padding may be left out, and the URL-safe - and _ are read as + and /.
+fn unbase64(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=').as_bytes();
+    if text.len() % 4 == 1 {
+        return None;
+    }
+    let mut decoded = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.chunks(4) {
+        let mut n = 0;
+        for (i, &c) in chunk.iter().enumerate() {
+            let value = match c {
+                b'-' => 62,
+                b'_' => 63,
+                c => BASE64.iter().position(|&b| b == c)? as u32,
+            };
+            n |= value << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            decoded.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(decoded)
+}
+
+// Two hex digits, in either case. from_str_radix alone would also take a
+// sign.
+fn hex_byte(pair: &[u8]) -> Option<u8> {
+    let digit = |c: u8| (c as char).to_digit(16);
+    match pair {
+        [high, low] => Some((digit(*high)? * 16 + digit(*low)?) as u8),
+        _ => None,
+    }
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 == 1 {
+        return None;
+    }
+    text.as_bytes().chunks(2).map(hex_byte).collect()
+}
+
+// Everything but letters, digits and -_.~ is percent-encoded, so the
+// result can go anywhere in a URL, spaces becoming %20.
+fn percent_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len());
+    for &byte in data {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn base64_encode(obj: &Object) -> Object {
+    encode(obj, base64)
+}
+
+fn base64_decode(obj: &Object) -> Object {
+    decode(obj, unbase64)
+}
+
+fn hex_encode(obj: &Object) -> Object {
+    encode(obj, hex)
+}
+
+fn hex_decode(obj: &Object) -> Object {
+    decode(obj, unhex)
+}
+
+fn url_encode(obj: &Object) -> Object {
+    encode(obj, percent_encode)
+}
+
+// Gives a string, or null when a % is not followed by two hex digits or
+// the result is not valid UTF-8. A + stays as it is.
+fn url_decode(obj: &Object) -> Object {
+    let text = match string(obj) {
+        Some(text) => text.as_bytes(),
+        None => return Object::Null,
+    };
+    let mut decoded = Vec::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        if text[i] == b'%' {
+            match text.get(i + 1..i + 3).and_then(hex_byte) {
+                Some(byte) => decoded.push(byte),
+                None => return Object::Null,
+            }
+            i += 3;
+        } else {
+            decoded.push(text[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_or(Object::Null, Object::from)
+}
+
+// Escapes &, <, >, " and ' so the text can go in an element or an
+// attribute.
+fn html_escape(obj: &Object) -> Object {
+    let text = match string(obj) {
+        Some(text) => text,
+        None => return Object::Null,
+    };
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    Object::from(escaped)
+}
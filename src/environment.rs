@@ -0,0 +1,69 @@
+use crate::evaluator::{args, PrimitiveFunction};
+use crate::object::Object;
+use std::collections::HashMap;
+use std::env;
+use std::process;
+
+// The process's environment variables, working directory and id.
+pub(crate) const PRIMITIVES: [(&str, PrimitiveFunction); 6] = [
+    ("env", get_env),
+    ("env-all", env_all),
+    ("set-env", set_env),
+    ("cwd", cwd),
+    ("cd", cd),
+    ("pid", pid),
+];
+
+fn string(obj: &Object, index: usize) -> Option<&str> {
+    match args(obj).get(index).map(Object::without_meta) {
+        Some(Object::String(string)) => Some(string),
+        _ => None,
+    }
+}
+
+pub(crate) fn get_env(obj: &Object) -> Object {
+    string(obj, 0)
+        .and_then(|name| env::var(name).ok())
+        .map_or(Object::Null, Object::from)
+}
+
+// Variables whose name or value is not valid Unicode are left out.
+fn env_all(_: &Object) -> Object {
+    let vars: HashMap<Object, Object> = env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .map(|(name, value)| (Object::from(name), Object::from(value)))
+        .collect();
+    Object::Map(vars)
+}
+
+// (set-env k null) removes k. Names that are empty or contain = or a NUL,
+// and values containing a NUL, cannot be set.
+fn set_env(obj: &Object) -> Object {
+    let name = match string(obj, 0) {
+        Some(name) if !name.is_empty() && !name.contains(['=', '\0']) => name,
+        _ => return Object::Null,
+    };
+    match args(obj).get(1).map(Object::without_meta) {
+        Some(Object::String(value)) if !value.contains('\0') => env::set_var(name, &**value),
+        Some(Object::Null) => env::remove_var(name),
+        _ => return Object::Null,
+    }
+    Object::Bool(true)
+}
+
+fn cwd(_: &Object) -> Object {
+    env::current_dir().map_or(Object::Null, |dir| {
+        Object::from(dir.to_string_lossy().into_owned())
+    })
+}
+
+fn cd(obj: &Object) -> Object {
+    match string(obj, 0).map(env::set_current_dir) {
+        Some(Ok(())) => Object::Bool(true),
+        _ => Object::Null,
+    }
+}
+
+fn pid(_: &Object) -> Object {
+    Object::Integer(process::id() as i64)
+}
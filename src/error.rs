@@ -0,0 +1,58 @@
+use crate::evaluator::EvalError;
+use crate::object::{FromObjectError, ParseObjectError};
+use std::error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    Parse(ParseObjectError),
+    Eval(EvalError),
+    Convert(FromObjectError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// Messages as the command line words them, for embedders that show errors
+// to people.
+#[cfg(any(feature = "wasm", feature = "fundot-capi"))]
+pub(crate) fn describe(error: &Error) -> String {
+    match error {
+        Error::Parse(_) => "syntax error: could not parse input".to_string(),
+        Error::Eval(EvalError::Exit(code)) => format!("exited with {}", code),
+        Error::Eval(EvalError::BudgetExceeded) => "evaluation ran out of fuel".to_string(),
+        Error::Eval(error) => format!("evaluation failed: {}", error),
+        Error::Convert(error) => format!("conversion failed: {}", error),
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Parse(err) => Some(err),
+            Error::Eval(err) => Some(err),
+            Error::Convert(err) => Some(err),
+        }
+    }
+}
+
+impl From<ParseObjectError> for Error {
+    fn from(err: ParseObjectError) -> Self {
+        Error::Parse(err)
+    }
+}
+
+impl From<EvalError> for Error {
+    fn from(err: EvalError) -> Self {
+        Error::Eval(err)
+    }
+}
+
+impl From<FromObjectError> for Error {
+    fn from(err: FromObjectError) -> Self {
+        Error::Convert(err)
+    }
+}
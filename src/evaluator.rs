@@ -1,12 +1,98 @@
-use crate::object::Object;
-use std::collections::{HashMap, LinkedList};
-use std::process;
-use std::sync::Arc;
+use crate::arithmetic;
+use crate::binary::BinaryObjectError;
+use crate::bytes;
+use crate::cli;
+use crate::clock::{Instant, SystemTime, UNIX_EPOCH};
+use crate::compiler::{self, CompiledProgram, Compiler, Proto};
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+use crate::compression;
+use crate::context::Context;
+#[cfg(feature = "crypto")]
+use crate::crypto;
+use crate::cycles::{self, Registry};
+use crate::debugger::{Breakpoint, Debugger};
+use crate::diff;
+use crate::encoding;
+use crate::environment;
+use crate::error::Error as FundotError;
+use crate::filesystem;
+#[cfg(not(feature = "rc"))]
+use crate::future::EvalFuture;
+use crate::history::Event;
+use crate::inspect;
+use crate::interpreter::Interpreter;
+use crate::logging::{self, Level, LogSink, Logger, Stderr};
+use crate::merge;
+use crate::network;
+#[cfg(feature = "bigint")]
+use crate::object::big_integer;
+use crate::object::{AnyValue, FromObject, Object, ParseObjectError, Shared};
+use crate::optimizer::Optimizer;
+use crate::output::{OutputSink, Stdio, Stream};
+use crate::process as subprocess;
+use crate::profiler::Profile;
+use crate::query;
+use crate::schema;
+use crate::symbol::Symbol;
+use crate::template;
+use crate::unicode;
+use crate::uuid;
+#[cfg(not(feature = "rc"))]
+use crossbeam_channel::{Receiver, RecvTimeoutError, Select, SendTimeoutError, Sender};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard};
+#[cfg(not(feature = "rc"))]
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
+// Builtins are given the whole call, as (f a b), with every argument
+// already evaluated, the same as a function defined in Fundot. Forms that
+// need their arguments unevaluated, like if and fn, are special forms of
+// the interpreter rather than builtins.
 pub type PrimitiveFunction = fn(&Object) -> Object;
 
-fn quit(_: &Object) -> Object {
-    process::exit(0)
+pub type NativeFunction = fn(&Interpreter, &Object) -> Result<Object, EvalError>;
+
+#[derive(Debug, PartialEq)]
+pub enum EvalError {
+    BudgetExceeded,
+    Interrupted,
+    Timeout,
+    MemoryLimitExceeded,
+    StackOverflow,
+    // Integer arithmetic overflowed under Overflow::Checked.
+    Overflow,
+    // Raised by (exit code). Ending the process is left to whoever runs
+    // the evaluation.
+    Exit(i32),
+    // No thread could be started for an evaluation that needs its own.
+    NoThread,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for EvalError {}
+
+// (exit) and (quit) exit with 0, (exit n) with n and anything else
+// with 1.
+fn exit(_: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let code = match args(obj).first().map(Object::without_meta) {
+        None | Some(Object::Null) => 0,
+        Some(Object::Integer(code)) => i32::try_from(*code).unwrap_or(1),
+        Some(_) => 1,
+    };
+    Err(EvalError::Exit(code))
 }
 
 fn get(obj: &Object) -> Object {
@@ -16,7 +102,7 @@ fn get(obj: &Object) -> Object {
         }
         let mut iter = list.iter();
         iter.next();
-        match iter.next().unwrap() {
+        match iter.next().unwrap().without_meta() {
             Object::Vector(vector) => {
                 if let Object::Integer(index) = iter.next().unwrap() {
                     if let Some(value) = vector.get(*index as usize) {
@@ -35,63 +121,2163 @@ fn get(obj: &Object) -> Object {
     Object::Null
 }
 
-pub struct Evaluator {
-    global: Object,
+#[cfg(feature = "json")]
+fn json_encode(obj: &Object) -> Object {
+    if let Object::List(list) = obj {
+        let mut iter = list.iter();
+        iter.next();
+        if let Some(value) = iter.next() {
+            let mut pretty = false;
+            if let Some(Object::Map(options)) = iter.next() {
+                if let Some(Object::Bool(b)) = options.get(&Object::Symbol(Symbol::new("pretty"))) {
+                    pretty = *b;
+                }
+            }
+            let result = if pretty {
+                serde_json::to_string_pretty(value)
+            } else {
+                serde_json::to_string(value)
+            };
+            if let Ok(s) = result {
+                return Object::String(s.into());
+            }
+        }
+    }
+    Object::Null
 }
 
-impl Evaluator {
-    pub fn new() -> Evaluator {
-        let mut map = HashMap::new();
-        let get: PrimitiveFunction = get;
-        map.insert(
-            Object::Symbol("get".to_string()),
-            Object::Other(Arc::new(get)),
-        );
-        let quit: PrimitiveFunction = quit;
-        map.insert(
-            Object::Symbol("quit".to_string()),
-            Object::Other(Arc::new(quit)),
-        );
-        Evaluator {
-            global: Object::Map(map),
+#[cfg(feature = "json")]
+fn json_decode(obj: &Object) -> Object {
+    if let Object::List(list) = obj {
+        let mut iter = list.iter();
+        iter.next();
+        if let Some(Object::String(s)) = iter.next() {
+            if let Ok(value) = serde_json::from_str::<Object>(s) {
+                return value;
+            }
         }
     }
+    Object::Null
+}
 
-    pub fn eval(&self, obj: &Object) -> Object {
-        match obj {
-            Object::Symbol(s) => self.eval_symbol(s),
-            Object::List(list) => self.eval_list(list),
-            _ => obj.clone(),
+#[cfg(feature = "toml")]
+fn toml_datetime_to_string(obj: Object) -> Object {
+    match obj {
+        Object::Vector(vector) => {
+            Object::Vector(vector.into_iter().map(toml_datetime_to_string).collect())
+        }
+        Object::Map(map) => {
+            let datetime = Object::Symbol(Symbol::new("$__toml_private_datetime"));
+            if map.len() == 1 {
+                if let Some(Object::String(s)) = map.get(&datetime) {
+                    return Object::String(s.clone());
+                }
+            }
+            Object::Map(
+                map.into_iter()
+                    .map(|(key, value)| (key, toml_datetime_to_string(value)))
+                    .collect(),
+            )
+        }
+        _ => obj,
+    }
+}
+
+#[cfg(feature = "toml")]
+fn toml_encode(obj: &Object) -> Object {
+    if let Object::List(list) = obj {
+        let mut iter = list.iter();
+        iter.next();
+        if let Some(value) = iter.next() {
+            let mut pretty = false;
+            if let Some(Object::Map(options)) = iter.next() {
+                if let Some(Object::Bool(b)) = options.get(&Object::Symbol(Symbol::new("pretty"))) {
+                    pretty = *b;
+                }
+            }
+            let result = if pretty {
+                toml::to_string_pretty(value)
+            } else {
+                toml::to_string(value)
+            };
+            if let Ok(s) = result {
+                return Object::String(s.into());
+            }
+        }
+    }
+    Object::Null
+}
+
+#[cfg(feature = "toml")]
+fn toml_decode(obj: &Object) -> Object {
+    if let Object::List(list) = obj {
+        let mut iter = list.iter();
+        iter.next();
+        if let Some(Object::String(s)) = iter.next() {
+            if let Ok(value) = toml::from_str::<Object>(s) {
+                return toml_datetime_to_string(value);
+            }
+        }
+    }
+    Object::Null
+}
+
+#[cfg(feature = "yaml")]
+fn yaml_encode(obj: &Object) -> Object {
+    if let Object::List(list) = obj {
+        let mut iter = list.iter();
+        iter.next();
+        if let Some(value) = iter.next() {
+            if let Ok(s) = serde_yaml::to_string(value) {
+                return Object::String(s.into());
+            }
         }
     }
+    Object::Null
+}
 
-    fn eval_symbol(&self, string: &str) -> Object {
-        let symbol = Object::Symbol(string.to_string());
-        if let Object::Map(global) = &self.global {
-            if let Some(obj) = global.get(&symbol) {
-                return obj.clone();
+#[cfg(feature = "yaml")]
+fn yaml_decode(obj: &Object) -> Object {
+    if let Object::List(list) = obj {
+        let mut iter = list.iter();
+        iter.next();
+        if let Some(Object::String(s)) = iter.next() {
+            if let Ok(value) = serde_yaml::from_str::<Object>(s) {
+                return value;
             }
         }
-        symbol
     }
+    Object::Null
+}
+
+fn meta(obj: &Object) -> Object {
+    if let Object::List(list) = obj {
+        let mut iter = list.iter();
+        iter.next();
+        if let Some(Some(meta)) = iter.next().map(Object::meta) {
+            return meta.clone();
+        }
+    }
+    Object::Null
+}
 
-    fn eval_list(&self, list: &LinkedList<Object>) -> Object {
-        if list.is_empty() {
+fn with_meta(obj: &Object) -> Object {
+    if let Object::List(list) = obj {
+        if list.len() < 3 {
             return Object::Null;
         }
         let mut iter = list.iter();
-        let obj = self.eval(iter.next().unwrap());
-        if let Object::Other(other) = obj.clone() {
-            if let Some(primitive_function) = other.downcast_ref::<PrimitiveFunction>() {
-                let mut after_eval = LinkedList::new();
-                after_eval.push_back(obj);
-                for obj in iter {
-                    after_eval.push_back(obj.clone());
+        iter.next();
+        let value = iter.next().unwrap();
+        return value.with_meta(iter.next().unwrap().clone());
+    }
+    Object::Null
+}
+
+// (csv-read path options f) calls f with each row as it is read and gives
+// how many rows there were, so that a file of any size can be processed.
+// Without f it gives all the rows in a vector. With the headers option a
+// row is a map from the names in the first line to its fields, otherwise
+// a vector of fields. The offset option skips that many rows and limit
+// stops after that many; either one being anything but a non-negative
+// integer gives null.
+#[cfg(feature = "csv")]
+fn csv_read(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let args = args(obj);
+    let path = match args.first() {
+        Some(Object::String(path)) => path,
+        _ => return Ok(Object::Null),
+    };
+    let options = match args.get(1) {
+        Some(Object::Map(options)) => options.clone(),
+        _ => HashMap::new(),
+    };
+    let option = |name| options.get(&Object::Symbol(Symbol::new(name)));
+    let headers = matches!(option("headers"), Some(Object::Bool(true)));
+    let count = |name, default| match option(name) {
+        None => Some(default),
+        Some(Object::Integer(n)) => usize::try_from(*n).ok(),
+        Some(_) => None,
+    };
+    let (offset, limit) = match (count("offset", 0), count("limit", usize::MAX)) {
+        (Some(offset), Some(limit)) => (offset, limit),
+        _ => return Ok(Object::Null),
+    };
+    let callback = args.get(2).filter(|f| !matches!(f, Object::Null));
+    let mut reader = match csv::ReaderBuilder::new()
+        .has_headers(headers)
+        .flexible(true)
+        .from_path(&**path)
+    {
+        Ok(reader) => reader,
+        Err(_) => return Ok(Object::Null),
+    };
+    let keys: Vec<Object> = if headers {
+        match reader.headers() {
+            Ok(record) => record
+                .iter()
+                .map(|key| Symbol::try_new(key).map_or_else(|| Object::from(key), Object::Symbol))
+                .collect(),
+            Err(_) => return Ok(Object::Null),
+        }
+    } else {
+        Vec::new()
+    };
+    let mut rows = Vec::new();
+    let mut read = 0;
+    for record in reader.into_records().skip(offset).take(limit) {
+        let record = match record {
+            Ok(record) => record,
+            Err(_) => return Ok(Object::Null),
+        };
+        let fields = record.iter().map(|field| Object::String(field.into()));
+        let row = if headers {
+            Object::Map(keys.iter().cloned().zip(fields).collect())
+        } else {
+            Object::Vector(fields.collect())
+        };
+        match callback {
+            Some(f) => {
+                interpreter.apply(&[f.clone(), row])?;
+            }
+            None => rows.push(row),
+        }
+        read += 1;
+    }
+    Ok(match callback {
+        Some(_) => Object::Integer(read),
+        None => Object::Vector(rows),
+    })
+}
+
+#[cfg(feature = "csv")]
+fn csv_field(obj: Option<&Object>) -> String {
+    match obj {
+        Some(Object::String(s)) => s.to_string(),
+        Some(Object::Null) | None => String::new(),
+        Some(obj) => obj.to_string(),
+    }
+}
+
+#[cfg(feature = "csv")]
+fn csv_write(obj: &Object) -> Object {
+    if let Object::List(list) = obj {
+        let mut iter = list.iter();
+        iter.next();
+        if let (Some(Object::String(path)), Some(Object::Vector(rows))) = (iter.next(), iter.next())
+        {
+            let mut columns = None;
+            if let Some(Object::Map(options)) = iter.next() {
+                if let Some(Object::Vector(vector)) =
+                    options.get(&Object::Symbol(Symbol::new("columns")))
+                {
+                    columns = Some(vector.clone());
+                }
+            }
+            if columns.is_none() {
+                if let Some(Object::Map(first)) = rows.first() {
+                    let mut keys: Vec<Object> = first.keys().cloned().collect();
+                    keys.sort_by_key(|key| key.to_string());
+                    columns = Some(keys);
+                }
+            }
+            let mut writer = match csv::WriterBuilder::new().flexible(true).from_path(&**path) {
+                Ok(writer) => writer,
+                Err(_) => return Object::Null,
+            };
+            if let Some(columns) = &columns {
+                let record = columns.iter().map(|key| match key {
+                    Object::Symbol(s) => s.to_string(),
+                    _ => csv_field(Some(key)),
+                });
+                if writer.write_record(record).is_err() {
+                    return Object::Null;
+                }
+            }
+            for row in rows {
+                let result = match (row, &columns) {
+                    (Object::Map(map), Some(columns)) => {
+                        writer.write_record(columns.iter().map(|key| csv_field(map.get(key))))
+                    }
+                    (Object::Vector(vector), _) => {
+                        writer.write_record(vector.iter().map(|field| csv_field(Some(field))))
+                    }
+                    _ => return Object::Null,
+                };
+                if result.is_err() {
+                    return Object::Null;
+                }
+            }
+            if writer.flush().is_ok() {
+                return Object::Integer(rows.len() as i64);
+            }
+        }
+    }
+    Object::Null
+}
+
+fn disassemble(obj: &Object) -> Object {
+    if let Some(Object::Other(other)) = args(obj).first().map(Object::without_meta) {
+        if let Some(function) = other.downcast_ref::<Function>() {
+            if let Some(chunk) = &function.proto.chunk {
+                return Object::String(chunk.disassemble().into());
+            }
+        }
+    }
+    Object::Null
+}
+
+// Arguments are separated by spaces and strings are written without their
+// quotes.
+fn output(obj: &Object) -> String {
+    let words: Vec<String> = args(obj)
+        .iter()
+        .map(|arg| match arg.without_meta() {
+            Object::String(string) => string.to_string(),
+            arg => arg.to_string(),
+        })
+        .collect();
+    words.join(" ")
+}
+
+fn print(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    interpreter.evaluator().write(Stream::Stdout, &output(obj));
+    Ok(Object::Null)
+}
+
+fn println(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let mut text = output(obj);
+    text.push('\n');
+    interpreter.evaluator().write(Stream::Stdout, &text);
+    Ok(Object::Null)
+}
+
+fn eval(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    match args(obj).first() {
+        Some(obj) => interpreter.eval(obj),
+        None => Ok(Object::Null),
+    }
+}
+
+// (require "collections/queue") evaluates collections/queue.fd from the
+// first search path that has it into the evaluator's globals, and gives the
+// value of its last form. A library is evaluated once per evaluator; it is
+// recorded before it runs, so a library requiring itself, directly or
+// not, gets null back instead of looping. Failing to evaluate it forgets
+// it again.
+fn require(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let evaluator = interpreter.evaluator();
+    let path = match args(obj).first() {
+        Some(Object::String(name)) => evaluator.resolve(name),
+        _ => None,
+    };
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(Object::Null),
+    };
+    let libraries = || {
+        evaluator
+            .libraries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    };
+    if let Some(value) = libraries().get(&path) {
+        return Ok(value.clone());
+    }
+    let forms = match fs::read_to_string(&path).map(|source| Object::parse_all(&source)) {
+        Ok(Ok(forms)) => forms,
+        _ => return Ok(Object::Null),
+    };
+    libraries().insert(path.clone(), Object::Null);
+    let library = evaluator.interpreter(evaluator.limits);
+    let mut value = Object::Null;
+    for form in &forms {
+        value = match library.eval(form) {
+            Ok(value) => value,
+            Err(error) => {
+                libraries().remove(&path);
+                return Err(error);
+            }
+        };
+    }
+    libraries().insert(path, value.clone());
+    Ok(value)
+}
+
+// (load-native "libmyext.so") loads a plugin into the evaluator's globals
+// and gives the names it defined.
+#[cfg(feature = "plugins")]
+fn load_native(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let path = match args(obj).first() {
+        Some(Object::String(path)) => path.to_string(),
+        _ => return Ok(Object::Null),
+    };
+    Ok(match interpreter.evaluator().load_plugin(path) {
+        Ok(names) => Object::Vector(names.into_iter().map(Object::from).collect()),
+        Err(_) => Object::Null,
+    })
+}
+
+fn profile_report(interpreter: &Interpreter, _: &Object) -> Result<Object, EvalError> {
+    Ok(interpreter.evaluator().profile().to_object())
+}
+
+fn history(interpreter: &Interpreter, _: &Object) -> Result<Object, EvalError> {
+    let history = interpreter.evaluator().history();
+    Ok(Object::Vector(
+        history.iter().map(Event::to_object).collect(),
+    ))
+}
+
+// A function may run on several threads at once when nothing it can reach
+// assigns a global or calls a native function. Functions it calls through
+// globals or captures are checked the same way.
+#[cfg(all(feature = "rayon", not(feature = "rc")))]
+fn parallel_safe(interpreter: &Interpreter, obj: &Object, seen: &mut Vec<*const Proto>) -> bool {
+    fn safe_body(
+        interpreter: &Interpreter,
+        obj: &Object,
+        locals: &[Symbol],
+        seen: &mut Vec<*const Proto>,
+    ) -> bool {
+        match obj {
+            Object::Symbol(symbol) if locals.contains(symbol) => true,
+            Object::Symbol(symbol) => {
+                parallel_safe(interpreter, &interpreter.lookup_global(*symbol), seen)
+            }
+            Object::List(list) => match list.first() {
+                Some(Object::Symbol(head)) if head.as_str() == "quote" => true,
+                Some(Object::Symbol(head)) if ["set", "defdynamic"].contains(&head.as_str()) => {
+                    false
+                }
+                _ => list
+                    .iter()
+                    .all(|obj| safe_body(interpreter, obj, locals, seen)),
+            },
+            _ => true,
+        }
+    }
+    fn bound(obj: &Object, locals: &mut Vec<Symbol>) {
+        if let Object::List(list) = obj {
+            match list.as_slice() {
+                [Object::Symbol(head), Object::List(params), ..]
+                    if ["fn", "fn*"].contains(&head.as_str()) =>
+                {
+                    locals.extend(params.iter().filter_map(|param| match param {
+                        Object::Symbol(symbol) => Some(*symbol),
+                        _ => None,
+                    }));
                 }
-                return primitive_function(&Object::List(after_eval));
+                [Object::Symbol(head), Object::Symbol(continuation), ..]
+                    if head.as_str() == "shift" =>
+                {
+                    locals.push(*continuation);
+                }
+                [Object::Symbol(head), Object::List(bindings), ..] if head.as_str() == "let" => {
+                    for binding in bindings {
+                        if let Object::List(pair) = binding {
+                            if let Some(Object::Symbol(symbol)) = pair.first() {
+                                locals.push(*symbol);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+            list.iter().for_each(|obj| bound(obj, locals));
+        }
+    }
+    let other = match obj.without_meta() {
+        Object::Other(other) => other,
+        _ => return true,
+    };
+    if let Some(primitive) = other.downcast_ref::<PrimitiveFunction>() {
+        return pure(*primitive);
+    }
+    if let Some(partial) = other.downcast_ref::<Partial>() {
+        return parallel_safe(interpreter, &partial.function, seen)
+            && partial
+                .args
+                .iter()
+                .all(|obj| parallel_safe(interpreter, obj, seen));
+    }
+    if let Some(composed) = other.downcast_ref::<Composed>() {
+        return composed
+            .functions
+            .iter()
+            .all(|obj| parallel_safe(interpreter, obj, seen));
+    }
+    let function = match other.downcast_ref::<Function>() {
+        Some(function) => function,
+        None => return false,
+    };
+    let proto = Shared::as_ptr(&function.proto);
+    if seen.contains(&proto) {
+        return true;
+    }
+    seen.push(proto);
+    if !function
+        .captured
+        .iter()
+        .all(|obj| parallel_safe(interpreter, obj, seen))
+    {
+        return false;
+    }
+    let mut locals: Vec<Symbol> = function.proto.params.clone();
+    locals.extend(function.proto.captures.iter().map(|(symbol, _)| *symbol));
+    locals.extend(compiler::SPECIAL_FORMS.iter().map(|name| Symbol::new(name)));
+    function
+        .proto
+        .body
+        .iter()
+        .for_each(|obj| bound(obj, &mut locals));
+    function
+        .proto
+        .body
+        .iter()
+        .all(|obj| safe_body(interpreter, obj, &locals, seen))
+}
+
+#[cfg(all(feature = "rayon", not(feature = "rc")))]
+fn pool() -> Option<&'static rayon::ThreadPool> {
+    static POOL: std::sync::OnceLock<Option<rayon::ThreadPool>> = std::sync::OnceLock::new();
+    // Workers get a main-thread sized stack so deep recursion behaves the
+    // same as in sequential evaluation.
+    POOL.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .stack_size(8 << 20)
+            .build()
+            .ok()
+    })
+    .as_ref()
+}
+
+// Evaluates the calls on the worker pool when the function and the
+// interpreter allow it, or returns None to have the caller run them in order.
+#[cfg(all(feature = "rayon", not(feature = "rc")))]
+fn parallel_map(
+    interpreter: &Interpreter,
+    function: &Object,
+    items: &[Object],
+) -> Option<Result<Vec<Object>, EvalError>> {
+    use rayon::prelude::*;
+    if items.len() < 2 || !parallel_safe(interpreter, function, &mut Vec::new()) {
+        return None;
+    }
+    let fork = interpreter.fork()?;
+    let pool = pool()?;
+    Some(pool.install(|| {
+        items
+            .par_iter()
+            .map(|item| fork.interpreter().apply(&[function.clone(), item.clone()]))
+            .collect()
+    }))
+}
+
+#[cfg(not(all(feature = "rayon", not(feature = "rc"))))]
+fn parallel_map(
+    _interpreter: &Interpreter,
+    _function: &Object,
+    _items: &[Object],
+) -> Option<Result<Vec<Object>, EvalError>> {
+    None
+}
+
+fn pmap(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let (function, items, vector) = match args(obj) {
+        [function, coll] => match coll.without_meta() {
+            Object::Vector(items) => (function, items, true),
+            Object::List(items) => (function, items, false),
+            _ => return Ok(Object::Null),
+        },
+        _ => return Ok(Object::Null),
+    };
+    let results = match parallel_map(interpreter, function, items) {
+        Some(results) => results?,
+        None => items
+            .iter()
+            .map(|item| interpreter.apply(&[function.clone(), item.clone()]))
+            .collect::<Result<Vec<Object>, EvalError>>()?,
+    };
+    Ok(if vector {
+        Object::Vector(results)
+    } else {
+        Object::List(results)
+    })
+}
+
+#[cfg(not(feature = "rc"))]
+pub(crate) enum ThreadState {
+    Running(JoinHandle<Result<Object, EvalError>>),
+    Joined(Option<Object>),
+}
+
+// The handle returned by (spawn). Joining twice returns the same value.
+#[cfg(not(feature = "rc"))]
+pub(crate) struct Thread {
+    pub(crate) state: Mutex<ThreadState>,
+}
+
+// The thread runs the function in a context of its own, seeded with the
+// spawning context's bindings, so its assignments stay private unless they
+// go to a name only the shared globals define.
+#[cfg(not(feature = "rc"))]
+fn spawn(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let function = match args(obj).first() {
+        Some(function) => function.clone(),
+        None => return Ok(Object::Null),
+    };
+    let evaluator = Arc::new(interpreter.evaluator().child());
+    let context = Context::with_bindings(evaluator, interpreter.context_bindings());
+    let handle = thread::Builder::new()
+        .stack_size(8 << 20)
+        .spawn(move || context.try_eval(&Object::List(vec![function])));
+    Ok(match handle {
+        Ok(handle) => {
+            let thread: Shared<AnyValue> = Shared::new(Thread {
+                state: Mutex::new(ThreadState::Running(handle)),
+            });
+            interpreter.evaluator().track(&thread);
+            Object::Other(thread)
+        }
+        Err(_) => Object::Null,
+    })
+}
+
+#[cfg(not(feature = "rc"))]
+fn join(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let thread = match args(obj).first().map(Object::without_meta) {
+        Some(Object::Other(other)) => match other.downcast_ref::<Thread>() {
+            Some(thread) => thread,
+            None => return Ok(Object::Null),
+        },
+        _ => return Ok(Object::Null),
+    };
+    loop {
+        match &*thread.state.lock().unwrap_or_else(PoisonError::into_inner) {
+            ThreadState::Joined(result) => return Ok(result.clone().unwrap_or(Object::Null)),
+            ThreadState::Running(handle) if handle.is_finished() => break,
+            ThreadState::Running(_) => {}
+        }
+        interpreter.check()?;
+        thread::sleep(POLL_INTERVAL);
+    }
+    let mut state = thread.state.lock().unwrap_or_else(PoisonError::into_inner);
+    if let ThreadState::Joined(result) = &*state {
+        return Ok(result.clone().unwrap_or(Object::Null));
+    }
+    let handle = match std::mem::replace(&mut *state, ThreadState::Joined(None)) {
+        ThreadState::Running(handle) => handle,
+        ThreadState::Joined(_) => unreachable!(),
+    };
+    let result = match handle.join() {
+        Ok(result) => result?,
+        Err(_) => Object::Null,
+    };
+    *state = ThreadState::Joined(Some(result.clone()));
+    Ok(result)
+}
+
+// How long a blocking builtin waits before checking for an interrupt or
+// an expired deadline.
+#[cfg(not(feature = "rc"))]
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Both ends live in one object, as in Go, so a channel never disconnects
+// and (recv) blocks until a value arrives. The queue carries keys to the
+// values sent, which are kept where the cycle collector can see them.
+#[cfg(not(feature = "rc"))]
+pub(crate) struct Channel {
+    sender: Sender<u64>,
+    receiver: Receiver<u64>,
+    next_key: AtomicU64,
+    pub(crate) values: Mutex<HashMap<u64, Object>>,
+}
+
+#[cfg(not(feature = "rc"))]
+impl Channel {
+    fn take(&self, key: u64) -> Object {
+        self.values
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&key)
+            .unwrap_or(Object::Null)
+    }
+}
+
+#[cfg(not(feature = "rc"))]
+fn channel(obj: &Object) -> Option<&Channel> {
+    match obj.without_meta() {
+        Object::Other(other) => other.downcast_ref::<Channel>(),
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "rc"))]
+fn chan(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let (sender, receiver) = match args(obj).first().map(Object::without_meta) {
+        Some(Object::Integer(capacity)) if *capacity >= 0 => {
+            crossbeam_channel::bounded(*capacity as usize)
+        }
+        _ => crossbeam_channel::unbounded(),
+    };
+    let channel: Shared<AnyValue> = Shared::new(Channel {
+        sender,
+        receiver,
+        next_key: AtomicU64::new(0),
+        values: Mutex::new(HashMap::new()),
+    });
+    interpreter.evaluator().track(&channel);
+    Ok(Object::Other(channel))
+}
+
+#[cfg(not(feature = "rc"))]
+fn send(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let (channel, value) = match args(obj) {
+        [channel_obj, value] => match channel(channel_obj) {
+            Some(channel) => (channel, value.clone()),
+            None => return Ok(Object::Bool(false)),
+        },
+        _ => return Ok(Object::Bool(false)),
+    };
+    let key = channel.next_key.fetch_add(1, AtomicOrdering::Relaxed);
+    channel
+        .values
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(key, value);
+    loop {
+        if let Err(error) = interpreter.check() {
+            channel.take(key);
+            return Err(error);
+        }
+        match channel.sender.send_timeout(key, POLL_INTERVAL) {
+            Ok(()) => return Ok(Object::Bool(true)),
+            Err(SendTimeoutError::Timeout(_)) => {}
+            Err(SendTimeoutError::Disconnected(_)) => {
+                channel.take(key);
+                return Ok(Object::Bool(false));
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "rc"))]
+fn recv(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let channel = match args(obj).first().and_then(channel) {
+        Some(channel) => channel,
+        None => return Ok(Object::Null),
+    };
+    loop {
+        interpreter.check()?;
+        match channel.receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(key) => return Ok(channel.take(key)),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(Object::Null),
+        }
+    }
+}
+
+// (select ch ...) waits for whichever channel has a value first and
+// returns [ch value].
+#[cfg(not(feature = "rc"))]
+fn select(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let channels: Vec<(&Object, &Channel)> = args(obj)
+        .iter()
+        .filter_map(|obj| channel(obj).map(|channel| (obj, channel)))
+        .collect();
+    if channels.is_empty() {
+        return Ok(Object::Null);
+    }
+    let mut select = Select::new();
+    for (_, channel) in &channels {
+        select.recv(&channel.receiver);
+    }
+    loop {
+        interpreter.check()?;
+        if let Ok(operation) = select.select_timeout(POLL_INTERVAL) {
+            let (obj, channel) = channels[operation.index()];
+            if let Ok(key) = operation.recv(&channel.receiver) {
+                return Ok(Object::Vector(vec![obj.clone(), channel.take(key)]));
+            }
+        }
+    }
+}
+
+fn generator(obj: &Object) -> Option<&Generator> {
+    match obj.without_meta() {
+        Object::Other(other) => other.downcast_ref::<Generator>(),
+        _ => None,
+    }
+}
+
+fn next(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    match args(obj).first().and_then(generator) {
+        Some(generator) => Ok(interpreter.resume(generator)?.unwrap_or(Object::Null)),
+        None => Ok(Object::Null),
+    }
+}
+
+fn done(_: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    Ok(Object::Bool(match args(obj).first().and_then(generator) {
+        Some(generator) => {
+            generator
+                .state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .done
+        }
+        None => true,
+    }))
+}
+
+// (take n g) resumes g up to n times; (collect g) until it finishes.
+fn take(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let (limit, generator) = match args(obj) {
+        [Object::Integer(n), generator_obj] => match generator(generator_obj) {
+            Some(generator) => (Some((*n).max(0) as usize), generator),
+            None => return Ok(Object::Null),
+        },
+        [generator_obj] => match generator(generator_obj) {
+            Some(generator) => (None, generator),
+            None => return Ok(Object::Null),
+        },
+        _ => return Ok(Object::Null),
+    };
+    let mut values = Vec::new();
+    while limit.is_none_or(|limit| values.len() < limit) {
+        match interpreter.resume(generator)? {
+            Some(value) => values.push(value),
+            None => break,
+        }
+    }
+    Ok(Object::Vector(values))
+}
+
+fn same(a: &Object, b: &Object) -> bool {
+    match (a, b) {
+        (Object::Other(a), Object::Other(b)) => Shared::ptr_eq(a, b),
+        _ => false,
+    }
+}
+
+// A traced function may be named or passed by value, in which case its
+// global name is found by identity.
+fn trace_name(interpreter: &Interpreter, obj: &Object) -> Option<Symbol> {
+    match obj {
+        Object::Symbol(symbol) => Some(*symbol),
+        Object::String(string) => Some(Symbol::new(string)),
+        Object::Other(other) => match other.downcast_ref::<Traced>() {
+            Some(traced) => Some(traced.name),
+            None => interpreter
+                .find_globals(|value| same(value, obj))
+                .into_iter()
+                .next(),
+        },
+        _ => None,
+    }
+}
+
+fn trace(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let mut traced = Vec::new();
+    for arg in args(obj) {
+        if let Some(name) = trace_name(interpreter, arg) {
+            let function = interpreter.lookup_global(name);
+            let is_traced = matches!(&function, Object::Other(other) if other.is::<Traced>());
+            if callable(&function) && !is_traced {
+                interpreter.set_global(name, Object::Other(Shared::new(Traced { name, function })));
+                traced.push(Object::Symbol(name));
+            }
+        }
+    }
+    Ok(Object::Vector(traced))
+}
+
+fn untrace(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let is_traced = |value: &Object| matches!(value, Object::Other(other) if other.is::<Traced>());
+    let names = match args(obj) {
+        [] => interpreter.find_globals(is_traced),
+        args => args
+            .iter()
+            .filter_map(|arg| trace_name(interpreter, arg))
+            .collect(),
+    };
+    let mut untraced = Vec::new();
+    for name in names {
+        if let Object::Other(other) = interpreter.lookup_global(name) {
+            if let Some(traced) = other.downcast_ref::<Traced>() {
+                interpreter.set_global(name, traced.function.clone());
+                untraced.push(Object::Symbol(name));
             }
         }
-        Object::Null
+    }
+    Ok(Object::Vector(untraced))
+}
+
+fn callable(obj: &Object) -> bool {
+    match obj.without_meta() {
+        Object::Other(other) => {
+            other.is::<Function>()
+                || other.is::<PrimitiveFunction>()
+                || other.is::<NativeFunction>()
+                || other.is::<Traced>()
+                || other.is::<Memoized>()
+                || other.is::<Partial>()
+                || other.is::<Composed>()
+        }
+        _ => false,
+    }
+}
+
+fn partial(_: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    match args(obj) {
+        [function, args @ ..] if callable(function) => Ok(Object::Other(Shared::new(Partial {
+            function: function.clone(),
+            args: args.to_vec(),
+        }))),
+        _ => Ok(Object::Null),
+    }
+}
+
+fn comp(_: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let functions = args(obj);
+    if !functions.iter().all(callable) {
+        return Ok(Object::Null);
+    }
+    Ok(Object::Other(Shared::new(Composed {
+        functions: functions.to_vec(),
+    })))
+}
+
+// (memoize f) or (memoize f {size: n, ttl: ms}).
+fn memoize(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let (function, options) = match args(obj) {
+        [function] => (function, HashMap::new()),
+        [function, Object::Map(options)] => (function, options.clone()),
+        _ => return Ok(Object::Null),
+    };
+    if !callable(function) {
+        return Ok(Object::Null);
+    }
+    let option = |name| match options.get(&Object::Symbol(Symbol::new(name))) {
+        Some(Object::Integer(n)) if *n > 0 => Some(*n as u64),
+        _ => None,
+    };
+    let memoized: Shared<AnyValue> = Shared::new(Memoized {
+        function: function.clone(),
+        size: option("size").map(|size| size as usize),
+        ttl: option("ttl")
+            .filter(|_| interpreter.evaluator().sandbox.time)
+            .map(Duration::from_millis),
+        cache: Mutex::new(MemoCache::default()),
+    });
+    interpreter.evaluator().track(&memoized);
+    Ok(Object::Other(memoized))
+}
+
+// Returns the number of cached results dropped, or null when the argument
+// is not memoized.
+fn memo_clear(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let memoized = match args(obj).first() {
+        Some(Object::Symbol(name)) => interpreter.lookup_global(*name),
+        Some(value) => value.clone(),
+        None => return Ok(Object::Null),
+    };
+    match memoized.without_meta() {
+        Object::Other(other) => match other.downcast_ref::<Memoized>() {
+            Some(memoized) => {
+                let mut cache = memoized
+                    .cache
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                Ok(Object::Integer(cache.clear() as i64))
+            }
+            None => Ok(Object::Null),
+        },
+        _ => Ok(Object::Null),
+    }
+}
+
+fn random(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let x = interpreter.evaluator().next_random();
+    match args(obj).first().map(Object::without_meta) {
+        Some(Object::Integer(n)) if *n > 0 => Ok(Object::Integer((x % *n as u64) as i64)),
+        _ => Ok(Object::Float((x >> 11) as f64 / (1u64 << 53) as f64)),
+    }
+}
+
+pub(crate) fn pure(primitive: PrimitiveFunction) -> bool {
+    let pure: [PrimitiveFunction; 6] = [get, meta, lt, gt, eq, not];
+    pure.iter().any(|f| std::ptr::fn_addr_eq(*f, primitive)) || arithmetic::is_pure(primitive)
+}
+
+pub(crate) fn args(obj: &Object) -> &[Object] {
+    match obj {
+        Object::List(list) if !list.is_empty() => &list[1..],
+        _ => &[],
+    }
+}
+
+pub(crate) fn truthy(obj: &Object) -> bool {
+    !matches!(obj.without_meta(), Object::Null | Object::Bool(false))
+}
+
+fn compare(obj: &Object, ordering: Ordering) -> Object {
+    let args = args(obj);
+    for pair in args.windows(2) {
+        let result = match (pair[0].without_meta(), pair[1].without_meta()) {
+            (Object::Integer(x), Object::Integer(y)) => x.partial_cmp(y),
+            (Object::Integer(x), Object::Float(y)) => (*x as f64).partial_cmp(y),
+            (Object::Float(x), Object::Integer(y)) => x.partial_cmp(&(*y as f64)),
+            (Object::Float(x), Object::Float(y)) => x.partial_cmp(y),
+            (Object::String(x), Object::String(y)) => x.partial_cmp(y),
+            #[cfg(feature = "bigint")]
+            (x, y) => match (big_integer(x), big_integer(y)) {
+                (Some(x), Some(y)) => x.partial_cmp(&y),
+                _ => None,
+            },
+            #[cfg(not(feature = "bigint"))]
+            _ => None,
+        };
+        if result != Some(ordering) {
+            return Object::Bool(false);
+        }
+    }
+    Object::Bool(true)
+}
+
+fn lt(obj: &Object) -> Object {
+    compare(obj, Ordering::Less)
+}
+
+fn gt(obj: &Object) -> Object {
+    compare(obj, Ordering::Greater)
+}
+
+fn eq(obj: &Object) -> Object {
+    let args = args(obj);
+    Object::Bool(args.windows(2).all(|pair| pair[0] == pair[1]))
+}
+
+fn not(obj: &Object) -> Object {
+    Object::Bool(!args(obj).first().is_some_and(truthy))
+}
+
+// Integers are finite numbers; anything else that is not a float is
+// neither NaN nor finite.
+fn is_nan(obj: &Object) -> Object {
+    match args(obj).first().map(Object::without_meta) {
+        Some(Object::Float(n)) => Object::Bool(n.is_nan()),
+        _ => Object::Bool(false),
+    }
+}
+
+fn is_finite(obj: &Object) -> Object {
+    match args(obj).first().map(Object::without_meta) {
+        Some(Object::Float(n)) => Object::Bool(n.is_finite()),
+        Some(Object::Integer(_)) => Object::Bool(true),
+        _ => Object::Bool(false),
+    }
+}
+
+// Closures copy the values they capture when they are created, so a
+// function can never reach itself through its captures: recursion goes
+// through a global name. Cycles can only pass through objects whose
+// contents change after they are made, which are tracked for the cycle
+// collector, see cycles.rs.
+#[derive(Debug)]
+pub struct Function {
+    pub proto: Shared<Proto>,
+    pub captured: Vec<Object>,
+}
+
+// A global function replaced by (trace name); calls through it are printed
+// to stderr, indented by trace depth, until (untrace name) puts the
+// original function back.
+#[derive(Debug)]
+pub(crate) struct Traced {
+    pub(crate) name: Symbol,
+    pub(crate) function: Object,
+}
+
+// (partial f a b) calls f with a and b ahead of its own arguments.
+#[derive(Debug)]
+pub(crate) struct Partial {
+    pub(crate) function: Object,
+    pub(crate) args: Vec<Object>,
+}
+
+// (comp f g h) calls h with its arguments, then g and f in turn with the
+// previous result. With no functions it returns its first argument.
+#[derive(Debug)]
+pub(crate) struct Composed {
+    pub(crate) functions: Vec<Object>,
+}
+
+// Calls through (memoize f) are answered from a cache keyed on the
+// argument list. Errors are not cached. Past the size limit the oldest
+// entry is evicted, and an entry older than the ttl is dropped and
+// computed again.
+#[derive(Debug)]
+pub(crate) struct Memoized {
+    pub(crate) function: Object,
+    pub(crate) size: Option<usize>,
+    pub(crate) ttl: Option<Duration>,
+    pub(crate) cache: Mutex<MemoCache>,
+}
+
+// Entries are kept in order of when they were stored, oldest first, so
+// both the one to evict and those past the ttl are at the front.
+#[derive(Debug, Default)]
+pub(crate) struct MemoCache {
+    pub(crate) entries: HashMap<Vec<Object>, (Object, Instant)>,
+    order: VecDeque<Vec<Object>>,
+}
+
+impl MemoCache {
+    fn expire(&mut self, ttl: Option<Duration>) {
+        let ttl = match ttl {
+            Some(ttl) => ttl,
+            None => return,
+        };
+        while let Some(oldest) = self.order.front() {
+            if self.entries[oldest].1.elapsed() < ttl {
+                break;
+            }
+            self.entries.remove(oldest);
+            self.order.pop_front();
+        }
+    }
+
+    pub(crate) fn get(&mut self, args: &[Object], ttl: Option<Duration>) -> Option<Object> {
+        self.expire(ttl);
+        self.entries.get(args).map(|(value, _)| value.clone())
+    }
+
+    pub(crate) fn insert(&mut self, args: Vec<Object>, value: Object, memoized: &Memoized) {
+        self.expire(memoized.ttl);
+        if self.entries.contains_key(&args) {
+            self.order.retain(|key| *key != args);
+        }
+        self.order.push_back(args.clone());
+        self.entries.insert(args, (value, Instant::now()));
+        while self.entries.len() > memoized.size.unwrap_or(usize::MAX) {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn clear(&mut self) -> usize {
+        self.order.clear();
+        self.entries.drain().count()
+    }
+
+    // Empties the cache, giving what it held.
+    pub(crate) fn take(&mut self) -> impl Iterator<Item = Object> {
+        self.order.clear();
+        std::mem::take(&mut self.entries)
+            .into_iter()
+            .flat_map(|(args, (value, _))| args.into_iter().chain(Some(value)))
+    }
+}
+
+// Calling a function made with fn* returns one of these instead of running
+// the body. The saved stack holds the generator frame between resumes; a
+// yield always happens in that frame, since only the generator's own body
+// may contain one.
+#[derive(Debug)]
+pub(crate) struct Generator {
+    pub(crate) proto: Shared<Proto>,
+    pub(crate) state: Mutex<GeneratorState>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct GeneratorState {
+    pub(crate) stack: Vec<Object>,
+    pub(crate) ip: usize,
+    pub(crate) done: bool,
+}
+
+// The frames between a shift and its nearest reset, outermost first, with
+// the stack they used. Each frame is its proto, resume point, base relative
+// to the saved stack and whether it is a delimiter. Calling the
+// continuation copies them back, so it can be resumed any number of times.
+#[derive(Debug)]
+pub(crate) struct Continuation {
+    pub(crate) frames: Vec<(Shared<Proto>, usize, usize, bool)>,
+    pub(crate) stack: Vec<Object>,
+}
+
+pub trait EvalHook: Send + Sync {
+    fn before(&self, _form: &Object) -> Option<Object> {
+        None
+    }
+
+    fn after(&self, _form: &Object, _value: &Object) {}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HookId(u64);
+
+#[derive(Clone, Debug)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, AtomicOrdering::Relaxed);
+    }
+}
+
+const DEFAULT_MAX_DEPTH: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sandbox {
+    pub filesystem: bool,
+    pub network: bool,
+    pub process: bool,
+    pub environment: bool,
+    // Without time, nothing a script sees depends on the clock: (time)
+    // reports nothing, memoize ignores ttl and random numbers start from
+    // the same seed unless the builder is given one.
+    pub time: bool,
+}
+
+impl Sandbox {
+    pub fn trusted() -> Sandbox {
+        Sandbox {
+            filesystem: true,
+            network: true,
+            process: true,
+            environment: true,
+            time: true,
+        }
+    }
+
+    pub fn pure() -> Sandbox {
+        Sandbox {
+            filesystem: false,
+            network: false,
+            process: false,
+            environment: false,
+            time: false,
+        }
+    }
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Sandbox::trusted()
+    }
+}
+
+// What integer arithmetic does when a result does not fit in an i64:
+// wrap around, which is the default, stop at i64::MIN or i64::MAX, fail
+// with EvalError::Overflow, or, with the bigint feature, give a big
+// integer. Whatever the mode, checked-add and the like give null on
+// overflow, and wrapping-add and saturating-add wrap and saturate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    #[default]
+    Wrap,
+    Saturate,
+    Checked,
+    #[cfg(feature = "bigint")]
+    Promote,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Module {
+    Json,
+    Toml,
+    Yaml,
+    Csv,
+    Random,
+    Crypto,
+    Compression,
+}
+
+impl Module {
+    pub fn all() -> Vec<Module> {
+        vec![
+            Module::Json,
+            Module::Toml,
+            Module::Yaml,
+            Module::Csv,
+            Module::Random,
+            Module::Crypto,
+            Module::Compression,
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Limits {
+    fuel: Option<u64>,
+    memory: Option<usize>,
+    timeout: Option<Duration>,
+}
+
+pub struct EvaluatorBuilder {
+    modules: Vec<Module>,
+    sandbox: Sandbox,
+    limits: Limits,
+    max_depth: usize,
+    seed: Option<u64>,
+    lib_paths: Vec<PathBuf>,
+    log_level: Level,
+    log_sink: Arc<dyn LogSink>,
+    output_sink: Arc<dyn OutputSink>,
+    bindings: Vec<(String, Object)>,
+    overflow: Overflow,
+}
+
+impl Default for EvaluatorBuilder {
+    fn default() -> Self {
+        EvaluatorBuilder {
+            modules: Module::all(),
+            sandbox: Sandbox::default(),
+            limits: Limits::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            seed: None,
+            lib_paths: Vec::new(),
+            log_level: Level::Info,
+            log_sink: Arc::new(Stderr),
+            output_sink: Arc::new(Stdio),
+            bindings: Vec::new(),
+            overflow: Overflow::default(),
+        }
+    }
+}
+
+impl EvaluatorBuilder {
+    pub fn modules(mut self, modules: &[Module]) -> EvaluatorBuilder {
+        self.modules = modules.to_vec();
+        self
+    }
+
+    pub fn sandbox(mut self, sandbox: Sandbox) -> EvaluatorBuilder {
+        self.sandbox = sandbox;
+        self
+    }
+
+    pub fn overflow(mut self, overflow: Overflow) -> EvaluatorBuilder {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn fuel(mut self, max_steps: u64) -> EvaluatorBuilder {
+        self.limits.fuel = Some(max_steps);
+        self
+    }
+
+    pub fn memory_limit(mut self, max_bytes: usize) -> EvaluatorBuilder {
+        self.limits.memory = Some(max_bytes);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> EvaluatorBuilder {
+        self.limits.timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> EvaluatorBuilder {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> EvaluatorBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    // Adds a directory require searches, after the ones added before it.
+    pub fn lib_path(mut self, path: impl Into<PathBuf>) -> EvaluatorBuilder {
+        self.lib_paths.push(path.into());
+        self
+    }
+
+    // Records below the level, info unless set, are left out.
+    pub fn log_level(mut self, level: Level) -> EvaluatorBuilder {
+        self.log_level = level;
+        self
+    }
+
+    // Where the log builtins write, stderr unless set.
+    pub fn log_sink(mut self, sink: impl LogSink + 'static) -> EvaluatorBuilder {
+        self.log_sink = Arc::new(sink);
+        self
+    }
+
+    // Where print and println write, the process's stdout unless set.
+    pub fn output_sink(mut self, sink: impl OutputSink + 'static) -> EvaluatorBuilder {
+        self.output_sink = Arc::new(sink);
+        self
+    }
+
+    pub fn define(mut self, name: &str, value: Object) -> EvaluatorBuilder {
+        self.bindings.push((name.to_string(), value));
+        self
+    }
+
+    pub fn primitive(self, name: &str, primitive: PrimitiveFunction) -> EvaluatorBuilder {
+        self.define(name, Object::Other(Shared::new(primitive)))
+    }
+
+    pub fn native(self, name: &str, native: NativeFunction) -> EvaluatorBuilder {
+        self.define(name, Object::Other(Shared::new(native)))
+    }
+
+    pub fn build(self) -> Evaluator {
+        let seed = self.seed.unwrap_or_else(|| match self.sandbox.time {
+            true => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_nanos() as u64),
+            // Any fixed seed would do; a small one starts with small numbers.
+            false => 0x9e37_79b9_7f4a_7c15,
+        });
+        let evaluator = Evaluator {
+            global: Shared::new(RwLock::new(HashMap::new())),
+            limits: self.limits,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            max_depth: self.max_depth,
+            rng: AtomicU64::new(seed | 1),
+            hooks: RwLock::new(Vec::new()),
+            next_hook: AtomicU64::new(0),
+            profiling: AtomicBool::new(false),
+            profile: Mutex::new(Profile::default()),
+            debugger: RwLock::new(None),
+            breakpoints: RwLock::new(Vec::new()),
+            recording: AtomicBool::new(false),
+            history: Mutex::new(Vec::new()),
+            dynamic: Shared::new(RwLock::new(HashSet::new())),
+            lib_paths: self.lib_paths.clone(),
+            libraries: Shared::new(Mutex::new(HashMap::new())),
+            logger: Shared::new(Logger::new(self.log_level, self.log_sink.clone())),
+            output: Shared::new(RwLock::new(self.output_sink.clone())),
+            cycles: Shared::new(Mutex::new(Registry::default())),
+            sandbox: self.sandbox,
+        };
+        let module = |module| self.modules.contains(&module);
+        evaluator.define_primitives(&[
+            ("get", get),
+            ("meta", meta),
+            ("with-meta", with_meta),
+            ("<", lt),
+            (">", gt),
+            ("=", eq),
+            ("not", not),
+            ("is-nan?", is_nan),
+            ("is-finite?", is_finite),
+            ("disassemble", disassemble),
+        ]);
+        match self.overflow {
+            Overflow::Wrap => evaluator.define_primitives(&arithmetic::WRAPPING),
+            Overflow::Saturate => evaluator.define_primitives(&arithmetic::SATURATING),
+            Overflow::Checked => evaluator.define_natives(&arithmetic::CHECKED),
+            #[cfg(feature = "bigint")]
+            Overflow::Promote => evaluator.define_primitives(&arithmetic::PROMOTING),
+        }
+        evaluator.define_primitives(&arithmetic::PRIMITIVES);
+        evaluator.define_natives(&[
+            ("print", print),
+            ("println", println),
+            ("eval", eval),
+            ("profile-report", profile_report),
+            ("trace", trace),
+            ("untrace", untrace),
+            ("history", history),
+            ("pmap", pmap),
+            ("next", next),
+            ("done?", done),
+            ("take", take),
+            ("collect", take),
+            ("memoize", memoize),
+            ("memo-clear", memo_clear),
+            ("partial", partial),
+            ("comp", comp),
+            ("exit", exit),
+            ("quit", exit),
+        ]);
+        #[cfg(feature = "json")]
+        if module(Module::Json) {
+            evaluator
+                .define_primitives(&[("json-encode", json_encode), ("json-decode", json_decode)]);
+        }
+        #[cfg(feature = "toml")]
+        if module(Module::Toml) {
+            evaluator
+                .define_primitives(&[("toml-encode", toml_encode), ("toml-decode", toml_decode)]);
+        }
+        #[cfg(feature = "yaml")]
+        if module(Module::Yaml) {
+            evaluator
+                .define_primitives(&[("yaml-encode", yaml_encode), ("yaml-decode", yaml_decode)]);
+        }
+        #[cfg(feature = "csv")]
+        if module(Module::Csv) && self.sandbox.filesystem {
+            evaluator.define_natives(&[("csv-read", csv_read)]);
+            evaluator.define_primitives(&[("csv-write", csv_write)]);
+        }
+        #[cfg(feature = "crypto")]
+        if module(Module::Crypto) {
+            evaluator.define_primitives(&crypto::PRIMITIVES);
+        }
+        #[cfg(feature = "gzip")]
+        if module(Module::Compression) {
+            evaluator.define_primitives(&compression::GZIP_PRIMITIVES);
+            evaluator.define_natives(&compression::GZIP_NATIVES);
+        }
+        #[cfg(feature = "zstd")]
+        if module(Module::Compression) {
+            evaluator.define_primitives(&compression::ZSTD_PRIMITIVES);
+            evaluator.define_natives(&compression::ZSTD_NATIVES);
+        }
+        evaluator.define_natives(&logging::NATIVES);
+        evaluator.define_natives(&cli::NATIVES);
+        evaluator.define_natives(&inspect::NATIVES);
+        evaluator.define_natives(&merge::NATIVES);
+        evaluator.define_natives(&template::NATIVES);
+        evaluator.define_primitives(&bytes::PRIMITIVES);
+        evaluator.define_primitives(&encoding::PRIMITIVES);
+        evaluator.define_primitives(&unicode::PRIMITIVES);
+        evaluator.define_primitives(&query::PRIMITIVES);
+        evaluator.define_primitives(&diff::PRIMITIVES);
+        evaluator.define_primitives(&schema::PRIMITIVES);
+        evaluator.define_primitives(&filesystem::PATH_PRIMITIVES);
+        if self.sandbox.filesystem {
+            evaluator.define_primitives(&filesystem::PRIMITIVES);
+            evaluator.define_natives(&[("require", require)]);
+        }
+        if self.sandbox.environment {
+            evaluator.define_primitives(&environment::PRIMITIVES);
+        }
+        if self.sandbox.network {
+            evaluator.define_primitives(&network::PRIMITIVES);
+            #[cfg(unix)]
+            evaluator.define_primitives(&network::UNIX_PRIMITIVES);
+        }
+        // Native code can do anything, so only a trusted sandbox loads it.
+        #[cfg(feature = "plugins")]
+        if self.sandbox == Sandbox::trusted() {
+            evaluator.define_natives(&[("load-native", load_native)]);
+        }
+        evaluator.define_primitives(&uuid::PRIMITIVES);
+        if module(Module::Random) {
+            evaluator.define_natives(&[("random", random)]);
+            evaluator.define_natives(&uuid::NATIVES);
+        }
+        if self.sandbox.process {
+            evaluator.define_primitives(&subprocess::PRIMITIVES);
+            evaluator.define_natives(&subprocess::NATIVES);
+            #[cfg(not(feature = "rc"))]
+            evaluator.define_natives(&[
+                ("spawn", spawn),
+                ("join", join),
+                ("chan", chan),
+                ("send", send),
+                ("recv", recv),
+                ("select", select),
+            ]);
+        }
+        for (name, value) in self.bindings {
+            evaluator.define(&name, value);
+        }
+        evaluator
+    }
+}
+
+// A snapshot holds every global binding whose value can be encoded with
+// Object::to_bytes, including closures. Bindings that hold native handles
+// (primitives, native functions, foreign objects) are left out and are
+// expected to be provided again by the builder of the restoring evaluator.
+pub struct Snapshot {
+    bindings: Object,
+}
+
+impl Snapshot {
+    pub fn len(&self) -> usize {
+        match &self.bindings {
+            Object::Map(map) => map.len(),
+            _ => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BinaryObjectError> {
+        self.bindings.to_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, BinaryObjectError> {
+        match Object::from_bytes(bytes)? {
+            Object::Map(map) if map.keys().all(|key| matches!(key, Object::Symbol(_))) => {
+                Ok(Snapshot {
+                    bindings: Object::Map(map),
+                })
+            }
+            _ => Err(BinaryObjectError::Foreign),
+        }
+    }
+}
+
+fn portable(obj: &Object) -> bool {
+    obj.to_bytes().is_ok()
+}
+
+// The global environment sits behind an RwLock that is only held for a
+// single lookup or assignment, never across a call, so concurrent
+// evaluations cannot deadlock on it. Assignments by one evaluation are
+// visible to others as soon as they happen; there is no isolation between
+// concurrent evaluations beyond that. Per-call state (fuel, memory budget,
+// deadline, depth) lives in an Interpreter created for each evaluation;
+// an interrupt stops whichever running evaluation observes it first.
+// Threads started with (spawn) share the same environment through a child
+// evaluator.
+pub(crate) type Globals = RwLock<HashMap<Symbol, Object>>;
+
+pub struct Evaluator {
+    global: Shared<Globals>,
+    pub(crate) limits: Limits,
+    pub(crate) interrupted: Arc<AtomicBool>,
+    pub(crate) max_depth: usize,
+    rng: AtomicU64,
+    hooks: RwLock<Vec<(HookId, Arc<dyn EvalHook>)>>,
+    next_hook: AtomicU64,
+    pub(crate) profiling: AtomicBool,
+    pub(crate) profile: Mutex<Profile>,
+    debugger: RwLock<Option<Arc<dyn Debugger>>>,
+    breakpoints: RwLock<Vec<Breakpoint>>,
+    pub(crate) recording: AtomicBool,
+    history: Mutex<Vec<Event>>,
+    dynamic: Shared<RwLock<HashSet<Symbol>>>,
+    lib_paths: Vec<PathBuf>,
+    libraries: Shared<Mutex<HashMap<PathBuf, Object>>>,
+    logger: Shared<Logger>,
+    output: Shared<RwLock<Arc<dyn OutputSink>>>,
+    cycles: Shared<Mutex<Registry>>,
+    pub(crate) sandbox: Sandbox,
+}
+
+impl Evaluator {
+    pub fn new() -> Evaluator {
+        Evaluator::builder().build()
+    }
+
+    pub fn builder() -> EvaluatorBuilder {
+        EvaluatorBuilder::default()
+    }
+
+    pub fn define(&self, name: &str, value: Object) {
+        self.set(Symbol::new(name), value);
+    }
+
+    // Loads the plugin at path, see plugin::Registrar, defining its builtins
+    // as globals. Gives the names it defined.
+    #[cfg(feature = "plugins")]
+    pub fn load_plugin(
+        &self,
+        path: impl AsRef<std::ffi::OsStr>,
+    ) -> Result<Vec<String>, crate::plugin::PluginError> {
+        let bindings = crate::plugin::load(path.as_ref())?;
+        let mut names = Vec::new();
+        for (name, value) in bindings {
+            self.define(&name, value);
+            names.push(name);
+        }
+        Ok(names)
+    }
+
+    pub fn lib_paths(&self) -> &[PathBuf] {
+        &self.lib_paths
+    }
+
+    // The file a library name stands for: the name with .fd appended, under
+    // the first search path where it exists. Names are relative and cannot
+    // leave the search path.
+    fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let name = Path::new(name);
+        if !name
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return None;
+        }
+        let mut file = name.as_os_str().to_owned();
+        file.push(".fd");
+        self.lib_paths
+            .iter()
+            .map(|dir| dir.join(&file))
+            .find(|path| path.is_file())
+            .map(|path| fs::canonicalize(&path).unwrap_or(path))
+    }
+
+    pub fn context(self: &Arc<Self>) -> Context {
+        Context::new(self.clone())
+    }
+
+    pub fn add_eval_hook(&self, hook: Arc<dyn EvalHook>) -> HookId {
+        let id = HookId(self.next_hook.fetch_add(1, AtomicOrdering::Relaxed));
+        self.hooks
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((id, hook));
+        id
+    }
+
+    pub fn remove_eval_hook(&self, id: HookId) -> bool {
+        let mut hooks = self.hooks.write().unwrap_or_else(PoisonError::into_inner);
+        let len = hooks.len();
+        hooks.retain(|(hook_id, _)| *hook_id != id);
+        hooks.len() != len
+    }
+
+    pub(crate) fn hooks(&self) -> Vec<Arc<dyn EvalHook>> {
+        self.hooks
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(_, hook)| hook.clone())
+            .collect()
+    }
+
+    pub fn attach_debugger(&self, debugger: Arc<dyn Debugger>) {
+        *self
+            .debugger
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(debugger);
+    }
+
+    pub fn detach_debugger(&self) {
+        *self
+            .debugger
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    pub(crate) fn debugger(&self) -> Option<Arc<dyn Debugger>> {
+        self.debugger
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn add_breakpoint(&self, breakpoint: Breakpoint) {
+        self.breakpoints
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&self, breakpoint: &Breakpoint) -> bool {
+        let mut breakpoints = self
+            .breakpoints
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let len = breakpoints.len();
+        breakpoints.retain(|other| other != breakpoint);
+        breakpoints.len() != len
+    }
+
+    pub fn clear_breakpoints(&self) {
+        self.breakpoints
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    pub(crate) fn breakpoints(&self) -> Vec<Breakpoint> {
+        self.breakpoints
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn enable_profiling(&self) {
+        self.profiling.store(true, AtomicOrdering::Relaxed);
+    }
+
+    pub fn disable_profiling(&self) {
+        self.profiling.store(false, AtomicOrdering::Relaxed);
+    }
+
+    pub fn profile(&self) -> Profile {
+        self.profile
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn reset_profile(&self) {
+        *self.profile.lock().unwrap_or_else(PoisonError::into_inner) = Profile::default();
+    }
+
+    pub fn profile_report(&self) -> Object {
+        self.profile().to_object()
+    }
+
+    // An evaluator for another thread over the same globals, limits,
+    // interrupt flag and loaded libraries. Hooks carry over; the debugger,
+    // profiler and recorder stay with this evaluator.
+    #[cfg(not(feature = "rc"))]
+    pub(crate) fn child(&self) -> Evaluator {
+        Evaluator {
+            global: self.global.clone(),
+            limits: self.limits,
+            interrupted: self.interrupted.clone(),
+            max_depth: self.max_depth,
+            rng: AtomicU64::new(self.next_random() | 1),
+            hooks: RwLock::new(
+                self.hooks
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone(),
+            ),
+            next_hook: AtomicU64::new(self.next_hook.load(AtomicOrdering::Relaxed)),
+            profiling: AtomicBool::new(false),
+            profile: Mutex::new(Profile::default()),
+            debugger: RwLock::new(None),
+            breakpoints: RwLock::new(Vec::new()),
+            recording: AtomicBool::new(false),
+            history: Mutex::new(Vec::new()),
+            dynamic: self.dynamic.clone(),
+            lib_paths: self.lib_paths.clone(),
+            libraries: self.libraries.clone(),
+            logger: self.logger.clone(),
+            output: self.output.clone(),
+            cycles: self.cycles.clone(),
+            sandbox: self.sandbox,
+        }
+    }
+
+    // Registers an object whose contents can change after it is made, so
+    // that cycles through it are collected, and collects them when enough
+    // such objects have been made.
+    pub(crate) fn track(&self, obj: &Shared<AnyValue>) {
+        let due = self
+            .cycles
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .track(obj);
+        if due {
+            self.collect_cycles();
+        }
+    }
+
+    // Frees the reference cycles among objects made by this evaluator and
+    // its threads, which happens on its own as they are made. Gives how
+    // many objects were in them.
+    pub fn collect_cycles(&self) -> usize {
+        let lock = || self.cycles.lock().unwrap_or_else(PoisonError::into_inner);
+        let roots = lock().roots();
+        let freed = cycles::collect(roots);
+        lock().collected();
+        freed
+    }
+
+    pub fn log_level(&self) -> Level {
+        self.logger.level()
+    }
+
+    pub fn set_log_level(&self, level: Level) {
+        self.logger.set_level(level);
+    }
+
+    pub fn set_log_sink(&self, sink: impl LogSink + 'static) {
+        self.logger.set_sink(Arc::new(sink));
+    }
+
+    pub(crate) fn logger(&self) -> &Logger {
+        &self.logger
+    }
+
+    pub fn output_sink(&self) -> Arc<dyn OutputSink> {
+        self.output
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set_output_sink(&self, sink: impl OutputSink + 'static) {
+        *self.output.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(sink);
+    }
+
+    pub(crate) fn write(&self, stream: Stream, text: &str) {
+        self.output_sink().write(stream, text);
+    }
+
+    pub fn enable_recording(&self) {
+        self.recording.store(true, AtomicOrdering::Relaxed);
+    }
+
+    pub fn disable_recording(&self) {
+        self.recording.store(false, AtomicOrdering::Relaxed);
+    }
+
+    pub fn history(&self) -> Vec<Event> {
+        self.history
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn clear_history(&self) {
+        self.history
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    // Names declared with defdynamic, which binding may rebind for the
+    // extent of its body.
+    pub(crate) fn declare_dynamic(&self, name: Symbol) {
+        self.dynamic
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(name);
+    }
+
+    pub(crate) fn is_dynamic(&self, name: Symbol) -> bool {
+        self.dynamic
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(&name)
+    }
+
+    pub(crate) fn record(&self, event: Event) {
+        self.history
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(event);
+    }
+
+    // Reapplies the assignments in a log, so replaying a prefix of the
+    // history reconstructs the globals as they were at that point. Calls
+    // are not repeated, since their effects are already in the log.
+    pub fn replay(&self, log: &[Event]) {
+        for event in log {
+            if let Event::Set { name, value } = event {
+                self.set(*name, value.clone());
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let bindings = self
+            .globals()
+            .iter()
+            .filter(|(_, value)| portable(value))
+            .map(|(symbol, value)| (Object::Symbol(*symbol), value.clone()))
+            .collect();
+        Snapshot {
+            bindings: Object::Map(bindings),
+        }
+    }
+
+    pub fn restore(&self, snapshot: &Snapshot) {
+        let mut global = self.global.write().unwrap_or_else(PoisonError::into_inner);
+        global.retain(|_, value| !portable(value));
+        if let Object::Map(map) = &snapshot.bindings {
+            for (key, value) in map {
+                if let Object::Symbol(symbol) = key {
+                    global.insert(*symbol, value.clone());
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Object> {
+        self.globals().get(&Symbol::new(name)).cloned()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .globals()
+            .keys()
+            .map(|symbol| symbol.as_str().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub(crate) fn globals(&self) -> RwLockReadGuard<'_, HashMap<Symbol, Object>> {
+        self.global.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn lookup(&self, symbol: Symbol) -> Object {
+        match self.globals().get(&symbol) {
+            Some(obj) => obj.clone(),
+            None => Object::Symbol(symbol),
+        }
+    }
+
+    pub(crate) fn set(&self, symbol: Symbol, value: Object) {
+        self.global
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(symbol, value);
+    }
+
+    // Removes every global whose name keep rejects.
+    #[cfg(feature = "serde")]
+    pub(crate) fn retain(&self, keep: impl Fn(&str) -> bool) {
+        self.global
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|symbol, _| keep(symbol.as_str()));
+    }
+
+    pub(crate) fn next_random(&self) -> u64 {
+        let step = |mut x: u64| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x
+        };
+        let previous = self
+            .rng
+            .fetch_update(AtomicOrdering::Relaxed, AtomicOrdering::Relaxed, |x| {
+                Some(step(x))
+            })
+            .unwrap_or_else(|x| x);
+        step(previous)
+    }
+
+    fn define_primitives(&self, primitives: &[(&str, PrimitiveFunction)]) {
+        for (name, primitive) in primitives {
+            self.define(name, Object::Other(Shared::new(*primitive)));
+        }
+    }
+
+    fn define_natives(&self, natives: &[(&str, NativeFunction)]) {
+        for (name, native) in natives {
+            self.define(name, Object::Other(Shared::new(*native)));
+        }
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle {
+            interrupted: self.interrupted.clone(),
+        }
+    }
+
+    pub fn eval(&self, obj: &Object) -> Object {
+        self.try_eval(obj).unwrap_or(Object::Null)
+    }
+
+    pub fn try_eval(&self, obj: &Object) -> Result<Object, EvalError> {
+        self.interpreter(self.limits).eval(obj)
+    }
+
+    // Evaluates obj on a thread of its own, see EvalFuture: the evaluation
+    // does not yield at fuel checkpoints or on blocking builtins, so each
+    // call takes a thread until it finishes. When no thread can be
+    // started the future gives EvalError::NoThread rather than evaluating
+    // on the caller's.
+    #[cfg(not(feature = "rc"))]
+    pub fn eval_async(self: &Arc<Self>, obj: &Object) -> EvalFuture {
+        EvalFuture::spawn(self.clone(), obj.clone())
+    }
+
+    pub fn eval_str(&self, source: &str) -> Result<Object, FundotError> {
+        let forms = Object::parse_all(source)?;
+        let interpreter = self.interpreter(self.limits);
+        let mut result = Object::Null;
+        for form in &forms {
+            result = interpreter.eval(form)?;
+        }
+        Ok(result)
+    }
+
+    // Evaluates every form in source, as eval_str does, and converts the
+    // last value.
+    pub fn eval_as<T: FromObject>(&self, source: &str) -> Result<T, FundotError> {
+        Ok(T::from_object(&self.eval_str(source)?)?)
+    }
+
+    pub fn eval_with_fuel(&self, obj: &Object, max_steps: u64) -> Result<Object, EvalError> {
+        let limits = Limits {
+            fuel: Some(max_steps),
+            ..self.limits
+        };
+        self.interpreter(limits).eval(obj)
+    }
+
+    pub fn eval_with_memory_limit(
+        &self,
+        obj: &Object,
+        max_bytes: usize,
+    ) -> Result<Object, EvalError> {
+        let limits = Limits {
+            memory: Some(max_bytes),
+            ..self.limits
+        };
+        self.interpreter(limits).eval(obj)
+    }
+
+    pub fn eval_with_timeout(&self, obj: &Object, timeout: Duration) -> Result<Object, EvalError> {
+        let limits = Limits {
+            timeout: Some(timeout),
+            ..self.limits
+        };
+        self.interpreter(limits).eval(obj)
+    }
+
+    pub fn eval_with_scope(
+        &self,
+        obj: &Object,
+        scope: &HashMap<String, Object>,
+    ) -> Result<Object, EvalError> {
+        let bindings = scope
+            .iter()
+            .map(|(name, value)| (Symbol::new(name), value.clone()))
+            .collect();
+        self.interpreter(self.limits)
+            .eval_with_bindings(obj, bindings)
+    }
+
+    pub(crate) fn interpreter(&self, limits: Limits) -> Interpreter<'_> {
+        let deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+        Interpreter::new(self, limits.fuel, limits.memory, deadline)
+    }
+
+    // Every form in source, compiled as one do body that gives the value of
+    // the last.
+    pub fn compile(&self, source: &str) -> Result<CompiledProgram, ParseObjectError> {
+        let forms = Object::parse_all(source)?;
+        let obj = Object::List([vec![Object::Symbol(Symbol::new("do"))], forms].concat());
+        let proto = Compiler::compile(&obj).unwrap_or_else(|_| Proto {
+            params: Vec::new(),
+            captures: Vec::new(),
+            body: vec![obj],
+            chunk: None,
+            generator: false,
+        });
+        Ok(CompiledProgram {
+            hash: compiler::source_hash(source),
+            proto: Shared::new(proto),
+        })
+    }
+
+    pub fn execute(&self, program: &CompiledProgram) -> Result<Object, EvalError> {
+        let function = Function {
+            proto: program.proto.clone(),
+            captured: Vec::new(),
+        };
+        self.interpreter(self.limits).call(&function, &[])
+    }
+
+    pub fn optimize(&self, obj: &Object) -> Object {
+        let dynamic = self
+            .dynamic
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        Optimizer::new(&self.globals(), dynamic).optimize(obj)
     }
 }
 
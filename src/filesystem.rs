@@ -0,0 +1,223 @@
+use crate::bytes;
+use crate::evaluator::{args, PrimitiveFunction};
+use crate::object::{Bytes, Object};
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{self, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// Path manipulation that does not look at the filesystem, available in any
+// sandbox.
+pub(crate) const PATH_PRIMITIVES: [(&str, PrimitiveFunction); 4] = [
+    ("path-join", path_join),
+    ("path-parent", path_parent),
+    ("path-filename", path_filename),
+    ("path-ext", path_ext),
+];
+
+// Like the other primitives, these give null when the operation fails.
+pub(crate) const PRIMITIVES: [(&str, PrimitiveFunction); 12] = [
+    ("read-file", read_file),
+    ("read-bytes", read_bytes),
+    ("write-file", write_file),
+    ("append-file", append_file),
+    ("file-exists?", file_exists),
+    ("list-dir", list_dir),
+    ("mkdir", mkdir),
+    ("remove-file", remove_file),
+    ("copy-file", copy_file),
+    ("file-metadata", file_metadata),
+    ("path-absolute", path_absolute),
+    ("glob", glob),
+];
+
+fn string(obj: &Object, index: usize) -> Option<&str> {
+    match args(obj).get(index).map(Object::without_meta) {
+        Some(Object::String(string)) => Some(string),
+        _ => None,
+    }
+}
+
+fn path_string(path: &Path) -> Object {
+    Object::from(path.to_string_lossy().into_owned())
+}
+
+fn path_join(obj: &Object) -> Object {
+    let mut joined = PathBuf::new();
+    for arg in args(obj) {
+        match arg.without_meta() {
+            Object::String(part) => joined.push(&**part),
+            _ => return Object::Null,
+        }
+    }
+    path_string(&joined)
+}
+
+fn path_parent(obj: &Object) -> Object {
+    string(obj, 0)
+        .and_then(|path| Path::new(path).parent())
+        .map_or(Object::Null, path_string)
+}
+
+fn path_filename(obj: &Object) -> Object {
+    string(obj, 0)
+        .and_then(|path| Path::new(path).file_name())
+        .map_or(Object::Null, |name| path_string(Path::new(name)))
+}
+
+// The extension without its dot: "gz" for "a.tar.gz".
+fn path_ext(obj: &Object) -> Object {
+    string(obj, 0)
+        .and_then(|path| Path::new(path).extension())
+        .map_or(Object::Null, |ext| path_string(Path::new(ext)))
+}
+
+// Relative paths are taken from the current directory. The path does not
+// have to exist and symbolic links are not resolved.
+fn path_absolute(obj: &Object) -> Object {
+    string(obj, 0)
+        .and_then(|path| path::absolute(path).ok())
+        .map_or(Object::Null, |path| path_string(&path))
+}
+
+// The paths matching a pattern such as "src/**/*.fd", sorted. Entries that
+// cannot be read are left out.
+fn glob(obj: &Object) -> Object {
+    let paths = match string(obj, 0).map(glob::glob) {
+        Some(Ok(paths)) => paths,
+        _ => return Object::Null,
+    };
+    let mut paths: Vec<PathBuf> = paths.filter_map(Result::ok).collect();
+    paths.sort();
+    Object::Vector(paths.iter().map(|path| path_string(path)).collect())
+}
+
+// Strings and bytes are written as they are and anything else as it
+// prints.
+fn contents(obj: &Object) -> Option<Vec<u8>> {
+    let value = args(obj).get(1)?;
+    match bytes::data(value) {
+        Some(data) => Some(data.to_vec()),
+        None => Some(value.to_string().into_bytes()),
+    }
+}
+
+fn read_file(obj: &Object) -> Object {
+    string(obj, 0)
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map_or(Object::Null, Object::from)
+}
+
+// The contents of a file as bytes, for files that are not text.
+fn read_bytes(obj: &Object) -> Object {
+    string(obj, 0)
+        .and_then(|path| fs::read(path).ok())
+        .map_or(Object::Null, |data| Object::from(Bytes(data)))
+}
+
+// Gives the number of bytes written.
+fn write(obj: &Object, append: bool) -> Option<Object> {
+    let path = string(obj, 0)?;
+    let contents = contents(obj)?;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .ok()?;
+    file.write_all(&contents).ok()?;
+    Some(Object::Integer(contents.len() as i64))
+}
+
+fn write_file(obj: &Object) -> Object {
+    write(obj, false).unwrap_or(Object::Null)
+}
+
+fn append_file(obj: &Object) -> Object {
+    write(obj, true).unwrap_or(Object::Null)
+}
+
+fn file_exists(obj: &Object) -> Object {
+    let exists = string(obj, 0).is_some_and(|path| fs::exists(path).unwrap_or(false));
+    Object::Bool(exists)
+}
+
+// The names of the entries, sorted.
+fn list_dir(obj: &Object) -> Object {
+    let entries = string(obj, 0).and_then(|path| fs::read_dir(path).ok());
+    let entries = match entries {
+        Some(entries) => entries,
+        None => return Object::Null,
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        match entry {
+            Ok(entry) => names.push(entry.file_name().to_string_lossy().into_owned()),
+            Err(_) => return Object::Null,
+        }
+    }
+    names.sort();
+    Object::Vector(names.into_iter().map(Object::from).collect())
+}
+
+// Missing parent directories are created as well.
+fn mkdir(obj: &Object) -> Object {
+    match string(obj, 0).map(fs::create_dir_all) {
+        Some(Ok(())) => Object::Bool(true),
+        _ => Object::Null,
+    }
+}
+
+fn remove_file(obj: &Object) -> Object {
+    match string(obj, 0).map(fs::remove_file) {
+        Some(Ok(())) => Object::Bool(true),
+        _ => Object::Null,
+    }
+}
+
+// Gives the number of bytes copied.
+fn copy_file(obj: &Object) -> Object {
+    let copied = match (string(obj, 0), string(obj, 1)) {
+        (Some(from), Some(to)) => fs::copy(from, to).ok(),
+        _ => None,
+    };
+    copied.map_or(Object::Null, |bytes| Object::Integer(bytes as i64))
+}
+
+// {size, type, readonly, modified}, where type is "file", "dir" or
+// "symlink" and modified is in seconds since the Unix epoch. Symbolic
+// links are described themselves rather than what they point to.
+fn file_metadata(obj: &Object) -> Object {
+    let metadata = match string(obj, 0).map(fs::symlink_metadata) {
+        Some(Ok(metadata)) => metadata,
+        _ => return Object::Null,
+    };
+    let kind = if metadata.is_dir() {
+        "dir"
+    } else if metadata.is_symlink() {
+        "symlink"
+    } else {
+        "file"
+    };
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(Object::Null, |duration| {
+            Object::Integer(duration.as_secs() as i64)
+        });
+    let entries = [
+        ("size", Object::Integer(metadata.len() as i64)),
+        ("type", Object::from(kind)),
+        ("readonly", Object::Bool(metadata.permissions().readonly())),
+        ("modified", modified),
+    ];
+    let map: HashMap<Object, Object> = entries
+        .iter()
+        .map(|(key, value)| (Object::Symbol(Symbol::new(key)), value.clone()))
+        .collect();
+    Object::Map(map)
+}
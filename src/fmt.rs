@@ -0,0 +1,168 @@
+use crate::object::{Object, ParseObjectError};
+use crate::pretty::{Doc, PrettyPrinter};
+
+const WIDTH: usize = 80;
+
+// A token keeps its source text, so literals are reprinted as written and
+// maps keep their order, along with the number of line breaks before it.
+struct Token {
+    text: String,
+    newlines: usize,
+}
+
+fn is_delimiter(c: char) -> bool {
+    "()[]{},:".contains(c)
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseObjectError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut newlines = 0;
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            newlines += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut text = c.to_string();
+        if c == '"' || c == '|' {
+            loop {
+                match chars.next() {
+                    Some(quote) if quote == c => break,
+                    Some('\\') => {
+                        text.push('\\');
+                        text.push(chars.next().ok_or(ParseObjectError {})?);
+                        continue;
+                    }
+                    Some(c) => text.push(c),
+                    None => return Err(ParseObjectError {}),
+                }
+            }
+            text.push(c);
+        } else if !is_delimiter(c) {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' || c == '|' || is_delimiter(c) {
+                    break;
+                }
+                text.push(c);
+                chars.next();
+            }
+        }
+        tokens.push(Token { text, newlines });
+        newlines = 0;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens
+            .get(self.position)
+            .map(|token| token.text.as_str())
+    }
+
+    fn next(&mut self) -> Result<&str, ParseObjectError> {
+        let token = self.tokens.get(self.position).ok_or(ParseObjectError {})?;
+        self.position += 1;
+        Ok(&token.text)
+    }
+
+    fn text(text: &str) -> Doc {
+        Doc::Text(text.to_string(), text.chars().count())
+    }
+
+    fn group(open: &str, close: &str, items: Vec<Doc>, list: bool) -> Doc {
+        Doc::Group {
+            open: Box::new(Parser::text(open)),
+            close: Box::new(Parser::text(close)),
+            items,
+            list,
+        }
+    }
+
+    // Commas separate vector and map elements; inside a list they are
+    // ordinary symbols.
+    fn form(&mut self) -> Result<Doc, ParseObjectError> {
+        let token = self.next()?.to_string();
+        let mut items = Vec::new();
+        match token.as_str() {
+            "(" => {
+                while self.peek() != Some(")") {
+                    items.push(self.form()?);
+                }
+                self.next()?;
+                Ok(Parser::group("(", ")", items, true))
+            }
+            "[" => {
+                while self.peek() != Some("]") {
+                    if self.peek() == Some(",") {
+                        self.next()?;
+                        continue;
+                    }
+                    items.push(self.form()?);
+                }
+                self.next()?;
+                Ok(Parser::group("[", "]", items, false))
+            }
+            "{" => {
+                while self.peek() != Some("}") {
+                    if self.peek() == Some(",") {
+                        self.next()?;
+                        continue;
+                    }
+                    let key = self.form()?;
+                    if self.next()? != ":" {
+                        return Err(ParseObjectError {});
+                    }
+                    let value = self.form()?;
+                    items.push(Doc::Pair(Box::new(key), Box::new(value)));
+                }
+                self.next()?;
+                Ok(Parser::group("{", "}", items, false))
+            }
+            ")" | "]" | "}" => Err(ParseObjectError {}),
+            _ => Ok(Parser::text(&token)),
+        }
+    }
+}
+
+// Reprints source with canonical spacing and indentation, breaking forms
+// that do not fit in 80 columns. A leading #! line is kept as it is, and a
+// blank line between top-level forms is kept as one. The result always
+// parses to the same forms as the source.
+pub fn format(source: &str) -> Result<String, ParseObjectError> {
+    let mut output = String::new();
+    let mut body = source;
+    if source.starts_with("#!") {
+        let end = source.find('\n').unwrap_or(source.len());
+        output.push_str(&source[..end]);
+        output.push('\n');
+        body = &source[end..];
+    }
+    let mut parser = Parser {
+        tokens: tokenize(body)?,
+        position: 0,
+    };
+    let printer = PrettyPrinter::new(WIDTH);
+    let mut first = true;
+    while let Some(token) = parser.tokens.get(parser.position) {
+        if !first && token.newlines > 1 {
+            output.push('\n');
+        }
+        first = false;
+        let form = parser.form()?;
+        output.push_str(&printer.layout(&form));
+        output.push('\n');
+    }
+    if Object::parse_all(&output)? != Object::parse_all(source)? {
+        return Err(ParseObjectError {});
+    }
+    Ok(output)
+}
@@ -0,0 +1,66 @@
+use crate::evaluator::{EvalError, Evaluator};
+use crate::object::Object;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+#[derive(Default)]
+struct State {
+    result: Option<Result<Object, EvalError>>,
+    waker: Option<Waker>,
+}
+
+// The evaluator recurses on the native stack, so an evaluation cannot be
+// suspended part way through: it does not yield at fuel checkpoints, and
+// blocking builtins such as recv block its thread. Instead it runs on a
+// thread of its own, one per future, and the future completes when that
+// thread finishes, which keeps async executors free while scripts run.
+// Bounding how many run at once is left to the caller. Dropping the future
+// does not stop the evaluation; use Evaluator::interrupt_handle for that.
+pub struct EvalFuture {
+    state: Arc<Mutex<State>>,
+}
+
+fn complete(state: &Mutex<State>, result: Result<Object, EvalError>) {
+    let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+    state.result = Some(result);
+    if let Some(waker) = state.waker.take() {
+        waker.wake();
+    }
+}
+
+impl EvalFuture {
+    pub(crate) fn spawn(evaluator: Arc<Evaluator>, obj: Object) -> EvalFuture {
+        let state = Arc::new(Mutex::new(State::default()));
+        let task = {
+            let state = state.clone();
+            move || complete(&state, evaluator.try_eval(&obj))
+        };
+        // Evaluating here instead would block the executor polling it.
+        if thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(task)
+            .is_err()
+        {
+            complete(&state, Err(EvalError::NoThread));
+        }
+        EvalFuture { state }
+    }
+}
+
+impl Future for EvalFuture {
+    type Output = Result<Object, EvalError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use crate::object::Object;
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+
+// Calls are recorded only when made directly by a top-level form, not
+// from inside another call, which keeps the log readable for long REPL
+// sessions. Every assignment to a global is recorded wherever it happens.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Set {
+        name: Symbol,
+        value: Object,
+    },
+    Call {
+        callee: Option<Symbol>,
+        args: Vec<Object>,
+    },
+}
+
+impl Event {
+    pub fn to_object(&self) -> Object {
+        let mut map = HashMap::new();
+        match self {
+            Event::Set { name, value } => {
+                map.insert(Object::from("event"), Object::from("set"));
+                map.insert(Object::from("name"), Object::Symbol(*name));
+                map.insert(Object::from("value"), value.clone());
+            }
+            Event::Call { callee, args } => {
+                let callee = callee.map_or(Object::Null, Object::Symbol);
+                map.insert(Object::from("event"), Object::from("call"));
+                map.insert(Object::from("callee"), callee);
+                map.insert(Object::from("args"), Object::Vector(args.clone()));
+            }
+        }
+        Object::Map(map)
+    }
+}
@@ -0,0 +1,242 @@
+use crate::evaluator::{args, EvalError, NativeFunction};
+use crate::interpreter::Interpreter;
+use crate::object::{DisplayLimits, Object};
+use crate::output::Stream;
+use crate::pretty::PrettyPrinter;
+use std::collections::HashSet;
+
+pub(crate) const NATIVES: [(&str, NativeFunction); 1] = [("inspect", inspect)];
+
+const DEFAULT_DEPTH: usize = 3;
+
+const SUMMARY_LIMITS: DisplayLimits = DisplayLimits {
+    max_elements: 8,
+    max_depth: 1,
+};
+
+// (inspect obj depth) pretty-prints obj down to depth levels, 3 unless
+// given, and a limited number of elements at each, then gives null. The
+// command line replaces it with an interactive viewer on a terminal.
+fn inspect(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let args = args(obj);
+    let value = args.first().unwrap_or(&Object::Null);
+    let depth = match args.get(1).map(Object::without_meta) {
+        Some(Object::Integer(depth)) if *depth > 0 => *depth as usize,
+        _ => DEFAULT_DEPTH,
+    };
+    let limits = DisplayLimits {
+        max_elements: 20,
+        max_depth: depth,
+    };
+    let mut text = PrettyPrinter::new(80).limits(limits).print(value);
+    text.push('\n');
+    interpreter.evaluator().write(Stream::Stdout, &text);
+    Ok(Object::Null)
+}
+
+// The entries of a container in the order the viewer shows them, map
+// entries sorted by how their keys print.
+fn children(value: &Object) -> Vec<(Option<String>, &Object)> {
+    match value.without_meta() {
+        Object::List(items) | Object::Vector(items) => {
+            items.iter().map(|item| (None, item)).collect()
+        }
+        Object::Map(map) => {
+            let mut entries: Vec<(Option<String>, &Object)> = map
+                .iter()
+                .map(|(key, value)| (Some(key.to_string()), value))
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            entries
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn is_container(value: &Object) -> bool {
+    matches!(
+        value.without_meta(),
+        Object::List(items) | Object::Vector(items) if !items.is_empty()
+    ) || matches!(value.without_meta(), Object::Map(map) if !map.is_empty())
+}
+
+// One row of the viewer. Path is the indices leading to it from the root
+// and key is how its key prints when it is in a map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub path: Vec<usize>,
+    pub depth: usize,
+    pub key: Option<String>,
+    pub text: String,
+    pub expandable: bool,
+    pub expanded: bool,
+}
+
+// A tree view of a value for browsing large maps and vectors: containers
+// expand and collapse, a cursor moves over the visible rows and search
+// finds rows by their text, expanding what hides them. Keys are shown
+// before their values and collapsed containers with their size and a
+// short preview.
+pub struct Inspector {
+    root: Object,
+    expanded: HashSet<Vec<usize>>,
+    cursor: usize,
+}
+
+impl Inspector {
+    // Starts with the root expanded.
+    pub fn new(root: Object) -> Inspector {
+        let mut expanded = HashSet::new();
+        expanded.insert(Vec::new());
+        Inspector {
+            root,
+            expanded,
+            cursor: 0,
+        }
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn row(&self, path: Vec<usize>, key: Option<&str>, value: &Object) -> Row {
+        let expandable = is_container(value);
+        let expanded = expandable && self.expanded.contains(&path);
+        let mut text = key.map_or(String::new(), |key| format!("{}: ", key));
+        if expandable {
+            let (count, noun) = match value.without_meta() {
+                Object::Map(map) => (map.len(), "keys"),
+                Object::List(items) | Object::Vector(items) => (items.len(), "items"),
+                _ => (0, "items"),
+            };
+            match expanded {
+                true => text.push_str(&format!("({} {})", count, noun)),
+                false => text.push_str(&format!(
+                    "{} ({} {})",
+                    value.display_with(SUMMARY_LIMITS),
+                    count,
+                    noun
+                )),
+            }
+        } else {
+            text.push_str(&value.display_with(SUMMARY_LIMITS).to_string());
+        }
+        Row {
+            depth: path.len(),
+            path,
+            key: key.map(str::to_string),
+            text,
+            expandable,
+            expanded,
+        }
+    }
+
+    // The rows in order: the ones shown when visible_only, otherwise all
+    // of them.
+    fn walk(&self, visible_only: bool) -> Vec<Row> {
+        let mut rows = Vec::new();
+        let mut pending = vec![(Vec::new(), None, &self.root)];
+        while let Some((path, key, value)) = pending.pop() {
+            let row = self.row(path.clone(), key.as_deref(), value);
+            let descend = row.expandable && (row.expanded || !visible_only);
+            rows.push(row);
+            if descend {
+                let entries = children(value);
+                for (index, (key, child)) in entries.into_iter().enumerate().rev() {
+                    let mut child_path = path.clone();
+                    child_path.push(index);
+                    pending.push((child_path, key, child));
+                }
+            }
+        }
+        rows
+    }
+
+    pub fn rows(&self) -> Vec<Row> {
+        self.walk(true)
+    }
+
+    pub fn up(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.rows().len() - 1);
+    }
+
+    pub fn top(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn bottom(&mut self) {
+        self.cursor = self.rows().len() - 1;
+    }
+
+    fn current(&self) -> Row {
+        self.rows().swap_remove(self.cursor)
+    }
+
+    pub fn toggle(&mut self) {
+        let row = self.current();
+        if row.expanded {
+            self.expanded.remove(&row.path);
+        } else if row.expandable {
+            self.expanded.insert(row.path);
+        }
+    }
+
+    pub fn expand(&mut self) {
+        let row = self.current();
+        if row.expandable {
+            self.expanded.insert(row.path);
+        }
+    }
+
+    // Collapses the row under the cursor, or moves to its parent when
+    // there is nothing to collapse.
+    pub fn collapse(&mut self) {
+        let row = self.current();
+        if row.expanded {
+            self.expanded.remove(&row.path);
+        } else if let Some((_, parent)) = row.path.split_last() {
+            self.select(parent);
+        }
+    }
+
+    fn select(&mut self, path: &[usize]) {
+        for end in 0..path.len() {
+            self.expanded.insert(path[..end].to_vec());
+        }
+        if let Some(index) = self.rows().iter().position(|row| row.path == path) {
+            self.cursor = index;
+        }
+    }
+
+    // Moves to the next row after the cursor whose text contains query,
+    // ignoring case and wrapping around, and gives whether there was one.
+    // Containers are only found by their keys, not by their previews.
+    pub fn search(&mut self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        let current = self.current().path;
+        let rows = self.walk(false);
+        let start = rows.iter().position(|row| row.path == current).unwrap_or(0);
+        let found = rows[start + 1..]
+            .iter()
+            .chain(&rows[..=start])
+            .find(|row| {
+                let text = match row.expandable {
+                    true => row.key.as_deref().unwrap_or_default(),
+                    false => &row.text,
+                };
+                text.to_lowercase().contains(&query)
+            })
+            .map(|row| row.path.clone());
+        match found {
+            Some(path) => {
+                self.select(&path);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,1199 @@
+use crate::clock::Instant;
+use crate::compiler::{dynamic_bindings, Compiler, Op, Proto};
+use crate::debugger::{Breakpoint, Debugger, Pause, Step};
+use crate::evaluator::{
+    truthy, Composed, Continuation, EvalError, EvalHook, Evaluator, Function, Generator,
+    GeneratorState, Globals, Memoized, NativeFunction, Partial, PrimitiveFunction, Traced,
+};
+use crate::history::Event;
+use crate::object::{AnyValue, Bytes, Object, Shared};
+use crate::output::Stream;
+use crate::profiler::CallStack;
+use crate::symbol::Symbol;
+use crate::types;
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, PoisonError, TryLockError};
+
+const DEADLINE_INTERVAL: u32 = 1024;
+
+struct Frame {
+    proto: Shared<Proto>,
+    ip: usize,
+    base: usize,
+    delimiter: bool,
+    _span: Option<CallSpan>,
+}
+
+#[cfg(feature = "tracing")]
+type CallSpan = tracing::span::EnteredSpan;
+
+#[cfg(not(feature = "tracing"))]
+struct CallSpan;
+
+// The callee is resolved lazily so untraced builds skip the lookup.
+#[cfg(feature = "tracing")]
+fn call_span(callee: impl FnOnce() -> Option<Symbol>, argc: usize) -> CallSpan {
+    let callee = callee().map_or("<anonymous>", |symbol| symbol.as_str());
+    tracing::debug_span!("call", callee, argc).entered()
+}
+
+#[cfg(not(feature = "tracing"))]
+fn call_span(_callee: impl FnOnce() -> Option<Symbol>, _argc: usize) -> CallSpan {
+    CallSpan
+}
+
+enum Exit {
+    Return(Object),
+    Yield(Object, usize),
+}
+
+type Scope = Vec<(Symbol, Object)>;
+
+#[cfg(feature = "stacker")]
+fn grow<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(64 * 1024, 1024 * 1024, f)
+}
+
+#[cfg(not(feature = "stacker"))]
+fn grow<R>(f: impl FnOnce() -> R) -> R {
+    f()
+}
+
+// What a value held in Object::Other holds beyond itself, for the values
+// that can be large.
+fn payload_size(other: &Shared<AnyValue>) -> usize {
+    if let Some(bytes) = other.downcast_ref::<Bytes>() {
+        return bytes.0.len();
+    }
+    #[cfg(feature = "bigint")]
+    if let Some(n) = other.downcast_ref::<num_bigint::BigInt>() {
+        return (n.bits() / 8) as usize;
+    }
+    0
+}
+
+fn approximate_size(obj: &Object, limit: usize) -> usize {
+    let mut size = 0;
+    let mut pending = vec![obj];
+    while let Some(obj) = pending.pop() {
+        size += std::mem::size_of::<Object>();
+        match obj.without_meta() {
+            Object::String(s) => size += s.len(),
+            Object::Other(other) => size += payload_size(other),
+            Object::List(list) | Object::Vector(list) => pending.extend(list),
+            Object::Map(map) => {
+                for (key, value) in map {
+                    pending.push(key);
+                    pending.push(value);
+                }
+            }
+            _ => {}
+        }
+        if size > limit {
+            break;
+        }
+    }
+    size
+}
+
+pub struct Interpreter<'a> {
+    evaluator: &'a Evaluator,
+    globals: Option<&'a Globals>,
+    hooks: Vec<Arc<dyn EvalHook>>,
+    fuel: Cell<Option<u64>>,
+    memory: Cell<Option<usize>>,
+    deadline: Option<Instant>,
+    ticks: Cell<u32>,
+    steps: Cell<u64>,
+    depth: Cell<usize>,
+    calls: Option<CallStack>,
+    debugger: Option<Arc<dyn Debugger>>,
+    breakpoints: Vec<Breakpoint>,
+    stepping: Cell<(Step, usize)>,
+    trace_depth: Cell<usize>,
+    recording: bool,
+    call_depth: Cell<usize>,
+    bindings: RefCell<Vec<(Symbol, Object)>>,
+}
+
+// What a worker thread needs to evaluate on behalf of an interpreter.
+#[cfg(all(feature = "rayon", not(feature = "rc")))]
+pub(crate) struct Fork<'a> {
+    evaluator: &'a Evaluator,
+    globals: Option<&'a Globals>,
+    deadline: Option<Instant>,
+    depth: usize,
+}
+
+#[cfg(all(feature = "rayon", not(feature = "rc")))]
+impl<'a> Fork<'a> {
+    pub(crate) fn interpreter(&self) -> Interpreter<'a> {
+        let interpreter = Interpreter::new(self.evaluator, None, None, self.deadline);
+        interpreter.depth.set(self.depth);
+        match self.globals {
+            Some(globals) => interpreter.with_globals(globals),
+            None => interpreter,
+        }
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    pub(crate) fn new(
+        evaluator: &'a Evaluator,
+        fuel: Option<u64>,
+        memory: Option<usize>,
+        deadline: Option<Instant>,
+    ) -> Interpreter<'a> {
+        Interpreter {
+            evaluator,
+            globals: None,
+            hooks: evaluator.hooks(),
+            fuel: Cell::new(fuel),
+            memory: Cell::new(memory),
+            deadline,
+            ticks: Cell::new(0),
+            steps: Cell::new(0),
+            depth: Cell::new(0),
+            calls: if evaluator.profiling.load(Ordering::Relaxed) {
+                Some(CallStack::default())
+            } else {
+                None
+            },
+            debugger: evaluator.debugger(),
+            breakpoints: evaluator.breakpoints(),
+            stepping: Cell::new((Step::StepIn, 0)),
+            trace_depth: Cell::new(0),
+            recording: evaluator.recording.load(Ordering::Relaxed),
+            call_depth: Cell::new(0),
+            bindings: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn with_globals(mut self, globals: &'a Globals) -> Interpreter<'a> {
+        self.globals = Some(globals);
+        self
+    }
+
+    pub fn evaluator(&self) -> &'a Evaluator {
+        self.evaluator
+    }
+
+    pub(crate) fn lookup_global(&self, symbol: Symbol) -> Object {
+        if let Some((_, value)) = self
+            .bindings
+            .borrow()
+            .iter()
+            .rev()
+            .find(|(name, _)| *name == symbol)
+        {
+            return value.clone();
+        }
+        if let Some(globals) = self.globals {
+            let globals = globals.read().unwrap_or_else(PoisonError::into_inner);
+            if let Some(value) = globals.get(&symbol) {
+                return value.clone();
+            }
+        }
+        self.evaluator.lookup(symbol)
+    }
+
+    #[cfg(not(feature = "rc"))]
+    pub(crate) fn context_bindings(&self) -> std::collections::HashMap<Symbol, Object> {
+        match self.globals {
+            Some(globals) => globals
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+            None => Default::default(),
+        }
+    }
+
+    pub(crate) fn find_globals(&self, predicate: impl Fn(&Object) -> bool) -> Vec<Symbol> {
+        let mut names = Vec::new();
+        if let Some(globals) = self.globals {
+            let globals = globals.read().unwrap_or_else(PoisonError::into_inner);
+            names.extend(
+                globals
+                    .iter()
+                    .filter(|(_, value)| predicate(value))
+                    .map(|(name, _)| *name),
+            );
+        }
+        let globals = self.evaluator.globals();
+        names.extend(
+            globals
+                .iter()
+                .filter(|(_, value)| predicate(value))
+                .map(|(name, _)| *name),
+        );
+        names
+    }
+
+    // Assigning a dynamically bound name changes its innermost binding.
+    pub(crate) fn set_global(&self, symbol: Symbol, value: Object) {
+        if let Some((_, binding)) = self
+            .bindings
+            .borrow_mut()
+            .iter_mut()
+            .rev()
+            .find(|(name, _)| *name == symbol)
+        {
+            *binding = value;
+            return;
+        }
+        if self.recording {
+            self.evaluator.record(Event::Set {
+                name: symbol,
+                value: value.clone(),
+            });
+        }
+        match self.globals {
+            Some(globals) => {
+                globals
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(symbol, value);
+            }
+            None => self.evaluator.set(symbol, value),
+        }
+    }
+
+    // Budgets, hooks, debuggers, the profiler and the recorder all keep
+    // per-evaluation state that workers could not share.
+    #[cfg(all(feature = "rayon", not(feature = "rc")))]
+    pub(crate) fn fork(&self) -> Option<Fork<'a>> {
+        let limited = self.fuel.get().is_some() || self.memory.get().is_some();
+        let bound = !self.bindings.borrow().is_empty();
+        if limited || bound || self.walking() || self.calls.is_some() || self.recording {
+            return None;
+        }
+        Some(Fork {
+            evaluator: self.evaluator,
+            globals: self.globals,
+            deadline: self.deadline,
+            depth: self.depth.get(),
+        })
+    }
+
+    // Hooks and debuggers observe individual forms, which only the
+    // tree-walker preserves.
+    fn walking(&self) -> bool {
+        !self.hooks.is_empty() || self.debugger.is_some()
+    }
+
+    pub fn eval(&self, obj: &Object) -> Result<Object, EvalError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("eval").entered();
+        if self.walking() {
+            return self.walk(obj, &mut Vec::new());
+        }
+        match Compiler::compile(obj) {
+            Ok(proto) => {
+                let function = Function {
+                    proto: Shared::new(proto),
+                    captured: Vec::new(),
+                };
+                self.run(&function, &[])
+            }
+            Err(_) => self.walk(obj, &mut Vec::new()),
+        }
+    }
+
+    pub fn eval_with_bindings(
+        &self,
+        obj: &Object,
+        mut bindings: Vec<(Symbol, Object)>,
+    ) -> Result<Object, EvalError> {
+        if self.walking() {
+            return self.walk(obj, &mut bindings);
+        }
+        let params = bindings
+            .iter()
+            .map(|(symbol, _)| Object::Symbol(*symbol))
+            .collect();
+        match Compiler::compile_function(&Object::List(params), std::slice::from_ref(obj), &[]) {
+            Ok(closure) => {
+                let function = Function {
+                    proto: closure.proto,
+                    captured: Vec::new(),
+                };
+                let args: Vec<Object> = bindings.into_iter().map(|(_, value)| value).collect();
+                self.run(&function, &args)
+            }
+            Err(_) => self.walk(obj, &mut bindings),
+        }
+    }
+
+    fn descend(&self) -> Result<(), EvalError> {
+        let depth = self.depth.get() + 1;
+        if depth > self.evaluator.max_depth {
+            return Err(EvalError::StackOverflow);
+        }
+        self.depth.set(depth);
+        Ok(())
+    }
+
+    fn step(&self) -> Result<(), EvalError> {
+        if self.evaluator.interrupted.load(Ordering::Relaxed) {
+            self.evaluator.interrupted.store(false, Ordering::Relaxed);
+            return Err(EvalError::Interrupted);
+        }
+        self.steps.set(self.steps.get() + 1);
+        if let Some(deadline) = self.deadline {
+            let ticks = self.ticks.get().wrapping_add(1);
+            self.ticks.set(ticks);
+            if ticks.is_multiple_of(DEADLINE_INTERVAL) && Instant::now() >= deadline {
+                return Err(EvalError::Timeout);
+            }
+        }
+        match self.fuel.get() {
+            Some(0) => Err(EvalError::BudgetExceeded),
+            Some(fuel) => {
+                self.fuel.set(Some(fuel - 1));
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn enter_call(&self, callee: impl FnOnce() -> Option<Symbol>, args: &[Object]) {
+        let depth = self.call_depth.get();
+        self.call_depth.set(depth + 1);
+        let record = self.recording && depth == 0;
+        if self.calls.is_none() && !record {
+            return;
+        }
+        let callee = callee();
+        if let Some(calls) = &self.calls {
+            calls.enter(callee);
+        }
+        if record {
+            self.evaluator.record(Event::Call {
+                callee,
+                args: args.to_vec(),
+            });
+        }
+    }
+
+    fn exit_call(&self) {
+        self.call_depth.set(self.call_depth.get() - 1);
+        if let Some(calls) = &self.calls {
+            calls.exit(&self.evaluator.profile);
+        }
+    }
+
+    // How much more an evaluation under a memory limit may make, so that
+    // builtins can stop making a large value before it is charged.
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    pub(crate) fn memory_left(&self) -> Option<usize> {
+        self.memory.get()
+    }
+
+    // Lets builtins that block (joins, channel operations) give up when the
+    // evaluation is interrupted or out of time.
+    #[cfg(not(feature = "rc"))]
+    pub(crate) fn check(&self) -> Result<(), EvalError> {
+        if self.evaluator.interrupted.swap(false, Ordering::Relaxed) {
+            return Err(EvalError::Interrupted);
+        }
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(EvalError::Timeout),
+            _ => Ok(()),
+        }
+    }
+
+    // Reports how long the body took and how many steps it ran on stderr,
+    // along with the memory it was charged for when there is a limit.
+    // Sandboxes without time only run the body.
+    fn time(&self, body: impl FnOnce() -> Result<Object, EvalError>) -> Result<Object, EvalError> {
+        if !self.evaluator.sandbox.time {
+            return body();
+        }
+        let start = Instant::now();
+        let steps = self.steps.get();
+        let memory = self.memory.get();
+        let result = body()?;
+        let mut report = format!(
+            "time: {:?}, {} steps",
+            start.elapsed(),
+            self.steps.get() - steps
+        );
+        if let (Some(before), Some(after)) = (memory, self.memory.get()) {
+            report.push_str(&format!(", {} bytes", before - after));
+        }
+        report.push('\n');
+        self.evaluator.write(Stream::Stderr, &report);
+        Ok(result)
+    }
+
+    // Runs body and, if it fails, puts back the depths that the failure
+    // left unwound, so that evaluation can carry on after it.
+    pub(crate) fn attempt(
+        &self,
+        body: impl FnOnce() -> Result<Object, EvalError>,
+    ) -> Result<Object, EvalError> {
+        let depth = self.depth.get();
+        let call_depth = self.call_depth.get();
+        let trace_depth = self.trace_depth.get();
+        let result = body();
+        if result.is_err() {
+            self.depth.set(depth);
+            self.call_depth.set(call_depth);
+            self.trace_depth.set(trace_depth);
+        }
+        result
+    }
+
+    // Names not declared with defdynamic are left alone.
+    fn bind(
+        &self,
+        bindings: Vec<(Symbol, Object)>,
+        body: impl FnOnce() -> Result<Object, EvalError>,
+    ) -> Result<Object, EvalError> {
+        let len = self.bindings.borrow().len();
+        self.bindings.borrow_mut().extend(
+            bindings
+                .into_iter()
+                .filter(|(name, _)| self.evaluator.is_dynamic(*name)),
+        );
+        let result = body();
+        self.bindings.borrow_mut().truncate(len);
+        result
+    }
+
+    fn charge(&self, obj: Object) -> Result<Object, EvalError> {
+        if let Some(memory) = self.memory.get() {
+            let size = approximate_size(&obj, memory);
+            if size > memory {
+                self.memory.set(Some(0));
+                return Err(EvalError::MemoryLimitExceeded);
+            }
+            self.memory.set(Some(memory - size));
+        }
+        Ok(obj)
+    }
+
+    pub fn apply(&self, list: &[Object]) -> Result<Object, EvalError> {
+        if let Some(Object::Other(other)) = list.first().map(Object::without_meta) {
+            if let Some(primitive_function) = other.downcast_ref::<PrimitiveFunction>() {
+                return self.charge(primitive_function(&Object::List(list.to_vec())));
+            }
+            if let Some(native_function) = other.downcast_ref::<NativeFunction>() {
+                return self.charge(native_function(self, &Object::List(list.to_vec()))?);
+            }
+            if let Some(function) = other.downcast_ref::<Function>() {
+                return self.call(function, &list[1..]);
+            }
+            if let Some(traced) = other.downcast_ref::<Traced>() {
+                return self.apply_traced(traced, &list[1..]);
+            }
+            if let Some(partial) = other.downcast_ref::<Partial>() {
+                let list = [
+                    std::slice::from_ref(&partial.function),
+                    &partial.args,
+                    &list[1..],
+                ];
+                return self.apply(&list.concat());
+            }
+            if let Some(composed) = other.downcast_ref::<Composed>() {
+                let mut functions = composed.functions.iter().rev();
+                let mut value = match functions.next() {
+                    Some(first) => {
+                        self.apply(&[std::slice::from_ref(first), &list[1..]].concat())?
+                    }
+                    None => list.get(1).cloned().unwrap_or(Object::Null),
+                };
+                for function in functions {
+                    value = self.apply(&[function.clone(), value])?;
+                }
+                return Ok(value);
+            }
+            if let Some(memoized) = other.downcast_ref::<Memoized>() {
+                return self.apply_memoized(memoized, &list[1..]);
+            }
+            if let Some(continuation) = other.downcast_ref::<Continuation>() {
+                let value = list.get(1).cloned().unwrap_or(Object::Null);
+                return self.resume_continuation(continuation, value);
+            }
+        }
+        Ok(Object::Null)
+    }
+
+    fn apply_traced(&self, traced: &Traced, args: &[Object]) -> Result<Object, EvalError> {
+        let depth = self.trace_depth.get();
+        let indent = "  ".repeat(depth);
+        let call = [&[Object::Symbol(traced.name)], args].concat();
+        let line = format!("{}{}\n", indent, Object::List(call));
+        self.evaluator.write(Stream::Stderr, &line);
+        self.trace_depth.set(depth + 1);
+        let result = self.apply(&[std::slice::from_ref(&traced.function), args].concat());
+        self.trace_depth.set(depth);
+        if let Ok(value) = &result {
+            let line = format!("{}=> {}\n", indent, value);
+            self.evaluator.write(Stream::Stderr, &line);
+        }
+        result
+    }
+
+    // The cache is not held during the call, so recursive calls through the
+    // memoized function are cached as well.
+    fn apply_memoized(&self, memoized: &Memoized, args: &[Object]) -> Result<Object, EvalError> {
+        let cache = || {
+            memoized
+                .cache
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+        };
+        if let Some(value) = cache().get(args, memoized.ttl) {
+            return Ok(value);
+        }
+        let value = self.apply(&[std::slice::from_ref(&memoized.function), args].concat())?;
+        cache().insert(args.to_vec(), value.clone(), memoized);
+        Ok(value)
+    }
+
+    pub(crate) fn call(&self, function: &Function, args: &[Object]) -> Result<Object, EvalError> {
+        if function.proto.generator {
+            if function.proto.chunk.is_none() {
+                return Ok(Object::Null);
+            }
+            let mut stack = vec![Object::Null];
+            stack.extend_from_slice(args);
+            Interpreter::enter(&mut stack, function, 1, args.len());
+            let generator: Shared<AnyValue> = Shared::new(Generator {
+                proto: function.proto.clone(),
+                state: std::sync::Mutex::new(GeneratorState {
+                    stack,
+                    ..GeneratorState::default()
+                }),
+            });
+            self.evaluator.track(&generator);
+            return Ok(Object::Other(generator));
+        }
+        if function.proto.chunk.is_some() && !self.walking() {
+            return self.run(function, args);
+        }
+        let proto = &function.proto;
+        let mut scope: Scope = proto
+            .captures
+            .iter()
+            .map(|(symbol, _)| *symbol)
+            .zip(function.captured.iter().cloned())
+            .collect();
+        for (i, param) in proto.params.iter().enumerate() {
+            scope.push((*param, args.get(i).cloned().unwrap_or(Object::Null)));
+        }
+        self.walk_body(&proto.body, &mut scope)
+    }
+
+    fn enter(stack: &mut Vec<Object>, function: &Function, base: usize, argc: usize) {
+        let proto = &function.proto;
+        let chunk = proto.chunk.as_ref().unwrap();
+        stack.truncate(base + argc.min(proto.params.len()));
+        stack.resize(base + chunk.locals as usize, Object::Null);
+        for ((_, slot), value) in proto.captures.iter().zip(function.captured.iter()) {
+            stack[base + *slot as usize] = value.clone();
+        }
+    }
+
+    fn run(&self, function: &Function, args: &[Object]) -> Result<Object, EvalError> {
+        let mut stack = Vec::with_capacity(args.len() + 1);
+        stack.push(Object::Null);
+        stack.extend_from_slice(args);
+        Interpreter::enter(&mut stack, function, 1, args.len());
+        let frame = Frame {
+            proto: function.proto.clone(),
+            ip: 0,
+            base: 1,
+            delimiter: false,
+            _span: None,
+        };
+        match self.drive(&mut stack, Vec::new(), frame)? {
+            Exit::Return(value) | Exit::Yield(value, _) => Ok(value),
+        }
+    }
+
+    // Returns the next value of a generator, or None once it has finished.
+    // A generator that is already running, such as one resuming itself,
+    // produces nothing.
+    pub(crate) fn resume(&self, generator: &Generator) -> Result<Option<Object>, EvalError> {
+        let mut state = match generator.state.try_lock() {
+            Ok(state) => state,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return Ok(None),
+        };
+        if state.done {
+            return Ok(None);
+        }
+        let mut stack = std::mem::take(&mut state.stack);
+        if state.ip > 0 {
+            stack.push(Object::Null);
+        }
+        let frame = Frame {
+            proto: generator.proto.clone(),
+            ip: state.ip,
+            base: 1,
+            delimiter: false,
+            _span: None,
+        };
+        match self.drive(&mut stack, Vec::new(), frame) {
+            Ok(Exit::Yield(value, ip)) => {
+                state.stack = stack;
+                state.ip = ip;
+                Ok(Some(value))
+            }
+            Ok(Exit::Return(_)) => {
+                state.done = true;
+                Ok(None)
+            }
+            Err(error) => {
+                state.done = true;
+                Err(error)
+            }
+        }
+    }
+
+    fn resume_continuation(
+        &self,
+        continuation: &Continuation,
+        value: Object,
+    ) -> Result<Object, EvalError> {
+        let mut stack = Vec::new();
+        let mut frames = Interpreter::reinstate(continuation, &mut stack, value);
+        let frame = frames.pop().unwrap();
+        match self.drive(&mut stack, frames, frame)? {
+            Exit::Return(value) | Exit::Yield(value, _) => Ok(value),
+        }
+    }
+
+    // Copies a continuation's frames onto the stack, outermost first, with
+    // the value of the shift it resumes from on top.
+    fn reinstate(
+        continuation: &Continuation,
+        stack: &mut Vec<Object>,
+        value: Object,
+    ) -> Vec<Frame> {
+        let base = stack.len();
+        stack.extend_from_slice(&continuation.stack);
+        stack.push(value);
+        continuation
+            .frames
+            .iter()
+            .map(|(proto, ip, offset, delimiter)| Frame {
+                proto: proto.clone(),
+                ip: *ip,
+                base: base + offset,
+                delimiter: *delimiter,
+                _span: None,
+            })
+            .collect()
+    }
+
+    // Accounts for frames pushed without a call, so that their returns
+    // balance.
+    fn reenter(&self, frames: usize) -> Result<(), EvalError> {
+        for _ in 0..frames {
+            self.descend()?;
+            self.enter_call(|| None, &[]);
+        }
+        Ok(())
+    }
+
+    fn drive(
+        &self,
+        stack: &mut Vec<Object>,
+        frames: Vec<Frame>,
+        frame: Frame,
+    ) -> Result<Exit, EvalError> {
+        let depth = self.depth.get();
+        let calls = self.call_depth.get();
+        let result = self
+            .descend()
+            .and_then(|_| self.reenter(frames.len()))
+            .and_then(|_| grow(|| self.execute(stack, frames, frame)));
+        self.depth.set(depth);
+        // Frames abandoned by an error still close their calls.
+        while self.call_depth.get() > calls {
+            self.exit_call();
+        }
+        result
+    }
+
+    fn execute(
+        &self,
+        stack: &mut Vec<Object>,
+        mut frames: Vec<Frame>,
+        mut frame: Frame,
+    ) -> Result<Exit, EvalError> {
+        loop {
+            let chunk = frame.proto.chunk.as_ref().unwrap();
+            let op = chunk.code[frame.ip];
+            frame.ip += 1;
+            self.step()?;
+            match op {
+                Op::Constant(index) => stack.push(chunk.constants[index as usize].clone()),
+                Op::GetLocal(slot) => stack.push(stack[frame.base + slot as usize].clone()),
+                Op::SetLocal(slot) => {
+                    stack[frame.base + slot as usize] = stack.last().unwrap().clone();
+                }
+                Op::GetGlobal(index) => {
+                    if let Object::Symbol(symbol) = &chunk.constants[index as usize] {
+                        stack.push(self.lookup_global(*symbol));
+                    }
+                }
+                Op::SetGlobal(index) => {
+                    if let Object::Symbol(symbol) = &chunk.constants[index as usize] {
+                        let value = stack.last().unwrap().clone();
+                        self.set_global(*symbol, value);
+                    }
+                }
+                Op::Jump(to) => frame.ip = to as usize,
+                Op::JumpIfFalse(to) => {
+                    if !truthy(&stack.pop().unwrap()) {
+                        frame.ip = to as usize;
+                    }
+                }
+                Op::Pop => {
+                    stack.pop();
+                }
+                Op::Call(argc) => {
+                    let span = call_span(|| chunk.callee(frame.ip - 1), argc as usize);
+                    let callee = stack.len() - argc as usize - 1;
+                    self.enter_call(|| chunk.callee(frame.ip - 1), &stack[callee + 1..]);
+                    if let Object::Other(other) = stack[callee].without_meta().clone() {
+                        if let Some(function) = other.downcast_ref::<Function>() {
+                            if function.proto.chunk.is_some() && !function.proto.generator {
+                                self.descend()?;
+                                Interpreter::enter(stack, function, callee + 1, argc as usize);
+                                let caller = std::mem::replace(
+                                    &mut frame,
+                                    Frame {
+                                        proto: function.proto.clone(),
+                                        ip: 0,
+                                        base: callee + 1,
+                                        delimiter: false,
+                                        _span: Some(span),
+                                    },
+                                );
+                                frames.push(caller);
+                                continue;
+                            }
+                        }
+                        if let Some(continuation) = other.downcast_ref::<Continuation>() {
+                            let value = match argc {
+                                0 => Object::Null,
+                                _ => stack[callee + 1].clone(),
+                            };
+                            stack.truncate(callee);
+                            self.descend()?;
+                            let mut resumed = Interpreter::reinstate(continuation, stack, value);
+                            self.reenter(resumed.len() - 1)?;
+                            let top = resumed.pop().unwrap();
+                            frames.push(std::mem::replace(&mut frame, top));
+                            frames.extend(resumed);
+                            continue;
+                        }
+                    }
+                    let list: Vec<Object> = stack.drain(callee..).collect();
+                    let result = self.apply(&list);
+                    self.exit_call();
+                    stack.push(result?);
+                }
+                Op::Closure(index) => {
+                    let closure = &chunk.closures[index as usize];
+                    let captured = closure
+                        .sources
+                        .iter()
+                        .map(|source| stack[frame.base + *source as usize].clone())
+                        .collect();
+                    stack.push(Object::Other(Shared::new(Function {
+                        proto: closure.proto.clone(),
+                        captured,
+                    })));
+                }
+                Op::Return => {
+                    let result = stack.pop().unwrap();
+                    stack.truncate(frame.base - 1);
+                    match frames.pop() {
+                        Some(caller) => frame = caller,
+                        None => return Ok(Exit::Return(result)),
+                    }
+                    self.exit_call();
+                    self.depth.set(self.depth.get() - 1);
+                    stack.push(result);
+                }
+                Op::Yield => {
+                    let value = stack.pop().unwrap();
+                    if frames.is_empty() {
+                        return Ok(Exit::Yield(value, frame.ip));
+                    }
+                    stack.push(Object::Null);
+                }
+                Op::Bind(index) => {
+                    let names = match &chunk.constants[index as usize] {
+                        Object::Vector(names) => names,
+                        _ => unreachable!(),
+                    };
+                    let thunk = stack.pop().unwrap();
+                    let values = stack.split_off(stack.len() - names.len());
+                    let bindings = names
+                        .iter()
+                        .zip(values)
+                        .filter_map(|(name, value)| match name {
+                            Object::Symbol(symbol) => Some((*symbol, value)),
+                            _ => None,
+                        })
+                        .collect();
+                    let result = self.bind(bindings, || self.apply(&[thunk]))?;
+                    stack.push(result);
+                }
+                Op::Time => {
+                    let thunk = stack.pop().unwrap();
+                    let result = self.time(|| self.apply(&[thunk]))?;
+                    stack.push(result);
+                }
+                Op::Dynamic(index) => {
+                    if let Object::Symbol(symbol) = &chunk.constants[index as usize] {
+                        self.evaluator.declare_dynamic(*symbol);
+                    }
+                }
+                Op::Reset => {
+                    let callee = stack.len() - 1;
+                    if let Object::Other(other) = stack[callee].without_meta().clone() {
+                        if let Some(function) = other.downcast_ref::<Function>() {
+                            if function.proto.chunk.is_some() && !function.proto.generator {
+                                self.descend()?;
+                                self.enter_call(|| None, &[]);
+                                Interpreter::enter(stack, function, callee + 1, 0);
+                                let caller = std::mem::replace(
+                                    &mut frame,
+                                    Frame {
+                                        proto: function.proto.clone(),
+                                        ip: 0,
+                                        base: callee + 1,
+                                        delimiter: true,
+                                        _span: None,
+                                    },
+                                );
+                                frames.push(caller);
+                                continue;
+                            }
+                        }
+                    }
+                    let thunk = stack.pop().unwrap();
+                    let result = self.apply(&[thunk])?;
+                    stack.push(result);
+                }
+                // The handler replaces every frame up to and including the
+                // nearest delimiter, or the first frame of this run when
+                // there is none, and becomes a delimiter itself.
+                Op::Shift => {
+                    let handler = stack.pop().unwrap();
+                    let other = match handler.without_meta() {
+                        Object::Other(other) if other.is::<Function>() => other.clone(),
+                        _ => {
+                            stack.push(Object::Null);
+                            continue;
+                        }
+                    };
+                    let function = other.downcast_ref::<Function>().unwrap();
+                    if function.proto.chunk.is_none() || function.proto.generator {
+                        stack.push(Object::Null);
+                        continue;
+                    }
+                    let index = match frame.delimiter {
+                        true => frames.len(),
+                        false => frames
+                            .iter()
+                            .rposition(|frame| frame.delimiter)
+                            .unwrap_or(0),
+                    };
+                    let start = frames.get(index).unwrap_or(&frame).base - 1;
+                    let saved = stack.split_off(start);
+                    let mut captured = frames.split_off(index);
+                    let handler_frame = Frame {
+                        proto: function.proto.clone(),
+                        ip: 0,
+                        base: start + 1,
+                        delimiter: true,
+                        _span: None,
+                    };
+                    captured.push(std::mem::replace(&mut frame, handler_frame));
+                    // The handler takes over the delimiter's call.
+                    for _ in 1..captured.len() {
+                        self.exit_call();
+                        self.depth.set(self.depth.get() - 1);
+                    }
+                    let continuation = Continuation {
+                        frames: captured
+                            .iter()
+                            .map(|frame| {
+                                let base = frame.base - start;
+                                (frame.proto.clone(), frame.ip, base, frame.delimiter)
+                            })
+                            .collect(),
+                        stack: saved,
+                    };
+                    stack.push(handler);
+                    stack.push(Object::Other(Shared::new(continuation)));
+                    Interpreter::enter(stack, function, start + 1, 1);
+                }
+            }
+        }
+    }
+
+    fn lookup(&self, symbol: Symbol, scope: &Scope) -> Object {
+        match scope.iter().rev().find(|(name, _)| *name == symbol) {
+            Some((_, value)) => value.clone(),
+            None => self.lookup_global(symbol),
+        }
+    }
+
+    fn walk(&self, obj: &Object, scope: &mut Scope) -> Result<Object, EvalError> {
+        self.step()?;
+        self.descend()?;
+        let result = grow(|| self.walk_hooked(obj, scope));
+        self.depth.set(self.depth.get() - 1);
+        result
+    }
+
+    fn debug(&self, debugger: &dyn Debugger, obj: &Object, scope: &Scope) -> Result<(), EvalError> {
+        let depth = self.depth.get();
+        let stop = match self.stepping.get() {
+            (Step::StepIn, _) | (Step::Abort, _) => true,
+            (Step::StepOver, from) => depth <= from,
+            (Step::StepOut, from) => depth < from,
+            (Step::Continue, _) => false,
+        };
+        if !stop
+            && !self
+                .breakpoints
+                .iter()
+                .any(|breakpoint| breakpoint.matches(obj))
+        {
+            return Ok(());
+        }
+        let step = debugger.pause(&Pause {
+            interpreter: self,
+            form: obj,
+            scope,
+            depth,
+        });
+        self.stepping.set((step, depth));
+        match step {
+            Step::Abort => Err(EvalError::Interrupted),
+            _ => Ok(()),
+        }
+    }
+
+    fn walk_hooked(&self, obj: &Object, scope: &mut Scope) -> Result<Object, EvalError> {
+        if let Some(debugger) = &self.debugger {
+            self.debug(debugger.as_ref(), obj, scope)?;
+        }
+        if self.hooks.is_empty() {
+            return self.walk_expr(obj, scope);
+        }
+        for hook in &self.hooks {
+            if let Some(value) = hook.before(obj) {
+                return Ok(value);
+            }
+        }
+        let value = self.walk_expr(obj, scope)?;
+        for hook in &self.hooks {
+            hook.after(obj, &value);
+        }
+        Ok(value)
+    }
+
+    fn walk_expr(&self, obj: &Object, scope: &mut Scope) -> Result<Object, EvalError> {
+        match obj {
+            Object::Symbol(symbol) => Ok(self.lookup(*symbol, scope)),
+            Object::List(list) if !list.is_empty() => {
+                if let Object::Symbol(symbol) = &list[0] {
+                    if let Some(result) = self.walk_special(symbol.as_str(), &list[1..], scope)? {
+                        return Ok(result);
+                    }
+                }
+                let callee = match &list[0] {
+                    Object::Symbol(symbol) => Some(*symbol),
+                    _ => None,
+                };
+                let list = list
+                    .iter()
+                    .map(|obj| self.walk(obj, scope))
+                    .collect::<Result<Vec<Object>, EvalError>>()?;
+                let _span = call_span(|| callee, list.len() - 1);
+                self.enter_call(|| callee, &list[1..]);
+                let result = self.apply(&list);
+                self.exit_call();
+                result
+            }
+            Object::List(_) => Ok(Object::Null),
+            _ => Ok(obj.clone()),
+        }
+    }
+
+    fn walk_body(&self, body: &[Object], scope: &mut Scope) -> Result<Object, EvalError> {
+        let mut result = Object::Null;
+        for obj in body {
+            result = self.walk(obj, scope)?;
+        }
+        Ok(result)
+    }
+
+    fn walk_special(
+        &self,
+        name: &str,
+        args: &[Object],
+        scope: &mut Scope,
+    ) -> Result<Option<Object>, EvalError> {
+        let unannotated = types::unannotated(name, args);
+        let args = unannotated.as_deref().unwrap_or(args);
+        let result = match (name, args) {
+            ("quote", [obj]) => obj.clone(),
+            ("if", [condition, then]) | ("if", [condition, then, _]) => {
+                if truthy(&self.walk(condition, scope)?) {
+                    self.walk(then, scope)?
+                } else {
+                    match args.get(2) {
+                        Some(otherwise) => self.walk(otherwise, scope)?,
+                        None => Object::Null,
+                    }
+                }
+            }
+            ("do", body) => self.walk_body(body, scope)?,
+            ("set", [Object::Symbol(symbol), value]) => {
+                let value = self.walk(value, scope)?;
+                match scope.iter_mut().rev().find(|(name, _)| name == symbol) {
+                    Some((_, local)) => *local = value.clone(),
+                    None => {
+                        self.set_global(*symbol, value.clone());
+                    }
+                }
+                value
+            }
+            ("let", [Object::List(bindings), body @ ..]) => {
+                let len = scope.len();
+                for binding in bindings {
+                    if let Object::List(pair) = binding {
+                        if let [Object::Symbol(symbol), value] = pair.as_slice() {
+                            let value = self.walk(value, scope)?;
+                            scope.push((*symbol, value));
+                        }
+                    }
+                }
+                let result = self.walk_body(body, scope)?;
+                scope.truncate(len);
+                result
+            }
+            ("fn*", [params, body @ ..]) => {
+                let names: Vec<Symbol> = scope.iter().map(|(name, _)| *name).collect();
+                match Compiler::compile_generator(params, body, &names) {
+                    Ok(closure) => Object::Other(Shared::new(Function {
+                        proto: closure.proto,
+                        captured: closure
+                            .sources
+                            .iter()
+                            .map(|source| scope[*source as usize].1.clone())
+                            .collect(),
+                    })),
+                    Err(_) => Object::Null,
+                }
+            }
+            ("fn", [params, body @ ..]) => {
+                let names: Vec<Symbol> = scope.iter().map(|(name, _)| *name).collect();
+                let function = match Compiler::compile_function(params, body, &names) {
+                    Ok(closure) => Function {
+                        proto: closure.proto,
+                        captured: closure
+                            .sources
+                            .iter()
+                            .map(|source| scope[*source as usize].1.clone())
+                            .collect(),
+                    },
+                    Err(_) => {
+                        let params = match params {
+                            Object::List(list) => list
+                                .iter()
+                                .filter_map(|param| match param {
+                                    Object::Symbol(symbol) => Some(*symbol),
+                                    _ => None,
+                                })
+                                .collect(),
+                            _ => Vec::new(),
+                        };
+                        Function {
+                            proto: Shared::new(Proto {
+                                params,
+                                captures: names.iter().map(|name| (*name, 0)).collect(),
+                                body: body.to_vec(),
+                                chunk: None,
+                                generator: false,
+                            }),
+                            captured: scope.iter().map(|(_, value)| value.clone()).collect(),
+                        }
+                    }
+                };
+                Object::Other(Shared::new(function))
+            }
+            ("defdynamic", [Object::Symbol(symbol)]) => {
+                self.evaluator.declare_dynamic(*symbol);
+                Object::Null
+            }
+            ("defdynamic", [Object::Symbol(symbol), value]) => {
+                self.evaluator.declare_dynamic(*symbol);
+                let value = self.walk(value, scope)?;
+                self.set_global(*symbol, value.clone());
+                value
+            }
+            ("binding", [bindings, body @ ..]) if dynamic_bindings(bindings).is_some() => {
+                let mut values = Vec::new();
+                for (name, value) in dynamic_bindings(bindings).unwrap() {
+                    values.push((name, self.walk(value, scope)?));
+                }
+                self.bind(values, || self.walk_body(body, scope))?
+            }
+            // Continuations only exist on the VM, so the whole reset runs
+            // there, out of sight of hooks and the debugger.
+            ("reset", _) => {
+                let names: Vec<Symbol> = scope.iter().map(|(name, _)| *name).collect();
+                let form = Object::List([&[Object::Symbol(Symbol::new(name))], args].concat());
+                match Compiler::compile_function(&Object::List(Vec::new()), &[form], &names) {
+                    Ok(closure) => self.run(
+                        &Function {
+                            proto: closure.proto,
+                            captured: closure
+                                .sources
+                                .iter()
+                                .map(|source| scope[*source as usize].1.clone())
+                                .collect(),
+                        },
+                        &[],
+                    )?,
+                    Err(_) => Object::Null,
+                }
+            }
+            ("time", body) => self.time(|| self.walk_body(body, scope))?,
+            ("while", [condition, body @ ..]) => {
+                while truthy(&self.walk(condition, scope)?) {
+                    self.walk_body(body, scope)?;
+                }
+                Object::Null
+            }
+            ("quote", _)
+            | ("if", _)
+            | ("set", _)
+            | ("let", _)
+            | ("fn", _)
+            | ("fn*", _)
+            | ("while", _)
+            | ("yield", _)
+            | ("shift", _)
+            | ("defdynamic", _)
+            | ("binding", _) => Object::Null,
+            _ => return Ok(None),
+        };
+        Ok(Some(result))
+    }
+}
@@ -1,2 +1,65 @@
+mod macros;
+
+#[cfg(any(feature = "proptest", feature = "quickcheck"))]
+pub mod arbitrary;
+mod arithmetic;
+pub mod binary;
+mod bytes;
+#[cfg(feature = "fundot-capi")]
+pub mod capi;
+mod cli;
+mod clock;
+pub mod compiler;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+mod compression;
+#[cfg(feature = "serde")]
+pub mod config;
+pub mod context;
+#[cfg(feature = "crypto")]
+mod crypto;
+mod cycles;
+#[cfg(all(feature = "json", not(feature = "rc")))]
+pub mod dap;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod debugger;
+pub mod diff;
+pub mod doc;
+mod encoding;
+mod environment;
+pub mod error;
 pub mod evaluator;
+mod filesystem;
+pub mod fmt;
+#[cfg(not(feature = "rc"))]
+pub mod future;
+pub mod history;
+pub mod inspect;
+pub mod interpreter;
+pub mod lint;
+pub mod logging;
+pub mod merge;
+mod network;
 pub mod object;
+mod optimizer;
+pub mod output;
+pub mod plugin;
+pub mod pretty;
+mod process;
+pub mod profiler;
+pub mod query;
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(all(feature = "json", not(feature = "rc")))]
+pub mod server;
+pub mod symbol;
+pub mod template;
+pub mod testing;
+pub mod types;
+mod unicode;
+mod uuid;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use crate::error::Error;
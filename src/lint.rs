@@ -0,0 +1,390 @@
+use crate::compiler::{self, SPECIAL_FORMS};
+use crate::evaluator::{self, Evaluator};
+use crate::object::{Object, ParseObjectError};
+use crate::symbol::Symbol;
+use crate::types;
+#[cfg(feature = "serde")]
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lint {
+    UnboundSymbol,
+    UnusedBinding,
+    WrongArity,
+    ShadowedBuiltin,
+    UnreachableBranch,
+}
+
+impl Lint {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnboundSymbol => "unbound-symbol",
+            Lint::UnusedBinding => "unused-binding",
+            Lint::WrongArity => "wrong-arity",
+            Lint::ShadowedBuiltin => "shadowed-builtin",
+            Lint::UnreachableBranch => "unreachable-branch",
+        }
+    }
+}
+
+// Parsed objects carry no positions, so a diagnostic points at the
+// top-level form it was found in, counting from 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub lint: Lint,
+    pub form: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "form {}: {} [{}]",
+            self.form,
+            self.message,
+            self.lint.name()
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Diagnostic {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Diagnostic", 3)?;
+        state.serialize_field("lint", self.lint.name())?;
+        state.serialize_field("form", &self.form)?;
+        state.serialize_field("message", &self.message)?;
+        state.end()
+    }
+}
+
+struct Local {
+    symbol: Symbol,
+    used: bool,
+    // Only let bindings are reported when unused; parameters often have to
+    // be there whether they are used or not.
+    report: bool,
+}
+
+struct Linter {
+    builtins: HashSet<String>,
+    globals: HashSet<Symbol>,
+    arities: HashMap<Symbol, usize>,
+    locals: Vec<Local>,
+    unbound: HashSet<Symbol>,
+    form: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+fn head(list: &[Object]) -> Option<&str> {
+    match list.first() {
+        Some(Object::Symbol(symbol)) => Some(symbol.as_str()),
+        _ => None,
+    }
+}
+
+// Names assigned anywhere in the file count as defined everywhere, and the
+// ones assigned exactly once to a fn literal have a known arity.
+fn definitions(obj: &Object, globals: &mut HashSet<Symbol>, functions: &mut Vec<(Symbol, usize)>) {
+    if let Object::List(list) = obj {
+        match list.as_slice() {
+            [Object::Symbol(head), ..] if head.as_str() == "quote" => return,
+            [Object::Symbol(head), Object::Symbol(name), rest @ ..]
+                if ["set", "defdynamic"].contains(&head.as_str()) =>
+            {
+                globals.insert(*name);
+                let arity = match rest {
+                    [Object::List(function)] | [_, _, Object::List(function)] => {
+                        match function.as_slice() {
+                            [Object::Symbol(head), Object::List(params), ..]
+                                if head.as_str() == "fn" =>
+                            {
+                                types::params(params).len()
+                            }
+                            _ => usize::MAX,
+                        }
+                    }
+                    _ => usize::MAX,
+                };
+                functions.push((*name, arity));
+            }
+            _ => {}
+        }
+        for obj in list {
+            definitions(obj, globals, functions);
+        }
+    }
+}
+
+// The number of insertions, deletions, substitutions and swaps of
+// neighbouring characters that turn a into b.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let substitution = rows[i - 1][j - 1] + (a[i - 1] != b[j - 1]) as usize;
+            row[j] = substitution.min(rows[i - 1][j] + 1).min(row[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+// The name among candidates that name is most likely a typo of: the
+// closest one, if it is within one edit for names of up to five characters
+// and a third of the length for longer ones. Ties go to the first in
+// alphabetical order.
+pub fn suggestion<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+// Literals and quoted forms, whose truth is known without evaluating.
+fn constant(obj: &Object) -> Option<&Object> {
+    match obj {
+        Object::Symbol(_) => None,
+        Object::List(list) => match list.as_slice() {
+            [Object::Symbol(head), value] if head.as_str() == "quote" => Some(value),
+            _ => None,
+        },
+        _ => Some(obj),
+    }
+}
+
+impl Linter {
+    fn report(&mut self, lint: Lint, message: String) {
+        self.diagnostics.push(Diagnostic {
+            lint,
+            form: self.form,
+            message,
+        });
+    }
+
+    fn declare(&mut self, obj: &Object, report: bool) {
+        if let Object::Symbol(symbol) = obj {
+            self.shadow(*symbol);
+            self.locals.push(Local {
+                symbol: *symbol,
+                used: false,
+                report,
+            });
+        }
+    }
+
+    fn shadow(&mut self, symbol: Symbol) {
+        let name = symbol.as_str();
+        if self.builtins.contains(name) || SPECIAL_FORMS.contains(&name) {
+            self.report(
+                Lint::ShadowedBuiltin,
+                format!("{} shadows the builtin of the same name", name),
+            );
+        }
+    }
+
+    fn leave(&mut self, len: usize) {
+        for local in self.locals.split_off(len) {
+            let name = local.symbol.as_str();
+            if local.report && !local.used && !name.starts_with('_') {
+                self.report(
+                    Lint::UnusedBinding,
+                    format!("{} is bound but never used", name),
+                );
+            }
+        }
+    }
+
+    fn body(&mut self, body: &[Object]) {
+        for obj in body {
+            self.expr(obj);
+        }
+    }
+
+    fn symbol(&mut self, symbol: Symbol) {
+        if let Some(local) = self
+            .locals
+            .iter_mut()
+            .rev()
+            .find(|local| local.symbol == symbol)
+        {
+            local.used = true;
+            return;
+        }
+        let name = symbol.as_str();
+        let known = self.builtins.contains(name)
+            || self.globals.contains(&symbol)
+            || SPECIAL_FORMS.contains(&name);
+        if !known && self.unbound.insert(symbol) {
+            let locals = self.locals.iter().map(|local| local.symbol.as_str());
+            let globals = self.globals.iter().map(|global| global.as_str());
+            let builtins = self.builtins.iter().map(String::as_str);
+            let names = locals
+                .chain(globals)
+                .chain(builtins)
+                .chain(SPECIAL_FORMS.iter().copied());
+            let message = match suggestion(name, names) {
+                Some(similar) => format!("{} is not bound; did you mean {}?", name, similar),
+                None => format!("{} is not bound", name),
+            };
+            self.report(Lint::UnboundSymbol, message);
+        }
+    }
+
+    fn branch(&mut self, condition: &Object, branch: Option<&Object>, taken: bool) {
+        if let (Some(value), Some(branch)) = (constant(condition), branch) {
+            if evaluator::truthy(value) != taken {
+                self.report(
+                    Lint::UnreachableBranch,
+                    format!("{} can never run, since {} is constant", branch, condition),
+                );
+            }
+        }
+    }
+
+    // Vectors and maps are data and are not evaluated, so only lists and
+    // symbols are looked into.
+    fn expr(&mut self, obj: &Object) {
+        let list = match obj {
+            Object::Symbol(symbol) => return self.symbol(*symbol),
+            Object::List(list) => list,
+            _ => return,
+        };
+        let len = self.locals.len();
+        let args = &list[1.min(list.len())..];
+        let unannotated = head(list).and_then(|name| types::unannotated(name, args));
+        match (head(list), unannotated.as_deref().unwrap_or(args)) {
+            (Some("quote"), _) => {}
+            (Some("if"), [condition, then, rest @ ..]) => {
+                self.expr(condition);
+                self.branch(condition, Some(then), true);
+                self.branch(condition, rest.first(), false);
+                self.body(&list[2..]);
+            }
+            (Some("while"), [condition, body @ ..]) => {
+                self.expr(condition);
+                if let Some(value) = constant(condition) {
+                    if !evaluator::truthy(value) && !body.is_empty() {
+                        self.report(
+                            Lint::UnreachableBranch,
+                            format!(
+                                "the body of the loop can never run, since {} is constant",
+                                condition
+                            ),
+                        );
+                    }
+                }
+                self.body(body);
+            }
+            (Some("set"), [Object::Symbol(name), value]) => {
+                if !self.locals.iter().any(|local| local.symbol == *name) {
+                    self.shadow(*name);
+                }
+                self.expr(value);
+            }
+            (Some("let"), [Object::List(bindings), body @ ..]) => {
+                for binding in bindings {
+                    if let Object::List(pair) = binding {
+                        if let [name, value] = pair.as_slice() {
+                            self.expr(value);
+                            self.declare(name, true);
+                        }
+                    }
+                }
+                self.body(body);
+                self.leave(len);
+            }
+            (Some("fn"), [Object::List(params), body @ ..])
+            | (Some("fn*"), [Object::List(params), body @ ..]) => {
+                for param in params {
+                    self.declare(param, false);
+                }
+                self.body(body);
+                self.leave(len);
+            }
+            (Some("shift"), [continuation, body @ ..]) => {
+                self.declare(continuation, false);
+                self.body(body);
+                self.leave(len);
+            }
+            (Some("defdynamic"), [_, value @ ..]) => self.body(value),
+            (Some("binding"), [bindings, body @ ..]) => {
+                if let Some(bindings) = compiler::dynamic_bindings(bindings) {
+                    for (_, value) in bindings {
+                        self.expr(value);
+                    }
+                }
+                self.body(body);
+            }
+            (Some(name), args) if SPECIAL_FORMS.contains(&name) => self.body(args),
+            (_, args) => {
+                if let Some(Object::Symbol(symbol)) = list.first() {
+                    let local = self.locals.iter().any(|local| local.symbol == *symbol);
+                    match self.arities.get(symbol) {
+                        Some(arity) if !local && *arity != args.len() => self.report(
+                            Lint::WrongArity,
+                            format!(
+                                "{} takes {} arguments but is called with {}",
+                                symbol,
+                                arity,
+                                args.len()
+                            ),
+                        ),
+                        _ => {}
+                    }
+                }
+                self.body(list);
+            }
+        }
+    }
+}
+
+// Checks a program without running it. Names are resolved against the
+// globals of the evaluator the program is meant for and whatever the
+// program itself assigns.
+pub fn lint(source: &str, evaluator: &Evaluator) -> Result<Vec<Diagnostic>, ParseObjectError> {
+    let forms = Object::parse_all(source)?;
+    let mut globals = HashSet::new();
+    let mut functions = Vec::new();
+    for form in &forms {
+        definitions(form, &mut globals, &mut functions);
+    }
+    let mut arities = HashMap::new();
+    for (name, arity) in &functions {
+        let assignments = functions.iter().filter(|(other, _)| other == name).count();
+        if assignments == 1 && *arity != usize::MAX {
+            arities.insert(*name, *arity);
+        }
+    }
+    let mut linter = Linter {
+        builtins: evaluator.names().into_iter().collect(),
+        globals,
+        arities,
+        locals: Vec::new(),
+        unbound: HashSet::new(),
+        form: 0,
+        diagnostics: Vec::new(),
+    };
+    for (index, form) in forms.iter().enumerate() {
+        linter.form = index + 1;
+        linter.unbound.clear();
+        linter.expr(form);
+    }
+    Ok(linter.diagnostics)
+}
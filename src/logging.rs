@@ -0,0 +1,263 @@
+use crate::clock::{SystemTime, UNIX_EPOCH};
+use crate::evaluator::{args, EvalError, NativeFunction};
+use crate::interpreter::Interpreter;
+use crate::object::Object;
+use crate::symbol::Symbol;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+pub(crate) const NATIVES: [(&str, NativeFunction); 5] = [
+    ("log-debug", log_debug),
+    ("log-info", log_info),
+    ("log-warn", log_warn),
+    ("log-error", log_error),
+    ("log-level", log_level),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseLevelError {}
+
+impl fmt::Display for ParseLevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected debug, info, warn or error")
+    }
+}
+
+impl std::error::Error for ParseLevelError {}
+
+impl FromStr for Level {
+    type Err = ParseLevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "debug" => Ok(Level::Debug),
+            "info" => Ok(Level::Info),
+            "warn" => Ok(Level::Warn),
+            "error" => Ok(Level::Error),
+            _ => Err(ParseLevelError {}),
+        }
+    }
+}
+
+// One call to a log builtin. Fields is the map given with the message, or
+// null when there was none.
+#[derive(Debug)]
+pub struct Record<'a> {
+    pub level: Level,
+    pub time: SystemTime,
+    pub message: &'a str,
+    pub fields: &'a Object,
+}
+
+// A line such as 2026-01-02T03:04:05.678Z WARN disk low {free: 12}.
+impl fmt::Display for Record<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let since_epoch = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let seconds = since_epoch.as_secs();
+        let (year, month, day) = civil_date((seconds / 86400) as i64);
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z {:5} {}",
+            year,
+            month,
+            day,
+            seconds / 3600 % 24,
+            seconds / 60 % 60,
+            seconds % 60,
+            since_epoch.subsec_millis(),
+            self.level.as_str().to_uppercase(),
+            self.message
+        )?;
+        match self.fields {
+            Object::Null => Ok(()),
+            fields => write!(f, " {}", fields),
+        }
+    }
+}
+
+// The proleptic Gregorian date of a day counted from 1970-01-01.
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+// Where records at or above the evaluator's log level go. Closures taking
+// a record are sinks, so a host can pass them on to its own logging.
+pub trait LogSink: Send + Sync {
+    fn log(&self, record: &Record);
+}
+
+impl<F: Fn(&Record) + Send + Sync> LogSink for F {
+    fn log(&self, record: &Record) {
+        self(record)
+    }
+}
+
+// The default sink, writing one line per record.
+pub struct Stderr;
+
+impl LogSink for Stderr {
+    fn log(&self, record: &Record) {
+        let _ = writeln!(io::stderr(), "{}", record);
+    }
+}
+
+// Appends one line per record to a file, creating it if needed.
+pub struct FileSink {
+    file: Mutex<File>,
+}
+
+impl FileSink {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<FileSink> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileSink {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl LogSink for FileSink {
+    fn log(&self, record: &Record) {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ = writeln!(file, "{}", record);
+    }
+}
+
+// Shared by an evaluator and the ones spawned threads run in.
+pub(crate) struct Logger {
+    level: RwLock<Level>,
+    sink: RwLock<Arc<dyn LogSink>>,
+}
+
+impl Logger {
+    pub(crate) fn new(level: Level, sink: Arc<dyn LogSink>) -> Logger {
+        Logger {
+            level: RwLock::new(level),
+            sink: RwLock::new(sink),
+        }
+    }
+
+    pub(crate) fn level(&self) -> Level {
+        *self.level.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn set_level(&self, level: Level) {
+        *self.level.write().unwrap_or_else(PoisonError::into_inner) = level;
+    }
+
+    pub(crate) fn set_sink(&self, sink: Arc<dyn LogSink>) {
+        *self.sink.write().unwrap_or_else(PoisonError::into_inner) = sink;
+    }
+
+    fn log(&self, level: Level, message: &str, fields: &Object) {
+        if level < self.level() {
+            return;
+        }
+        let sink = self
+            .sink
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        sink.log(&Record {
+            level,
+            time: SystemTime::now(),
+            message,
+            fields,
+        });
+    }
+}
+
+// (log-info message fields) logs a message, which is printed unless it is
+// a string, with an optional map of fields, and gives null.
+fn log(interpreter: &Interpreter, obj: &Object, level: Level) -> Result<Object, EvalError> {
+    let args = args(obj);
+    let message = match args.first().map(Object::without_meta) {
+        Some(Object::String(message)) => message.to_string(),
+        Some(message) => message.to_string(),
+        None => String::new(),
+    };
+    let fields = args.get(1).unwrap_or(&Object::Null);
+    interpreter
+        .evaluator()
+        .logger()
+        .log(level, &message, fields);
+    Ok(Object::Null)
+}
+
+fn log_debug(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    log(interpreter, obj, Level::Debug)
+}
+
+fn log_info(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    log(interpreter, obj, Level::Info)
+}
+
+fn log_warn(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    log(interpreter, obj, Level::Warn)
+}
+
+fn log_error(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    log(interpreter, obj, Level::Error)
+}
+
+// (log-level) gives the current level and (log-level warn) changes it,
+// giving null for a level it does not know.
+fn log_level(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let logger = interpreter.evaluator().logger();
+    let level = match args(obj).first().map(Object::without_meta) {
+        None => logger.level(),
+        Some(Object::String(name)) => match name.parse() {
+            Ok(level) => level,
+            Err(_) => return Ok(Object::Null),
+        },
+        Some(Object::Symbol(name)) => match name.as_str().parse() {
+            Ok(level) => level,
+            Err(_) => return Ok(Object::Null),
+        },
+        Some(_) => return Ok(Object::Null),
+    };
+    logger.set_level(level);
+    Ok(Object::Symbol(Symbol::new(level.as_str())))
+}
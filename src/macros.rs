@@ -0,0 +1,92 @@
+/// Builds an `Object` from Fundot literal syntax.
+///
+/// Rust values are spliced in with `#name` or `#(expr)`. Since Rust reads
+/// `-1` as the token `-` followed by `1`, negative numbers must be spliced
+/// as well.
+///
+/// ```
+/// use fundot::fundot;
+///
+/// let n = 3;
+/// let obj = fundot!({name: "x", items: [1, 2, #n], call: (get items #(n - 2))});
+/// ```
+#[macro_export]
+macro_rules! fundot {
+    (@list [$($elems:expr,)*]) => {
+        $crate::object::Object::List(::std::vec![$($elems),*])
+    };
+    (@list [$($elems:expr,)*] # $next:tt $($rest:tt)*) => {
+        $crate::fundot!(@list [$($elems,)* $crate::fundot!(# $next),] $($rest)*)
+    };
+    (@list [$($elems:expr,)*] $next:tt $($rest:tt)*) => {
+        $crate::fundot!(@list [$($elems,)* $crate::fundot!($next),] $($rest)*)
+    };
+
+    (@vector [$($elems:expr,)*]) => {
+        $crate::object::Object::Vector(::std::vec![$($elems),*])
+    };
+    (@vector [$($elems:expr,)*] # $next:tt $(, $($rest:tt)*)?) => {
+        $crate::fundot!(@vector [$($elems,)* $crate::fundot!(# $next),] $($($rest)*)?)
+    };
+    (@vector [$($elems:expr,)*] $next:tt $(, $($rest:tt)*)?) => {
+        $crate::fundot!(@vector [$($elems,)* $crate::fundot!($next),] $($($rest)*)?)
+    };
+
+    (@map $map:ident) => {};
+    (@map $map:ident # $key:tt : # $value:tt $(, $($rest:tt)*)?) => {
+        $map.insert($crate::fundot!(# $key), $crate::fundot!(# $value));
+        $crate::fundot!(@map $map $($($rest)*)?);
+    };
+    (@map $map:ident # $key:tt : $value:tt $(, $($rest:tt)*)?) => {
+        $map.insert($crate::fundot!(# $key), $crate::fundot!($value));
+        $crate::fundot!(@map $map $($($rest)*)?);
+    };
+    (@map $map:ident $key:tt : # $value:tt $(, $($rest:tt)*)?) => {
+        $map.insert($crate::fundot!($key), $crate::fundot!(# $value));
+        $crate::fundot!(@map $map $($($rest)*)?);
+    };
+    (@map $map:ident $key:tt : $value:tt $(, $($rest:tt)*)?) => {
+        $map.insert($crate::fundot!($key), $crate::fundot!($value));
+        $crate::fundot!(@map $map $($($rest)*)?);
+    };
+
+    (# $value:ident) => {
+        ::std::convert::Into::<$crate::object::Object>::into($value)
+    };
+    (# ($value:expr)) => {
+        ::std::convert::Into::<$crate::object::Object>::into($value)
+    };
+    (null) => {
+        $crate::object::Object::Null
+    };
+    (true) => {
+        $crate::object::Object::Bool(true)
+    };
+    (false) => {
+        $crate::object::Object::Bool(false)
+    };
+    (($($elems:tt)*)) => {
+        $crate::fundot!(@list [] $($elems)*)
+    };
+    ([$($elems:tt)*]) => {
+        $crate::fundot!(@vector [] $($elems)*)
+    };
+    ({$($elems:tt)*}) => {{
+        #[allow(unused_mut)]
+        let mut map = ::std::collections::HashMap::new();
+        $crate::fundot!(@map map $($elems)*);
+        $crate::object::Object::Map(map)
+    }};
+    ($value:ident) => {
+        $crate::object::Object::Symbol($crate::symbol::Symbol::new(::std::stringify!($value)))
+    };
+    (-) => {
+        $crate::object::Object::Symbol($crate::symbol::Symbol::new("-"))
+    };
+    ($value:literal) => {
+        ::std::convert::Into::<$crate::object::Object>::into($value)
+    };
+    ($value:tt) => {
+        $crate::object::Object::Symbol($crate::symbol::Symbol::new(::std::stringify!($value)))
+    };
+}
@@ -1,19 +1,1174 @@
-use fundot::evaluator::Evaluator;
-use fundot::object::Object;
-use std::io::{self, prelude::*};
+use fundot::compiler::SPECIAL_FORMS;
+#[cfg(all(feature = "json", not(feature = "rc")))]
+use fundot::dap::Adapter;
+use fundot::doc;
+use fundot::error::Error;
+#[cfg(all(feature = "json", not(feature = "rc")))]
+use fundot::evaluator::Sandbox;
+use fundot::evaluator::{EvalError, Evaluator, EvaluatorBuilder};
+use fundot::fmt;
+use fundot::inspect::Inspector;
+use fundot::interpreter::Interpreter;
+use fundot::lint;
+use fundot::object::{DisplayLimits, Object};
+use fundot::pretty::PrettyPrinter;
+#[cfg(all(feature = "json", not(feature = "rc")))]
+use fundot::server::Server;
+use fundot::testing;
+use fundot::types;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+#[cfg(all(feature = "json", not(feature = "rc")))]
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs;
+#[cfg(all(feature = "json", not(feature = "rc")))]
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, IsTerminal, Read, Write};
+#[cfg(all(feature = "json", not(feature = "rc")))]
+use std::net::TcpListener;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
+#[cfg(all(feature = "json", not(feature = "rc")))]
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
-fn main() {
-    let evaluator = Evaluator::new();
+fn history_path() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(".fundot_history"))
+}
+
+fn init_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::home_dir().map(|home| home.join(".config")))
+        .map(|config| config.join("fundot").join("init.fd"))
+}
+
+// A missing init file is not an error, and one that fails still leaves
+// the REPL to start with whatever it managed to define.
+fn load_init(evaluator: &Evaluator) {
+    let path = match init_path() {
+        Some(path) => path,
+        None => return,
+    };
+    match fs::read_to_string(&path) {
+        Ok(source) => {
+            if eval(evaluator, &source).is_none() {
+                eprintln!("fundot: could not load {}", path.display());
+            }
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+        Err(error) => eprintln!("fundot: {}: {}", path.display(), error),
+    }
+}
+
+struct Repl<'a> {
+    evaluator: &'a Evaluator,
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "()[]{},:#\"|".contains(c)
+}
+
+// The token ending at the end of text, which may be empty.
+fn last_word(text: &str) -> &str {
+    let start = text.rfind(is_delimiter).map_or(0, |index| {
+        index + text[index..].chars().next().unwrap().len_utf8()
+    });
+    &text[start..]
+}
+
+impl Repl<'_> {
+    // After (get name the keys of the map bound to name are offered, and
+    // otherwise special forms and global names.
+    fn candidates(&self, before: &str) -> Vec<String> {
+        let before = before.trim_end();
+        let name = last_word(before);
+        let head = before[..before.len() - name.len()].trim_end();
+        if !name.is_empty() && head.ends_with("(get") {
+            if let Some(Object::Map(map)) = self.evaluator.get(name) {
+                return map.keys().map(|key| key.to_string()).collect();
+            }
+        }
+        let mut names: Vec<String> = SPECIAL_FORMS.iter().map(|name| name.to_string()).collect();
+        names.extend(self.evaluator.names());
+        names
+    }
+}
+
+impl Completer for Repl<'_> {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let word = last_word(&line[..pos]);
+        let start = pos - word.len();
+        let mut candidates: Vec<String> = self
+            .candidates(&line[..start])
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for Repl<'_> {
+    type Hint = String;
+}
+
+impl Highlighter for Repl<'_> {}
+
+impl Validator for Repl<'_> {}
+
+impl Helper for Repl<'_> {}
+
+fn describe(error: &Error) -> String {
+    match error {
+        Error::Parse(_) => "syntax error: could not parse input".to_string(),
+        Error::Eval(error) => format!("evaluation failed: {}", error),
+        Error::Convert(error) => format!("conversion failed: {}", error),
+    }
+}
+
+// _ and _1 hold the latest result, _2 and _3 the two before it.
+fn remember(evaluator: &Evaluator, value: &Object) {
+    for n in (1..3).rev() {
+        if let Some(older) = evaluator.get(&format!("_{}", n)) {
+            evaluator.define(&format!("_{}", n + 1), older);
+        }
+    }
+    evaluator.define("_1", value.clone());
+    evaluator.define("_", value.clone());
+}
+
+// require searches the directories given with --lib-path, then the ones
+// in FUNDOT_PATH.
+fn builder(lib_paths: &[PathBuf]) -> EvaluatorBuilder {
+    let from_env = env::var_os("FUNDOT_PATH")
+        .map(|paths| env::split_paths(&paths).collect::<Vec<PathBuf>>())
+        .unwrap_or_default();
+    lib_paths
+        .iter()
+        .cloned()
+        .chain(from_env)
+        .fold(Evaluator::builder(), EvaluatorBuilder::lib_path)
+}
+
+// The terminal as it was before the viewer took it over, restored when
+// the viewer is done however it ends.
+#[cfg(unix)]
+struct RawMode(libc::termios);
+
+#[cfg(unix)]
+impl RawMode {
+    // Reads keys one at a time without echoing them, and turns Ctrl-C
+    // into a key rather than a signal.
+    fn enable() -> io::Result<RawMode> {
+        let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let original = termios;
+        termios.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        termios.c_cc[libc::VMIN] = 1;
+        termios.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        print!("\x1b[?1049h\x1b[?25l");
+        Ok(RawMode(original))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0) };
+    }
+}
+
+#[cfg(unix)]
+enum Key {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Backspace,
+    Escape,
+}
+
+#[cfg(unix)]
+fn read_key(input: &mut impl Read) -> io::Result<Key> {
+    let mut byte = [0];
+    input.read_exact(&mut byte)?;
+    Ok(match byte[0] {
+        b'\r' | b'\n' => Key::Enter,
+        127 | 8 => Key::Backspace,
+        3 => Key::Char('q'),
+        // Arrows arrive as ESC [ A to D; anything else after an escape is
+        // taken as the escape alone.
+        27 => {
+            let mut sequence = [0; 2];
+            match input.read(&mut sequence[..1]) {
+                Ok(1) if sequence[0] == b'[' => {
+                    input.read_exact(&mut sequence[1..])?;
+                    match sequence[1] {
+                        b'A' => Key::Up,
+                        b'B' => Key::Down,
+                        b'C' => Key::Right,
+                        b'D' => Key::Left,
+                        _ => Key::Escape,
+                    }
+                }
+                _ => Key::Escape,
+            }
+        }
+        byte => Key::Char(byte as char),
+    })
+}
+
+// Draws the rows that fit above the status line, scrolled so the cursor
+// is among them.
+#[cfg(unix)]
+fn draw(inspector: &Inspector, top: &mut usize, status: &str) -> io::Result<()> {
+    let (width, height) = terminal_size::terminal_size().map_or((80, 24), |(width, height)| {
+        (width.0 as usize, height.0 as usize)
+    });
+    let shown = height.saturating_sub(1).max(1);
+    let cursor = inspector.cursor();
+    if cursor < *top {
+        *top = cursor;
+    } else if cursor >= *top + shown {
+        *top = cursor + 1 - shown;
+    }
+    let mut screen = String::from("\x1b[H\x1b[2J");
+    for (index, row) in inspector.rows().iter().enumerate().skip(*top).take(shown) {
+        let marker = match (row.expandable, row.expanded) {
+            (true, true) => "▾ ",
+            (true, false) => "▸ ",
+            _ => "  ",
+        };
+        let line = format!("{}{}{}", "  ".repeat(row.depth), marker, row.text);
+        let line: String = line.chars().take(width).collect();
+        match index == cursor {
+            true => screen.push_str(&format!("\x1b[7m{}\x1b[0m\r\n", line)),
+            false => screen.push_str(&format!("{}\r\n", line)),
+        }
+    }
+    screen.push_str(&format!("\x1b[{};1H{}", height, status));
+    let mut stdout = io::stdout();
+    stdout.write_all(screen.as_bytes())?;
+    stdout.flush()
+}
+
+#[cfg(unix)]
+const INSPECT_HELP: &str = "j/k move  enter toggle  h/l collapse/expand  / search  n next  q quit";
+
+#[cfg(unix)]
+fn view(value: Object) -> io::Result<()> {
+    let _raw = RawMode::enable()?;
+    let mut input = io::stdin().lock();
+    let mut inspector = Inspector::new(value);
+    let mut top = 0;
+    let mut query = String::new();
+    let mut status = INSPECT_HELP.to_string();
+    loop {
+        draw(&inspector, &mut top, &status)?;
+        status = INSPECT_HELP.to_string();
+        match read_key(&mut input)? {
+            Key::Char('q') | Key::Escape => return Ok(()),
+            Key::Char('j') | Key::Down => inspector.down(),
+            Key::Char('k') | Key::Up => inspector.up(),
+            Key::Char('l') | Key::Right => inspector.expand(),
+            Key::Char('h') | Key::Left => inspector.collapse(),
+            Key::Char('g') => inspector.top(),
+            Key::Char('G') => inspector.bottom(),
+            Key::Enter | Key::Char(' ') => inspector.toggle(),
+            Key::Char('/') => {
+                query.clear();
+                loop {
+                    draw(&inspector, &mut top, &format!("/{}", query))?;
+                    match read_key(&mut input)? {
+                        Key::Enter => break,
+                        Key::Escape => {
+                            query.clear();
+                            break;
+                        }
+                        Key::Backspace => {
+                            query.pop();
+                        }
+                        Key::Char(c) if !c.is_control() => query.push(c),
+                        _ => {}
+                    }
+                }
+                if !query.is_empty() && !inspector.search(&query) {
+                    status = format!("no match for {}", query);
+                }
+            }
+            Key::Char('n') if !query.is_empty() && !inspector.search(&query) => {
+                status = format!("no match for {}", query);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Replaces the inspect builtin when both ends are a terminal: (inspect
+// obj) opens a viewer of obj that takes over the screen until q.
+#[cfg(unix)]
+fn inspect(_: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let value = match obj {
+        Object::List(list) => list.get(1).cloned().unwrap_or(Object::Null),
+        _ => Object::Null,
+    };
+    if let Err(error) = view(value) {
+        eprintln!("fundot: inspect: {}", error);
+    }
+    Ok(Object::Null)
+}
+
+const USAGE: &str = "\
+usage: fundot [--no-color] [--no-init] [--lib-path dir]... [--watch [--keep-state]]
+              [-e expr | script | -] [args...]
+       fundot fmt [--check] [file...]
+       fundot lint [--json] [file...]
+       fundot check [--json] [file...]
+       fundot test [--lib-path dir]... [path...]
+       fundot doc [--html] [-o dir] [path...]
+       fundot dap [--port port]
+       fundot serve [--port port] [--lib-path dir]... [--trusted]";
+
+enum Command {
+    Repl,
+    Expression(String),
+    Script(String),
+    Stdin,
+}
+
+// NO_COLOR is honoured when set to anything but the empty string.
+fn use_color() -> bool {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !no_color && io::stdout().is_terminal()
+}
+
+fn terminal_width() -> usize {
+    terminal_size::terminal_size().map_or(80, |(width, _)| width.0 as usize)
+}
+
+fn printer(color: bool) -> PrettyPrinter {
+    PrettyPrinter::new(terminal_width())
+        .color(color)
+        .limits(DisplayLimits::default())
+}
+
+// Evaluates every form in source and gives the last value, or None after
+// reporting what failed. A panic has already been reported by the panic
+// hook, so catching it only turns it into a failure. When the program
+// calls exit the process ends here with its code, since only the command
+// line, not the library, ends the process.
+fn eval(evaluator: &Evaluator, source: &str) -> Option<Object> {
+    match panic::catch_unwind(AssertUnwindSafe(|| evaluator.eval_str(source))) {
+        Ok(Ok(value)) => Some(value),
+        Ok(Err(Error::Eval(EvalError::Exit(code)))) => process::exit(code),
+        Ok(Err(error)) => {
+            eprintln!("{}", describe(&error));
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+fn repl(evaluator: &Evaluator, color: bool) {
+    let mut editor: Editor<Repl, DefaultHistory> =
+        Editor::new().expect("Failed to start line editor");
+    editor.set_helper(Some(Repl { evaluator }));
+    // Ctrl-C at the prompt is read by the line editor; while evaluating it
+    // arrives as a signal and stops the evaluation instead of the process.
+    // Installed after the editor, which sets a SIGINT handler of its own.
+    let handle = evaluator.interrupt_handle();
+    if let Err(error) = ctrlc::set_handler(move || handle.interrupt()) {
+        eprintln!("warning: Ctrl-C cannot interrupt evaluation: {}", error);
+    }
+    let history = history_path();
+    if let Some(path) = &history {
+        let _ = editor.load_history(path);
+    }
+    let mut input = String::new();
+    let mut session = Vec::new();
+    loop {
+        // Lines are collected until every delimiter is closed.
+        let prompt = if input.is_empty() { ">>> " } else { "... " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                input.push_str(&line);
+                input.push('\n');
+            }
+            Err(ReadlineError::Interrupted) => {
+                input.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                break;
+            }
+        }
+        if Object::is_incomplete(&input) {
+            continue;
+        }
+        let source = std::mem::take(&mut input);
+        let source = source.trim_end();
+        if source.trim().is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(source);
+        // Saved after every line, since exit ends the process directly.
+        if let Some(path) = &history {
+            let _ = editor.save_history(path);
+        }
+        // :time expr is shorthand for (time expr) and :replay file evaluates
+        // the file as if it had been typed in. :save file writes every input
+        // that evaluated successfully, so the session can be rerun as a
+        // script.
+        let expanded;
+        let source = if let Some(expr) = source.strip_prefix(":time") {
+            expanded = format!("(time {})", expr.trim());
+            expanded.as_str()
+        } else if let Some(path) = source.strip_prefix(":replay") {
+            match fs::read_to_string(path.trim()) {
+                Ok(text) => {
+                    expanded = text;
+                    expanded.trim()
+                }
+                Err(error) => {
+                    eprintln!("could not replay {}: {}", path.trim(), error);
+                    continue;
+                }
+            }
+        } else if let Some(path) = source.strip_prefix(":save") {
+            if path.trim().is_empty() {
+                eprintln!("usage: :save file");
+                continue;
+            }
+            let mut text = session.join("\n");
+            text.push('\n');
+            if let Err(error) = fs::write(path.trim(), text) {
+                eprintln!("could not save {}: {}", path.trim(), error);
+            }
+            continue;
+        } else if let Some(command) = unknown_command(source) {
+            match lint::suggestion(command, REPL_COMMANDS) {
+                Some(similar) => {
+                    eprintln!("unknown command :{}; did you mean :{}?", command, similar)
+                }
+                None => eprintln!("unknown command :{}", command),
+            }
+            continue;
+        } else {
+            source
+        };
+        if let Some(value) = eval(evaluator, source) {
+            println!("{}", printer(color).print(&value));
+            remember(evaluator, &value);
+            session.push(source.to_string());
+        }
+    }
+}
+
+const REPL_COMMANDS: [&str; 3] = ["time", "replay", "save"];
+
+// The name of a colon command that is not one of the above, as in :tme.
+fn unknown_command(source: &str) -> Option<&str> {
+    let command = source.strip_prefix(':')?.split_whitespace().next()?;
+    match command.starts_with(char::is_alphabetic) && !REPL_COMMANDS.contains(&command) {
+        true => Some(command),
+        false => None,
+    }
+}
+
+fn read_stdin() -> Result<String, (String, io::Error)> {
+    let mut source = String::new();
+    io::stdin()
+        .read_to_string(&mut source)
+        .map(|_| source)
+        .map_err(|error| ("-".to_string(), error))
+}
+
+// The file is polled rather than watched for events, which editors that
+// save by replacing the file tend to lose. Unless state is kept, every run
+// starts from a fresh evaluator.
+fn watch(path: &str, keep_state: bool, new_evaluator: impl Fn() -> Evaluator) -> ! {
+    let mut evaluator = new_evaluator();
+    let mut last = None;
+    let mut missing = false;
     loop {
-        let mut input = String::new();
-        print!(">>> ");
-        io::stdout().flush().expect("Failed to flush output");
-        io::stdin()
-            .read_line(&mut input)
-            .expect("Failed to read line");
-        let obj = input
-            .parse::<Object>()
-            .expect("Failed to parse string as object");
-        println!("{}", evaluator.eval(&obj));
+        let modified = fs::metadata(path).and_then(|metadata| metadata.modified());
+        match modified {
+            Ok(modified) if last != Some(modified) => {
+                last = Some(modified);
+                missing = false;
+                if !keep_state {
+                    evaluator = new_evaluator();
+                }
+                match fs::read_to_string(path) {
+                    Ok(source) => {
+                        eval(&evaluator, &source);
+                    }
+                    Err(error) => eprintln!("fundot: {}: {}", path, error),
+                }
+                eprintln!("fundot: watching {} for changes", path);
+            }
+            Ok(_) => {}
+            Err(error) if !missing => {
+                eprintln!("fundot: {}: {}", path, error);
+                last = None;
+                missing = true;
+            }
+            Err(_) => {}
+        }
+        thread::sleep(Duration::from_millis(200));
+    }
+}
+
+// Files are rewritten in place, or with --check only listed when they are
+// not formatted. Without files, or for -, stdin is formatted to stdout.
+fn format_files(args: impl Iterator<Item = String>) -> ! {
+    let mut check = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            "-" => paths.push(arg),
+            _ if arg.starts_with('-') => usage(),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        paths.push("-".to_string());
+    }
+    let mut failed = false;
+    for path in paths {
+        let source = match read_source(&path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("fundot: {}: {}", path, error);
+                failed = true;
+                continue;
+            }
+        };
+        let formatted = match fmt::format(&source) {
+            Ok(formatted) => formatted,
+            Err(_) => {
+                eprintln!("fundot: {}: could not parse input", path);
+                failed = true;
+                continue;
+            }
+        };
+        if check {
+            if formatted != source {
+                println!("{}", path);
+                failed = true;
+            }
+        } else if path == "-" {
+            print!("{}", formatted);
+        } else if formatted != source {
+            if let Err(error) = fs::write(&path, formatted) {
+                eprintln!("fundot: {}: {}", path, error);
+                failed = true;
+            }
+        }
+    }
+    process::exit(failed as i32)
+}
+
+fn read_source(path: &str) -> io::Result<String> {
+    if path == "-" {
+        read_stdin().map_err(|(_, error)| error)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+// Exits with 1 when anything was reported. With --json the diagnostics of
+// all files are printed as one array, each tagged with its file.
+fn lint_files(args: impl Iterator<Item = String>) -> ! {
+    let mut json = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-" => paths.push(arg),
+            _ if arg.starts_with('-') => usage(),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        paths.push("-".to_string());
+    }
+    #[cfg(not(feature = "json"))]
+    if json {
+        eprintln!("fundot: built without JSON support");
+        process::exit(2);
+    }
+    let evaluator = Evaluator::new();
+    let mut failed = false;
+    #[cfg(feature = "json")]
+    let mut reports = Vec::new();
+    for path in paths {
+        let diagnostics = match read_source(&path) {
+            Ok(source) => lint::lint(&source, &evaluator),
+            Err(error) => {
+                eprintln!("fundot: {}: {}", path, error);
+                failed = true;
+                continue;
+            }
+        };
+        let diagnostics = match diagnostics {
+            Ok(diagnostics) => diagnostics,
+            Err(_) => {
+                eprintln!("fundot: {}: could not parse input", path);
+                failed = true;
+                continue;
+            }
+        };
+        failed |= !diagnostics.is_empty();
+        for diagnostic in diagnostics {
+            #[cfg(feature = "json")]
+            if json {
+                let mut report = serde_json::to_value(&diagnostic).unwrap();
+                report["file"] = path.clone().into();
+                reports.push(report);
+                continue;
+            }
+            println!("{}: {}", path, diagnostic);
+        }
+    }
+    #[cfg(feature = "json")]
+    if json {
+        println!("{}", serde_json::Value::Array(reports));
+    }
+    process::exit(failed as i32)
+}
+
+// Checks the type annotations of the files together, as one program, so
+// calls are checked against functions defined in any of them. Exits and
+// prints as lint does.
+fn check_files(args: impl Iterator<Item = String>) -> ! {
+    let mut json = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-" => paths.push(arg),
+            _ if arg.starts_with('-') => usage(),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        paths.push("-".to_string());
+    }
+    #[cfg(not(feature = "json"))]
+    if json {
+        eprintln!("fundot: built without JSON support");
+        process::exit(2);
+    }
+    let mut failed = false;
+    let mut checked = Vec::new();
+    let mut sources = Vec::new();
+    for path in paths {
+        match read_source(&path) {
+            Ok(source) if Object::parse_all(&source).is_ok() => {
+                checked.push(path);
+                sources.push(source);
+            }
+            Ok(_) => {
+                eprintln!("fundot: {}: could not parse input", path);
+                failed = true;
+            }
+            Err(error) => {
+                eprintln!("fundot: {}: {}", path, error);
+                failed = true;
+            }
+        }
+    }
+    let sources: Vec<&str> = sources.iter().map(String::as_str).collect();
+    let errors = types::check(&sources).unwrap_or_default();
+    failed |= !errors.is_empty();
+    #[cfg(feature = "json")]
+    if json {
+        let reports: Vec<_> = errors
+            .iter()
+            .map(|error| {
+                let mut report = serde_json::to_value(error).unwrap();
+                report["file"] = checked[error.file].clone().into();
+                report
+            })
+            .collect();
+        println!("{}", serde_json::Value::Array(reports));
+        process::exit(failed as i32)
+    }
+    for error in errors {
+        println!("{}: {}", checked[error.file], error);
+    }
+    process::exit(failed as i32)
+}
+
+// Runs the *_test.fd files under each path, or under the current directory
+// when none is given, and exits with 1 unless every test passed.
+fn test_files(mut args: impl Iterator<Item = String>) -> ! {
+    let mut lib_paths = Vec::new();
+    let mut paths = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lib-path" => {
+                lib_paths.push(args.next().map(PathBuf::from).unwrap_or_else(|| usage()))
+            }
+            _ if arg.starts_with('-') => usage(),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        paths.push(".".to_string());
+    }
+    let evaluator = testing::evaluator(builder(&lib_paths));
+    let mut passed = 0;
+    let mut failed = 0;
+    for path in paths {
+        let files = match testing::discover(Path::new(&path)) {
+            Ok(files) => files,
+            Err(error) => {
+                eprintln!("fundot: {}: {}", path, error);
+                failed += 1;
+                continue;
+            }
+        };
+        for file in files {
+            let results = fs::read_to_string(&file)
+                .map_err(|error| error.to_string())
+                .and_then(|source| {
+                    testing::run(&source, &evaluator)
+                        .map_err(|_| "could not parse input".to_string())
+                });
+            let results = match results {
+                Ok(results) => results,
+                Err(error) => {
+                    eprintln!("fundot: {}: {}", file.display(), error);
+                    failed += 1;
+                    continue;
+                }
+            };
+            for result in results {
+                if result.passed() {
+                    println!("ok   {}: {}", file.display(), result.name);
+                    passed += 1;
+                    continue;
+                }
+                println!("FAIL {}: {}", file.display(), result.name);
+                failed += 1;
+                for failure in &result.failures {
+                    println!("    {}", failure.message);
+                    for line in failure.diff() {
+                        println!("      {}", line);
+                    }
+                }
+                if !result.output.is_empty() {
+                    println!("    output:");
+                    for line in result.output.lines() {
+                        println!("      {}", line);
+                    }
+                }
+            }
+        }
+    }
+    println!("{} passed; {} failed", passed, failed);
+    process::exit((failed > 0) as i32)
+}
+
+// The .fd files under path, or path itself when it is a file, skipping
+// hidden directories.
+fn source_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        fs::metadata(path)?;
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if path.is_dir() && !hidden {
+                pending.push(path);
+            } else if path.extension().is_some_and(|extension| extension == "fd") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// Writes a page per file, mirroring the layout of the sources, and an index
+// linking them.
+fn document_files(mut args: impl Iterator<Item = String>) -> ! {
+    let mut html = false;
+    let mut output = PathBuf::from("docs");
+    let mut paths = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--html" => html = true,
+            "-o" => output = args.next().map(PathBuf::from).unwrap_or_else(|| usage()),
+            _ if arg.starts_with('-') => usage(),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        paths.push(".".to_string());
+    }
+    let extension = if html { "html" } else { "md" };
+    let mut failed = false;
+    let mut pages = Vec::new();
+    for path in paths {
+        let root = Path::new(&path);
+        let files = match source_files(root) {
+            Ok(files) => files,
+            Err(error) => {
+                eprintln!("fundot: {}: {}", path, error);
+                failed = true;
+                continue;
+            }
+        };
+        for file in files {
+            let module = match fs::read_to_string(&file) {
+                Ok(source) => {
+                    doc::extract(&source).map_err(|_| "could not parse input".to_string())
+                }
+                Err(error) => Err(error.to_string()),
+            };
+            let module = match module {
+                Ok(module) => module,
+                Err(error) => {
+                    eprintln!("fundot: {}: {}", file.display(), error);
+                    failed = true;
+                    continue;
+                }
+            };
+            let relative = file
+                .strip_prefix(root)
+                .ok()
+                .filter(|relative| !relative.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new(file.file_name().unwrap()))
+                .with_extension("");
+            let title = relative.to_string_lossy().into_owned();
+            let page = relative.with_extension(extension);
+            let text = if html {
+                module.to_html(&title)
+            } else {
+                module.to_markdown(&title)
+            };
+            let target = output.join(&page);
+            let written = target
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&target, text));
+            if let Err(error) = written {
+                eprintln!("fundot: {}: {}", target.display(), error);
+                failed = true;
+                continue;
+            }
+            pages.push((title, page, module.doc));
+        }
+    }
+    let index = doc::index(&pages, html);
+    let target = output.join(format!("index.{}", extension));
+    if let Err(error) = fs::create_dir_all(&output).and_then(|_| fs::write(&target, index)) {
+        eprintln!("fundot: {}: {}", target.display(), error);
+        failed = true;
+    }
+    process::exit(failed as i32)
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2)
+}
+
+// The protocol owns stdout, so what the program prints is caught in a pipe
+// and passed on to the client as output.
+#[cfg(all(unix, feature = "json", not(feature = "rc")))]
+fn serve_stdio(evaluator: Arc<Evaluator>) -> io::Result<()> {
+    use std::os::fd::{AsFd, AsRawFd};
+    let protocol = io::stdout().as_fd().try_clone_to_owned()?;
+    let (mut output, writer) = io::pipe()?;
+    if unsafe { libc::dup2(writer.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    drop(writer);
+    let adapter = Adapter::new(evaluator, fs::File::from(protocol));
+    let forwarder = adapter.clone();
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        while let Ok(n @ 1..) = output.read(&mut buffer) {
+            forwarder.output("stdout", &String::from_utf8_lossy(&buffer[..n]));
+        }
+    });
+    adapter.serve(io::stdin().lock())
+}
+
+#[cfg(all(not(unix), feature = "json", not(feature = "rc")))]
+fn serve_stdio(evaluator: Arc<Evaluator>) -> io::Result<()> {
+    Adapter::new(evaluator, io::stdout()).serve(io::stdin().lock())
+}
+
+// Serves the Debug Adapter Protocol on stdin and stdout, or to the first
+// client connecting to the port, which must give the token printed at
+// startup, or FUNDOT_TOKEN, as the token argument of launch.
+#[cfg(all(feature = "json", not(feature = "rc")))]
+fn debug_adapter(mut args: impl Iterator<Item = String>) -> ! {
+    let mut port = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => {
+                let value = args.next().and_then(|value| value.parse::<u16>().ok());
+                port = Some(value.unwrap_or_else(|| usage()));
+            }
+            _ => usage(),
+        }
+    }
+    let evaluator = Arc::new(builder(&[]).build());
+    let served = match port {
+        Some(port) => TcpListener::bind(("127.0.0.1", port)).and_then(|listener| {
+            let token = token();
+            eprintln!("fundot: listening on {}", listener.local_addr()?);
+            eprintln!("fundot: token {}", token);
+            let (stream, _) = listener.accept()?;
+            Adapter::new(evaluator, stream.try_clone()?)
+                .token(&token)
+                .serve(io::BufReader::new(stream))
+        }),
+        None => serve_stdio(evaluator),
+    };
+    if let Err(error) = served {
+        eprintln!("fundot: {}", error);
+        process::exit(2);
+    }
+    process::exit(0)
+}
+
+// A token for the remote REPL, from the random keys std gives each hash
+// map, so that other users of the machine cannot guess it.
+#[cfg(all(feature = "json", not(feature = "rc")))]
+fn new_token() -> String {
+    (0..2u64)
+        .map(|i| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(i);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+// The token clients of a port must give: FUNDOT_TOKEN, or else a new one.
+#[cfg(all(feature = "json", not(feature = "rc")))]
+fn token() -> String {
+    env::var("FUNDOT_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+        .unwrap_or_else(new_token)
+}
+
+// Serves a remote REPL to any number of clients on the port, 7878 unless
+// given. Any process on the machine can connect, so clients must give the
+// token, FUNDOT_TOKEN or else one made up and printed at startup, and code
+// runs sandboxed without files, the network, processes, the environment or
+// the clock unless --trusted is given.
+#[cfg(all(feature = "json", not(feature = "rc")))]
+fn remote_repl(mut args: impl Iterator<Item = String>) -> ! {
+    let mut port = 7878;
+    let mut lib_paths = Vec::new();
+    let mut sandbox = Sandbox::pure();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => {
+                let value = args.next().and_then(|value| value.parse::<u16>().ok());
+                port = value.unwrap_or_else(|| usage());
+            }
+            "--lib-path" => {
+                lib_paths.push(args.next().map(PathBuf::from).unwrap_or_else(|| usage()))
+            }
+            "--trusted" => sandbox = Sandbox::trusted(),
+            _ => usage(),
+        }
+    }
+    let token = token();
+    let evaluator = builder(&lib_paths).sandbox(sandbox).build();
+    let server = Server::new(Arc::new(evaluator)).token(&token);
+    let served = TcpListener::bind(("127.0.0.1", port)).and_then(|listener| {
+        eprintln!("fundot: listening on {}", listener.local_addr()?);
+        eprintln!("fundot: token {}", token);
+        server.serve(listener)
+    });
+    if let Err(error) = served {
+        eprintln!("fundot: {}", error);
+        process::exit(2);
+    }
+    process::exit(0)
+}
+
+// Options come first; the arguments after the script, the expression or
+// - are left to the program as *argv*, which parse-args reads. Failing to
+// evaluate exits with 1 and bad usage or an unreadable script with 2.
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("fmt") => {
+            args.next();
+            format_files(args);
+        }
+        Some("lint") => {
+            args.next();
+            lint_files(args);
+        }
+        Some("check") => {
+            args.next();
+            check_files(args);
+        }
+        Some("test") => {
+            args.next();
+            test_files(args);
+        }
+        Some("doc") => {
+            args.next();
+            document_files(args);
+        }
+        #[cfg(all(feature = "json", not(feature = "rc")))]
+        Some("dap") => {
+            args.next();
+            debug_adapter(args);
+        }
+        #[cfg(all(feature = "json", not(feature = "rc")))]
+        Some("serve") => {
+            args.next();
+            remote_repl(args);
+        }
+        _ => {}
+    }
+    let mut color = true;
+    let mut init = true;
+    let mut watching = false;
+    let mut keep_state = false;
+    let mut lib_paths = Vec::new();
+    let mut command = Command::Repl;
+    while let Some(arg) = args.next() {
+        command = match arg.as_str() {
+            "--version" => {
+                println!("fundot {}", env!("CARGO_PKG_VERSION"));
+                return;
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--no-color" => {
+                color = false;
+                continue;
+            }
+            "--no-init" => {
+                init = false;
+                continue;
+            }
+            "--watch" => {
+                watching = true;
+                continue;
+            }
+            "--keep-state" => {
+                keep_state = true;
+                continue;
+            }
+            "--lib-path" => {
+                lib_paths.push(args.next().map(PathBuf::from).unwrap_or_else(|| usage()));
+                continue;
+            }
+            "-e" => Command::Expression(args.next().unwrap_or_else(|| usage())),
+            "-" => Command::Stdin,
+            _ if arg.starts_with('-') => usage(),
+            _ => Command::Script(arg),
+        };
+        break;
+    }
+    let argv: Vec<String> = args.collect();
+    // The script's absolute path and its directory, or null without one.
+    let script_path = match &command {
+        Command::Script(path) => std::path::absolute(path).ok(),
+        _ => None,
+    };
+    let script_dir = script_path.as_deref().and_then(Path::parent);
+    let path_object =
+        |path: Option<&Path>| Object::from(path.map(|path| path.to_string_lossy().into_owned()));
+    #[cfg(unix)]
+    let interactive = io::stdin().is_terminal() && io::stdout().is_terminal();
+    let new_evaluator = || {
+        let mut builder = builder(&lib_paths);
+        #[cfg(unix)]
+        if interactive {
+            builder = builder.native("inspect", inspect);
+        }
+        let evaluator = builder.build();
+        evaluator.define("*argv*", Object::from(argv.clone()));
+        evaluator.define("*script-path*", path_object(script_path.as_deref()));
+        evaluator.define("*script-dir*", path_object(script_dir));
+        evaluator
+    };
+    let evaluator = new_evaluator();
+    let color = color && use_color();
+    let source = match command {
+        Command::Script(path) if watching => watch(&path, keep_state, new_evaluator),
+        _ if watching => usage(),
+        // Piped input is read as a whole program, as with -.
+        Command::Repl if !io::stdin().is_terminal() => read_stdin(),
+        Command::Repl => {
+            if init {
+                load_init(&evaluator);
+            }
+            return repl(&evaluator, color);
+        }
+        Command::Expression(source) => {
+            let value = eval(&evaluator, &source).unwrap_or_else(|| process::exit(1));
+            println!("{}", printer(color).print(&value));
+            return;
+        }
+        Command::Script(path) => fs::read_to_string(&path).map_err(|error| (path, error)),
+        Command::Stdin => read_stdin(),
+    };
+    match source {
+        Ok(source) => {
+            if eval(&evaluator, &source).is_none() {
+                process::exit(1);
+            }
+        }
+        Err((path, error)) => {
+            eprintln!("fundot: {}: {}", path, error);
+            process::exit(2);
+        }
     }
 }
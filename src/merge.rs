@@ -0,0 +1,244 @@
+use crate::diff::path_string;
+use crate::evaluator::{args, EvalError, NativeFunction};
+use crate::interpreter::Interpreter;
+use crate::object::Object;
+use crate::symbol::Symbol;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+
+pub(crate) const NATIVES: [(&str, NativeFunction); 1] = [("deep-merge", deep_merge)];
+
+// How two lists or vectors at the same place are merged. Replace leaves
+// them to the conflict strategy like any other pair of values, concat
+// appends the right to the left and union appends only the items the left
+// does not have.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lists {
+    Replace,
+    Concat,
+    Union,
+}
+
+// Two values at the same place that cannot be merged, because they are not
+// both maps or, under concat and union, both lists.
+#[derive(Debug)]
+pub struct MergeError {
+    pub path: Vec<Object>,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "conflicting values at {}", path_string(&self.path))
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+// Decides a conflict, given where it is and the left and right values.
+// Closures taking the same are strategies.
+pub trait Resolve {
+    fn resolve(&self, path: &[Object], left: &Object, right: &Object)
+        -> Result<Object, MergeError>;
+}
+
+impl<F: Fn(&[Object], &Object, &Object) -> Result<Object, MergeError>> Resolve for F {
+    fn resolve(
+        &self,
+        path: &[Object],
+        left: &Object,
+        right: &Object,
+    ) -> Result<Object, MergeError> {
+        self(path, left, right)
+    }
+}
+
+// The right value wins, so later layers override earlier ones.
+pub struct Right;
+
+impl Resolve for Right {
+    fn resolve(&self, _: &[Object], _: &Object, right: &Object) -> Result<Object, MergeError> {
+        Ok(right.clone())
+    }
+}
+
+// The left value is kept.
+pub struct Left;
+
+impl Resolve for Left {
+    fn resolve(&self, _: &[Object], left: &Object, _: &Object) -> Result<Object, MergeError> {
+        Ok(left.clone())
+    }
+}
+
+// Any conflict fails the merge.
+pub struct Fail;
+
+impl Resolve for Fail {
+    fn resolve(&self, path: &[Object], _: &Object, _: &Object) -> Result<Object, MergeError> {
+        Err(MergeError {
+            path: path.to_vec(),
+        })
+    }
+}
+
+// How Object::deep_merge merges: maps key by key at every depth, lists as
+// lists says and any other pair of different values as conflicts decides.
+// Equal values are never a conflict. The default replaces lists and lets
+// the right win.
+pub struct MergeOptions<'a> {
+    lists: Lists,
+    conflicts: Box<dyn Resolve + 'a>,
+}
+
+impl Default for MergeOptions<'_> {
+    fn default() -> Self {
+        MergeOptions {
+            lists: Lists::Replace,
+            conflicts: Box::new(Right),
+        }
+    }
+}
+
+impl<'a> MergeOptions<'a> {
+    pub fn new() -> MergeOptions<'a> {
+        MergeOptions::default()
+    }
+
+    pub fn lists(mut self, lists: Lists) -> Self {
+        self.lists = lists;
+        self
+    }
+
+    pub fn conflicts(mut self, conflicts: impl Resolve + 'a) -> Self {
+        self.conflicts = Box::new(conflicts);
+        self
+    }
+}
+
+pub(crate) fn merge(
+    path: &mut Vec<Object>,
+    left: &Object,
+    right: &Object,
+    options: &MergeOptions,
+) -> Result<Object, MergeError> {
+    match (left.without_meta(), right.without_meta(), options.lists) {
+        (Object::Map(left), Object::Map(right), _) => {
+            let mut merged = left.clone();
+            for (key, value) in right {
+                let value = match left.get(key) {
+                    Some(existing) => {
+                        path.push(key.clone());
+                        let value = merge(path, existing, value, options)?;
+                        path.pop();
+                        value
+                    }
+                    None => value.clone(),
+                };
+                merged.insert(key.clone(), value);
+            }
+            Ok(Object::Map(merged))
+        }
+        (
+            Object::Vector(items) | Object::List(items),
+            Object::Vector(more) | Object::List(more),
+            lists @ (Lists::Concat | Lists::Union),
+        ) => {
+            let mut merged = items.clone();
+            for item in more {
+                if lists == Lists::Concat || !merged.contains(item) {
+                    merged.push(item.clone());
+                }
+            }
+            Ok(match left.without_meta() {
+                Object::List(_) => Object::List(merged),
+                _ => Object::Vector(merged),
+            })
+        }
+        (l, r, _) if l == r => Ok(left.clone()),
+        _ => options.conflicts.resolve(path, left, right),
+    }
+}
+
+fn option<'a>(options: &'a HashMap<Object, Object>, name: &str) -> Option<&'a Object> {
+    options
+        .get(&Object::Symbol(Symbol::new(name)))
+        .or_else(|| options.get(&Object::from(name)))
+        .map(Object::without_meta)
+}
+
+fn name(value: &Object) -> Option<&str> {
+    match value {
+        Object::String(name) => Some(name),
+        Object::Symbol(name) => Some(name.as_str()),
+        _ => None,
+    }
+}
+
+// (deep-merge a b {lists: "concat", conflicts: "right"}) merges b into a.
+// Lists is replace, concat or union and conflicts is right, left, error or
+// a function of the path, the left and the right value giving the value to
+// keep; the defaults are replace and right. Maps are not evaluated, so a
+// function is given by the name of a global one, as in {conflicts:
+// prefer-longer}. It gives null when the options are not understood or a
+// conflict is an error.
+fn deep_merge(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let args = args(obj);
+    let (left, right) = match args {
+        [left, right] | [left, right, _] => (left, right),
+        _ => return Ok(Object::Null),
+    };
+    let empty = HashMap::new();
+    let options = match args.get(2).map(Object::without_meta) {
+        Some(Object::Map(options)) => options,
+        Some(Object::Null) | None => &empty,
+        Some(_) => return Ok(Object::Null),
+    };
+    let lists = match option(options, "lists").map(name) {
+        None | Some(Some("replace")) => Lists::Replace,
+        Some(Some("concat")) => Lists::Concat,
+        Some(Some("union")) => Lists::Union,
+        Some(_) => return Ok(Object::Null),
+    };
+    // An error from the function is kept here while the merge unwinds.
+    let failure = RefCell::new(None);
+    let failed = &failure;
+    let function = match option(options, "conflicts") {
+        None => None,
+        Some(strategy) => match name(strategy) {
+            Some("right") | Some("left") | Some("error") => None,
+            Some(_) if matches!(strategy, Object::String(_)) => return Ok(Object::Null),
+            Some(name) => Some(interpreter.lookup_global(Symbol::new(name))),
+            None => Some(strategy.clone()),
+        },
+    };
+    let merge_options = MergeOptions::new().lists(lists);
+    let merge_options = match (option(options, "conflicts").and_then(name), function) {
+        (_, Some(function)) => {
+            merge_options.conflicts(move |path: &[Object], left: &Object, right: &Object| {
+                let call = [
+                    function.clone(),
+                    Object::Vector(path.to_vec()),
+                    left.clone(),
+                    right.clone(),
+                ];
+                interpreter.apply(&call).map_err(|error| {
+                    *failed.borrow_mut() = Some(error);
+                    MergeError {
+                        path: path.to_vec(),
+                    }
+                })
+            })
+        }
+        (Some("left"), None) => merge_options.conflicts(Left),
+        (Some("error"), None) => merge_options.conflicts(Fail),
+        _ => merge_options,
+    };
+    let merged = left.deep_merge(right, &merge_options);
+    drop(merge_options);
+    match (merged, failure.into_inner()) {
+        (_, Some(error)) => Err(error),
+        (Ok(merged), None) => Ok(merged),
+        (Err(_), None) => Ok(Object::Null),
+    }
+}
@@ -0,0 +1,217 @@
+use crate::bytes;
+use crate::evaluator::{args, PrimitiveFunction};
+use crate::object::{Bytes, Object, Shared};
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+// Sockets and listeners are foreign objects. Reading, writing and accepting
+// block until they are done, and failures give null.
+pub(crate) const PRIMITIVES: [(&str, PrimitiveFunction); 7] = [
+    ("tcp-connect", tcp_connect),
+    ("tcp-listen", tcp_listen),
+    ("socket-accept", socket_accept),
+    ("socket-read", socket_read),
+    ("socket-write", socket_write),
+    ("socket-close", socket_close),
+    ("socket-port", socket_port),
+];
+
+#[cfg(unix)]
+pub(crate) const UNIX_PRIMITIVES: [(&str, PrimitiveFunction); 2] =
+    [("unix-connect", unix_connect), ("unix-listen", unix_listen)];
+
+#[derive(Debug)]
+enum Socket {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+#[derive(Debug)]
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+// Both kinds of stream can be read and written through a shared reference,
+// so one thread can read a socket while another writes to it.
+impl Socket {
+    fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => (&mut &*stream).read(buf),
+            #[cfg(unix)]
+            Socket::Unix(stream) => (&mut &*stream).read(buf),
+        }
+    }
+
+    fn read_to_end(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => (&mut &*stream).read_to_end(buf),
+            #[cfg(unix)]
+            Socket::Unix(stream) => (&mut &*stream).read_to_end(buf),
+        }
+    }
+
+    fn write_all(&self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => (&mut &*stream).write_all(buf),
+            #[cfg(unix)]
+            Socket::Unix(stream) => (&mut &*stream).write_all(buf),
+        }
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.shutdown(Shutdown::Both),
+        }
+    }
+}
+
+fn socket(obj: &Object) -> Option<&Socket> {
+    match args(obj).first()?.without_meta() {
+        Object::Other(other) => other.downcast_ref::<Socket>(),
+        _ => None,
+    }
+}
+
+fn listener(obj: &Object) -> Option<&Listener> {
+    match args(obj).first()?.without_meta() {
+        Object::Other(other) => other.downcast_ref::<Listener>(),
+        _ => None,
+    }
+}
+
+fn string(obj: &Object, index: usize) -> Option<&str> {
+    match args(obj).get(index).map(Object::without_meta) {
+        Some(Object::String(string)) => Some(string),
+        _ => None,
+    }
+}
+
+fn port(obj: &Object, index: usize) -> Option<u16> {
+    match args(obj).get(index).map(Object::without_meta) {
+        Some(Object::Integer(port)) => u16::try_from(*port).ok(),
+        _ => None,
+    }
+}
+
+fn foreign<T: std::any::Any + Send + Sync>(value: Option<T>) -> Object {
+    value.map_or(Object::Null, |value| Object::Other(Shared::new(value)))
+}
+
+fn tcp_connect(obj: &Object) -> Object {
+    let stream = match (string(obj, 0), port(obj, 1)) {
+        (Some(host), Some(port)) => TcpStream::connect((host, port)).ok(),
+        _ => None,
+    };
+    foreign(stream.map(Socket::Tcp))
+}
+
+// (tcp-listen port host) listens on 127.0.0.1 unless a host is given.
+// Port 0 picks a free port, which socket-port tells.
+fn tcp_listen(obj: &Object) -> Object {
+    let host = match args(obj).get(1).map(Object::without_meta) {
+        Some(Object::String(host)) => host,
+        Some(Object::Null) | None => "127.0.0.1",
+        _ => return Object::Null,
+    };
+    let listener = port(obj, 0).and_then(|port| TcpListener::bind((host, port)).ok());
+    foreign(listener.map(Listener::Tcp))
+}
+
+#[cfg(unix)]
+fn unix_connect(obj: &Object) -> Object {
+    let stream = string(obj, 0).and_then(|path| UnixStream::connect(path).ok());
+    foreign(stream.map(Socket::Unix))
+}
+
+// The socket file is left behind when the listener goes away, and binding
+// fails while it exists.
+#[cfg(unix)]
+fn unix_listen(obj: &Object) -> Object {
+    let listener = string(obj, 0).and_then(|path| UnixListener::bind(path).ok());
+    foreign(listener.map(Listener::Unix))
+}
+
+// Waits for the next connection to a listener.
+fn socket_accept(obj: &Object) -> Object {
+    let socket = listener(obj).and_then(|listener| match listener {
+        Listener::Tcp(listener) => listener
+            .accept()
+            .ok()
+            .map(|(stream, _)| Socket::Tcp(stream)),
+        #[cfg(unix)]
+        Listener::Unix(listener) => listener
+            .accept()
+            .ok()
+            .map(|(stream, _)| Socket::Unix(stream)),
+    });
+    foreign(socket)
+}
+
+// Most bytes one socket-read gives. Its buffer is made before reading, so
+// n from a script must not decide alone how much is allocated.
+const READ_LIMIT: i64 = 64 * 1024;
+
+// (socket-read s n) gives at most n bytes as soon as some arrive, and empty
+// bytes once the other end has closed. Without n it reads until then.
+fn socket_read(obj: &Object) -> Object {
+    let socket = match socket(obj) {
+        Some(socket) => socket,
+        None => return Object::Null,
+    };
+    let mut bytes = Vec::new();
+    let read = match args(obj).get(1).map(Object::without_meta) {
+        Some(Object::Integer(n)) if *n >= 0 => {
+            bytes.resize((*n).min(READ_LIMIT) as usize, 0);
+            socket.read(&mut bytes)
+        }
+        Some(Object::Null) | None => socket.read_to_end(&mut bytes),
+        _ => return Object::Null,
+    };
+    match read {
+        Ok(n) => {
+            bytes.truncate(n);
+            Object::from(Bytes(bytes))
+        }
+        Err(_) => Object::Null,
+    }
+}
+
+// Writes bytes or a string, which is sent as UTF-8, and gives the number
+// of bytes written.
+fn socket_write(obj: &Object) -> Object {
+    let data = args(obj).get(1).and_then(bytes::data);
+    match (socket(obj), data) {
+        (Some(socket), Some(data)) => match socket.write_all(data) {
+            Ok(()) => Object::Integer(data.len() as i64),
+            Err(_) => Object::Null,
+        },
+        _ => Object::Null,
+    }
+}
+
+// Shuts a socket down in both directions, so the other end sees it closed
+// even while the socket is still referenced.
+fn socket_close(obj: &Object) -> Object {
+    match socket(obj).map(Socket::shutdown) {
+        Some(Ok(())) => Object::Bool(true),
+        _ => Object::Null,
+    }
+}
+
+// The local port of a TCP socket or listener.
+fn socket_port(obj: &Object) -> Object {
+    let addr = match (socket(obj), listener(obj)) {
+        (Some(Socket::Tcp(stream)), _) => stream.local_addr().ok(),
+        (_, Some(Listener::Tcp(listener))) => listener.local_addr().ok(),
+        _ => None,
+    };
+    addr.map_or(Object::Null, |addr| Object::Integer(addr.port() as i64))
+}
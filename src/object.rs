@@ -1,11 +1,33 @@
+use crate::diff::{self, Patch, PatchError};
+use crate::evaluator::{
+    Composed, Continuation, Function, Generator, Memoized, Partial, PrimitiveFunction, Traced,
+};
+use crate::merge::{self, MergeError, MergeOptions};
+use crate::symbol::Symbol;
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
 use std::any::Any;
-use std::collections::{HashMap, LinkedList};
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::ops::Index;
 use std::result::Result;
 use std::str::{Chars, FromStr};
-use std::sync::Arc;
+use std::sync::{OnceLock, PoisonError, RwLock};
+
+// Shared object internals are atomically counted by default so objects
+// and evaluators can cross threads. The `rc` feature switches them to Rc,
+// which is cheaper for single-threaded embedders but makes them !Send.
+#[cfg(not(feature = "rc"))]
+pub type Shared<T> = std::sync::Arc<T>;
+#[cfg(not(feature = "rc"))]
+pub type AnyValue = dyn Any + Send + Sync;
+#[cfg(feature = "rc")]
+pub type Shared<T> = std::rc::Rc<T>;
+#[cfg(feature = "rc")]
+pub type AnyValue = dyn Any;
 
 #[derive(Clone, Debug)]
 pub enum Object {
@@ -13,31 +35,118 @@ pub enum Object {
     Bool(bool),
     Integer(i64),
     Float(f64),
-    String(String),
-    Symbol(String),
-    List(LinkedList<Object>),
+    String(Shared<str>),
+    Symbol(Symbol),
+    List(Vec<Object>),
     Vector(Vec<Object>),
     Map(HashMap<Object, Object>),
-    Other(Arc<dyn Any>),
+    Other(Shared<AnyValue>),
+}
+
+// Raw binary data, such as what a socket reads. It compares and hashes by
+// content.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Bytes(pub Vec<u8>);
+
+#[derive(Debug)]
+pub struct WithMeta {
+    pub value: Object,
+    pub meta: Object,
+}
+
+impl Object {
+    pub fn with_meta(&self, meta: Object) -> Object {
+        Object::Other(Shared::new(WithMeta {
+            value: self.without_meta().clone(),
+            meta,
+        }))
+    }
+
+    pub fn meta(&self) -> Option<&Object> {
+        match self {
+            Object::Other(other) => other.downcast_ref::<WithMeta>().map(|w| &w.meta),
+            _ => None,
+        }
+    }
+
+    pub fn without_meta(&self) -> &Object {
+        match self {
+            Object::Other(other) => match other.downcast_ref::<WithMeta>() {
+                Some(w) => &w.value,
+                None => self,
+            },
+            _ => self,
+        }
+    }
+}
+
+// Equality is between values rather than IEEE comparison: every NaN equals
+// every other, so NaN can be a map key and = is an equivalence, while 0.0
+// and -0.0 are equal as numbers. Integers equal the floats of the same
+// value and hash like them.
+// The integer a float is exactly, if any. Converting the integer to a float
+// instead would round those past 2^53, making 2^53 + 1 equal 2^53.0.
+fn float_integer(n: f64) -> Option<i64> {
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    match n.fract() == 0.0 && (-LIMIT..LIMIT).contains(&n) {
+        true => Some(n as i64),
+        false => None,
+    }
 }
 
 impl PartialEq for Object {
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Object::Null, Object::Null) => true,
-            (Object::Null, Object::Bool(x)) => !x,
-            (Object::Bool(x), Object::Bool(y)) => x == y,
-            (Object::Integer(x), Object::Integer(y)) => x == y,
-            (Object::Integer(x), Object::Float(y)) => *x as f64 == *y,
-            (Object::Float(x), Object::Integer(y)) => *x == *y as f64,
-            (Object::Float(x), Object::Float(y)) => x == y,
-            (Object::String(x), Object::String(y)) => x == y,
-            (Object::Symbol(x), Object::Symbol(y)) => x == y,
-            (Object::List(x), Object::List(y)) => x == y,
-            (Object::Vector(x), Object::Vector(y)) => x == y,
-            (Object::Map(x), Object::Map(y)) => x == y,
-            _ => false,
+        let mut stack = vec![(self, other)];
+        while let Some((x, y)) = stack.pop() {
+            let equal = match (x.without_meta(), y.without_meta()) {
+                (Object::Null, Object::Null) => true,
+                (Object::Null, Object::Bool(x)) | (Object::Bool(x), Object::Null) => !x,
+                (Object::Bool(x), Object::Bool(y)) => x == y,
+                (Object::Integer(x), Object::Integer(y)) => x == y,
+                (Object::Integer(x), Object::Float(y)) => float_integer(*y) == Some(*x),
+                (Object::Float(x), Object::Integer(y)) => float_integer(*x) == Some(*y),
+                (Object::Float(x), Object::Float(y)) => x == y || (x.is_nan() && y.is_nan()),
+                (Object::String(x), Object::String(y)) => x == y,
+                (Object::Symbol(x), Object::Symbol(y)) => x == y,
+                #[cfg(feature = "bigint")]
+                (Object::Other(x), Object::Other(y)) if x.is::<BigInt>() => {
+                    y.downcast_ref::<BigInt>() == x.downcast_ref::<BigInt>()
+                }
+                (Object::Other(x), Object::Other(y)) => {
+                    match (x.downcast_ref::<Bytes>(), y.downcast_ref::<Bytes>()) {
+                        (Some(x), Some(y)) => x == y,
+                        _ => false,
+                    }
+                }
+                (Object::List(x), Object::List(y)) => {
+                    x.len() == y.len() && {
+                        stack.extend(x.iter().zip(y.iter()));
+                        true
+                    }
+                }
+                (Object::Vector(x), Object::Vector(y)) => {
+                    x.len() == y.len() && {
+                        stack.extend(x.iter().zip(y.iter()));
+                        true
+                    }
+                }
+                (Object::Map(x), Object::Map(y)) => {
+                    x.len() == y.len()
+                        && x.iter().all(|(key, value)| match y.get(key) {
+                            Some(other) => {
+                                stack.push((value, other));
+                                true
+                            }
+                            None => false,
+                        })
+                }
+                _ => false,
+            };
+            if !equal {
+                return false;
+            }
         }
+        true
     }
 }
 
@@ -45,70 +154,195 @@ impl Eq for Object {}
 
 impl Hash for Object {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        match self {
+        match self.without_meta() {
             Object::Integer(n) => n.hash(state),
+            Object::Float(n) if float_integer(*n).is_some() => (*n as i64).hash(state),
+            Object::Float(n) if n.is_nan() => f64::NAN.to_bits().hash(state),
+            Object::Float(n) => n.to_bits().hash(state),
             Object::String(s) => s.hash(state),
             Object::Symbol(s) => s.hash(state),
+            Object::Other(other) => {
+                if let Some(bytes) = other.downcast_ref::<Bytes>() {
+                    bytes.hash(state)
+                }
+                #[cfg(feature = "bigint")]
+                if let Some(n) = other.downcast_ref::<BigInt>() {
+                    n.hash(state)
+                }
+            }
             _ => {}
         }
     }
 }
 
-impl fmt::Display for Object {
+// Floats print so that they read back as the same float: always with a
+// fraction or an exponent, as in 1.0 and 1e20, and nan and inf for the
+// values that are not numbers or not finite.
+fn float_string(n: f64) -> String {
+    match n {
+        n if n.is_nan() => "nan".to_string(),
+        f64::INFINITY => "inf".to_string(),
+        f64::NEG_INFINITY => "-inf".to_string(),
+        n => format!("{:?}", n),
+    }
+}
+
+const MAX_DISPLAY_DEPTH: usize = 256;
+
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayLimits {
+    pub max_elements: usize,
+    pub max_depth: usize,
+}
+
+impl Default for DisplayLimits {
+    fn default() -> Self {
+        DisplayLimits {
+            max_elements: 100,
+            max_depth: 16,
+        }
+    }
+}
+
+pub struct DisplayWith<'a> {
+    obj: &'a Object,
+    limits: DisplayLimits,
+}
+
+impl fmt::Display for DisplayWith<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.obj.fmt_with(f, &self.limits, 0)
+    }
+}
+
+impl Object {
+    pub fn display_with(&self, limits: DisplayLimits) -> DisplayWith<'_> {
+        DisplayWith { obj: self, limits }
+    }
+
+    fn fmt_with(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        limits: &DisplayLimits,
+        depth: usize,
+    ) -> fmt::Result {
         match self {
             Object::Null => write!(f, "null"),
             Object::Bool(n) => write!(f, "{}", n),
             Object::Integer(n) => write!(f, "{}", n),
-            Object::Float(n) => write!(f, "{}", n),
+            Object::Float(n) => write!(f, "{}", float_string(*n)),
             Object::String(s) => write!(f, "{:?}", s),
-            Object::Symbol(s) => write!(f, "{}", s),
+            Object::Symbol(s) => fmt_symbol(f, s.as_str()),
             Object::List(list) => {
-                if list.is_empty() {
-                    return write!(f, "()");
+                write!(f, "(")?;
+                if !list.is_empty() && depth >= limits.max_depth {
+                    write!(f, "…")?;
+                } else {
+                    for (i, obj) in list.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, " ")?;
+                        }
+                        if i >= limits.max_elements {
+                            write!(f, "…")?;
+                            break;
+                        }
+                        // A colon or comma in a list reads back bare.
+                        match obj {
+                            Object::Symbol(s) if [":", ","].contains(&s.as_str()) => {
+                                write!(f, "{}", s)?
+                            }
+                            obj => obj.fmt_with(f, limits, depth + 1)?,
+                        }
+                    }
                 }
-                let mut s = String::new();
-                s.push('(');
-                for obj in list {
-                    s.push_str(&(obj.to_string() + " "));
-                }
-                s.pop();
-                s.push(')');
-                write!(f, "{}", s)
+                write!(f, ")")
             }
             Object::Vector(vector) => {
-                if vector.is_empty() {
-                    return write!(f, "[]");
-                }
-                let mut s = String::new();
-                s.push('[');
-                for obj in vector {
-                    s.push_str(&(obj.to_string() + ", "));
+                write!(f, "[")?;
+                if !vector.is_empty() && depth >= limits.max_depth {
+                    write!(f, "…")?;
+                } else {
+                    for (i, obj) in vector.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        if i >= limits.max_elements {
+                            write!(f, "…")?;
+                            break;
+                        }
+                        obj.fmt_with(f, limits, depth + 1)?;
+                    }
                 }
-                s.pop();
-                s.pop();
-                s.push(']');
-                write!(f, "{}", s)
+                write!(f, "]")
             }
             Object::Map(map) => {
-                if map.is_empty() {
-                    return write!(f, "{{}}");
+                write!(f, "{{")?;
+                if !map.is_empty() && depth >= limits.max_depth {
+                    write!(f, "…")?;
+                } else {
+                    for (i, (key, value)) in map.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        if i >= limits.max_elements {
+                            write!(f, "…")?;
+                            break;
+                        }
+                        key.fmt_with(f, limits, depth + 1)?;
+                        write!(f, ": ")?;
+                        value.fmt_with(f, limits, depth + 1)?;
+                    }
+                }
+                write!(f, "}}")
+            }
+            Object::Other(other) => {
+                #[cfg(feature = "bigint")]
+                if let Some(n) = other.downcast_ref::<BigInt>() {
+                    return write!(f, "{}", n);
                 }
-                let mut s = String::new();
-                s.push('{');
-                for (key, value) in map {
-                    s.push_str(&(key.to_string() + ": " + &value.to_string() + ", "));
+                if let Some(w) = other.downcast_ref::<WithMeta>() {
+                    w.value.fmt_with(f, limits, depth)
+                } else if let Some(Bytes(bytes)) = other.downcast_ref::<Bytes>() {
+                    write!(f, "<bytes ")?;
+                    for (i, byte) in bytes.iter().enumerate() {
+                        if i >= limits.max_elements {
+                            write!(f, "…")?;
+                            break;
+                        }
+                        write!(f, "{:02x}", byte)?;
+                    }
+                    write!(f, ">")
+                } else if let Some(traced) = other.downcast_ref::<Traced>() {
+                    traced.function.fmt_with(f, limits, depth)
+                } else if let Some(memoized) = other.downcast_ref::<Memoized>() {
+                    memoized.function.fmt_with(f, limits, depth)
+                } else if other.is::<Function>() || other.is::<Partial>() || other.is::<Composed>()
+                {
+                    write!(f, "<fn>")
+                } else if other.is::<Generator>() {
+                    write!(f, "<generator>")
+                } else if other.is::<Continuation>() {
+                    write!(f, "<continuation>")
+                } else if other.is::<PrimitiveFunction>() {
+                    write!(f, "<primitive>")
+                } else {
+                    write!(f, "<{:?}>", other)
                 }
-                s.pop();
-                s.pop();
-                s.push('}');
-                write!(f, "{}", s)
             }
-            Object::Other(other) => write!(f, "<{:?}>", other),
         }
     }
 }
 
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limits = DisplayLimits {
+            max_elements: usize::MAX,
+            max_depth: MAX_DISPLAY_DEPTH,
+        };
+        self.fmt_with(f, &limits, 0)
+    }
+}
+
 #[derive(Debug)]
 pub struct ParseObjectError;
 
@@ -120,28 +354,88 @@ impl fmt::Display for ParseObjectError {
 
 impl Error for ParseObjectError {}
 
-fn atomize_expr_escape_char(chars: &mut Chars) -> Result<char, ParseObjectError> {
-    if let Some(c) = chars.next() {
+// Delimiters end a symbol wherever they appear. Every other character,
+// punctuation included, may be part of one, so a-b, a.b and <= are each a
+// single symbol. A token that starts with a digit, or with + or - and then
+// a digit, is a number. Any other name is written between bars, as in
+// |two words|, with \| and \\ for a bar or backslash inside.
+const DELIMITERS: &str = "()[]{},:#\"|";
+
+fn is_symbol_char(c: char) -> bool {
+    !c.is_whitespace() && !DELIMITERS.contains(c)
+}
+
+fn is_number(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some('+') | Some('-') => chars.next().is_some_and(|c| c.is_ascii_digit()),
+        Some(c) => c.is_ascii_digit(),
+        None => false,
+    }
+}
+
+// Whether name reads back as the same symbol written bare.
+fn is_plain_symbol(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(is_symbol_char)
+        && !is_number(name)
+        && !["null", "true", "false", "nan", "inf", "-inf"].contains(&name)
+}
+
+fn fmt_symbol(f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+    if is_plain_symbol(name) {
+        return write!(f, "{}", name);
+    }
+    write!(f, "|")?;
+    for c in name.chars() {
         match c {
-            '"' => Ok('"'),
-            '\\' => Ok('\\'),
-            'n' => Ok('\n'),
-            'r' => Ok('\r'),
-            't' => Ok('\t'),
-            _ => Err(ParseObjectError {}),
+            '|' => write!(f, "\\|")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c => write!(f, "{}", c)?,
         }
-    } else {
-        Err(ParseObjectError {})
+    }
+    write!(f, "|")
+}
+
+// What the lexer gives the parser. Delimiters are kept apart from atoms so
+// that an escaped symbol such as |(| is never taken for one.
+enum Token {
+    Delimiter(char),
+    Atom(Object),
+}
+
+// Escapes are those strings print with: \n \r \t \0 \\ \' and \u{hex},
+// along with the closing quote.
+fn atomize_expr_escape_char(chars: &mut Chars, quote: char) -> Result<char, ParseObjectError> {
+    match chars.next() {
+        Some(c) if c == quote => Ok(c),
+        Some('\\') => Ok('\\'),
+        Some('\'') => Ok('\''),
+        Some('n') => Ok('\n'),
+        Some('r') => Ok('\r'),
+        Some('t') => Ok('\t'),
+        Some('0') => Ok('\0'),
+        Some('u') if chars.next() == Some('{') => {
+            let hex: String = chars.take_while(|&c| c != '}').collect();
+            u32::from_str_radix(&hex, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or(ParseObjectError {})
+        }
+        _ => Err(ParseObjectError {}),
     }
 }
 
-fn atomize_expr_chars_to_str(chars: &mut Chars) -> Result<Object, ParseObjectError> {
+fn atomize_expr_quoted(chars: &mut Chars, quote: char) -> Result<String, ParseObjectError> {
     let mut s = String::new();
     while let Some(c) = chars.next() {
         if c == '\\' {
-            s.push(atomize_expr_escape_char(chars)?);
-        } else if c == '"' {
-            return Ok(Object::String(s));
+            s.push(atomize_expr_escape_char(chars, quote)?);
+        } else if c == quote {
+            return Ok(s);
         } else {
             s.push(c);
         }
@@ -149,140 +443,554 @@ fn atomize_expr_chars_to_str(chars: &mut Chars) -> Result<Object, ParseObjectErr
     Err(ParseObjectError {})
 }
 
-fn atomize_expr_push(
-    expr: &mut LinkedList<Object>,
-    s: &mut String,
-) -> Result<(), ParseObjectError> {
+// Integer literals too large for i64 read as big integers when there are
+// any, and as floats otherwise.
+#[cfg(feature = "bigint")]
+fn big_literal(s: &str) -> Option<Object> {
+    let digits = s.strip_prefix(['+', '-']).unwrap_or(s);
+    match digits.chars().all(|c| c.is_ascii_digit()) {
+        true => s.parse::<BigInt>().ok().map(Object::from),
+        false => None,
+    }
+}
+
+#[cfg(not(feature = "bigint"))]
+fn big_literal(_: &str) -> Option<Object> {
+    None
+}
+
+fn atomize_expr_push(expr: &mut VecDeque<Token>, s: &mut String) -> Result<(), ParseObjectError> {
     if s.is_empty() {
         return Ok(());
     }
-    if s.chars().next().unwrap().is_numeric() {
+    let atom = if is_number(s) {
         if let Ok(n) = s.parse::<i64>() {
-            expr.push_back(Object::Integer(n));
+            Object::Integer(n)
+        } else if let Some(n) = big_literal(s) {
+            n
         } else if let Ok(n) = s.parse::<f64>() {
-            expr.push_back(Object::Float(n));
+            Object::Float(n)
         } else {
             return Err(ParseObjectError {});
         }
-    } else if s == "null" {
-        expr.push_back(Object::Null);
-    } else if s == "true" {
-        expr.push_back(Object::Bool(true));
-    } else if s == "false" {
-        expr.push_back(Object::Bool(false));
     } else {
-        expr.push_back(Object::Symbol(s.clone()));
-    }
+        match s.as_str() {
+            "nan" => Object::Float(f64::NAN),
+            "inf" => Object::Float(f64::INFINITY),
+            "-inf" => Object::Float(f64::NEG_INFINITY),
+            "null" => Object::Null,
+            "true" => Object::Bool(true),
+            "false" => Object::Bool(false),
+            _ => Object::Symbol(Symbol::new(s)),
+        }
+    };
+    expr.push_back(Token::Atom(atom));
     s.clear();
     Ok(())
 }
 
-fn atomize_expr(s: &str) -> Result<LinkedList<Object>, ParseObjectError> {
-    let mut expr = LinkedList::new();
+fn atomize_expr(s: &str) -> Result<VecDeque<Token>, ParseObjectError> {
+    let mut expr = VecDeque::new();
     let mut chars = s.chars();
     let mut s = String::new();
     while let Some(c) = chars.next() {
-        if c == '"' {
-            atomize_expr_push(&mut expr, &mut s)?;
-            expr.push_back(atomize_expr_chars_to_str(&mut chars)?);
-        } else if c.is_whitespace() {
-            atomize_expr_push(&mut expr, &mut s)?;
-        } else if c == '.' && s.chars().next().unwrap().is_numeric() {
-            s.push(c);
-        } else if c.is_ascii_punctuation() && c != '_' {
-            atomize_expr_push(&mut expr, &mut s)?;
-            expr.push_back(Object::Symbol(c.to_string()));
-        } else {
+        if is_symbol_char(c) {
             s.push(c);
+            continue;
+        }
+        atomize_expr_push(&mut expr, &mut s)?;
+        match c {
+            '"' => {
+                let string = atomize_expr_quoted(&mut chars, '"')?;
+                expr.push_back(Token::Atom(Object::String(string.into())));
+            }
+            '|' => {
+                let name = atomize_expr_quoted(&mut chars, '|')?;
+                expr.push_back(Token::Atom(Object::Symbol(Symbol::new(&name))));
+            }
+            c if c.is_whitespace() => {}
+            c => expr.push_back(Token::Delimiter(c)),
         }
     }
+    atomize_expr_push(&mut expr, &mut s)?;
     Ok(expr)
 }
 
-fn parse_list(
-    expr: &mut LinkedList<Object>,
-    is_delimiter: &mut dyn FnMut(&Object) -> bool,
-) -> Result<LinkedList<Object>, ParseObjectError> {
-    let mut list = LinkedList::new();
+fn is_delimiter(expr: &VecDeque<Token>, delimiters: &str) -> bool {
+    matches!(expr.front(), Some(Token::Delimiter(c)) if delimiters.contains(*c))
+}
+
+fn parse_list(expr: &mut VecDeque<Token>, close: &str) -> Result<Vec<Object>, ParseObjectError> {
+    let mut list = Vec::new();
     while !expr.is_empty() {
-        if is_delimiter(expr.front().unwrap()) {
+        if is_delimiter(expr, close) {
             return Ok(list);
         }
-        list.push_back(parse_mut_expr(expr)?);
+        list.push(parse_mut_expr(expr)?);
     }
     Err(ParseObjectError {})
 }
 
-fn parse_mut_expr(expr: &mut LinkedList<Object>) -> Result<Object, ParseObjectError> {
-    if expr.is_empty() {
-        return Err(ParseObjectError {});
-    }
-    if *expr.front().unwrap() == Object::Symbol("(".to_string()) {
-        expr.pop_front();
-        let list = parse_list(expr, &mut |obj| *obj == Object::Symbol(")".to_string()))?;
-        expr.pop_front();
-        return Ok(Object::List(list));
-    }
-    if *expr.front().unwrap() == Object::Symbol("[".to_string()) {
-        expr.pop_front();
-        let mut vector = Vec::new();
-        while !expr.is_empty() {
-            if *expr.front().unwrap() == Object::Symbol("]".to_string()) {
-                expr.pop_front();
-                return Ok(Object::Vector(vector));
+// Reads the form after a tag such as #uuid into the value it stands for,
+// or None when the form is not one the tag accepts.
+pub type TagReader = fn(&Object) -> Option<Object>;
+
+fn tags() -> &'static RwLock<HashMap<Symbol, TagReader>> {
+    static TAGS: OnceLock<RwLock<HashMap<Symbol, TagReader>>> = OnceLock::new();
+    TAGS.get_or_init(|| {
+        let uuid: TagReader = crate::uuid::read_tag;
+        RwLock::new(HashMap::from([(Symbol::new("uuid"), uuid)]))
+    })
+}
+
+// Makes #name form read as what reader makes of form, for every parse in
+// the process. Parsing fails for a tag that is not defined or a form the
+// reader does not accept.
+pub fn define_tag(name: &str, reader: TagReader) {
+    tags()
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(Symbol::new(name), reader);
+}
+
+fn parse_mut_expr(expr: &mut VecDeque<Token>) -> Result<Object, ParseObjectError> {
+    let delimiter = match expr.pop_front() {
+        Some(Token::Atom(atom)) => return Ok(atom),
+        Some(Token::Delimiter(c)) => c,
+        None => return Err(ParseObjectError {}),
+    };
+    match delimiter {
+        '#' => {
+            let reader = match expr.pop_front() {
+                Some(Token::Atom(Object::Symbol(tag))) => tags()
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .get(&tag)
+                    .copied(),
+                _ => None,
+            };
+            let form = parse_mut_expr(expr)?;
+            reader
+                .and_then(|reader| reader(&form))
+                .ok_or(ParseObjectError {})
+        }
+        '(' => {
+            let list = parse_list(expr, ")")?;
+            expr.pop_front();
+            Ok(Object::List(list))
+        }
+        '[' => {
+            let mut vector = Vec::new();
+            while !expr.is_empty() {
+                if is_delimiter(expr, "]") {
+                    expr.pop_front();
+                    return Ok(Object::Vector(vector));
+                }
+                if is_delimiter(expr, ",") {
+                    expr.pop_front();
+                }
+                let mut list = parse_list(expr, ",]")?;
+                if list.len() != 1 {
+                    return Err(ParseObjectError {});
+                }
+                vector.push(list.pop().unwrap());
             }
-            if *expr.front().unwrap() == Object::Symbol(",".to_string()) {
+            Err(ParseObjectError {})
+        }
+        '{' => {
+            let mut map = HashMap::new();
+            while !expr.is_empty() {
+                if is_delimiter(expr, "}") {
+                    expr.pop_front();
+                    return Ok(Object::Map(map));
+                }
+                if is_delimiter(expr, ",") {
+                    expr.pop_front();
+                }
+                let key = parse_list(expr, ":,}")?;
+                if key.len() != 1 || !is_delimiter(expr, ":") {
+                    return Err(ParseObjectError {});
+                }
                 expr.pop_front();
+                let mut value = parse_list(expr, ",}")?;
+                if value.len() != 1 {
+                    return Err(ParseObjectError {});
+                }
+                map.insert(key.into_iter().next().unwrap(), value.pop().unwrap());
             }
-            let mut list = parse_list(expr, &mut |obj| {
-                *obj == Object::Symbol(",".to_string()) || *obj == Object::Symbol("]".to_string())
-            })?;
-            if list.len() != 1 {
-                return Err(ParseObjectError {});
+            Err(ParseObjectError {})
+        }
+        // Outside maps and vectors a colon or comma is a symbol, as in the
+        // annotations of fundot check.
+        ':' | ',' => Ok(Object::Symbol(Symbol::new(&delimiter.to_string()))),
+        _ => Err(ParseObjectError {}),
+    }
+}
+
+impl Object {
+    // True when the source ends inside a string or escaped symbol or with
+    // delimiters left open, so that more input could still make it parse.
+    pub fn is_incomplete(s: &str) -> bool {
+        let mut depth = 0usize;
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' | '|' => loop {
+                    match chars.next() {
+                        Some(quote) if quote == c => break,
+                        Some('\\') => {
+                            chars.next();
+                        }
+                        Some(_) => {}
+                        None => return true,
+                    }
+                },
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth = depth.saturating_sub(1),
+                _ => {}
             }
-            vector.push(list.pop_front().unwrap());
         }
-        return Err(ParseObjectError {});
+        depth > 0
     }
-    if *expr.front().unwrap() == Object::Symbol("{".to_string()) {
-        expr.pop_front();
-        let mut map = HashMap::new();
+
+    pub fn parse_all(s: &str) -> Result<Vec<Object>, ParseObjectError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("parse", len = s.len()).entered();
+        // A #! line at the very start lets scripts be executed directly.
+        let s = match s.strip_prefix("#!") {
+            Some(rest) => rest.find('\n').map_or("", |end| &rest[end..]),
+            None => s,
+        };
+        let mut expr = atomize_expr(s)?;
+        let mut forms = Vec::new();
         while !expr.is_empty() {
-            if *expr.front().unwrap() == Object::Symbol("}".to_string()) {
-                expr.pop_front();
-                return Ok(Object::Map(map));
-            }
-            if *expr.front().unwrap() == Object::Symbol(",".to_string()) {
-                expr.pop_front();
-            }
-            let mut list = parse_list(expr, &mut |obj| {
-                *obj == Object::Symbol(",".to_string()) || *obj == Object::Symbol("}".to_string())
-            })?;
-            if list.len() != 3 {
-                return Err(ParseObjectError {});
+            forms.push(parse_mut_expr(&mut expr)?);
+        }
+        Ok(forms)
+    }
+}
+
+impl FromStr for Object {
+    type Err = ParseObjectError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("parse", len = s.len()).entered();
+        let mut expr = atomize_expr(s)?;
+        parse_mut_expr(&mut expr)
+    }
+}
+
+#[derive(Debug)]
+pub struct FromObjectError;
+
+impl fmt::Display for FromObjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for FromObjectError {}
+
+pub trait FromObject: Sized {
+    fn from_object(obj: &Object) -> Result<Self, FromObjectError>;
+}
+
+pub trait IntoObject {
+    fn into_object(self) -> Object;
+}
+
+impl<T: Into<Object>> IntoObject for T {
+    fn into_object(self) -> Object {
+        self.into()
+    }
+}
+
+impl From<i64> for Object {
+    fn from(n: i64) -> Self {
+        Object::Integer(n)
+    }
+}
+
+impl From<f64> for Object {
+    fn from(n: f64) -> Self {
+        Object::Float(n)
+    }
+}
+
+impl From<bool> for Object {
+    fn from(b: bool) -> Self {
+        Object::Bool(b)
+    }
+}
+
+impl From<String> for Object {
+    fn from(s: String) -> Self {
+        Object::String(s.into())
+    }
+}
+
+impl From<&str> for Object {
+    fn from(s: &str) -> Self {
+        Object::String(s.into())
+    }
+}
+
+impl From<Bytes> for Object {
+    fn from(bytes: Bytes) -> Self {
+        Object::Other(Shared::new(bytes))
+    }
+}
+
+// An integer too large for i64, which arithmetic gives when it promotes.
+// One that fits is an Integer, so the two never hold the same value.
+#[cfg(feature = "bigint")]
+impl From<BigInt> for Object {
+    fn from(n: BigInt) -> Self {
+        match i64::try_from(&n) {
+            Ok(n) => Object::Integer(n),
+            Err(_) => Object::Other(Shared::new(n)),
+        }
+    }
+}
+
+#[cfg(feature = "bigint")]
+pub(crate) fn is_big(obj: &Object) -> bool {
+    matches!(obj.without_meta(), Object::Other(other) if other.is::<BigInt>())
+}
+
+// The value of an integer of either size.
+#[cfg(feature = "bigint")]
+pub(crate) fn big_integer(obj: &Object) -> Option<BigInt> {
+    match obj.without_meta() {
+        Object::Integer(n) => Some(BigInt::from(*n)),
+        Object::Other(other) => other.downcast_ref::<BigInt>().cloned(),
+        _ => None,
+    }
+}
+
+impl<T: Into<Object>> From<Vec<T>> for Object {
+    fn from(vector: Vec<T>) -> Self {
+        Object::Vector(vector.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<K: Into<Object>, V: Into<Object>> From<HashMap<K, V>> for Object {
+    fn from(map: HashMap<K, V>) -> Self {
+        Object::Map(
+            map.into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        )
+    }
+}
+
+impl<T: Into<Object>> From<Option<T>> for Object {
+    fn from(option: Option<T>) -> Self {
+        match option {
+            Some(value) => value.into(),
+            None => Object::Null,
+        }
+    }
+}
+
+impl FromObject for Object {
+    fn from_object(obj: &Object) -> Result<Self, FromObjectError> {
+        Ok(obj.clone())
+    }
+}
+
+impl FromObject for i64 {
+    fn from_object(obj: &Object) -> Result<Self, FromObjectError> {
+        match obj.without_meta() {
+            Object::Integer(n) => Ok(*n),
+            _ => Err(FromObjectError {}),
+        }
+    }
+}
+
+impl FromObject for f64 {
+    fn from_object(obj: &Object) -> Result<Self, FromObjectError> {
+        match obj.without_meta() {
+            Object::Integer(n) => Ok(*n as f64),
+            Object::Float(n) => Ok(*n),
+            _ => Err(FromObjectError {}),
+        }
+    }
+}
+
+impl FromObject for bool {
+    fn from_object(obj: &Object) -> Result<Self, FromObjectError> {
+        match obj.without_meta() {
+            Object::Bool(b) => Ok(*b),
+            _ => Err(FromObjectError {}),
+        }
+    }
+}
+
+impl FromObject for String {
+    fn from_object(obj: &Object) -> Result<Self, FromObjectError> {
+        match obj.without_meta() {
+            Object::String(s) => Ok(s.to_string()),
+            _ => Err(FromObjectError {}),
+        }
+    }
+}
+
+impl FromObject for Bytes {
+    fn from_object(obj: &Object) -> Result<Self, FromObjectError> {
+        match obj.without_meta() {
+            Object::Other(other) => match other.downcast_ref::<Bytes>() {
+                Some(bytes) => Ok(bytes.clone()),
+                None => Err(FromObjectError {}),
+            },
+            _ => Err(FromObjectError {}),
+        }
+    }
+}
+
+impl<T: FromObject> FromObject for Vec<T> {
+    fn from_object(obj: &Object) -> Result<Self, FromObjectError> {
+        match obj.without_meta() {
+            Object::List(list) => list.iter().map(T::from_object).collect(),
+            Object::Vector(vector) => vector.iter().map(T::from_object).collect(),
+            _ => Err(FromObjectError {}),
+        }
+    }
+}
+
+impl<K: FromObject + Eq + Hash, V: FromObject> FromObject for HashMap<K, V> {
+    fn from_object(obj: &Object) -> Result<Self, FromObjectError> {
+        match obj.without_meta() {
+            Object::Map(map) => map
+                .iter()
+                .map(|(key, value)| Ok((K::from_object(key)?, V::from_object(value)?)))
+                .collect(),
+            _ => Err(FromObjectError {}),
+        }
+    }
+}
+
+impl<T: FromObject> FromObject for Option<T> {
+    fn from_object(obj: &Object) -> Result<Self, FromObjectError> {
+        match obj.without_meta() {
+            Object::Null => Ok(None),
+            _ => Ok(Some(T::from_object(obj)?)),
+        }
+    }
+}
+
+macro_rules! impl_try_from_object {
+    ($($ty:ty),*) => {
+        $(
+            impl TryFrom<Object> for $ty {
+                type Error = FromObjectError;
+
+                fn try_from(obj: Object) -> Result<Self, Self::Error> {
+                    <$ty>::from_object(&obj)
+                }
             }
-            let first = list.pop_front().unwrap();
-            let second = list.pop_front().unwrap();
-            if second != Object::Symbol(":".to_string()) {
-                return Err(ParseObjectError {});
+
+            impl TryFrom<&Object> for $ty {
+                type Error = FromObjectError;
+
+                fn try_from(obj: &Object) -> Result<Self, Self::Error> {
+                    <$ty>::from_object(obj)
+                }
             }
-            let third = list.pop_front().unwrap();
-            map.insert(first, third);
+        )*
+    };
+}
+
+impl_try_from_object!(i64, f64, bool, String);
+
+impl<T: FromObject> TryFrom<Object> for Vec<T> {
+    type Error = FromObjectError;
+
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        Vec::from_object(&obj)
+    }
+}
+
+impl<K: FromObject + Eq + Hash, V: FromObject> TryFrom<Object> for HashMap<K, V> {
+    type Error = FromObjectError;
+
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        HashMap::from_object(&obj)
+    }
+}
+
+impl Object {
+    fn get_key(&self, key: &str) -> Option<&Object> {
+        match self.without_meta() {
+            Object::Map(map) => map
+                .get(&Object::Symbol(Symbol::new(key)))
+                .or_else(|| map.get(&Object::String(key.into()))),
+            Object::List(list) => list.get(key.parse::<usize>().ok()?),
+            Object::Vector(vector) => vector.get(key.parse::<usize>().ok()?),
+            _ => None,
         }
-        return Err(ParseObjectError {});
     }
-    Ok(expr.pop_front().unwrap())
+
+    pub fn get_path(&self, path: &str) -> Option<&Object> {
+        path.split('.')
+            .filter(|key| !key.is_empty())
+            .try_fold(self, |obj, key| obj.get_key(key))
+    }
+
+    // What turns self into other, as a list of added, removed and changed
+    // paths.
+    pub fn diff(&self, other: &Object) -> Patch {
+        let mut changes = Vec::new();
+        diff::changes(&mut Vec::new(), self, other, &mut changes);
+        Patch { changes }
+    }
+
+    // Self with a patch applied, checking that what it changes or removes
+    // is there as the patch says.
+    pub fn apply_patch(&self, patch: &Patch) -> Result<Object, PatchError> {
+        diff::apply_patch(self, patch)
+    }
+
+    // Other merged into self at every depth, as options say.
+    pub fn deep_merge(&self, other: &Object, options: &MergeOptions) -> Result<Object, MergeError> {
+        merge::merge(&mut Vec::new(), self, other, options)
+    }
 }
 
-fn parse_expr(expr: &LinkedList<Object>) -> Result<Object, ParseObjectError> {
-    parse_mut_expr(&mut expr.clone())
+impl Index<usize> for Object {
+    type Output = Object;
+
+    fn index(&self, index: usize) -> &Object {
+        let value = match self.without_meta() {
+            Object::List(list) => list.get(index),
+            Object::Vector(vector) => vector.get(index),
+            _ => None,
+        };
+        value.unwrap_or(&Object::Null)
+    }
 }
 
-impl FromStr for Object {
-    type Err = ParseObjectError;
+impl Index<&Object> for Object {
+    type Output = Object;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let expr = atomize_expr(s)?;
-        parse_expr(&expr)
+    fn index(&self, key: &Object) -> &Object {
+        let value = match (self.without_meta(), key) {
+            (Object::Map(map), _) => map.get(key),
+            (_, Object::Integer(index)) if *index >= 0 => return &self[*index as usize],
+            _ => None,
+        };
+        value.unwrap_or(&Object::Null)
+    }
+}
+
+impl Index<&str> for Object {
+    type Output = Object;
+
+    fn index(&self, key: &str) -> &Object {
+        match self.without_meta() {
+            Object::Map(_) => self.get_key(key).unwrap_or(&Object::Null),
+            _ => &Object::Null,
+        }
     }
 }
@@ -0,0 +1,200 @@
+use crate::evaluator::{self, PrimitiveFunction};
+use crate::object::Object;
+use crate::symbol::Symbol;
+use crate::types;
+use std::collections::{HashMap, HashSet};
+
+pub(crate) struct Optimizer<'a> {
+    global: &'a HashMap<Symbol, Object>,
+    assigned: HashSet<Symbol>,
+    locals: Vec<Symbol>,
+}
+
+fn assigned(obj: &Object, result: &mut HashSet<Symbol>) {
+    if let Object::List(list) = obj {
+        match list.as_slice() {
+            [Object::Symbol(head), ..] if head.as_str() == "quote" => {}
+            [Object::Symbol(head), Object::Symbol(name), ..]
+                if ["set", "defdynamic"].contains(&head.as_str()) =>
+            {
+                result.insert(*name);
+                list[2..].iter().for_each(|obj| assigned(obj, result));
+            }
+            _ => list.iter().for_each(|obj| assigned(obj, result)),
+        }
+    }
+}
+
+fn constant(obj: &Object) -> Option<&Object> {
+    match obj {
+        Object::List(list) => match list.as_slice() {
+            [Object::Symbol(head), value] if head.as_str() == "quote" => Some(value),
+            _ => None,
+        },
+        Object::Symbol(_) => None,
+        _ => Some(obj),
+    }
+}
+
+fn quoted(obj: Object) -> Object {
+    match obj {
+        Object::Symbol(_) | Object::List(_) => {
+            Object::List(vec![Object::Symbol(Symbol::new("quote")), obj])
+        }
+        _ => obj,
+    }
+}
+
+impl<'a> Optimizer<'a> {
+    // Dynamic names count as assigned, since a binding may replace them.
+    pub(crate) fn new(
+        global: &'a HashMap<Symbol, Object>,
+        dynamic: HashSet<Symbol>,
+    ) -> Optimizer<'a> {
+        Optimizer {
+            global,
+            assigned: dynamic,
+            locals: Vec::new(),
+        }
+    }
+
+    pub(crate) fn optimize(&mut self, obj: &Object) -> Object {
+        assigned(obj, &mut self.assigned);
+        self.expr(obj)
+    }
+
+    fn primitive(&self, symbol: Symbol) -> Option<&'a Object> {
+        if self.locals.contains(&symbol) || self.assigned.contains(&symbol) {
+            return None;
+        }
+        let value = self.global.get(&symbol)?;
+        match value {
+            Object::Other(other) if other.is::<PrimitiveFunction>() => Some(value),
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self, obj: &Object) -> Object {
+        match obj {
+            Object::Symbol(symbol) => match self.primitive(*symbol) {
+                Some(primitive) => primitive.clone(),
+                None => obj.clone(),
+            },
+            Object::List(list) if !list.is_empty() => {
+                if let Object::Symbol(symbol) = &list[0] {
+                    if let Some(result) = self.special(symbol.as_str(), &list[1..]) {
+                        return result;
+                    }
+                }
+                let list: Vec<Object> = list.iter().map(|obj| self.expr(obj)).collect();
+                self.fold(list)
+            }
+            _ => obj.clone(),
+        }
+    }
+
+    fn fold(&self, list: Vec<Object>) -> Object {
+        let primitive = match &list[0] {
+            Object::Other(other) => match other.downcast_ref::<PrimitiveFunction>() {
+                Some(primitive) if evaluator::pure(*primitive) => *primitive,
+                _ => return Object::List(list),
+            },
+            _ => return Object::List(list),
+        };
+        let mut call = vec![list[0].clone()];
+        for obj in &list[1..] {
+            match constant(obj) {
+                Some(value) => call.push(value.clone()),
+                None => return Object::List(list),
+            }
+        }
+        quoted(primitive(&Object::List(call)))
+    }
+
+    fn body(&mut self, body: &[Object]) -> Vec<Object> {
+        body.iter().map(|obj| self.expr(obj)).collect()
+    }
+
+    fn special(&mut self, name: &str, args: &[Object]) -> Option<Object> {
+        let unannotated = types::unannotated(name, args);
+        let args = unannotated.as_deref().unwrap_or(args);
+        let head = Object::Symbol(Symbol::new(name));
+        let result = match (name, args) {
+            ("quote", _) => return Some(Object::List([&[head], args].concat())),
+            ("if", [condition, then]) | ("if", [condition, then, _]) => {
+                let condition = self.expr(condition);
+                let then = self.expr(then);
+                let otherwise = args.get(2).map(|obj| self.expr(obj));
+                match constant(&condition) {
+                    Some(value) if evaluator::truthy(value) => then,
+                    Some(_) => otherwise.unwrap_or(Object::Null),
+                    None => {
+                        let mut list = vec![head, condition, then];
+                        list.extend(otherwise);
+                        Object::List(list)
+                    }
+                }
+            }
+            ("do", [obj]) => self.expr(obj),
+            ("do", body) => Object::List([vec![head], self.body(body)].concat()),
+            ("set", [symbol @ Object::Symbol(_), value]) => {
+                Object::List(vec![head, symbol.clone(), self.expr(value)])
+            }
+            ("let", [Object::List(bindings), body @ ..]) => {
+                let len = self.locals.len();
+                let mut optimized = Vec::new();
+                for binding in bindings {
+                    match binding {
+                        Object::List(pair) if pair.len() == 2 => {
+                            let value = self.expr(&pair[1]);
+                            if let Object::Symbol(symbol) = pair[0] {
+                                self.locals.push(symbol);
+                            }
+                            optimized.push(Object::List(vec![pair[0].clone(), value]));
+                        }
+                        _ => optimized.push(binding.clone()),
+                    }
+                }
+                let body = self.body(body);
+                self.locals.truncate(len);
+                Object::List([vec![head, Object::List(optimized)], body].concat())
+            }
+            ("fn", [params, body @ ..]) | ("fn*", [params, body @ ..]) => {
+                let len = self.locals.len();
+                if let Object::List(list) = params {
+                    for param in list {
+                        if let Object::Symbol(symbol) = param {
+                            self.locals.push(*symbol);
+                        }
+                    }
+                }
+                let body = self.body(body);
+                self.locals.truncate(len);
+                Object::List([vec![head, params.clone()], body].concat())
+            }
+            ("shift", [Object::Symbol(continuation), body @ ..]) => {
+                let len = self.locals.len();
+                self.locals.push(*continuation);
+                let body = self.body(body);
+                self.locals.truncate(len);
+                Object::List([vec![head, Object::Symbol(*continuation)], body].concat())
+            }
+            ("while", [condition, body @ ..]) => {
+                let condition = self.expr(condition);
+                match constant(&condition) {
+                    Some(value) if !evaluator::truthy(value) => Object::Null,
+                    _ => Object::List([vec![head, condition], self.body(body)].concat()),
+                }
+            }
+            ("if", _)
+            | ("set", _)
+            | ("let", _)
+            | ("fn", _)
+            | ("fn*", _)
+            | ("while", _)
+            | ("shift", _) => Object::List([&[head], args].concat()),
+            _ => return None,
+        };
+        Some(result)
+    }
+}
@@ -0,0 +1,74 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, PoisonError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+// Where print and println write, and where time and trace report, so a
+// host can capture what a program prints instead of letting it reach the
+// console. Text comes in the pieces the builtins write, each ending at a
+// newline unless it came from print.
+pub trait OutputSink: Send + Sync {
+    fn write(&self, stream: Stream, text: &str);
+}
+
+impl<T: OutputSink + ?Sized> OutputSink for Arc<T> {
+    fn write(&self, stream: Stream, text: &str) {
+        (**self).write(stream, text)
+    }
+}
+
+// The default sink, writing to the process's stdout and stderr. Text is
+// flushed as it comes, so a prompt written with print shows at once.
+pub struct Stdio;
+
+impl OutputSink for Stdio {
+    fn write(&self, stream: Stream, text: &str) {
+        let _ = match stream {
+            Stream::Stdout => {
+                let mut stdout = io::stdout();
+                stdout
+                    .write_all(text.as_bytes())
+                    .and_then(|()| stdout.flush())
+            }
+            Stream::Stderr => io::stderr().write_all(text.as_bytes()),
+        };
+    }
+}
+
+// Collects both streams, in the order they were written, into a string.
+// Keep an Arc of it to read what was captured.
+#[derive(Debug, Default)]
+pub struct Buffer {
+    text: Mutex<String>,
+}
+
+impl Buffer {
+    pub fn new() -> Buffer {
+        Buffer::default()
+    }
+
+    pub fn contents(&self) -> String {
+        self.text
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    // Gives what was captured and empties the buffer.
+    pub fn take(&self) -> String {
+        std::mem::take(&mut *self.text.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl OutputSink for Buffer {
+    fn write(&self, _: Stream, text: &str) {
+        self.text
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push_str(text);
+    }
+}
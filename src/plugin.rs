@@ -0,0 +1,138 @@
+use crate::evaluator::{NativeFunction, PrimitiveFunction};
+use crate::object::Object;
+#[cfg(feature = "plugins")]
+use crate::object::Shared;
+use crate::symbol::{self, Interner};
+#[cfg(feature = "plugins")]
+use std::error::Error;
+#[cfg(feature = "plugins")]
+use std::ffi::OsStr;
+#[cfg(feature = "plugins")]
+use std::fmt;
+#[cfg(feature = "plugins")]
+use std::mem;
+use std::sync::RwLock;
+
+// A plugin is a shared library built as a cdylib against the same version
+// of this crate, with the same features and compiler, that declares its
+// registration function with
+//
+//     fundot::plugin!(register);
+//
+//     fn register(registrar: &mut Registrar) { ... }
+//
+// and defines its builtins through the registrar it is given.
+pub struct Registrar {
+    interner: &'static RwLock<Interner>,
+    adopted: bool,
+    bindings: Vec<(String, Binding)>,
+}
+
+// Types differ between the plugin's copy of this crate and the loading
+// program's, so a function can only be wrapped into an object by the
+// program, which would not recognise one the plugin wrapped.
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+enum Binding {
+    Value(Object),
+    Primitive(PrimitiveFunction),
+    Native(NativeFunction),
+}
+
+impl Registrar {
+    // Called by plugin! before any code of the plugin's own runs, so that
+    // the plugin makes no symbols before taking the program's.
+    #[doc(hidden)]
+    pub fn adopt(&mut self) -> bool {
+        self.adopted = symbol::adopt(self.interner);
+        self.adopted
+    }
+
+    fn bind(&mut self, name: &str, binding: Binding) {
+        self.bindings.push((name.to_string(), binding));
+    }
+
+    pub fn define(&mut self, name: &str, value: Object) {
+        self.bind(name, Binding::Value(value));
+    }
+
+    pub fn primitive(&mut self, name: &str, primitive: PrimitiveFunction) {
+        self.bind(name, Binding::Primitive(primitive));
+    }
+
+    pub fn native(&mut self, name: &str, native: NativeFunction) {
+        self.bind(name, Binding::Native(native));
+    }
+}
+
+// Exports the registration function of a plugin, which takes the symbols
+// of the program loading it over and then calls register with the
+// registrar.
+#[macro_export]
+macro_rules! plugin {
+    ($register:expr) => {
+        #[no_mangle]
+        pub fn fundot_plugin_register(registrar: &mut $crate::plugin::Registrar) {
+            if registrar.adopt() {
+                ($register)(registrar)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "plugins")]
+#[derive(Debug)]
+pub enum PluginError {
+    Load(libloading::Error),
+    // The plugin made symbols before taking the program's, or did not
+    // declare its registration function with plugin!.
+    Symbols,
+}
+
+#[cfg(feature = "plugins")]
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[cfg(feature = "plugins")]
+impl Error for PluginError {}
+
+#[cfg(feature = "plugins")]
+impl From<libloading::Error> for PluginError {
+    fn from(err: libloading::Error) -> Self {
+        PluginError::Load(err)
+    }
+}
+
+// Runs the plugin's registration and gives what it defined. The library
+// stays loaded for the rest of the process, since the functions it defines
+// live on in whatever values hold them.
+#[cfg(feature = "plugins")]
+pub(crate) fn load(path: &OsStr) -> Result<Vec<(String, Object)>, PluginError> {
+    let mut registrar = Registrar {
+        interner: symbol::interner(),
+        adopted: false,
+        bindings: Vec::new(),
+    };
+    // Loading runs the library's initializers, and the registration
+    // function is trusted to have the signature above.
+    unsafe {
+        let library = libloading::Library::new(path)?;
+        let register = library.get::<fn(&mut Registrar)>(b"fundot_plugin_register\0")?;
+        register(&mut registrar);
+        mem::forget(library);
+    }
+    if !registrar.adopted {
+        return Err(PluginError::Symbols);
+    }
+    let bindings = registrar.bindings.into_iter().map(|(name, binding)| {
+        let value = match binding {
+            Binding::Value(value) => value,
+            Binding::Primitive(primitive) => Object::Other(Shared::new(primitive)),
+            Binding::Native(native) => Object::Other(Shared::new(native)),
+        };
+        (name, value)
+    });
+    Ok(bindings.collect())
+}
@@ -0,0 +1,195 @@
+use crate::object::{DisplayLimits, Object};
+
+const NUMBER: &str = "\x1b[36m";
+const STRING: &str = "\x1b[32m";
+const SYMBOL: &str = "\x1b[34m";
+const CONSTANT: &str = "\x1b[35m";
+const DELIMITER: &str = "\x1b[90m";
+const RESET: &str = "\x1b[0m";
+
+// Lays an object out within a line width. Collections that do not fit on
+// the rest of the line are broken into one element per line; lists keep
+// their head next to the opening parenthesis. Unless the limits elide
+// something, the output parses back to the same object.
+#[derive(Clone, Copy, Debug)]
+pub struct PrettyPrinter {
+    width: usize,
+    indent: usize,
+    color: bool,
+    limits: DisplayLimits,
+}
+
+pub(crate) enum Doc {
+    Text(String, usize),
+    Group {
+        open: Box<Doc>,
+        close: Box<Doc>,
+        items: Vec<Doc>,
+        list: bool,
+    },
+    Pair(Box<Doc>, Box<Doc>),
+}
+
+impl Doc {
+    fn width(&self) -> usize {
+        match self {
+            Doc::Text(_, width) => *width,
+            Doc::Group {
+                open,
+                close,
+                items,
+                list,
+            } => {
+                let separator = if *list { 1 } else { 2 };
+                let gaps = separator * items.len().saturating_sub(1);
+                open.width() + items.iter().map(Doc::width).sum::<usize>() + gaps + close.width()
+            }
+            Doc::Pair(key, value) => key.width() + 2 + value.width(),
+        }
+    }
+}
+
+impl PrettyPrinter {
+    pub fn new(width: usize) -> PrettyPrinter {
+        PrettyPrinter {
+            width,
+            indent: 2,
+            color: false,
+            limits: DisplayLimits {
+                max_elements: usize::MAX,
+                max_depth: usize::MAX,
+            },
+        }
+    }
+
+    pub fn indent(mut self, indent: usize) -> PrettyPrinter {
+        self.indent = indent;
+        self
+    }
+
+    // Colors tokens with ANSI escapes: numbers, strings, symbols, constants
+    // and delimiters each get their own.
+    pub fn color(mut self, color: bool) -> PrettyPrinter {
+        self.color = color;
+        self
+    }
+
+    pub fn limits(mut self, limits: DisplayLimits) -> PrettyPrinter {
+        self.limits = limits;
+        self
+    }
+
+    pub fn print(&self, obj: &Object) -> String {
+        self.layout(&self.doc(obj, 0))
+    }
+
+    pub(crate) fn layout(&self, doc: &Doc) -> String {
+        let mut output = String::new();
+        self.render(doc, 0, 0, &mut output);
+        output
+    }
+
+    fn text(&self, text: String, style: &str) -> Doc {
+        let width = text.chars().count();
+        if self.color {
+            Doc::Text(format!("{}{}{}", style, text, RESET), width)
+        } else {
+            Doc::Text(text, width)
+        }
+    }
+
+    fn doc(&self, obj: &Object, depth: usize) -> Doc {
+        let obj = obj.without_meta();
+        let (open, close, list) = match obj {
+            Object::List(_) => ("(", ")", true),
+            Object::Vector(_) => ("[", "]", false),
+            Object::Map(_) => ("{", "}", false),
+            Object::Integer(_) | Object::Float(_) => return self.text(obj.to_string(), NUMBER),
+            Object::String(_) => return self.text(obj.to_string(), STRING),
+            Object::Symbol(_) => return self.text(obj.to_string(), SYMBOL),
+            Object::Null | Object::Bool(_) => return self.text(obj.to_string(), CONSTANT),
+            Object::Other(_) => return self.text(obj.to_string(), ""),
+        };
+        let elided = || self.text("…".to_string(), DELIMITER);
+        let mut items = Vec::new();
+        let len = match obj {
+            Object::List(list) | Object::Vector(list) => list.len(),
+            Object::Map(map) => map.len(),
+            _ => 0,
+        };
+        if len > 0 && depth >= self.limits.max_depth {
+            items.push(elided());
+        } else {
+            match obj {
+                Object::List(list) | Object::Vector(list) => {
+                    for obj in list.iter().take(self.limits.max_elements) {
+                        items.push(self.doc(obj, depth + 1));
+                    }
+                }
+                Object::Map(map) => {
+                    for (key, value) in map.iter().take(self.limits.max_elements) {
+                        items.push(Doc::Pair(
+                            Box::new(self.doc(key, depth + 1)),
+                            Box::new(self.doc(value, depth + 1)),
+                        ));
+                    }
+                }
+                _ => {}
+            }
+            if len > self.limits.max_elements {
+                items.push(elided());
+            }
+        }
+        Doc::Group {
+            open: Box::new(self.text(open.to_string(), DELIMITER)),
+            close: Box::new(self.text(close.to_string(), DELIMITER)),
+            items,
+            list,
+        }
+    }
+
+    // Column is where the doc starts; indent is the indentation of the
+    // line it starts on, which broken collections indent from.
+    fn render(&self, doc: &Doc, column: usize, indent: usize, output: &mut String) {
+        match doc {
+            Doc::Text(text, _) => output.push_str(text),
+            Doc::Pair(key, value) => {
+                self.render(key, column, indent, output);
+                output.push_str(": ");
+                self.render(value, column + key.width() + 2, indent, output);
+            }
+            Doc::Group {
+                open,
+                close,
+                items,
+                list,
+            } => {
+                let flat = items.is_empty() || column + doc.width() <= self.width;
+                self.render(open, column, indent, output);
+                let inner = indent + self.indent;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 && !*list {
+                        output.push(',');
+                    }
+                    if flat {
+                        if i > 0 {
+                            output.push(' ');
+                        }
+                        self.render(item, column, indent, output);
+                    } else if *list && i == 0 {
+                        self.render(item, column + 1, indent, output);
+                    } else {
+                        output.push('\n');
+                        output.push_str(&" ".repeat(inner));
+                        self.render(item, inner, inner, output);
+                    }
+                }
+                if !flat && !*list {
+                    output.push('\n');
+                    output.push_str(&" ".repeat(indent));
+                }
+                self.render(close, column, indent, output);
+            }
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::evaluator::{args, EvalError, NativeFunction, PrimitiveFunction};
+use crate::interpreter::Interpreter;
+use crate::object::Object;
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+
+// Commands run directly, without a shell, and give null when they cannot
+// be started.
+pub(crate) const PRIMITIVES: [(&str, PrimitiveFunction); 2] = [("sh", sh), ("pipeline", pipeline)];
+
+pub(crate) const NATIVES: [(&str, NativeFunction); 1] = [("sh-stream", sh_stream)];
+
+// Strings are passed as they are and anything else as it prints.
+fn text(obj: &Object) -> String {
+    match obj.without_meta() {
+        Object::String(string) => string.to_string(),
+        obj => obj.to_string(),
+    }
+}
+
+fn option<'a>(options: Option<&'a Object>, name: &str) -> Option<&'a Object> {
+    match options.map(Object::without_meta) {
+        Some(Object::Map(map)) => map
+            .get(&Object::Symbol(Symbol::new(name)))
+            .or_else(|| map.get(&Object::from(name))),
+        _ => None,
+    }
+}
+
+// {cwd: dir, env: {name: value}, stdin: text}, all optional. Variables in
+// env are added to the ones the process has.
+fn command(program: &Object, arguments: &[Object], options: Option<&Object>) -> Option<Command> {
+    let program = match program.without_meta() {
+        Object::String(program) => program,
+        _ => return None,
+    };
+    let mut command = Command::new(&**program);
+    command.args(arguments.iter().map(text));
+    if let Some(dir) = option(options, "cwd") {
+        command.current_dir(text(dir));
+    }
+    if let Some(Object::Map(vars)) = option(options, "env").map(Object::without_meta) {
+        command.envs(vars.iter().map(|(name, value)| (text(name), text(value))));
+    }
+    Some(command)
+}
+
+fn input(options: Option<&Object>) -> Option<String> {
+    option(options, "stdin").map(text)
+}
+
+// Writes the input from a thread of its own, so a process that fills its
+// output before reading all of it cannot block.
+fn feed(child: &mut Child, input: Option<String>) -> Option<JoinHandle<()>> {
+    let (mut stdin, input) = (child.stdin.take()?, input?);
+    Some(thread::spawn(move || {
+        let _ = stdin.write_all(input.as_bytes());
+    }))
+}
+
+fn collect(reader: Option<impl Read + Send + 'static>) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut reader) = reader {
+            let _ = reader.read_to_end(&mut bytes);
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    })
+}
+
+// The exit code, or null when the process was killed by a signal.
+fn exit(status: ExitStatus) -> Object {
+    status
+        .code()
+        .map_or(Object::Null, |code| Object::Integer(code as i64))
+}
+
+fn result(status: ExitStatus, stdout: String, stderr: String) -> Object {
+    let entries = [
+        ("exit", exit(status)),
+        ("stdout", Object::from(stdout)),
+        ("stderr", Object::from(stderr)),
+    ];
+    let map: HashMap<Object, Object> = entries
+        .iter()
+        .map(|(key, value)| (Object::Symbol(Symbol::new(key)), value.clone()))
+        .collect();
+    Object::Map(map)
+}
+
+fn arguments(obj: Option<&Object>) -> Option<&[Object]> {
+    match obj.map(Object::without_meta) {
+        Some(Object::Vector(arguments)) | Some(Object::List(arguments)) => Some(arguments),
+        Some(Object::Null) | None => Some(&[]),
+        _ => None,
+    }
+}
+
+// (sh program [arg...] options) runs a command to completion and gives
+// {exit, stdout, stderr}. Its stdin is empty unless options give one.
+fn sh(obj: &Object) -> Object {
+    run(obj).unwrap_or(Object::Null)
+}
+
+fn run(obj: &Object) -> Option<Object> {
+    let args = args(obj);
+    let options = args.get(2);
+    let mut command = command(args.first()?, arguments(args.get(1))?, options)?;
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let feeding = feed(&mut child, input(options));
+    drop(child.stdin.take());
+    let output = child.wait_with_output().ok()?;
+    if let Some(feeding) = feeding {
+        let _ = feeding.join();
+    }
+    Some(result(
+        output.status,
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    ))
+}
+
+// (sh-stream program [arg...] f options) calls f with each line the command
+// writes to stdout, as it is written, and gives the exit code. Without f
+// the command writes to the terminal directly. Either way it shares stdin
+// and stderr with the interpreter, so the stdin option is ignored. Should
+// f fail, the command is killed.
+fn sh_stream(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let args = args(obj);
+    let command = match (args.first(), arguments(args.get(1))) {
+        (Some(program), Some(arguments)) => command(program, arguments, args.get(3)),
+        _ => None,
+    };
+    let mut command = match command {
+        Some(command) => command,
+        None => return Ok(Object::Null),
+    };
+    let callback = args.get(2).filter(|f| !matches!(f, Object::Null));
+    if callback.is_some() {
+        command.stdout(Stdio::piped());
+    }
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(_) => return Ok(Object::Null),
+    };
+    if let (Some(f), Some(stdout)) = (callback, child.stdout.take()) {
+        for line in BufReader::new(stdout).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if let Err(error) = interpreter.apply(&[f.clone(), Object::from(line)]) {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(error);
+            }
+        }
+    }
+    Ok(child.wait().map_or(Object::Null, exit))
+}
+
+// (pipeline [[program arg...] ...] options) connects the stdout of each
+// command to the stdin of the next and gives {exit, stdout, stderr}: the
+// exit code and stdout of the last command and the stderr of all of them.
+// The stdin option goes to the first command; cwd and env apply to all.
+fn pipeline(obj: &Object) -> Object {
+    let args = args(obj);
+    let stages = match args.first().map(Object::without_meta) {
+        Some(Object::Vector(stages)) | Some(Object::List(stages)) if !stages.is_empty() => stages,
+        _ => return Object::Null,
+    };
+    let options = args.get(1);
+    let mut children: Vec<Child> = Vec::new();
+    let mut errors = Vec::new();
+    let mut feeding = None;
+    for stage in stages {
+        let command = match stage.without_meta() {
+            Object::Vector(stage) | Object::List(stage) if !stage.is_empty() => {
+                command(&stage[0], &stage[1..], options)
+            }
+            _ => None,
+        };
+        let stdin = match children.last_mut().and_then(|child| child.stdout.take()) {
+            Some(previous) => Stdio::from(previous),
+            None => Stdio::piped(),
+        };
+        let spawned = command.and_then(|mut command| {
+            command
+                .stdin(stdin)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .ok()
+        });
+        let mut child = match spawned {
+            Some(child) => child,
+            None => {
+                for mut child in children {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+                return Object::Null;
+            }
+        };
+        if children.is_empty() {
+            feeding = feed(&mut child, input(options));
+            drop(child.stdin.take());
+        }
+        errors.push(collect(child.stderr.take()));
+        children.push(child);
+    }
+    let stdout = collect(children.last_mut().and_then(|child| child.stdout.take()));
+    let mut status = None;
+    for mut child in children {
+        status = child.wait().ok();
+    }
+    if let Some(feeding) = feeding {
+        let _ = feeding.join();
+    }
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr: String = errors
+        .into_iter()
+        .map(|error| error.join().unwrap_or_default())
+        .collect();
+    match status {
+        Some(status) => result(status, stdout, stderr),
+        None => Object::Null,
+    }
+}
@@ -0,0 +1,133 @@
+use crate::clock::Instant;
+use crate::object::Object;
+use crate::symbol::Symbol;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+const ANONYMOUS: &str = "<anonymous>";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FunctionProfile {
+    pub calls: u64,
+    pub inclusive: Duration,
+    pub exclusive: Duration,
+}
+
+// Functions are keyed by the symbol they were called through, or
+// "<anonymous>" when the callee was not a symbol. Stacks are keyed by the
+// semicolon-separated names from the outermost call of an evaluation.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    functions: HashMap<String, FunctionProfile>,
+    stacks: HashMap<String, Duration>,
+}
+
+impl Profile {
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    pub fn function(&self, name: &str) -> Option<&FunctionProfile> {
+        self.functions.get(name)
+    }
+
+    pub fn functions(&self) -> Vec<(&str, &FunctionProfile)> {
+        let mut functions: Vec<(&str, &FunctionProfile)> = self
+            .functions
+            .iter()
+            .map(|(name, profile)| (name.as_str(), profile))
+            .collect();
+        functions.sort_by(|a, b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(b.0)));
+        functions
+    }
+
+    pub fn to_object(&self) -> Object {
+        let micros = |duration: Duration| Object::Integer(duration.as_micros() as i64);
+        let rows = self
+            .functions()
+            .into_iter()
+            .map(|(name, profile)| {
+                let mut row = HashMap::new();
+                row.insert(Object::from("name"), Object::from(name));
+                row.insert(Object::from("calls"), Object::Integer(profile.calls as i64));
+                row.insert(Object::from("inclusive"), micros(profile.inclusive));
+                row.insert(Object::from("exclusive"), micros(profile.exclusive));
+                Object::Map(row)
+            })
+            .collect();
+        Object::Vector(rows)
+    }
+
+    // One line per distinct stack with its exclusive time in microseconds,
+    // the input format of flamegraph.pl and inferno.
+    pub fn folded(&self) -> String {
+        let mut stacks: Vec<(&String, &Duration)> = self.stacks.iter().collect();
+        stacks.sort();
+        let mut output = String::new();
+        for (stack, duration) in stacks {
+            writeln!(output, "{} {}", stack, duration.as_micros()).unwrap();
+        }
+        output
+    }
+}
+
+struct Entry {
+    callee: Option<Symbol>,
+    start: Instant,
+    children: Duration,
+}
+
+#[derive(Default)]
+pub(crate) struct CallStack {
+    entries: RefCell<Vec<Entry>>,
+}
+
+fn name(callee: Option<Symbol>) -> &'static str {
+    callee.map_or(ANONYMOUS, |symbol| symbol.as_str())
+}
+
+impl CallStack {
+    pub(crate) fn enter(&self, callee: Option<Symbol>) {
+        self.entries.borrow_mut().push(Entry {
+            callee,
+            start: Instant::now(),
+            children: Duration::ZERO,
+        });
+    }
+
+    pub(crate) fn exit(&self, profile: &Mutex<Profile>) {
+        let mut entries = self.entries.borrow_mut();
+        let entry = match entries.pop() {
+            Some(entry) => entry,
+            None => return,
+        };
+        let elapsed = entry.start.elapsed();
+        let exclusive = elapsed.saturating_sub(entry.children);
+        if let Some(parent) = entries.last_mut() {
+            parent.children += elapsed;
+        }
+        // Only the outermost activation of a recursive function counts
+        // towards its inclusive time.
+        let recursive = entries.iter().any(|outer| outer.callee == entry.callee);
+        let mut stack = String::new();
+        for outer in entries.iter() {
+            stack.push_str(name(outer.callee));
+            stack.push(';');
+        }
+        stack.push_str(name(entry.callee));
+        let mut profile = profile.lock().unwrap_or_else(PoisonError::into_inner);
+        let function = profile
+            .functions
+            .entry(name(entry.callee).to_string())
+            .or_default();
+        function.calls += 1;
+        function.exclusive += exclusive;
+        if !recursive {
+            function.inclusive += elapsed;
+        }
+        *profile.stacks.entry(stack).or_default() += exclusive;
+    }
+}
@@ -0,0 +1,1067 @@
+use crate::evaluator::{args, truthy, PrimitiveFunction};
+use crate::object::Object;
+use crate::symbol::Symbol;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+pub(crate) const PRIMITIVES: [(&str, PrimitiveFunction); 1] = [("query", query)];
+
+// (query data ".users[] | select(.age > 30) | .name") runs a query over
+// data and gives a vector of everything it outputs, or null when the query
+// is malformed or fails on the data.
+fn query(obj: &Object) -> Object {
+    let args = args(obj);
+    let (data, text) = match args {
+        [data, text] => match text.without_meta() {
+            Object::String(text) => (data, text),
+            _ => return Object::Null,
+        },
+        _ => return Object::Null,
+    };
+    match text.parse::<Query>().and_then(|query| query.run(data)) {
+        Ok(outputs) => Object::Vector(outputs),
+        Err(_) => Object::Null,
+    }
+}
+
+#[derive(Debug)]
+pub struct QueryError {
+    message: String,
+}
+
+impl QueryError {
+    fn new(message: impl Into<String>) -> QueryError {
+        QueryError {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+// A compiled query in a small subset of jq. Paths are written .name,
+// ."any key", .[0], .[-1], .[1:3] and .[], .. is the value and everything
+// inside it, | passes each output of the left to the right and , outputs
+// both sides. There are literals, [...] and {name: query} constructors,
+// arithmetic, comparisons, and, or, // for a default when the left gives
+// only null or false, and the functions length, keys, values, has(key),
+// map(f), select(f), sort, sort_by(f), group_by(f), unique, reverse,
+// first, last, add, min, max, not, empty, tostring and tonumber.
+//
+// Map keys are read as the symbols the decoders make, or as strings, and
+// names may contain - as symbols do, so subtract with spaces around the -.
+// Integers stay integers, so 7 / 2 is 3 as elsewhere in the language.
+#[derive(Clone, Debug)]
+pub struct Query {
+    filter: Filter,
+}
+
+impl Query {
+    pub fn run(&self, input: &Object) -> Result<Vec<Object>, QueryError> {
+        let mut outputs = Vec::new();
+        self.filter.run(input, &mut outputs)?;
+        Ok(outputs)
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: lex(s)?,
+            position: 0,
+        };
+        let filter = parser.pipe()?;
+        match parser.peek() {
+            None => Ok(Query { filter }),
+            Some(token) => Err(QueryError::new(format!("unexpected {}", token))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Field(String),
+    Dot,
+    Recurse,
+    Name(String),
+    Literal(Object),
+    Punct(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Field(name) => write!(f, ".{}", name),
+            Token::Dot => write!(f, "."),
+            Token::Recurse => write!(f, ".."),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Literal(value) => write!(f, "{}", value),
+            Token::Punct(punct) => write!(f, "{}", punct),
+        }
+    }
+}
+
+// Longest first, so <= is not read as < then =.
+const PUNCTS: [&str; 23] = [
+    "==", "!=", "<=", ">=", "//", "|", ",", "(", ")", "[", "]", "{", "}", ":", ";", "+", "-", "*",
+    "/", "%", "<", ">", "?",
+];
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+fn lex(text: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        let name_end = |text: &str| text.find(|c| !is_name_char(c)).unwrap_or(text.len());
+        if let Some(after) = rest.strip_prefix("..") {
+            tokens.push(Token::Recurse);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('.') {
+            match after.chars().next() {
+                Some(c) if is_name_start(c) => {
+                    let end = name_end(after);
+                    tokens.push(Token::Field(after[..end].to_string()));
+                    rest = &after[end..];
+                }
+                _ => {
+                    tokens.push(Token::Dot);
+                    rest = after;
+                }
+            }
+        } else if is_name_start(c) {
+            let end = name_end(rest);
+            tokens.push(Token::Name(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            let number = &rest[..end];
+            let value = match number.parse() {
+                Ok(n) => Object::Integer(n),
+                Err(_) => match number.parse() {
+                    Ok(n) => Object::Float(n),
+                    Err(_) => return Err(QueryError::new(format!("bad number {}", number))),
+                },
+            };
+            tokens.push(Token::Literal(value));
+            rest = &rest[end..];
+        } else if c == '"' {
+            let (string, after) = lex_string(&rest[1..])?;
+            tokens.push(Token::Literal(Object::from(string)));
+            rest = after;
+        } else if let Some(punct) = PUNCTS.iter().find(|punct| rest.starts_with(**punct)) {
+            tokens.push(Token::Punct(punct));
+            rest = &rest[punct.len()..];
+        } else {
+            return Err(QueryError::new(format!("unexpected {}", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+// A string after its opening quote, with the usual backslash escapes, and
+// what follows its closing quote.
+fn lex_string(text: &str) -> Result<(String, &str), QueryError> {
+    let mut string = String::new();
+    let mut chars = text.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Ok((string, &text[index + 1..])),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('n') => string.push('\n'),
+                Some('t') => string.push('\t'),
+                Some('r') => string.push('\r'),
+                Some(c) => string.push(c),
+                None => break,
+            },
+            c => string.push(c),
+        }
+    }
+    Err(QueryError::new("unterminated string"))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug)]
+enum Filter {
+    Identity,
+    Recurse,
+    Literal(Object),
+    Index(Box<Filter>, Box<Filter>),
+    Slice(Box<Filter>, Option<Box<Filter>>, Option<Box<Filter>>),
+    Iterate(Box<Filter>),
+    Try(Box<Filter>),
+    Pipe(Box<Filter>, Box<Filter>),
+    Comma(Box<Filter>, Box<Filter>),
+    Binary(Op, Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Alternative(Box<Filter>, Box<Filter>),
+    Negate(Box<Filter>),
+    Array(Option<Box<Filter>>),
+    Map(Vec<(Filter, Filter)>),
+    Call(String, Vec<Filter>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        match self.peek() {
+            Some(Token::Punct(p)) if *p == punct => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn eat_name(&mut self, name: &str) -> bool {
+        match self.peek() {
+            Some(Token::Name(n)) if n == name => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), QueryError> {
+        match self.eat(punct) {
+            true => Ok(()),
+            false => Err(match self.peek() {
+                Some(token) => QueryError::new(format!("expected {} before {}", punct, token)),
+                None => QueryError::new(format!("expected {}", punct)),
+            }),
+        }
+    }
+
+    // From the loosest binding: |, then ",", then //, or, and, comparisons,
+    // + and -, and * / %.
+    fn pipe(&mut self) -> Result<Filter, QueryError> {
+        let mut filter = self.comma()?;
+        while self.eat("|") {
+            filter = Filter::Pipe(Box::new(filter), Box::new(self.comma()?));
+        }
+        Ok(filter)
+    }
+
+    fn comma(&mut self) -> Result<Filter, QueryError> {
+        let mut filter = self.alternative()?;
+        while self.eat(",") {
+            filter = Filter::Comma(Box::new(filter), Box::new(self.alternative()?));
+        }
+        Ok(filter)
+    }
+
+    fn alternative(&mut self) -> Result<Filter, QueryError> {
+        let mut filter = self.or()?;
+        while self.eat("//") {
+            filter = Filter::Alternative(Box::new(filter), Box::new(self.or()?));
+        }
+        Ok(filter)
+    }
+
+    fn or(&mut self) -> Result<Filter, QueryError> {
+        let mut filter = self.and()?;
+        while self.eat_name("or") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, QueryError> {
+        let mut filter = self.comparison()?;
+        while self.eat_name("and") {
+            filter = Filter::And(Box::new(filter), Box::new(self.comparison()?));
+        }
+        Ok(filter)
+    }
+
+    fn comparison(&mut self) -> Result<Filter, QueryError> {
+        let filter = self.sum()?;
+        let op = match self.peek() {
+            Some(Token::Punct("==")) => Op::Eq,
+            Some(Token::Punct("!=")) => Op::Ne,
+            Some(Token::Punct("<")) => Op::Lt,
+            Some(Token::Punct("<=")) => Op::Le,
+            Some(Token::Punct(">")) => Op::Gt,
+            Some(Token::Punct(">=")) => Op::Ge,
+            _ => return Ok(filter),
+        };
+        self.position += 1;
+        Ok(Filter::Binary(op, Box::new(filter), Box::new(self.sum()?)))
+    }
+
+    fn sum(&mut self) -> Result<Filter, QueryError> {
+        let mut filter = self.product()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("+")) => Op::Add,
+                Some(Token::Punct("-")) => Op::Sub,
+                _ => return Ok(filter),
+            };
+            self.position += 1;
+            filter = Filter::Binary(op, Box::new(filter), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Filter, QueryError> {
+        let mut filter = self.postfix()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("*")) => Op::Mul,
+                Some(Token::Punct("/")) => Op::Div,
+                Some(Token::Punct("%")) => Op::Rem,
+                _ => return Ok(filter),
+            };
+            self.position += 1;
+            filter = Filter::Binary(op, Box::new(filter), Box::new(self.postfix()?));
+        }
+    }
+
+    // A term followed by any number of .name, ."key", [...] and ?.
+    fn postfix(&mut self) -> Result<Filter, QueryError> {
+        let mut filter = self.term()?;
+        loop {
+            filter = match self.peek() {
+                Some(Token::Field(name)) => {
+                    let key = Filter::Literal(Object::from(name.as_str()));
+                    self.position += 1;
+                    Filter::Index(Box::new(filter), Box::new(key))
+                }
+                Some(Token::Dot) => match self.tokens.get(self.position + 1) {
+                    Some(Token::Literal(key @ Object::String(_))) => {
+                        let key = Filter::Literal(key.clone());
+                        self.position += 2;
+                        Filter::Index(Box::new(filter), Box::new(key))
+                    }
+                    Some(Token::Punct("[")) => {
+                        self.position += 1;
+                        continue;
+                    }
+                    _ => return Ok(filter),
+                },
+                Some(Token::Punct("[")) => {
+                    self.position += 1;
+                    self.brackets(filter)?
+                }
+                Some(Token::Punct("?")) => {
+                    self.position += 1;
+                    Filter::Try(Box::new(filter))
+                }
+                _ => return Ok(filter),
+            };
+        }
+    }
+
+    // What follows the [ after a term: ], an index or a slice.
+    fn brackets(&mut self, filter: Filter) -> Result<Filter, QueryError> {
+        let filter = Box::new(filter);
+        if self.eat("]") {
+            return Ok(Filter::Iterate(filter));
+        }
+        let start = match self.eat(":") {
+            true => None,
+            false => {
+                let start = Box::new(self.pipe()?);
+                if self.eat("]") {
+                    return Ok(Filter::Index(filter, start));
+                }
+                self.expect(":")?;
+                Some(start)
+            }
+        };
+        let end = match self.eat("]") {
+            true => return Ok(Filter::Slice(filter, start, None)),
+            false => Some(Box::new(self.pipe()?)),
+        };
+        self.expect("]")?;
+        Ok(Filter::Slice(filter, start, end))
+    }
+
+    fn term(&mut self) -> Result<Filter, QueryError> {
+        let token = match self.next() {
+            Some(token) => token,
+            None => return Err(QueryError::new("unexpected end of query")),
+        };
+        match token {
+            Token::Field(name) => Ok(Filter::Index(
+                Box::new(Filter::Identity),
+                Box::new(Filter::Literal(Object::from(name))),
+            )),
+            // A [ after it is left to postfix.
+            Token::Dot => match self.peek() {
+                Some(Token::Literal(key @ Object::String(_))) => {
+                    let key = Filter::Literal(key.clone());
+                    self.position += 1;
+                    Ok(Filter::Index(Box::new(Filter::Identity), Box::new(key)))
+                }
+                _ => Ok(Filter::Identity),
+            },
+            Token::Recurse => Ok(Filter::Recurse),
+            Token::Literal(value) => Ok(Filter::Literal(value)),
+            Token::Punct("(") => {
+                let filter = self.pipe()?;
+                self.expect(")")?;
+                Ok(filter)
+            }
+            Token::Punct("[") => match self.eat("]") {
+                true => Ok(Filter::Array(None)),
+                false => {
+                    let filter = self.pipe()?;
+                    self.expect("]")?;
+                    Ok(Filter::Array(Some(Box::new(filter))))
+                }
+            },
+            Token::Punct("{") => self.map(),
+            Token::Punct("-") => Ok(Filter::Negate(Box::new(self.postfix()?))),
+            Token::Name(name) => match name.as_str() {
+                "true" => Ok(Filter::Literal(Object::Bool(true))),
+                "false" => Ok(Filter::Literal(Object::Bool(false))),
+                "null" => Ok(Filter::Literal(Object::Null)),
+                _ => {
+                    let mut args = Vec::new();
+                    if self.eat("(") {
+                        args.push(self.pipe()?);
+                        while self.eat(";") {
+                            args.push(self.pipe()?);
+                        }
+                        self.expect(")")?;
+                    }
+                    check_call(&name, args.len())?;
+                    Ok(Filter::Call(name, args))
+                }
+            },
+            token => Err(QueryError::new(format!("unexpected {}", token))),
+        }
+    }
+
+    // The entries of {a: .x, "b c": .y, (.k): .v, d} after the {. A name
+    // alone takes the input's value under it.
+    fn map(&mut self) -> Result<Filter, QueryError> {
+        let mut entries = Vec::new();
+        if self.eat("}") {
+            return Ok(Filter::Map(entries));
+        }
+        loop {
+            let (key, shorthand) = match self.next() {
+                Some(Token::Name(name)) => {
+                    (Filter::Literal(Object::from(name.clone())), Some(name))
+                }
+                Some(Token::Literal(key @ Object::String(_))) => (Filter::Literal(key), None),
+                Some(Token::Punct("(")) => {
+                    let key = self.pipe()?;
+                    self.expect(")")?;
+                    (key, None)
+                }
+                Some(token) => return Err(QueryError::new(format!("unexpected {}", token))),
+                None => return Err(QueryError::new("unexpected end of query")),
+            };
+            let value = match (self.eat(":"), shorthand) {
+                (true, _) => self.alternative()?,
+                (false, Some(name)) => Filter::Index(
+                    Box::new(Filter::Identity),
+                    Box::new(Filter::Literal(Object::from(name))),
+                ),
+                (false, None) => self.expect(":").map(|_| Filter::Identity)?,
+            };
+            entries.push((key, value));
+            if self.eat("}") {
+                return Ok(Filter::Map(entries));
+            }
+            self.expect(",")?;
+        }
+    }
+}
+
+// The functions a query can call and how many arguments each takes.
+const FUNCTIONS: [(&str, usize); 21] = [
+    ("length", 0),
+    ("keys", 0),
+    ("values", 0),
+    ("has", 1),
+    ("map", 1),
+    ("select", 1),
+    ("sort", 0),
+    ("sort_by", 1),
+    ("group_by", 1),
+    ("unique", 0),
+    ("reverse", 0),
+    ("first", 0),
+    ("last", 0),
+    ("add", 0),
+    ("min", 0),
+    ("max", 0),
+    ("not", 0),
+    ("empty", 0),
+    ("tostring", 0),
+    ("tonumber", 0),
+    ("recurse", 0),
+];
+
+fn check_call(name: &str, arity: usize) -> Result<(), QueryError> {
+    match FUNCTIONS.iter().find(|(function, _)| *function == name) {
+        Some((_, expected)) if *expected == arity => Ok(()),
+        Some((_, expected)) => Err(QueryError::new(format!(
+            "{} takes {} arguments, not {}",
+            name, expected, arity
+        ))),
+        None => Err(QueryError::new(format!("unknown function {}", name))),
+    }
+}
+
+fn key(name: &str) -> Object {
+    Object::Symbol(Symbol::new(name))
+}
+
+// How a key prints in results such as keys gives: symbols as their names.
+fn key_string(key: &Object) -> Object {
+    match key.without_meta() {
+        Object::Symbol(name) => Object::from(name.as_str()),
+        key => key.clone(),
+    }
+}
+
+// A map's entries sorted by key, so iterating a map is deterministic.
+fn entries(map: &HashMap<Object, Object>) -> Vec<(&Object, &Object)> {
+    let mut entries: Vec<(&Object, &Object)> = map.iter().collect();
+    entries.sort_by(|a, b| order(&key_string(a.0), &key_string(b.0)));
+    entries
+}
+
+fn items(value: &Object) -> Result<Vec<Object>, QueryError> {
+    match value.without_meta() {
+        Object::Vector(items) | Object::List(items) => Ok(items.clone()),
+        Object::Map(map) => Ok(entries(map)
+            .into_iter()
+            .map(|(_, value)| value.clone())
+            .collect()),
+        value => Err(QueryError::new(format!("cannot iterate over {}", value))),
+    }
+}
+
+// Null, booleans, numbers, strings, symbols, vectors and lists, maps, then
+// anything else, with the same kinds compared by value.
+fn order(a: &Object, b: &Object) -> Ordering {
+    fn rank(value: &Object) -> u8 {
+        match value {
+            Object::Null => 0,
+            Object::Bool(_) => 1,
+            Object::Integer(_) | Object::Float(_) => 2,
+            Object::String(_) => 3,
+            Object::Symbol(_) => 4,
+            Object::Vector(_) | Object::List(_) => 5,
+            Object::Map(_) => 6,
+            Object::Other(_) => 7,
+        }
+    }
+    let (a, b) = (a.without_meta(), b.without_meta());
+    match (a, b) {
+        (Object::Bool(x), Object::Bool(y)) => x.cmp(y),
+        (Object::Integer(x), Object::Integer(y)) => x.cmp(y),
+        (Object::Integer(x), Object::Float(y)) => (*x as f64).total_cmp(y),
+        (Object::Float(x), Object::Integer(y)) => x.total_cmp(&(*y as f64)),
+        (Object::Float(x), Object::Float(y)) => x.total_cmp(y),
+        (Object::String(x), Object::String(y)) => x.cmp(y),
+        (Object::Symbol(x), Object::Symbol(y)) => x.as_str().cmp(y.as_str()),
+        (Object::Vector(x) | Object::List(x), Object::Vector(y) | Object::List(y)) => x
+            .iter()
+            .zip(y)
+            .map(|(x, y)| order(x, y))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| x.len().cmp(&y.len())),
+        (Object::Map(x), Object::Map(y)) => {
+            let (x, y) = (entries(x), entries(y));
+            x.iter()
+                .zip(&y)
+                .map(|(x, y)| order(x.0, y.0).then_with(|| order(x.1, y.1)))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| x.len().cmp(&y.len()))
+        }
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn equal(a: &Object, b: &Object) -> bool {
+    match (a.without_meta(), b.without_meta()) {
+        (Object::Integer(_), Object::Float(_)) | (Object::Float(_), Object::Integer(_)) => {
+            order(a, b).is_eq()
+        }
+        (a, b) => a == b,
+    }
+}
+
+fn index(value: &Object, key: &Object) -> Result<Object, QueryError> {
+    match (value.without_meta(), key.without_meta()) {
+        (Object::Null, _) => Ok(Object::Null),
+        (Object::Map(map), Object::String(name)) => Ok(map
+            .get(&self::key(name))
+            .or_else(|| map.get(key))
+            .cloned()
+            .unwrap_or(Object::Null)),
+        (Object::Map(map), key) => Ok(map.get(key).cloned().unwrap_or(Object::Null)),
+        (Object::Vector(items) | Object::List(items), Object::Integer(n)) => {
+            let n = match *n < 0 {
+                true => items.len() as i64 + n,
+                false => *n,
+            };
+            Ok(usize::try_from(n)
+                .ok()
+                .and_then(|n| items.get(n))
+                .cloned()
+                .unwrap_or(Object::Null))
+        }
+        (value, key) => Err(QueryError::new(format!(
+            "cannot index {} with {}",
+            value, key
+        ))),
+    }
+}
+
+fn slice(value: &Object, start: &Object, end: &Object) -> Result<Object, QueryError> {
+    let bound = |bound: &Object, len: usize, default: usize| match bound.without_meta() {
+        Object::Null => Ok(default),
+        Object::Integer(n) if *n < 0 => Ok(len.saturating_sub(n.unsigned_abs() as usize)),
+        Object::Integer(n) => Ok((*n as usize).min(len)),
+        bound => Err(QueryError::new(format!("cannot slice with {}", bound))),
+    };
+    match value.without_meta() {
+        Object::Null => Ok(Object::Null),
+        Object::Vector(items) | Object::List(items) => {
+            let start = bound(start, items.len(), 0)?;
+            let end = bound(end, items.len(), items.len())?.max(start);
+            Ok(Object::Vector(items[start..end].to_vec()))
+        }
+        Object::String(string) => {
+            let chars: Vec<char> = string.chars().collect();
+            let start = bound(start, chars.len(), 0)?;
+            let end = bound(end, chars.len(), chars.len())?.max(start);
+            Ok(Object::from(chars[start..end].iter().collect::<String>()))
+        }
+        value => Err(QueryError::new(format!("cannot slice {}", value))),
+    }
+}
+
+fn arithmetic(op: Op, a: &Object, b: &Object) -> Result<Object, QueryError> {
+    let result = match (op, a.without_meta(), b.without_meta()) {
+        (Op::Add, Object::Null, b) => Some(b.clone()),
+        (Op::Add, a, Object::Null) => Some(a.clone()),
+        (op, Object::Integer(x), Object::Integer(y)) => match op {
+            Op::Add => Some(Object::Integer(x.wrapping_add(*y))),
+            Op::Sub => Some(Object::Integer(x.wrapping_sub(*y))),
+            Op::Mul => Some(Object::Integer(x.wrapping_mul(*y))),
+            Op::Div => x.checked_div(*y).map(Object::Integer),
+            _ => x.checked_rem(*y).map(Object::Integer),
+        },
+        (op, Object::Integer(_) | Object::Float(_), Object::Integer(_) | Object::Float(_)) => {
+            let float = |n: &Object| match n.without_meta() {
+                Object::Integer(n) => *n as f64,
+                Object::Float(n) => *n,
+                _ => 0.0,
+            };
+            let (x, y) = (float(a), float(b));
+            Some(Object::Float(match op {
+                Op::Add => x + y,
+                Op::Sub => x - y,
+                Op::Mul => x * y,
+                Op::Div => x / y,
+                _ => x % y,
+            }))
+        }
+        (Op::Add, Object::String(x), Object::String(y)) => {
+            Some(Object::from(format!("{}{}", x, y)))
+        }
+        (Op::Add, Object::Vector(x) | Object::List(x), Object::Vector(y) | Object::List(y)) => {
+            Some(Object::Vector(x.iter().chain(y).cloned().collect()))
+        }
+        (Op::Sub, Object::Vector(x) | Object::List(x), Object::Vector(y) | Object::List(y)) => {
+            Some(Object::Vector(
+                x.iter()
+                    .filter(|x| !y.iter().any(|y| equal(x, y)))
+                    .cloned()
+                    .collect(),
+            ))
+        }
+        (Op::Add, Object::Map(x), Object::Map(y)) => {
+            let mut map = x.clone();
+            map.extend(y.iter().map(|(key, value)| (key.clone(), value.clone())));
+            Some(Object::Map(map))
+        }
+        _ => None,
+    };
+    result.ok_or_else(|| QueryError::new(format!("cannot combine {} and {}", a, b)))
+}
+
+fn compare(op: Op, a: &Object, b: &Object) -> bool {
+    match op {
+        Op::Eq => equal(a, b),
+        Op::Ne => !equal(a, b),
+        Op::Lt => order(a, b).is_lt(),
+        Op::Le => order(a, b).is_le(),
+        Op::Gt => order(a, b).is_gt(),
+        _ => order(a, b).is_ge(),
+    }
+}
+
+fn recurse(value: &Object, outputs: &mut Vec<Object>) {
+    outputs.push(value.clone());
+    if let Ok(items) = items(value) {
+        for item in &items {
+            recurse(item, outputs);
+        }
+    }
+}
+
+impl Filter {
+    // Every output of the filter for one input.
+    fn outputs(&self, input: &Object) -> Result<Vec<Object>, QueryError> {
+        let mut outputs = Vec::new();
+        self.run(input, &mut outputs)?;
+        Ok(outputs)
+    }
+
+    fn run(&self, input: &Object, outputs: &mut Vec<Object>) -> Result<(), QueryError> {
+        match self {
+            Filter::Identity => outputs.push(input.clone()),
+            Filter::Recurse => recurse(input, outputs),
+            Filter::Literal(value) => outputs.push(value.clone()),
+            Filter::Index(target, key) => {
+                for value in target.outputs(input)? {
+                    for key in key.outputs(input)? {
+                        outputs.push(index(&value, &key)?);
+                    }
+                }
+            }
+            Filter::Slice(target, start, end) => {
+                let bound = |bound: &Option<Box<Filter>>| match bound {
+                    Some(bound) => bound.outputs(input),
+                    None => Ok(vec![Object::Null]),
+                };
+                for value in target.outputs(input)? {
+                    for start in bound(start)? {
+                        for end in bound(end)? {
+                            outputs.push(slice(&value, &start, &end)?);
+                        }
+                    }
+                }
+            }
+            Filter::Iterate(target) => {
+                for value in target.outputs(input)? {
+                    outputs.extend(items(&value)?);
+                }
+            }
+            // Keeps what came out before a failure and drops the failure.
+            Filter::Try(filter) => {
+                let mut values = Vec::new();
+                let _ = filter.run(input, &mut values);
+                outputs.extend(values);
+            }
+            Filter::Pipe(left, right) => {
+                for value in left.outputs(input)? {
+                    right.run(&value, outputs)?;
+                }
+            }
+            Filter::Comma(left, right) => {
+                left.run(input, outputs)?;
+                right.run(input, outputs)?;
+            }
+            Filter::Binary(op, left, right) => {
+                for b in right.outputs(input)? {
+                    for a in left.outputs(input)? {
+                        outputs.push(match op {
+                            Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Rem => {
+                                arithmetic(*op, &a, &b)?
+                            }
+                            _ => Object::Bool(compare(*op, &a, &b)),
+                        });
+                    }
+                }
+            }
+            Filter::And(left, right) => {
+                for a in left.outputs(input)? {
+                    match truthy(&a) {
+                        true => outputs.extend(
+                            right
+                                .outputs(input)?
+                                .iter()
+                                .map(|b| Object::Bool(truthy(b))),
+                        ),
+                        false => outputs.push(Object::Bool(false)),
+                    }
+                }
+            }
+            Filter::Or(left, right) => {
+                for a in left.outputs(input)? {
+                    match truthy(&a) {
+                        true => outputs.push(Object::Bool(true)),
+                        false => outputs.extend(
+                            right
+                                .outputs(input)?
+                                .iter()
+                                .map(|b| Object::Bool(truthy(b))),
+                        ),
+                    }
+                }
+            }
+            Filter::Alternative(left, right) => {
+                let mut values = Vec::new();
+                let _ = left.run(input, &mut values);
+                values.retain(truthy);
+                match values.is_empty() {
+                    true => right.run(input, outputs)?,
+                    false => outputs.extend(values),
+                }
+            }
+            Filter::Negate(filter) => {
+                for value in filter.outputs(input)? {
+                    outputs.push(arithmetic(Op::Sub, &Object::Integer(0), &value)?);
+                }
+            }
+            Filter::Array(filter) => outputs.push(Object::Vector(match filter {
+                Some(filter) => filter.outputs(input)?,
+                None => Vec::new(),
+            })),
+            // One map for every combination of the entries' outputs.
+            Filter::Map(entries) => {
+                let mut maps = vec![HashMap::new()];
+                for (key, value) in entries {
+                    let keys = key.outputs(input)?;
+                    let values = value.outputs(input)?;
+                    let mut combined = Vec::new();
+                    for map in &maps {
+                        for key in &keys {
+                            let key = match key.without_meta() {
+                                Object::String(name) => self::key(name),
+                                key => key.clone(),
+                            };
+                            for value in &values {
+                                let mut map = map.clone();
+                                map.insert(key.clone(), value.clone());
+                                combined.push(map);
+                            }
+                        }
+                    }
+                    maps = combined;
+                }
+                outputs.extend(maps.into_iter().map(Object::Map));
+            }
+            Filter::Call(name, args) => call(name, args, input, outputs)?,
+        }
+        Ok(())
+    }
+}
+
+// The items of the input sorted by what f gives for each.
+fn sorted_by(input: &Object, f: &Filter) -> Result<Vec<(Vec<Object>, Object)>, QueryError> {
+    let mut keyed = Vec::new();
+    for item in items(input)? {
+        keyed.push((f.outputs(&item)?, item));
+    }
+    keyed.sort_by(|a, b| {
+        a.0.iter()
+            .zip(&b.0)
+            .map(|(x, y)| order(x, y))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.0.len().cmp(&b.0.len()))
+    });
+    Ok(keyed)
+}
+
+fn call(
+    name: &str,
+    args: &[Filter],
+    input: &Object,
+    outputs: &mut Vec<Object>,
+) -> Result<(), QueryError> {
+    let output = match name {
+        "empty" => return Ok(()),
+        "recurse" => {
+            recurse(input, outputs);
+            return Ok(());
+        }
+        "select" => {
+            for value in args[0].outputs(input)? {
+                if truthy(&value) {
+                    outputs.push(input.clone());
+                }
+            }
+            return Ok(());
+        }
+        "map" => {
+            let mut values = Vec::new();
+            for item in items(input)? {
+                args[0].run(&item, &mut values)?;
+            }
+            Object::Vector(values)
+        }
+        "has" => {
+            for key in args[0].outputs(input)? {
+                let found = match (input.without_meta(), key.without_meta()) {
+                    (Object::Map(map), Object::String(name)) => {
+                        map.contains_key(&self::key(name)) || map.contains_key(&key)
+                    }
+                    (Object::Map(map), _) => map.contains_key(&key),
+                    (Object::Vector(items) | Object::List(items), Object::Integer(n)) => {
+                        *n >= 0 && (*n as usize) < items.len()
+                    }
+                    _ => {
+                        return Err(QueryError::new(format!(
+                            "cannot check {} for {}",
+                            input, key
+                        )))
+                    }
+                };
+                outputs.push(Object::Bool(found));
+            }
+            return Ok(());
+        }
+        "length" => match input.without_meta() {
+            Object::Null => Object::Integer(0),
+            Object::String(string) => Object::Integer(string.chars().count() as i64),
+            Object::Vector(items) | Object::List(items) => Object::Integer(items.len() as i64),
+            Object::Map(map) => Object::Integer(map.len() as i64),
+            Object::Integer(n) => Object::Integer(n.wrapping_abs()),
+            Object::Float(n) => Object::Float(n.abs()),
+            value => return Err(QueryError::new(format!("{} has no length", value))),
+        },
+        "keys" => match input.without_meta() {
+            Object::Map(map) => Object::Vector(
+                entries(map)
+                    .into_iter()
+                    .map(|(key, _)| key_string(key))
+                    .collect(),
+            ),
+            Object::Vector(items) | Object::List(items) => {
+                Object::Vector((0..items.len() as i64).map(Object::Integer).collect())
+            }
+            value => return Err(QueryError::new(format!("{} has no keys", value))),
+        },
+        "values" => Object::Vector(items(input)?),
+        "sort" => {
+            let mut items = items(input)?;
+            items.sort_by(order);
+            Object::Vector(items)
+        }
+        "sort_by" => Object::Vector(
+            sorted_by(input, &args[0])?
+                .into_iter()
+                .map(|(_, item)| item)
+                .collect(),
+        ),
+        "group_by" => {
+            let mut groups: Vec<(Vec<Object>, Vec<Object>)> = Vec::new();
+            for (key, item) in sorted_by(input, &args[0])? {
+                match groups.last_mut() {
+                    Some((last, group))
+                        if last.len() == key.len()
+                            && last.iter().zip(&key).all(|(x, y)| equal(x, y)) =>
+                    {
+                        group.push(item)
+                    }
+                    _ => groups.push((key, vec![item])),
+                }
+            }
+            Object::Vector(
+                groups
+                    .into_iter()
+                    .map(|(_, group)| Object::Vector(group))
+                    .collect(),
+            )
+        }
+        "unique" => {
+            let mut items = items(input)?;
+            items.sort_by(order);
+            items.dedup_by(|a, b| equal(a, b));
+            Object::Vector(items)
+        }
+        "reverse" => match input.without_meta() {
+            Object::String(string) => Object::from(string.chars().rev().collect::<String>()),
+            _ => Object::Vector(items(input)?.into_iter().rev().collect()),
+        },
+        "first" => items(input)?.into_iter().next().unwrap_or(Object::Null),
+        "last" => items(input)?.pop().unwrap_or(Object::Null),
+        "add" => {
+            let mut sum = Object::Null;
+            for item in items(input)? {
+                sum = arithmetic(Op::Add, &sum, &item)?;
+            }
+            sum
+        }
+        "min" => items(input)?
+            .into_iter()
+            .min_by(order)
+            .unwrap_or(Object::Null),
+        "max" => items(input)?
+            .into_iter()
+            .max_by(order)
+            .unwrap_or(Object::Null),
+        "not" => Object::Bool(!truthy(input)),
+        "tostring" => match input.without_meta() {
+            Object::String(_) => input.clone(),
+            value => Object::from(value.to_string()),
+        },
+        _ => match input.without_meta() {
+            Object::Integer(_) | Object::Float(_) => input.clone(),
+            Object::String(string) => match string.parse() {
+                Ok(n) => Object::Integer(n),
+                Err(_) => match string.parse() {
+                    Ok(n) => Object::Float(n),
+                    Err(_) => return Err(QueryError::new(format!("cannot parse {}", string))),
+                },
+            },
+            value => return Err(QueryError::new(format!("cannot parse {}", value))),
+        },
+    };
+    outputs.push(output);
+    Ok(())
+}
@@ -0,0 +1,422 @@
+use crate::diff::path_string;
+use crate::evaluator::{args, PrimitiveFunction};
+use crate::object::{Bytes, Object, ParseObjectError};
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+pub(crate) const PRIMITIVES: [(&str, PrimitiveFunction); 1] = [("validate", validate)];
+
+// (validate schema value) gives the ways value does not match schema, as
+// maps such as {path: [port], message: "expected int, got \"80\""}, empty
+// when it matches, or null when the schema is malformed.
+fn validate(obj: &Object) -> Object {
+    let (schema, value) = match args(obj) {
+        [schema, value] => (schema, value),
+        _ => return Object::Null,
+    };
+    let schema = match Schema::new(schema) {
+        Ok(schema) => schema,
+        Err(_) => return Object::Null,
+    };
+    let errors = match schema.validate(value) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors,
+    };
+    Object::Vector(errors.into_iter().map(Object::from).collect())
+}
+
+// A schema that is not one, with the part of it that is wrong.
+#[derive(Debug)]
+pub struct SchemaError {
+    pub form: Object,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not a schema: {}", self.form)
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+// Where a value does not match and how.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationError {
+    pub path: Vec<Object>,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", path_string(&self.path), self.message)
+    }
+}
+
+impl From<ValidationError> for Object {
+    fn from(error: ValidationError) -> Self {
+        Object::Map(HashMap::from([
+            (key("path"), Object::Vector(error.path)),
+            (key("message"), Object::from(error.message)),
+        ]))
+    }
+}
+
+fn key(name: &str) -> Object {
+    Object::Symbol(Symbol::new(name))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Any,
+    Null,
+    Bool,
+    Int,
+    Float,
+    Number,
+    String,
+    Symbol,
+    Vector,
+    List,
+    Map,
+    Bytes,
+}
+
+const KINDS: [(&str, Kind); 12] = [
+    ("any", Kind::Any),
+    ("null", Kind::Null),
+    ("bool", Kind::Bool),
+    ("int", Kind::Int),
+    ("float", Kind::Float),
+    ("number", Kind::Number),
+    ("string", Kind::String),
+    ("symbol", Kind::Symbol),
+    ("vector", Kind::Vector),
+    ("list", Kind::List),
+    ("map", Kind::Map),
+    ("bytes", Kind::Bytes),
+];
+
+impl Kind {
+    fn name(self) -> &'static str {
+        KINDS
+            .iter()
+            .find(|(_, kind)| *kind == self)
+            .map_or("any", |(name, _)| name)
+    }
+
+    fn matches(self, value: &Object) -> bool {
+        match (self, value) {
+            (Kind::Any, _)
+            | (Kind::Null, Object::Null)
+            | (Kind::Bool, Object::Bool(_))
+            | (Kind::Int, Object::Integer(_))
+            | (Kind::Float, Object::Float(_))
+            | (Kind::Number, Object::Integer(_) | Object::Float(_))
+            | (Kind::String, Object::String(_))
+            | (Kind::Symbol, Object::Symbol(_))
+            | (Kind::Vector, Object::Vector(_))
+            | (Kind::List, Object::List(_))
+            | (Kind::Map, Object::Map(_)) => true,
+            (Kind::Bytes, Object::Other(other)) => other.downcast_ref::<Bytes>().is_some(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Kind(Kind),
+    Literal(Object),
+    Items(Box<Node>),
+    Entries(Vec<(Object, Node, bool)>),
+    Range(Option<f64>, Option<f64>),
+    Length(Option<usize>, Option<usize>),
+    OneOf(Vec<Object>),
+    And(Vec<Node>),
+    Or(Vec<Node>),
+}
+
+// A description of the values a config or other data may hold, written as
+// data:
+//
+//     {port: (int (range 1 65535)), hosts: [string], mode: (one-of fast safe),
+//      timeout: (optional number), tls: (or null {cert: string, key: string})}
+//
+// A type name matches values of that type: any, null, bool, int, float,
+// number, string, symbol, vector, list, map or bytes. [s] is a vector of
+// items each matching s and {k: s} a map whose key k matches s; other keys
+// are allowed, and (optional s) makes a key one that may be left out. (t
+// c...) is type t with constraints c: (range lo hi) for numbers and
+// (length lo hi) for strings, vectors and maps, with null for a bound
+// that is open. (one-of v...) matches those values, (and s...) every
+// schema and (or s...) any of them. Anything else matches itself.
+#[derive(Clone, Debug)]
+pub struct Schema {
+    root: Node,
+}
+
+impl Schema {
+    pub fn new(schema: &Object) -> Result<Schema, SchemaError> {
+        Ok(Schema {
+            root: compile(schema)?,
+        })
+    }
+
+    // Every mismatch, in the order the value is walked, or Ok when there
+    // are none.
+    pub fn validate(&self, value: &Object) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        check(&self.root, value, &mut Vec::new(), &mut errors);
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+}
+
+// Parsing errors are reported as schema errors with the text that failed.
+impl FromStr for Schema {
+    type Err = SchemaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let schema = s.parse().map_err(|_: ParseObjectError| SchemaError {
+            form: Object::from(s),
+        })?;
+        Schema::new(&schema)
+    }
+}
+
+fn bound<T>(bound: &Object, f: impl Fn(&Object) -> Option<T>) -> Result<Option<T>, ()> {
+    match bound.without_meta() {
+        Object::Null => Ok(None),
+        bound => f(bound).map(Some).ok_or(()),
+    }
+}
+
+fn number(value: &Object) -> Option<f64> {
+    match value.without_meta() {
+        Object::Integer(n) => Some(*n as f64),
+        Object::Float(n) => Some(*n),
+        _ => None,
+    }
+}
+
+fn compile(schema: &Object) -> Result<Node, SchemaError> {
+    let invalid = || SchemaError {
+        form: schema.clone(),
+    };
+    match schema.without_meta() {
+        Object::Symbol(name) => match KINDS.iter().find(|(kind, _)| *kind == name.as_str()) {
+            Some((_, kind)) => Ok(Node::Kind(*kind)),
+            None => Ok(Node::Literal(schema.clone())),
+        },
+        Object::Vector(items) => match items.as_slice() {
+            [] => Ok(Node::Kind(Kind::Vector)),
+            [item] => Ok(Node::Items(Box::new(compile(item)?))),
+            _ => Err(invalid()),
+        },
+        Object::Map(map) => {
+            let mut entries = Vec::new();
+            for (key, value) in map {
+                let (value, optional) = match value.without_meta() {
+                    Object::List(list)
+                        if list.len() == 2
+                            && list[0] == Object::Symbol(Symbol::new("optional")) =>
+                    {
+                        (&list[1], true)
+                    }
+                    _ => (value, false),
+                };
+                entries.push((key.clone(), compile(value)?, optional));
+            }
+            entries.sort_by_key(|(key, _, _)| key.to_string());
+            Ok(Node::Entries(entries))
+        }
+        Object::List(list) => {
+            let (head, rest) = match list.split_first() {
+                Some((Object::Symbol(head), rest)) => (head.as_str(), rest),
+                _ => return Err(invalid()),
+            };
+            match (head, rest) {
+                ("range", [lo, hi]) => Ok(Node::Range(
+                    bound(lo, number).map_err(|_| invalid())?,
+                    bound(hi, number).map_err(|_| invalid())?,
+                )),
+                ("length", [lo, hi]) => {
+                    let size = |n: &Object| match n {
+                        Object::Integer(n) => usize::try_from(*n).ok(),
+                        _ => None,
+                    };
+                    Ok(Node::Length(
+                        bound(lo, size).map_err(|_| invalid())?,
+                        bound(hi, size).map_err(|_| invalid())?,
+                    ))
+                }
+                ("one-of", values) => Ok(Node::OneOf(values.to_vec())),
+                ("and", schemas) => Ok(Node::And(
+                    schemas.iter().map(compile).collect::<Result<_, _>>()?,
+                )),
+                ("or", schemas) => Ok(Node::Or(
+                    schemas.iter().map(compile).collect::<Result<_, _>>()?,
+                )),
+                (name, constraints) => match KINDS.iter().find(|(kind, _)| *kind == name) {
+                    Some((_, kind)) => {
+                        let mut nodes = vec![Node::Kind(*kind)];
+                        for constraint in constraints {
+                            nodes.push(compile(constraint)?);
+                        }
+                        Ok(Node::And(nodes))
+                    }
+                    None => Err(invalid()),
+                },
+            }
+        }
+        _ => Ok(Node::Literal(schema.clone())),
+    }
+}
+
+// What a schema expects, for messages.
+fn describe(node: &Node) -> String {
+    let bounds = |lo: String, hi: String| match (lo.is_empty(), hi.is_empty()) {
+        (true, true) => "any".to_string(),
+        (false, true) => format!("at least {}", lo),
+        (true, false) => format!("at most {}", hi),
+        (false, false) => format!("from {} to {}", lo, hi),
+    };
+    let show = |bound: Option<String>| bound.unwrap_or_default();
+    match node {
+        Node::Kind(kind) => kind.name().to_string(),
+        Node::Literal(value) => value.to_string(),
+        Node::Items(item) => format!("[{}]", describe(item)),
+        Node::Entries(_) => "map".to_string(),
+        Node::Range(lo, hi) => format!(
+            "a number {}",
+            bounds(
+                show(lo.map(|n| n.to_string())),
+                show(hi.map(|n| n.to_string()))
+            )
+        ),
+        Node::Length(lo, hi) => format!(
+            "a length {}",
+            bounds(
+                show(lo.map(|n| n.to_string())),
+                show(hi.map(|n| n.to_string()))
+            )
+        ),
+        Node::OneOf(values) => format!(
+            "one of {}",
+            values
+                .iter()
+                .map(Object::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Node::And(nodes) => nodes.iter().map(describe).collect::<Vec<_>>().join(" and "),
+        Node::Or(nodes) => nodes.iter().map(describe).collect::<Vec<_>>().join(" or "),
+    }
+}
+
+fn check(node: &Node, value: &Object, path: &mut Vec<Object>, errors: &mut Vec<ValidationError>) {
+    let value = value.without_meta();
+    let mismatch = |errors: &mut Vec<ValidationError>, path: &[Object]| {
+        errors.push(ValidationError {
+            path: path.to_vec(),
+            message: format!("expected {}, got {}", describe(node), value),
+        })
+    };
+    match node {
+        Node::Kind(kind) => {
+            if !kind.matches(value) {
+                mismatch(errors, path);
+            }
+        }
+        Node::Literal(expected) => {
+            if expected.without_meta() != value {
+                mismatch(errors, path);
+            }
+        }
+        Node::Items(item) => match value {
+            Object::Vector(items) => {
+                for (index, value) in items.iter().enumerate() {
+                    path.push(Object::Integer(index as i64));
+                    check(item, value, path, errors);
+                    path.pop();
+                }
+            }
+            _ => mismatch(errors, path),
+        },
+        Node::Entries(entries) => match value {
+            Object::Map(map) => {
+                for (key, node, optional) in entries {
+                    let found = match key.without_meta() {
+                        Object::Symbol(name) => map
+                            .get(key)
+                            .or_else(|| map.get(&Object::from(name.as_str()))),
+                        _ => map.get(key),
+                    };
+                    path.push(key.clone());
+                    match found {
+                        Some(value) => check(node, value, path, errors),
+                        None if *optional => {}
+                        None => errors.push(ValidationError {
+                            path: path.clone(),
+                            message: format!("missing, expected {}", describe(node)),
+                        }),
+                    }
+                    path.pop();
+                }
+            }
+            _ => mismatch(errors, path),
+        },
+        Node::Range(lo, hi) => match number(value) {
+            Some(n) if lo.is_none_or(|lo| n >= lo) && hi.is_none_or(|hi| n <= hi) => {}
+            _ => mismatch(errors, path),
+        },
+        Node::Length(lo, hi) => {
+            let len = match value {
+                Object::String(string) => Some(string.chars().count()),
+                Object::Vector(items) | Object::List(items) => Some(items.len()),
+                Object::Map(map) => Some(map.len()),
+                _ => None,
+            };
+            match len {
+                Some(n) if lo.is_none_or(|lo| n >= lo) && hi.is_none_or(|hi| n <= hi) => {}
+                _ => mismatch(errors, path),
+            }
+        }
+        Node::OneOf(values) => {
+            if !values
+                .iter()
+                .any(|expected| expected.without_meta() == value)
+            {
+                mismatch(errors, path);
+            }
+        }
+        // Stops at the first that fails, so a value of the wrong type is
+        // not also reported as out of range.
+        Node::And(nodes) => {
+            for node in nodes {
+                let before = errors.len();
+                check(node, value, path, errors);
+                if errors.len() > before {
+                    break;
+                }
+            }
+        }
+        // When nothing matches, reports the whole alternative rather than
+        // each part of it.
+        Node::Or(nodes) => {
+            let matched = nodes.iter().any(|node| {
+                let mut errors = Vec::new();
+                check(node, value, &mut path.clone(), &mut errors);
+                errors.is_empty()
+            });
+            if !matched {
+                mismatch(errors, path);
+            }
+        }
+    }
+}
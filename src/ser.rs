@@ -0,0 +1,384 @@
+use crate::object::Object;
+use crate::symbol::Symbol;
+use serde::ser::{self, Serialize};
+use std::collections::HashMap;
+#[cfg(feature = "bigint")]
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::result::Result;
+
+impl Serialize for Object {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Object::Null => serializer.serialize_unit(),
+            Object::Bool(b) => serializer.serialize_bool(*b),
+            Object::Integer(n) => serializer.serialize_i64(*n),
+            Object::Float(n) => serializer.serialize_f64(*n),
+            Object::String(s) => serializer.serialize_str(s),
+            Object::Symbol(s) => serializer.serialize_str(s.as_str()),
+            Object::List(list) => serializer.collect_seq(list),
+            Object::Vector(vector) => serializer.collect_seq(vector),
+            Object::Map(map) => serializer.collect_map(map),
+            // Big integers go as i128 when they fit, since serde has no
+            // larger integers.
+            #[cfg(feature = "bigint")]
+            Object::Other(other) if other.is::<num_bigint::BigInt>() => {
+                let n = other.downcast_ref::<num_bigint::BigInt>().unwrap();
+                match i128::try_from(n) {
+                    Ok(n) => serializer.serialize_i128(n),
+                    Err(_) => Err(ser::Error::custom("integer too large to serialize")),
+                }
+            }
+            Object::Other(_) => match self.meta() {
+                Some(_) => self.without_meta().serialize(serializer),
+                None => Err(ser::Error::custom("cannot serialize a foreign object")),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SerializeObjectError(String);
+
+impl fmt::Display for SerializeObjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for SerializeObjectError {}
+
+impl ser::Error for SerializeObjectError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerializeObjectError(msg.to_string())
+    }
+}
+
+pub fn to_object<T: Serialize + ?Sized>(value: &T) -> Result<Object, SerializeObjectError> {
+    value.serialize(ObjectSerializer)
+}
+
+fn key_object(key: Object) -> Object {
+    match key {
+        Object::String(s) => Symbol::try_new(&s).map_or(Object::String(s), Object::Symbol),
+        _ => key,
+    }
+}
+
+fn variant_map(variant: &str, value: Object) -> Object {
+    let mut map = HashMap::new();
+    map.insert(Object::Symbol(Symbol::new(variant)), value);
+    Object::Map(map)
+}
+
+struct ObjectSerializer;
+
+impl ser::Serializer for ObjectSerializer {
+    type Ok = Object;
+    type Error = SerializeObjectError;
+    type SerializeSeq = SerializeVector;
+    type SerializeTuple = SerializeVector;
+    type SerializeTupleStruct = SerializeVector;
+    type SerializeTupleVariant = SerializeVariant<SerializeVector>;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeVariant<SerializeMap>;
+
+    fn serialize_bool(self, v: bool) -> Result<Object, Self::Error> {
+        Ok(Object::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Object, Self::Error> {
+        Ok(Object::Integer(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Object, Self::Error> {
+        Ok(Object::Integer(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Object, Self::Error> {
+        Ok(Object::Integer(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Object, Self::Error> {
+        Ok(Object::Integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Object, Self::Error> {
+        Ok(Object::Integer(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Object, Self::Error> {
+        Ok(Object::Integer(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Object, Self::Error> {
+        Ok(Object::Integer(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Object, Self::Error> {
+        if v > i64::MAX as u64 {
+            return Err(ser::Error::custom("integer out of range"));
+        }
+        Ok(Object::Integer(v as i64))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Object, Self::Error> {
+        Ok(Object::Float(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Object, Self::Error> {
+        Ok(Object::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Object, Self::Error> {
+        Ok(Object::String(v.to_string().into()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Object, Self::Error> {
+        Ok(Object::String(v.into()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Object, Self::Error> {
+        Ok(Object::Vector(
+            v.iter()
+                .map(|byte| Object::Integer((*byte).into()))
+                .collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Object, Self::Error> {
+        Ok(Object::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Object, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Object, Self::Error> {
+        Ok(Object::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Object, Self::Error> {
+        Ok(Object::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Object, Self::Error> {
+        Ok(Object::String(variant.into()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Object, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Object, Self::Error> {
+        Ok(variant_map(variant, to_object(value)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SerializeVector {
+            vector: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(SerializeMap {
+            map: HashMap::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SerializeVector {
+    vector: Vec<Object>,
+}
+
+impl ser::SerializeSeq for SerializeVector {
+    type Ok = Object;
+    type Error = SerializeObjectError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.vector.push(to_object(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Object, Self::Error> {
+        Ok(Object::Vector(self.vector))
+    }
+}
+
+impl ser::SerializeTuple for SerializeVector {
+    type Ok = Object;
+    type Error = SerializeObjectError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Object, Self::Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeVector {
+    type Ok = Object;
+    type Error = SerializeObjectError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Object, Self::Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct SerializeMap {
+    map: HashMap<Object, Object>,
+    key: Option<Object>,
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Object;
+    type Error = SerializeObjectError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.key = Some(key_object(to_object(key)?));
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("value serialized before key"))?;
+        self.map.insert(key, to_object(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Object, Self::Error> {
+        Ok(Object::Map(self.map))
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Object;
+    type Error = SerializeObjectError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.map
+            .insert(Object::Symbol(Symbol::new(key)), to_object(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Object, Self::Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+struct SerializeVariant<T> {
+    variant: &'static str,
+    inner: T,
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeVector> {
+    type Ok = Object;
+    type Error = SerializeObjectError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Object, Self::Error> {
+        Ok(variant_map(
+            self.variant,
+            ser::SerializeSeq::end(self.inner)?,
+        ))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeMap> {
+    type Ok = Object;
+    type Error = SerializeObjectError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Object, Self::Error> {
+        Ok(variant_map(
+            self.variant,
+            ser::SerializeMap::end(self.inner)?,
+        ))
+    }
+}
@@ -0,0 +1,240 @@
+use crate::evaluator::{EvalError, Evaluator};
+use crate::object::Object;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+// The longest request line read, and how many may wait while one is
+// evaluated. Both are reached before a client gives the token, so they
+// bound what a client without it can make the server hold.
+const MAX_LINE: usize = 1024 * 1024;
+const MAX_WAITING: usize = 16;
+
+// What a session gives back for one request: the printed value of the last
+// form, an error message, or the code a program exited with, which also
+// ends the session.
+enum Reply {
+    Value(String),
+    Error(String),
+    Exit(i32),
+}
+
+// A remote REPL. Each line a client sends is one request and gets one line
+// back. A JSON request such as {"id": 1, "op": "eval", "code": "(+ 1 2)"}
+// is answered with {"id": 1, "value": "3"}, {"id": 1, "error": message} or
+// {"id": 1, "exit": code}; the id is echoed as it was sent and op defaults
+// to eval. Any other line is taken as code and answered with the printed
+// value or "error: message", which suits a plain terminal connection.
+//
+// With a token, a session must first give it, as {"op": "auth", "token":
+// token} or as a line of its own, and anything else ends the session. The
+// request {"op": "interrupt"} stops what the session sent before it, each
+// request answering with an error; it gets no answer of its own.
+//
+// Code is evaluated over the evaluator's globals, so what a client defines
+// is seen by the other clients and by the program embedding the evaluator,
+// and it can do whatever the evaluator's sandbox allows. Whatever it
+// prints goes to the process's stdout rather than to the client.
+#[derive(Clone)]
+pub struct Server {
+    evaluator: Arc<Evaluator>,
+    token: Option<Arc<str>>,
+}
+
+impl Server {
+    pub fn new(evaluator: Arc<Evaluator>) -> Server {
+        Server {
+            evaluator,
+            token: None,
+        }
+    }
+
+    pub fn token(mut self, token: &str) -> Server {
+        self.token = Some(token.into());
+        self
+    }
+
+    // Accepts clients until the listener fails, serving each on a thread
+    // of its own.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            let server = self.clone();
+            thread::spawn(move || {
+                let input = BufReader::new(stream.try_clone()?);
+                let served = server.session(input, &stream);
+                // Ends the read still waiting for the client's next line.
+                let _ = stream.shutdown(Shutdown::Both);
+                served
+            });
+        }
+    }
+
+    // Handles requests until the input ends, a program exits or the token
+    // is not given. Lines are read on a thread of their own, so that an
+    // interrupt is seen while code runs.
+    pub fn session(
+        &self,
+        input: impl BufRead + Send + 'static,
+        mut output: impl Write,
+    ) -> io::Result<()> {
+        // Each session has an interrupt flag of its own, so that an
+        // interrupt stops nothing another client runs.
+        let mut evaluator = self.evaluator.child();
+        evaluator.interrupted = Arc::new(AtomicBool::new(false));
+        let handle = evaluator.interrupt_handle();
+        // How many lines had been read at the last interrupt, so that it
+        // stops those still waiting as well as the one running, and not
+        // the lines read after it.
+        let interrupted_through = Arc::new(AtomicU64::new(0));
+        let through = interrupted_through.clone();
+        let (sender, receiver) = mpsc::sync_channel(MAX_WAITING);
+        thread::spawn(move || {
+            let mut input = input;
+            let mut read = 0;
+            while let Some(line) = read_line(&mut input).transpose() {
+                let failed = line.is_err();
+                if line.as_deref().is_ok_and(is_interrupt) {
+                    through.store(read, Ordering::SeqCst);
+                    handle.interrupt();
+                } else if sender.send(line).is_err() || failed {
+                    break;
+                } else {
+                    read += 1;
+                }
+            }
+        });
+        let mut authorized = self.token.is_none();
+        for (line, number) in receiver.into_iter().zip(1..) {
+            evaluator.interrupted.store(false, Ordering::SeqCst);
+            if interrupted_through.load(Ordering::SeqCst) >= number {
+                evaluator.interrupted.store(true, Ordering::SeqCst);
+            }
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request = serde_json::from_str::<Value>(&line)
+                .ok()
+                .filter(Value::is_object);
+            let reply = match (&request, authorized) {
+                (Some(request), true) => request_reply(&evaluator, request),
+                (None, true) => eval(&evaluator, &line),
+                (request, false) => {
+                    let token = match request {
+                        Some(request) if request.get("op") == Some(&json!("auth")) => {
+                            request.get("token").and_then(Value::as_str)
+                        }
+                        Some(_) => None,
+                        None => Some(line.trim()),
+                    };
+                    authorized =
+                        token.is_some_and(|token| authorizes(self.token.as_deref(), token));
+                    match authorized {
+                        true => Reply::Value("true".into()),
+                        false => Reply::Error("unauthorized".into()),
+                    }
+                }
+            };
+            let end = !authorized || matches!(reply, Reply::Exit(_));
+            writeln!(output, "{}", format_reply(reply, request.as_ref()))?;
+            output.flush()?;
+            if end {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Whether token is the one expected. Every byte is compared whatever the
+// first that differs, so that the time taken says nothing of the token.
+pub(crate) fn authorizes(expected: Option<&str>, token: &str) -> bool {
+    let expected = expected.unwrap_or_default();
+    expected.len() == token.len()
+        && expected
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+// The next line without its ending, or None at the end of the input. A
+// line longer than MAX_LINE is an error rather than read whole.
+fn read_line(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    let limit = MAX_LINE as u64 + 1;
+    if input.by_ref().take(limit).read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with('\n') && line.len() > MAX_LINE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "request line is too long",
+        ));
+    }
+    let end = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(end);
+    Ok(Some(line))
+}
+
+fn is_interrupt(line: &str) -> bool {
+    serde_json::from_str::<Value>(line)
+        .is_ok_and(|request| request.get("op") == Some(&json!("interrupt")))
+}
+
+// The reply as a line of JSON to a JSON request, or as plain text.
+fn format_reply(reply: Reply, request: Option<&Value>) -> String {
+    let request = match request {
+        Some(request) => request,
+        None => {
+            return match reply {
+                Reply::Value(value) => value,
+                Reply::Error(message) => format!("error: {}", message),
+                Reply::Exit(code) => format!("exit: {}", code),
+            }
+        }
+    };
+    let mut response = match reply {
+        Reply::Value(value) => json!({ "value": value }),
+        Reply::Error(message) => json!({ "error": message }),
+        Reply::Exit(code) => json!({ "exit": code }),
+    };
+    if let Some(id) = request.get("id") {
+        response["id"] = id.clone();
+    }
+    response.to_string()
+}
+
+fn request_reply(evaluator: &Evaluator, request: &Value) -> Reply {
+    match request.get("op").map_or(Some("eval"), Value::as_str) {
+        Some("eval") => match request.get("code").and_then(Value::as_str) {
+            Some(code) => eval(evaluator, code),
+            None => Reply::Error("eval needs code".into()),
+        },
+        // Giving the token again, or where none is needed, is harmless.
+        Some("auth") => Reply::Value("true".into()),
+        Some(op) => Reply::Error(format!("unknown op {}", op)),
+        None => Reply::Error("op must be a string".into()),
+    }
+}
+
+// Evaluates every form in the code, stopping at the first that fails.
+fn eval(evaluator: &Evaluator, code: &str) -> Reply {
+    let forms = match Object::parse_all(code) {
+        Ok(forms) => forms,
+        Err(_) => return Reply::Error("syntax error: could not parse input".into()),
+    };
+    let mut value = Object::Null;
+    for form in &forms {
+        value = match evaluator.try_eval(form) {
+            Ok(value) => value,
+            Err(EvalError::Exit(code)) => return Reply::Exit(code),
+            Err(error) => return Reply::Error(error.to_string()),
+        };
+    }
+    Reply::Value(value.to_string())
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{OnceLock, RwLock};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+pub(crate) struct Interner {
+    ids: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+static INTERNER: OnceLock<&'static RwLock<Interner>> = OnceLock::new();
+
+pub(crate) fn interner() -> &'static RwLock<Interner> {
+    INTERNER.get_or_init(|| Box::leak(Box::default()))
+}
+
+// A plugin links a copy of this crate of its own, which takes the symbols
+// of the program loading it over before making any, so both agree on what
+// each symbol stands for. Gives false when this copy already made symbols
+// of its own, whose ids the program would read as other names.
+pub(crate) fn adopt(interner: &'static RwLock<Interner>) -> bool {
+    let adopted = INTERNER.get_or_init(|| interner);
+    std::ptr::eq(*adopted, interner)
+}
+
+// Names are never freed, since a symbol may be held anywhere, so every
+// distinct name made into a symbol stays in memory for the rest of the
+// process. Code has few names, but data can have any number, so decoders
+// make keys into symbols with try_new, which adds no names past this
+// many.
+const DATA_LIMIT: usize = 1 << 16;
+
+impl Symbol {
+    pub fn new(name: &str) -> Symbol {
+        if let Some(symbol) = interner().read().unwrap().ids.get(name) {
+            return *symbol;
+        }
+        let mut interner = interner().write().unwrap();
+        if let Some(symbol) = interner.ids.get(name) {
+            return *symbol;
+        }
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let symbol = Symbol(interner.names.len() as u32);
+        interner.names.push(name);
+        interner.ids.insert(name, symbol);
+        symbol
+    }
+
+    // The symbol for a name from data, or None when it is new and the
+    // table already holds DATA_LIMIT names.
+    pub fn try_new(name: &str) -> Option<Symbol> {
+        {
+            let interner = interner().read().unwrap();
+            if let Some(symbol) = interner.ids.get(name) {
+                return Some(*symbol);
+            }
+            if interner.names.len() >= DATA_LIMIT {
+                return None;
+            }
+        }
+        Some(Symbol::new(name))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        interner().read().unwrap().names[self.0 as usize]
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::new(name)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
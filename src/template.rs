@@ -0,0 +1,402 @@
+use crate::evaluator::{args, truthy, EvalError, Evaluator, NativeFunction};
+use crate::interpreter::Interpreter;
+use crate::object::Object;
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+pub(crate) const NATIVES: [(&str, NativeFunction); 1] = [("render-template", render_template)];
+
+// (render-template text context) renders text with the entries of the map
+// context as variables. It gives null when text is not a template.
+fn render_template(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let (text, context) = match args(obj) {
+        [text, context] => (text, context),
+        [text] => (text, &Object::Null),
+        _ => return Ok(Object::Null),
+    };
+    let template = match text.without_meta() {
+        Object::String(text) => match text.parse::<Template>() {
+            Ok(template) => template,
+            Err(_) => return Ok(Object::Null),
+        },
+        _ => return Ok(Object::Null),
+    };
+    let eval = |obj: &Object, scope: &[(Symbol, Object)]| {
+        interpreter.eval_with_bindings(obj, scope.to_vec())
+    };
+    match template.render_with(&eval, context) {
+        Ok(text) => Ok(Object::from(text)),
+        Err(TemplateError::Eval(error)) => Err(error),
+        Err(TemplateError::Syntax { .. }) => Ok(Object::Null),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TemplateError {
+    // Where the template is malformed, counting lines from 1.
+    Syntax { line: usize, message: String },
+    Eval(EvalError),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            TemplateError::Eval(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl From<EvalError> for TemplateError {
+    fn from(error: EvalError) -> Self {
+        TemplateError::Eval(error)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Text(String),
+    Expr(Object),
+    For {
+        names: Vec<Symbol>,
+        items: Object,
+        body: Vec<Node>,
+    },
+    If {
+        branches: Vec<(Object, Vec<Node>)>,
+        otherwise: Vec<Node>,
+    },
+}
+
+enum Token {
+    Text(String),
+    Expr(String),
+    Tag(String),
+}
+
+// Splits text into text, {{ expr }} and {% tag %}, each with the line it
+// starts on, leaving out {# comments #}. A tag or comment alone on its line
+// takes the whole line with it, so blocks do not leave blank lines behind.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, TemplateError> {
+    let line = |offset: usize| text[..offset].matches('\n').count() + 1;
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    let mut search = 0;
+    while let Some(found) = text[search..].find('{') {
+        let start = search + found;
+        let close = match text.get(start + 1..start + 2) {
+            Some("{") => "}}",
+            Some("%") => "%}",
+            Some("#") => "#}",
+            _ => {
+                search = start + 1;
+                continue;
+            }
+        };
+        let end = match text[start + 2..].find(close) {
+            Some(end) => start + 2 + end,
+            None => {
+                return Err(TemplateError::Syntax {
+                    line: line(start),
+                    message: format!("missing {}", close),
+                })
+            }
+        };
+        let (mut text_end, mut next) = (start, end + 2);
+        if close != "}}" {
+            let line_start = text[..start].rfind('\n').map_or(0, |newline| newline + 1);
+            let line_end = text[next..]
+                .find('\n')
+                .map_or(text.len(), |newline| next + newline + 1);
+            if line_start >= position
+                && text[line_start..start].trim().is_empty()
+                && text[next..line_end].trim().is_empty()
+            {
+                text_end = line_start;
+                next = line_end;
+            }
+        }
+        push_text(&mut tokens, &text[position..text_end], line(position));
+        let inner = &text[start + 2..end];
+        match close {
+            "}}" => tokens.push((Token::Expr(inner.to_string()), line(start))),
+            "%}" => tokens.push((Token::Tag(inner.trim().to_string()), line(start))),
+            _ => {}
+        }
+        position = next;
+        search = next;
+    }
+    push_text(&mut tokens, &text[position..], line(position));
+    Ok(tokens)
+}
+
+fn push_text(tokens: &mut Vec<(Token, usize)>, text: &str, line: usize) {
+    if text.is_empty() {
+        return;
+    }
+    if let Some((Token::Text(previous), _)) = tokens.last_mut() {
+        previous.push_str(text);
+    } else {
+        tokens.push((Token::Text(text.to_string()), line));
+    }
+}
+
+fn expression(source: &str, line: usize) -> Result<Object, TemplateError> {
+    match Object::parse_all(source) {
+        Ok(mut forms) if forms.len() == 1 => Ok(forms.pop().unwrap()),
+        _ => Err(TemplateError::Syntax {
+            line,
+            message: format!("not an expression: {}", source.trim()),
+        }),
+    }
+}
+
+// Nodes and the tag that ended them, with its line.
+type Block = (Vec<Node>, Option<(String, usize)>);
+
+struct Parser {
+    tokens: std::vec::IntoIter<(Token, usize)>,
+}
+
+impl Parser {
+    // Nodes up to one of the tags in ends, which is given back with its
+    // line, or up to the end.
+    fn nodes(&mut self, ends: &[&str]) -> Result<Block, TemplateError> {
+        let mut nodes = Vec::new();
+        while let Some((token, line)) = self.tokens.next() {
+            let tag = match token {
+                Token::Text(text) => {
+                    nodes.push(Node::Text(text));
+                    continue;
+                }
+                Token::Expr(source) => {
+                    nodes.push(Node::Expr(expression(&source, line)?));
+                    continue;
+                }
+                Token::Tag(tag) => tag,
+            };
+            let (keyword, rest) = tag.split_once(char::is_whitespace).unwrap_or((&tag, ""));
+            if ends.contains(&keyword) {
+                return Ok((nodes, Some((tag.clone(), line))));
+            }
+            match keyword {
+                "for" => nodes.push(self.for_block(rest, line)?),
+                "if" => nodes.push(self.if_block(rest, line)?),
+                _ => {
+                    return Err(TemplateError::Syntax {
+                        line,
+                        message: format!("unexpected {{% {} %}}", tag),
+                    })
+                }
+            }
+        }
+        Ok((nodes, None))
+    }
+
+    fn block(
+        &mut self,
+        ends: &[&str],
+        open: &str,
+        line: usize,
+    ) -> Result<(Vec<Node>, String, usize), TemplateError> {
+        match self.nodes(ends)? {
+            (nodes, Some((tag, line))) => Ok((nodes, tag, line)),
+            (_, None) => Err(TemplateError::Syntax {
+                line,
+                message: format!("{{% {} %}} is not closed", open),
+            }),
+        }
+    }
+
+    // for x in items, or for key, value in items.
+    fn for_block(&mut self, rest: &str, line: usize) -> Result<Node, TemplateError> {
+        let malformed = || TemplateError::Syntax {
+            line,
+            message: format!("malformed {{% for {} %}}", rest),
+        };
+        let (names, items) = rest.split_once(" in ").ok_or_else(malformed)?;
+        let names = names
+            .split(',')
+            .map(|name| match name.trim() {
+                "" => None,
+                name if name.contains(char::is_whitespace) => None,
+                name => Some(Symbol::new(name)),
+            })
+            .collect::<Option<Vec<_>>>()
+            .filter(|names| names.len() <= 2)
+            .ok_or_else(malformed)?;
+        let items = expression(items, line)?;
+        let (body, _, _) = self.block(&["endfor"], "for", line)?;
+        Ok(Node::For { names, items, body })
+    }
+
+    fn if_block(&mut self, condition: &str, line: usize) -> Result<Node, TemplateError> {
+        let mut branches = Vec::new();
+        let mut condition = expression(condition, line)?;
+        loop {
+            let (body, tag, tag_line) = self.block(&["elif", "else", "endif"], "if", line)?;
+            branches.push((condition, body));
+            match tag.split_once(char::is_whitespace) {
+                Some(("elif", rest)) => condition = expression(rest, tag_line)?,
+                _ if tag == "else" => {
+                    let (otherwise, _, _) = self.block(&["endif"], "if", line)?;
+                    return Ok(Node::If {
+                        branches,
+                        otherwise,
+                    });
+                }
+                _ => {
+                    return Ok(Node::If {
+                        branches,
+                        otherwise: Vec::new(),
+                    })
+                }
+            }
+        }
+    }
+}
+
+// A text template. {{ expr }} is replaced by the value of expr, strings as
+// they are, null as nothing and anything else as it prints.
+//
+//     {% for host in hosts %}
+//     server {{ host }}:{{ (+ port 1) }}
+//     {% endfor %}
+//
+// {% if c %} ... {% elif d %} ... {% else %} ... {% endif %} keeps the
+// first branch whose condition is true, and {% for x in items %} ...
+// {% endfor %} repeats its body for each item of a vector or list. Over a
+// map, items are its [key, value] entries in the order their keys print,
+// which {% for key, value in map %} takes apart. {# comments #} are left
+// out.
+#[derive(Clone, Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl FromStr for Template {
+    type Err = TemplateError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?.into_iter(),
+        };
+        match parser.nodes(&[])? {
+            (nodes, None) => Ok(Template { nodes }),
+            (_, Some((tag, line))) => Err(TemplateError::Syntax {
+                line,
+                message: format!("unexpected {{% {} %}}", tag),
+            }),
+        }
+    }
+}
+
+type Eval<'a> = dyn Fn(&Object, &[(Symbol, Object)]) -> Result<Object, EvalError> + 'a;
+
+impl Template {
+    // Renders the template with evaluator, the entries of the map context
+    // being its variables.
+    pub fn render(&self, evaluator: &Evaluator, context: &Object) -> Result<String, TemplateError> {
+        let eval = |obj: &Object, scope: &[(Symbol, Object)]| {
+            let scope: HashMap<String, Object> = scope
+                .iter()
+                .map(|(name, value)| (name.as_str().to_string(), value.clone()))
+                .collect();
+            evaluator.eval_with_scope(obj, &scope)
+        };
+        self.render_with(&eval, context)
+    }
+
+    fn render_with(&self, eval: &Eval, context: &Object) -> Result<String, TemplateError> {
+        let mut scope = Vec::new();
+        if let Object::Map(map) = context.without_meta() {
+            for (key, value) in map {
+                match key.without_meta() {
+                    Object::Symbol(name) => scope.push((*name, value.clone())),
+                    Object::String(name) => scope.push((Symbol::new(name), value.clone())),
+                    _ => {}
+                }
+            }
+        }
+        let mut out = String::new();
+        render(&self.nodes, eval, &mut scope, &mut out)?;
+        Ok(out)
+    }
+}
+
+fn items(value: &Object) -> Vec<Object> {
+    match value.without_meta() {
+        Object::Vector(items) | Object::List(items) => items.clone(),
+        Object::Map(map) => {
+            let mut entries: Vec<(&Object, &Object)> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| key.to_string());
+            entries
+                .into_iter()
+                .map(|(key, value)| Object::Vector(vec![key.clone(), value.clone()]))
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+// The scope with only the innermost binding of each name.
+fn visible(scope: &[(Symbol, Object)]) -> Vec<(Symbol, Object)> {
+    let mut visible: Vec<(Symbol, Object)> = Vec::new();
+    for (name, value) in scope.iter().rev() {
+        if !visible.iter().any(|(other, _)| other == name) {
+            visible.push((*name, value.clone()));
+        }
+    }
+    visible
+}
+
+fn render(
+    nodes: &[Node],
+    eval: &Eval,
+    scope: &mut Vec<(Symbol, Object)>,
+    out: &mut String,
+) -> Result<(), EvalError> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Expr(expr) => match eval(expr, &visible(scope))? {
+                Object::String(text) => out.push_str(&text),
+                Object::Null => {}
+                value => out.push_str(&value.to_string()),
+            },
+            Node::For { names, items, body } => {
+                for item in self::items(&eval(items, &visible(scope))?) {
+                    let len = scope.len();
+                    match names.as_slice() {
+                        [key, value] => {
+                            let mut parts = self::items(&item).into_iter();
+                            scope.push((*key, parts.next().unwrap_or(Object::Null)));
+                            scope.push((*value, parts.next().unwrap_or(Object::Null)));
+                        }
+                        _ => scope.push((names[0], item)),
+                    }
+                    render(body, eval, scope, out)?;
+                    scope.truncate(len);
+                }
+            }
+            Node::If {
+                branches,
+                otherwise,
+            } => {
+                let mut chosen = otherwise;
+                for (condition, body) in branches {
+                    if truthy(&eval(condition, &visible(scope))?) {
+                        chosen = body;
+                        break;
+                    }
+                }
+                render(chosen, eval, scope, out)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use crate::context::Context;
+use crate::diff::{path_string, Change};
+use crate::evaluator::{self, EvalError, Evaluator, EvaluatorBuilder};
+use crate::interpreter::Interpreter;
+use crate::object::{Object, ParseObjectError};
+use crate::output::Buffer;
+use crate::symbol::Symbol;
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Failure {
+    pub message: String,
+    pub expected: Option<Object>,
+    pub actual: Option<Object>,
+}
+
+impl Failure {
+    // Where expected and actual differ, one line per place.
+    pub fn diff(&self) -> Vec<String> {
+        let (expected, actual) = match (&self.expected, &self.actual) {
+            (Some(expected), Some(actual)) => (expected, actual),
+            _ => return Vec::new(),
+        };
+        expected
+            .diff(actual)
+            .changes
+            .into_iter()
+            .map(|change| match change {
+                Change::Added { path, value } => {
+                    format!("{}: unexpected {}", path_string(&path), value)
+                }
+                Change::Removed { path, value } => {
+                    format!("{}: missing {}", path_string(&path), value)
+                }
+                Change::Changed { path, from, to } => {
+                    let place = match path.is_empty() {
+                        true => "value".to_string(),
+                        false => path_string(&path),
+                    };
+                    format!("{}: expected {}, got {}", place, from, to)
+                }
+            })
+            .collect()
+    }
+}
+
+// Output is what the test printed, setup included.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestResult {
+    pub name: String,
+    pub failures: Vec<Failure>,
+    pub output: String,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+thread_local! {
+    static FAILURES: RefCell<Vec<Failure>> = const { RefCell::new(Vec::new()) };
+}
+
+fn fail(message: String, expected: Option<Object>, actual: Option<Object>) {
+    FAILURES.with(|failures| {
+        failures.borrow_mut().push(Failure {
+            message,
+            expected,
+            actual,
+        })
+    });
+}
+
+fn is(obj: &Object) -> Object {
+    let args = evaluator::args(obj);
+    let value = args.first().cloned().unwrap_or(Object::Null);
+    let passed = evaluator::truthy(&value);
+    if !passed {
+        let message = match args.get(1) {
+            Some(Object::String(message)) => message.to_string(),
+            _ => format!("expected a true value, got {}", value),
+        };
+        fail(message, None, None);
+    }
+    Object::Bool(passed)
+}
+
+fn assert_eq(obj: &Object) -> Object {
+    let args = evaluator::args(obj);
+    let expected = args.first().cloned().unwrap_or(Object::Null);
+    let actual = args.get(1).cloned().unwrap_or(Object::Null);
+    let passed = expected == actual;
+    if !passed {
+        fail(
+            format!("expected {}, got {}", expected, actual),
+            Some(expected),
+            Some(actual),
+        );
+    }
+    Object::Bool(passed)
+}
+
+// Gets its body as a function from the rewriting done by run. An
+// interrupt or an exit is not something the body threw, so it is passed
+// on.
+fn assert_throws(interpreter: &Interpreter, obj: &Object) -> Result<Object, EvalError> {
+    let body = evaluator::args(obj)
+        .first()
+        .cloned()
+        .unwrap_or(Object::Null);
+    match interpreter.attempt(|| interpreter.apply(&[body])) {
+        Err(error @ EvalError::Interrupted) | Err(error @ EvalError::Exit(_)) => Err(error),
+        Err(_) => Ok(Object::Bool(true)),
+        Ok(value) => {
+            fail(
+                format!("expected an error, got {}", value),
+                None,
+                Some(value),
+            );
+            Ok(Object::Bool(false))
+        }
+    }
+}
+
+// The evaluator from builder with is, assert-eq and assert-throws defined,
+// shared by the contexts the tests run in.
+#[cfg_attr(feature = "rc", allow(clippy::arc_with_non_send_sync))]
+pub fn evaluator(builder: EvaluatorBuilder) -> Arc<Evaluator> {
+    Arc::new(
+        builder
+            .primitive("is", is)
+            .primitive("assert-eq", assert_eq)
+            .native("assert-throws", assert_throws)
+            .build(),
+    )
+}
+
+// There are no macros, so (assert-throws body...) is rewritten to pass its
+// body as a function before anything is evaluated.
+fn rewrite(obj: &Object) -> Object {
+    let list = match obj {
+        Object::List(list) => list,
+        _ => return obj.clone(),
+    };
+    match list.first() {
+        Some(Object::Symbol(head)) if head.as_str() == "quote" => obj.clone(),
+        Some(Object::Symbol(head)) if head.as_str() == "assert-throws" => {
+            let mut function = vec![Object::Symbol(Symbol::new("fn")), Object::List(Vec::new())];
+            function.extend(list[1..].iter().map(rewrite));
+            Object::List(vec![list[0].clone(), Object::List(function)])
+        }
+        _ => Object::List(list.iter().map(rewrite).collect()),
+    }
+}
+
+// Top-level (deftest name body...) forms are the tests and every other form
+// is setup. Each test runs in a context of its own, so the setup is
+// evaluated afresh for it and nothing it assigns reaches other tests. A
+// test passes when it evaluates without an error and none of its
+// assertions fail. What the tests print is captured rather than written
+// to the evaluator's output sink.
+pub fn run(source: &str, evaluator: &Arc<Evaluator>) -> Result<Vec<TestResult>, ParseObjectError> {
+    let mut setup = Vec::new();
+    let mut tests = Vec::new();
+    for form in Object::parse_all(source)? {
+        let form = rewrite(&form);
+        match &form {
+            Object::List(list)
+                if list.len() >= 2 && list[0] == Object::Symbol(Symbol::new("deftest")) =>
+            {
+                let name = match &list[1] {
+                    Object::String(name) => name.to_string(),
+                    name => name.to_string(),
+                };
+                tests.push((name, list[2..].to_vec()));
+            }
+            _ => setup.push(form),
+        }
+    }
+    let captured = Arc::new(Buffer::new());
+    let sink = evaluator.output_sink();
+    evaluator.set_output_sink(captured.clone());
+    let mut results = Vec::new();
+    for (name, body) in tests {
+        FAILURES.with(|failures| failures.borrow_mut().clear());
+        let context = Context::new(evaluator.clone());
+        for form in setup.iter().chain(&body) {
+            if let Err(error) = context.try_eval(form) {
+                fail(format!("evaluation failed: {}", error), None, None);
+                break;
+            }
+        }
+        let failures = FAILURES.with(|failures| failures.take());
+        let output = captured.take();
+        results.push(TestResult {
+            name,
+            failures,
+            output,
+        });
+    }
+    evaluator.set_output_sink(sink);
+    Ok(results)
+}
+
+// The *_test.fd files under path, or path itself when it is a file. Hidden
+// directories are skipped.
+pub fn discover(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !path.is_dir() {
+        fs::metadata(path)?;
+        files.push(path.to_path_buf());
+        return Ok(files);
+    }
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+            if path.is_dir() && !name.starts_with('.') {
+                pending.push(path);
+            } else if name.ends_with("_test.fd") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,669 @@
+use crate::object::{Object, ParseObjectError};
+use crate::symbol::Symbol;
+#[cfg(feature = "serde")]
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+
+// Bindings and functions may carry type annotations, written with a colon
+// after what they annotate:
+//
+//     (set limit : int 10)
+//     (let ((name : string "x")) ...)
+//     (set area (fn (w : float h : float) : float (* w h)))
+//
+// They are read by fundot check and ignored when running: each form that
+// can hold them is looked at through unannotated first.
+
+fn is_colon(obj: &Object) -> bool {
+    matches!(obj, Object::Symbol(symbol) if symbol.as_str() == ":")
+}
+
+// The parameters of a fn, each with its annotated type if it has one.
+pub(crate) fn params(params: &[Object]) -> Vec<(&Object, Option<&Object>)> {
+    let mut result = Vec::new();
+    let mut rest = params;
+    while let Some((param, after)) = rest.split_first() {
+        match after {
+            [colon, annotation, after @ ..] if is_colon(colon) => {
+                result.push((param, Some(annotation)));
+                rest = after;
+            }
+            _ => {
+                result.push((param, None));
+                rest = after;
+            }
+        }
+    }
+    result
+}
+
+// The arguments of a fn, fn*, let or set form with its annotations taken
+// out, or None when it has none, which is nearly always, so nothing is
+// copied then.
+pub(crate) fn unannotated(name: &str, args: &[Object]) -> Option<Vec<Object>> {
+    match (name, args) {
+        ("fn" | "fn*", [Object::List(list), body @ ..])
+            if list.iter().any(is_colon) || body.first().is_some_and(is_colon) =>
+        {
+            let names = params(list).into_iter().map(|(name, _)| name.clone());
+            let body = match body {
+                [colon, _, body @ ..] if is_colon(colon) => body,
+                _ => body,
+            };
+            Some([vec![Object::List(names.collect())], body.to_vec()].concat())
+        }
+        ("let", [Object::List(bindings), body @ ..])
+            if bindings.iter().any(
+                |binding| matches!(binding, Object::List(list) if list.iter().any(is_colon)),
+            ) =>
+        {
+            let bindings = bindings
+                .iter()
+                .map(|binding| match binding {
+                    Object::List(list) => match list.as_slice() {
+                        [name, colon, _, value] if is_colon(colon) => {
+                            Object::List(vec![name.clone(), value.clone()])
+                        }
+                        _ => binding.clone(),
+                    },
+                    _ => binding.clone(),
+                })
+                .collect();
+            Some([vec![Object::List(bindings)], body.to_vec()].concat())
+        }
+        ("set", [name, colon, _, value]) if is_colon(colon) => {
+            Some(vec![name.clone(), value.clone()])
+        }
+        _ => None,
+    }
+}
+
+// A type as annotations write it: any, null, bool, int, float, number,
+// string, symbol, list, map, vector or fn, [t] for a vector of t, (fn (t
+// ...) r) for a function and (or t ...) for any of several. Any is
+// compatible with every type both ways, so unannotated code is never
+// reported.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Any,
+    Null,
+    Bool,
+    Int,
+    Float,
+    Number,
+    String,
+    Symbol,
+    List,
+    Map,
+    Vector(Box<Type>),
+    Fn(Option<Vec<Type>>, Box<Type>),
+    Or(Vec<Type>),
+}
+
+const NAMES: [(&str, Type); 10] = [
+    ("any", Type::Any),
+    ("null", Type::Null),
+    ("bool", Type::Bool),
+    ("int", Type::Int),
+    ("float", Type::Float),
+    ("number", Type::Number),
+    ("string", Type::String),
+    ("symbol", Type::Symbol),
+    ("list", Type::List),
+    ("map", Type::Map),
+];
+
+impl Type {
+    pub fn from_object(obj: &Object) -> Option<Type> {
+        match obj.without_meta() {
+            // Null is read as a value rather than a symbol.
+            Object::Null => Some(Type::Null),
+            Object::Symbol(name) => match name.as_str() {
+                "vector" => Some(Type::Vector(Box::new(Type::Any))),
+                "fn" => Some(Type::Fn(None, Box::new(Type::Any))),
+                name => NAMES
+                    .iter()
+                    .find(|(other, _)| *other == name)
+                    .map(|(_, ty)| ty.clone()),
+            },
+            Object::Vector(items) => match items.as_slice() {
+                [item] => Some(Type::Vector(Box::new(Type::from_object(item)?))),
+                _ => None,
+            },
+            Object::List(list) => match list.as_slice() {
+                [Object::Symbol(head), Object::List(params), ret] if head.as_str() == "fn" => {
+                    Some(Type::Fn(
+                        Some(
+                            params
+                                .iter()
+                                .map(Type::from_object)
+                                .collect::<Option<_>>()?,
+                        ),
+                        Box::new(Type::from_object(ret)?),
+                    ))
+                }
+                [Object::Symbol(head), types @ ..] if head.as_str() == "or" => Some(join_all(
+                    types.iter().map(Type::from_object).collect::<Option<_>>()?,
+                )),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // Whether a value of this type may be used where other is expected.
+    pub fn fits(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Or(types), _) => types.iter().all(|ty| ty.fits(other)),
+            (_, Type::Or(types)) => types.iter().any(|ty| self.fits(ty)),
+            (Type::Int | Type::Float, Type::Number) => true,
+            (Type::Vector(item), Type::Vector(other)) => item.fits(other),
+            (Type::Fn(params, ret), Type::Fn(other_params, other_ret)) => {
+                let params = match (params, other_params) {
+                    (Some(params), Some(others)) => {
+                        params.len() == others.len()
+                            && params.iter().zip(others).all(|(a, b)| b.fits(a))
+                    }
+                    _ => true,
+                };
+                params && ret.fits(other_ret)
+            }
+            (a, b) => a == b,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |types: &[Type]| {
+            types
+                .iter()
+                .map(Type::to_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        match self {
+            Type::Vector(item) if **item == Type::Any => write!(f, "vector"),
+            Type::Vector(item) => write!(f, "[{}]", item),
+            Type::Fn(None, ret) if **ret == Type::Any => write!(f, "fn"),
+            Type::Fn(None, ret) => write!(f, "(fn ... {})", ret),
+            Type::Fn(Some(params), ret) => write!(f, "(fn ({}) {})", list(params), ret),
+            Type::Or(types) => write!(f, "(or {})", list(types)),
+            ty => {
+                let name = NAMES
+                    .iter()
+                    .find(|(_, other)| other == ty)
+                    .map_or("any", |(name, _)| name);
+                write!(f, "{}", name)
+            }
+        }
+    }
+}
+
+// A type that fits both, any when either is.
+fn join(a: Type, b: Type) -> Type {
+    join_all(vec![a, b])
+}
+
+fn join_all(types: Vec<Type>) -> Type {
+    let mut members: Vec<Type> = Vec::new();
+    for ty in types {
+        let flattened = match ty {
+            Type::Any => return Type::Any,
+            Type::Or(types) => types,
+            ty => vec![ty],
+        };
+        for ty in flattened {
+            if !members.contains(&ty) {
+                members.push(ty);
+            }
+        }
+    }
+    match members.len() {
+        0 => Type::Any,
+        1 => members.pop().unwrap(),
+        _ => Type::Or(members),
+    }
+}
+
+// A type mismatch found by check. File is the index of the source it is
+// in and form counts its top-level forms from 1, as lint does.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeError {
+    pub file: usize,
+    pub form: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "form {}: {}", self.form, self.message)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for TypeError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("TypeError", 2)?;
+        state.serialize_field("form", &self.form)?;
+        state.serialize_field("message", &self.message)?;
+        state.end()
+    }
+}
+
+// The builtins with a known type. Arithmetic and comparisons, which take
+// any number of arguments, are checked on their own.
+const BUILTINS: [(&str, &str); 10] = [
+    ("not", "(fn (any) bool)"),
+    ("is-nan?", "(fn (any) bool)"),
+    ("is-finite?", "(fn (any) bool)"),
+    ("print", "fn"),
+    ("println", "fn"),
+    ("json-encode", "(fn (any) (or string null))"),
+    ("json-decode", "(fn (string) any)"),
+    ("parse-uuid", "(fn (string) (or string null))"),
+    ("uuid?", "(fn (any) bool)"),
+    ("validate", "(fn (any any) (or vector null))"),
+];
+
+fn head(list: &[Object]) -> Option<&str> {
+    match list.first() {
+        Some(Object::Symbol(symbol)) => Some(symbol.as_str()),
+        _ => None,
+    }
+}
+
+// Whether body assigns name anywhere, so its type can change.
+fn assigns(body: &[Object], name: Symbol) -> bool {
+    body.iter().any(|obj| match obj {
+        Object::List(list) => match list.as_slice() {
+            [Object::Symbol(head), ..] if head.as_str() == "quote" => false,
+            [Object::Symbol(head), Object::Symbol(target), ..]
+                if head.as_str() == "set" && *target == name =>
+            {
+                true
+            }
+            _ => assigns(list, name),
+        },
+        _ => false,
+    })
+}
+
+// Every (set name ...) in obj, annotated or not.
+fn assignments<'a>(obj: &'a Object, found: &mut Vec<(Symbol, &'a [Object])>) {
+    if let Object::List(list) = obj {
+        match list.as_slice() {
+            [Object::Symbol(head), ..] if head.as_str() == "quote" => return,
+            [Object::Symbol(head), Object::Symbol(name), rest @ ..] if head.as_str() == "set" => {
+                found.push((*name, rest));
+            }
+            _ => {}
+        }
+        for obj in list {
+            assignments(obj, found);
+        }
+    }
+}
+
+struct Checker<'a> {
+    globals: &'a HashMap<Symbol, Type>,
+    locals: Vec<(Symbol, Type, bool)>,
+    file: usize,
+    form: usize,
+    errors: Vec<TypeError>,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, message: String) {
+        self.errors.push(TypeError {
+            file: self.file,
+            form: self.form,
+            message,
+        });
+    }
+
+    fn expect(&mut self, ty: &Type, expected: &Type, what: impl FnOnce() -> String) {
+        if !ty.fits(expected) {
+            let message = format!("{}: expected {}, got {}", what(), expected, ty);
+            self.report(message);
+        }
+    }
+
+    // An annotation, with any when it is not a type.
+    fn annotation(&mut self, obj: &Object) -> Type {
+        Type::from_object(obj).unwrap_or_else(|| {
+            self.report(format!("{} is not a type", obj));
+            Type::Any
+        })
+    }
+
+    fn lookup(&self, symbol: Symbol) -> Type {
+        if let Some((_, ty, _)) = self
+            .locals
+            .iter()
+            .rev()
+            .find(|(name, _, _)| *name == symbol)
+        {
+            return ty.clone();
+        }
+        if let Some(ty) = self.globals.get(&symbol) {
+            return ty.clone();
+        }
+        match BUILTINS.iter().find(|(name, _)| *name == symbol.as_str()) {
+            Some((_, ty)) => ty
+                .parse::<Object>()
+                .ok()
+                .and_then(|ty| Type::from_object(&ty))
+                .unwrap_or(Type::Any),
+            None => Type::Any,
+        }
+    }
+
+    fn body(&mut self, body: &[Object]) -> Type {
+        let mut ty = Type::Null;
+        for obj in body {
+            ty = self.infer(obj);
+        }
+        ty
+    }
+
+    fn function(&mut self, params: &[Object], body: &[Object]) -> Type {
+        let len = self.locals.len();
+        let mut types = Vec::new();
+        for (param, annotation) in self::params(params) {
+            let ty = match annotation {
+                Some(annotation) => self.annotation(annotation),
+                None => Type::Any,
+            };
+            if let Object::Symbol(name) = param {
+                self.locals.push((*name, ty.clone(), annotation.is_some()));
+            }
+            types.push(ty);
+        }
+        let (ret, body) = match body {
+            [colon, ret, body @ ..] if is_colon(colon) => (Some(self.annotation(ret)), body),
+            _ => (None, body),
+        };
+        let inferred = self.body(body);
+        self.locals.truncate(len);
+        let ret = match ret {
+            Some(ret) => {
+                self.expect(&inferred, &ret, || "return value".to_string());
+                ret
+            }
+            None => inferred,
+        };
+        Type::Fn(Some(types), Box::new(ret))
+    }
+
+    fn arithmetic(&mut self, name: &str, args: &[Object]) -> Type {
+        let types: Vec<Type> = args.iter().map(|arg| self.infer(arg)).collect();
+        if name == "+" && types.first() == Some(&Type::String) {
+            return Type::String;
+        }
+        for (index, ty) in types.iter().enumerate() {
+            self.expect(ty, &Type::Number, || {
+                format!("argument {} of {}", index + 1, name)
+            });
+        }
+        if types.iter().all(|ty| *ty == Type::Int) {
+            Type::Int
+        } else if types.contains(&Type::Float) && types.iter().all(|ty| ty.fits(&Type::Number)) {
+            Type::Float
+        } else {
+            Type::Any
+        }
+    }
+
+    fn call(&mut self, list: &[Object]) -> Type {
+        let callee = self.infer(&list[0]);
+        let args: Vec<Type> = list[1..].iter().map(|arg| self.infer(arg)).collect();
+        let name = || match &list[0] {
+            Object::Symbol(name) => name.as_str().to_string(),
+            callee => callee.to_string(),
+        };
+        match callee {
+            Type::Fn(Some(params), ret) => {
+                if params.len() != args.len() {
+                    self.report(format!(
+                        "{} takes {} arguments but is called with {}",
+                        name(),
+                        params.len(),
+                        args.len()
+                    ));
+                } else {
+                    for (index, (arg, param)) in args.iter().zip(&params).enumerate() {
+                        self.expect(arg, param, || {
+                            format!("argument {} of {}", index + 1, name())
+                        });
+                    }
+                }
+                *ret
+            }
+            Type::Fn(None, ret) => *ret,
+            Type::Any | Type::Or(_) | Type::Symbol => Type::Any,
+            ty => {
+                self.report(format!("{} is called but is {}", name(), ty));
+                Type::Any
+            }
+        }
+    }
+
+    fn infer(&mut self, obj: &Object) -> Type {
+        let list = match obj.without_meta() {
+            Object::Symbol(symbol) => return self.lookup(*symbol),
+            Object::List(list) => list,
+            obj => return literal(obj),
+        };
+        let name = match head(list) {
+            Some(name) => name,
+            None if list.is_empty() => return Type::List,
+            None => return self.call(list),
+        };
+        let args = &list[1..];
+        let local = |checker: &Self, name: &str| {
+            checker
+                .locals
+                .iter()
+                .any(|(local, _, _)| local.as_str() == name)
+        };
+        if local(self, name) {
+            return self.call(list);
+        }
+        match (name, args) {
+            ("quote", [obj]) => match obj {
+                Object::Symbol(_) => Type::Symbol,
+                Object::List(_) => Type::List,
+                obj => literal(obj),
+            },
+            ("if", [condition, then, rest @ ..]) => {
+                self.infer(condition);
+                let then = self.infer(then);
+                let otherwise = match rest.first() {
+                    Some(otherwise) => self.infer(otherwise),
+                    None => Type::Null,
+                };
+                join(then, otherwise)
+            }
+            ("do", body) => self.body(body),
+            ("while", [condition, body @ ..]) => {
+                self.infer(condition);
+                self.body(body);
+                Type::Null
+            }
+            ("set", [Object::Symbol(target), rest @ ..]) => {
+                let (declared, value) = match rest {
+                    [colon, annotation, value] if is_colon(colon) => {
+                        (Some(self.annotation(annotation)), value)
+                    }
+                    [value] => (None, value),
+                    _ => return Type::Any,
+                };
+                let ty = self.infer(value);
+                let local = self
+                    .locals
+                    .iter()
+                    .rev()
+                    .find(|(name, _, _)| name == target)
+                    .map(|(_, ty, annotated)| (ty.clone(), *annotated));
+                let expected = match (declared, local) {
+                    (Some(declared), _) => Some(declared),
+                    (None, Some((ty, true))) => Some(ty),
+                    (None, Some(_)) => None,
+                    (None, None) => self.globals.get(target).cloned(),
+                };
+                if let Some(expected) = expected {
+                    self.expect(&ty, &expected, || target.as_str().to_string());
+                }
+                ty
+            }
+            ("let", [Object::List(bindings), body @ ..]) => {
+                let len = self.locals.len();
+                for binding in bindings {
+                    let (name, annotation, value) = match binding {
+                        Object::List(binding) => match binding.as_slice() {
+                            [Object::Symbol(name), colon, annotation, value] if is_colon(colon) => {
+                                (*name, Some(annotation), value)
+                            }
+                            [Object::Symbol(name), value] => (*name, None, value),
+                            _ => continue,
+                        },
+                        _ => continue,
+                    };
+                    let ty = self.infer(value);
+                    let ty = match annotation {
+                        Some(annotation) => {
+                            let declared = self.annotation(annotation);
+                            self.expect(&ty, &declared, || name.as_str().to_string());
+                            declared
+                        }
+                        None if assigns(body, name) => Type::Any,
+                        None => ty,
+                    };
+                    self.locals.push((name, ty, annotation.is_some()));
+                }
+                let ty = self.body(body);
+                self.locals.truncate(len);
+                ty
+            }
+            ("fn", [Object::List(params), body @ ..]) => self.function(params, body),
+            ("fn*", [Object::List(params), body @ ..]) => {
+                self.function(params, body);
+                Type::Any
+            }
+            ("shift", [Object::Symbol(continuation), body @ ..]) => {
+                self.locals.push((*continuation, Type::Any, false));
+                self.body(body);
+                self.locals.pop();
+                Type::Any
+            }
+            ("defdynamic", [_, value @ ..]) => {
+                self.body(value);
+                Type::Any
+            }
+            ("+" | "-" | "*" | "/" | "%", args) => self.arithmetic(name, args),
+            ("<" | ">", args) => {
+                for arg in args {
+                    self.infer(arg);
+                }
+                Type::Bool
+            }
+            ("=", args) => {
+                for arg in args {
+                    self.infer(arg);
+                }
+                Type::Bool
+            }
+            (name, args) if crate::compiler::SPECIAL_FORMS.contains(&name) => {
+                for arg in args {
+                    if let Object::List(_) | Object::Symbol(_) = arg {
+                        self.infer(arg);
+                    }
+                }
+                Type::Any
+            }
+            _ => self.call(list),
+        }
+    }
+}
+
+// The type of a value written in the source. Vectors and maps are data, so
+// what is in them is not looked up.
+fn literal(obj: &Object) -> Type {
+    match obj.without_meta() {
+        Object::Null => Type::Null,
+        Object::Bool(_) => Type::Bool,
+        Object::Integer(_) => Type::Int,
+        Object::Float(_) => Type::Float,
+        Object::String(_) => Type::String,
+        Object::Symbol(_) => Type::Symbol,
+        Object::List(_) => Type::List,
+        Object::Map(_) => Type::Map,
+        Object::Vector(items) => Type::Vector(Box::new(match items.is_empty() {
+            true => Type::Any,
+            false => join_all(items.iter().map(literal).collect()),
+        })),
+        Object::Other(_) => Type::Any,
+    }
+}
+
+// Checks the annotations in several sources together, as the files of one
+// program, without running anything. Globals get their types from their
+// annotations or, when assigned only once, from the value assigned, so a
+// function defined in one file is checked where another calls it. Code
+// without annotations is inferred as far as it can be and only reported
+// where a type is certain to be wrong.
+pub fn check(sources: &[&str]) -> Result<Vec<TypeError>, ParseObjectError> {
+    let files = sources
+        .iter()
+        .map(|source| Object::parse_all(source))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut found = Vec::new();
+    for form in files.iter().flatten() {
+        assignments(form, &mut found);
+    }
+    let mut globals = HashMap::new();
+    let mut errors = Vec::new();
+    // Declared types first, then the types of values assigned once, in
+    // order, so each sees the ones before it.
+    for (name, rest) in &found {
+        if let [colon, annotation, _] = rest {
+            if is_colon(colon) {
+                if let Some(ty) = Type::from_object(annotation) {
+                    globals.insert(*name, ty);
+                }
+            }
+        }
+    }
+    for (name, rest) in &found {
+        let once = found.iter().filter(|(other, _)| other == name).count() == 1;
+        if let (true, [value], false) = (once, rest, globals.contains_key(name)) {
+            let mut checker = Checker {
+                globals: &globals,
+                locals: Vec::new(),
+                file: 0,
+                form: 0,
+                errors: Vec::new(),
+            };
+            let ty = checker.infer(value);
+            globals.insert(*name, ty);
+        }
+    }
+    for (file, forms) in files.iter().enumerate() {
+        let mut checker = Checker {
+            globals: &globals,
+            locals: Vec::new(),
+            file,
+            form: 0,
+            errors: Vec::new(),
+        };
+        for (index, form) in forms.iter().enumerate() {
+            checker.form = index + 1;
+            checker.infer(form);
+        }
+        errors.extend(checker.errors);
+    }
+    Ok(errors)
+}
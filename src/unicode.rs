@@ -0,0 +1,106 @@
+use crate::evaluator::{args, PrimitiveFunction};
+use crate::object::Object;
+use std::convert::TryFrom;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Strings are measured and cut in grapheme clusters, what a reader sees as
+// one character, so an emoji with modifiers or a letter with combining
+// accents is never split. Case mapping is Unicode's default, the same in
+// every locale.
+pub(crate) const PRIMITIVES: [(&str, PrimitiveFunction); 7] = [
+    ("str-len", str_len),
+    ("substr", substr),
+    ("graphemes", graphemes),
+    ("code-points", code_points),
+    ("normalize", normalize),
+    ("unicode-upper", unicode_upper),
+    ("unicode-lower", unicode_lower),
+];
+
+fn string(obj: &Object) -> Option<&str> {
+    match args(obj).first().map(Object::without_meta) {
+        Some(Object::String(string)) => Some(string),
+        _ => None,
+    }
+}
+
+fn str_len(obj: &Object) -> Object {
+    string(obj).map_or(Object::Null, |string| {
+        Object::Integer(string.graphemes(true).count() as i64)
+    })
+}
+
+fn index(obj: &Object) -> Option<usize> {
+    match obj.without_meta() {
+        Object::Integer(n) => usize::try_from(*n).ok(),
+        _ => None,
+    }
+}
+
+// (substr s start end) gives the graphemes of s from start up to but not
+// including end, or to the end of s when end is left out. Indices past the
+// end stop there.
+fn substr(obj: &Object) -> Object {
+    let string = match string(obj) {
+        Some(string) => string,
+        None => return Object::Null,
+    };
+    let (start, end) = match args(obj) {
+        [_, start] => (index(start), Some(usize::MAX)),
+        [_, start, end] => (index(start), index(end)),
+        _ => return Object::Null,
+    };
+    match (start, end) {
+        (Some(start), Some(end)) => Object::from(
+            string
+                .graphemes(true)
+                .skip(start)
+                .take(end.saturating_sub(start))
+                .collect::<String>(),
+        ),
+        _ => Object::Null,
+    }
+}
+
+fn graphemes(obj: &Object) -> Object {
+    string(obj).map_or(Object::Null, |string| {
+        Object::Vector(string.graphemes(true).map(Object::from).collect())
+    })
+}
+
+fn code_points(obj: &Object) -> Object {
+    string(obj).map_or(Object::Null, |string| {
+        Object::Vector(string.chars().map(|c| Object::Integer(c as i64)).collect())
+    })
+}
+
+// (normalize s form) with form nfc, the default, nfd, nfkc or nfkd.
+fn normalize(obj: &Object) -> Object {
+    let string = match string(obj) {
+        Some(string) => string,
+        None => return Object::Null,
+    };
+    let form = match args(obj).get(1).map(Object::without_meta) {
+        None => "nfc".to_string(),
+        Some(Object::Symbol(form)) => form.as_str().to_lowercase(),
+        Some(Object::String(form)) => form.to_lowercase(),
+        Some(_) => return Object::Null,
+    };
+    let normalized: String = match form.as_str() {
+        "nfc" => string.nfc().collect(),
+        "nfd" => string.nfd().collect(),
+        "nfkc" => string.nfkc().collect(),
+        "nfkd" => string.nfkd().collect(),
+        _ => return Object::Null,
+    };
+    Object::from(normalized)
+}
+
+fn unicode_upper(obj: &Object) -> Object {
+    string(obj).map_or(Object::Null, |string| Object::from(string.to_uppercase()))
+}
+
+fn unicode_lower(obj: &Object) -> Object {
+    string(obj).map_or(Object::Null, |string| Object::from(string.to_lowercase()))
+}
@@ -0,0 +1,88 @@
+use crate::evaluator::{args, EvalError, NativeFunction, PrimitiveFunction};
+use crate::interpreter::Interpreter;
+use crate::object::Object;
+
+// UUIDs are strings in the canonical lowercase, hyphenated form.
+pub(crate) const PRIMITIVES: [(&str, PrimitiveFunction); 2] =
+    [("uuid?", is_uuid), ("parse-uuid", parse_uuid)];
+
+// Generating takes the evaluator's random numbers, so a seeded evaluator
+// gives the same UUIDs every run.
+pub(crate) const NATIVES: [(&str, NativeFunction); 1] = [("uuid", uuid)];
+
+// Accepts the canonical form in either case, optionally in braces or
+// after urn:uuid:, and the 32 digits without hyphens.
+pub(crate) fn parse(text: &str) -> Option<[u8; 16]> {
+    let text = text.strip_prefix("urn:uuid:").unwrap_or(text);
+    let text = match text.strip_prefix('{') {
+        Some(inner) => inner.strip_suffix('}')?,
+        None => text,
+    };
+    let digits: Vec<u8> = match text.len() {
+        36 => {
+            let hyphens = [8, 13, 18, 23];
+            for (i, c) in text.bytes().enumerate() {
+                if (c == b'-') != hyphens.contains(&i) {
+                    return None;
+                }
+            }
+            text.bytes().filter(|&c| c != b'-').collect()
+        }
+        32 => text.bytes().collect(),
+        _ => return None,
+    };
+    let mut bytes = [0; 16];
+    for (byte, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+        let digit = |c: u8| (c as char).to_digit(16);
+        *byte = (digit(pair[0])? * 16 + digit(pair[1])?) as u8;
+    }
+    Some(bytes)
+}
+
+pub(crate) fn format(bytes: &[u8; 16]) -> String {
+    let mut text = String::with_capacity(36);
+    for (i, byte) in bytes.iter().enumerate() {
+        if [4, 6, 8, 10].contains(&i) {
+            text.push('-');
+        }
+        text.push_str(&format!("{:02x}", byte));
+    }
+    text
+}
+
+fn string(obj: &Object) -> Option<&str> {
+    match args(obj).first().map(Object::without_meta) {
+        Some(Object::String(string)) => Some(string),
+        _ => None,
+    }
+}
+
+// A version 4, variant 1 UUID.
+fn uuid(interpreter: &Interpreter, _: &Object) -> Result<Object, EvalError> {
+    let evaluator = interpreter.evaluator();
+    let mut bytes = [0; 16];
+    bytes[..8].copy_from_slice(&evaluator.next_random().to_be_bytes());
+    bytes[8..].copy_from_slice(&evaluator.next_random().to_be_bytes());
+    bytes[6] = bytes[6] & 0x0f | 0x40;
+    bytes[8] = bytes[8] & 0x3f | 0x80;
+    Ok(Object::from(format(&bytes)))
+}
+
+fn is_uuid(obj: &Object) -> Object {
+    Object::Bool(string(obj).and_then(parse).is_some())
+}
+
+// Gives the canonical form, or null for anything that is not a UUID.
+fn parse_uuid(obj: &Object) -> Object {
+    string(obj)
+        .and_then(parse)
+        .map_or(Object::Null, |bytes| Object::from(format(&bytes)))
+}
+
+// The reader tag #uuid "…", which reads as the canonical form.
+pub(crate) fn read_tag(obj: &Object) -> Option<Object> {
+    match obj {
+        Object::String(text) => parse(text).map(|bytes| Object::from(format(&bytes))),
+        _ => None,
+    }
+}
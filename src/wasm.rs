@@ -0,0 +1,76 @@
+use crate::error::describe;
+use crate::evaluator::{Evaluator, Sandbox};
+use crate::object::{DisplayLimits, Object};
+use crate::output::Buffer;
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+
+// Bindings for running Fundot in a browser, built with
+//
+//     wasm-pack build --target web --no-default-features --features wasm
+//
+// Code typed into a page cannot be trusted, so it runs pure: no files,
+// network, processes or environment, and a fuel limit on every call.
+
+const FUEL: u64 = 10_000_000;
+
+// Each form in source printed as it reads, one per line.
+#[wasm_bindgen]
+pub fn parse(source: &str) -> Result<String, JsError> {
+    let forms =
+        Object::parse_all(source).map_err(|error| JsError::new(&describe(&error.into())))?;
+    Ok(forms
+        .iter()
+        .map(Object::to_string)
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+#[wasm_bindgen]
+pub fn format(source: &str) -> Result<String, JsError> {
+    crate::fmt::format(source).map_err(|error| JsError::new(&describe(&error.into())))
+}
+
+// An evaluator whose globals last from one call to the next, as in a
+// REPL, with what print writes kept for the page to show.
+#[wasm_bindgen]
+pub struct Playground {
+    evaluator: Evaluator,
+    output: Arc<Buffer>,
+}
+
+#[wasm_bindgen]
+impl Playground {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Playground {
+        let output = Arc::new(Buffer::new());
+        let evaluator = Evaluator::builder()
+            .sandbox(Sandbox::pure())
+            .fuel(FUEL)
+            .output_sink(output.clone())
+            .build();
+        Playground { evaluator, output }
+    }
+
+    // Evaluates every form in source and gives the last value as it
+    // prints, or the error of the first form that fails.
+    #[wasm_bindgen(js_name = evalStr)]
+    pub fn eval_str(&self, source: &str) -> Result<String, JsError> {
+        match self.evaluator.eval_str(source) {
+            Ok(value) => Ok(value.display_with(DisplayLimits::default()).to_string()),
+            Err(error) => Err(JsError::new(&describe(&error))),
+        }
+    }
+
+    // What the code wrote since the last call.
+    #[wasm_bindgen(js_name = takeOutput)]
+    pub fn take_output(&self) -> String {
+        self.output.take()
+    }
+}
+
+impl Default for Playground {
+    fn default() -> Self {
+        Playground::new()
+    }
+}
@@ -0,0 +1,65 @@
+#![cfg(any(feature = "proptest", feature = "quickcheck"))]
+
+use fundot::object::Object;
+
+fn reads_back(obj: &Object) -> bool {
+    obj.to_string().parse::<Object>().ok().as_ref() == Some(obj)
+}
+
+#[cfg(feature = "proptest")]
+mod proptests {
+    use super::reads_back;
+    use fundot::arbitrary::{atom, object_from, ArbitraryLimits};
+    use fundot::evaluator::{Evaluator, Sandbox};
+    use fundot::object::Object;
+    use fundot::symbol::Symbol;
+    use proptest::prelude::*;
+
+    const SPECIAL_FORMS: [&str; 7] = ["do", "if", "let", "quote", "set", "fn", "fn*"];
+
+    fn evaluator() -> Evaluator {
+        Evaluator::builder()
+            .modules(&[])
+            .sandbox(Sandbox::pure())
+            .fuel(10_000)
+            .build()
+    }
+
+    // Random forms whose heads are mostly names the evaluator knows, so
+    // that they reach builtins rather than stopping at unbound symbols.
+    fn program() -> BoxedStrategy<Object> {
+        let mut names = evaluator().names();
+        names.extend(SPECIAL_FORMS.iter().map(|name| name.to_string()));
+        let name = prop::sample::select(names).prop_map(|name| Object::Symbol(Symbol::new(&name)));
+        let leaf = prop_oneof![atom(), name].boxed();
+        object_from(
+            leaf,
+            ArbitraryLimits {
+                max_depth: 3,
+                max_elements: 4,
+                max_size: 24,
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn printed_objects_read_back(obj: Object) {
+            prop_assert!(reads_back(&obj), "{} did not read back", obj);
+        }
+
+        #[test]
+        fn evaluation_stops(form in program()) {
+            let _ = evaluator().try_eval(&form);
+        }
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+#[test]
+fn quickcheck_objects_read_back() {
+    fn property(obj: Object) -> bool {
+        reads_back(&obj)
+    }
+    quickcheck::quickcheck(property as fn(Object) -> bool);
+}
@@ -0,0 +1,42 @@
+use fundot::binary::BinaryObjectError;
+use fundot::object::Object;
+use fundot::symbol::Symbol;
+
+// The encoding of a symbol named name, made without interning it.
+fn symbol_bytes(name: &str) -> Vec<u8> {
+    let mut bytes = Object::String(name.into()).to_bytes().unwrap();
+    let tag = bytes.iter().position(|&byte| byte == 5).unwrap();
+    bytes[tag] = 6;
+    bytes
+}
+
+#[test]
+fn decoding_adds_no_symbols_past_the_limit() {
+    let known = Object::from_bytes(&symbol_bytes("known-before-the-flood")).unwrap();
+    for i in 0..70_000 {
+        Symbol::new(&format!("flood-{}", i));
+    }
+    assert!(matches!(
+        Object::from_bytes(&symbol_bytes("new-after-the-flood")),
+        Err(BinaryObjectError::Symbols)
+    ));
+    assert_eq!(
+        Object::from_bytes(&symbol_bytes("known-before-the-flood")).unwrap(),
+        known
+    );
+}
+
+fn nested(depth: usize) -> Object {
+    (0..depth).fold(Object::Null, |obj, _| Object::List(vec![obj]))
+}
+
+#[test]
+fn values_too_deep_to_decode_are_not_encoded() {
+    let obj = nested(64);
+    assert_eq!(Object::from_bytes(&obj.to_bytes().unwrap()).unwrap(), obj);
+    assert!(nested(512).to_bytes().is_ok());
+    assert!(matches!(
+        nested(513).to_bytes(),
+        Err(BinaryObjectError::Depth)
+    ));
+}
@@ -0,0 +1,72 @@
+#![cfg(feature = "fundot-capi")]
+
+use fundot::capi::*;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+unsafe fn eval(fundot: *const fundot::evaluator::Evaluator, source: &str) -> (bool, String) {
+    let source = CString::new(source).unwrap();
+    let result = fundot_eval_str(fundot, source.as_ptr());
+    let is_error = fundot_result_is_error(result);
+    let text = match is_error {
+        true => fundot_result_to_string(result),
+        false => fundot_result_to_json(result),
+    };
+    let string = CStr::from_ptr(text).to_str().unwrap().to_string();
+    fundot_string_free(text);
+    fundot_result_free(result);
+    (is_error, string)
+}
+
+extern "C" fn count(call: *mut FundotCall, args_json: *const c_char, user_data: *mut c_void) {
+    let calls = unsafe { &*(user_data as *const AtomicUsize) };
+    calls.fetch_add(1, Ordering::SeqCst);
+    let args = unsafe { CStr::from_ptr(args_json) };
+    let json = CString::new(format!("{{\"args\": {}}}", args.to_str().unwrap())).unwrap();
+    unsafe { fundot_call_return_json(call, json.as_ptr()) };
+}
+
+#[test]
+fn evaluates_and_encodes_results() {
+    unsafe {
+        let fundot = fundot_new();
+        assert_eq!(
+            eval(fundot, "(set x 40) (+ x 2)"),
+            (false, "42".to_string())
+        );
+        assert_eq!(
+            eval(fundot, "[x, \"s\", null]"),
+            (false, "[\"x\",\"s\",null]".to_string())
+        );
+        assert_eq!(
+            eval(fundot, "(+ 1"),
+            (true, "syntax error: could not parse input".to_string())
+        );
+        fundot_free(fundot);
+    }
+}
+
+#[test]
+fn registered_functions_call_back() {
+    let calls = AtomicUsize::new(0);
+    unsafe {
+        let fundot = fundot_new();
+        let name = CString::new("host").unwrap();
+        let user_data = &calls as *const AtomicUsize as *mut c_void;
+        assert_eq!(
+            fundot_register_fn(fundot, name.as_ptr(), Some(count), user_data),
+            0
+        );
+        assert_eq!(
+            eval(fundot, "(get (host 1 [2] \"three\") args)"),
+            (false, "[1,[2],\"three\"]".to_string())
+        );
+        assert_eq!(
+            fundot_register_fn(fundot, name.as_ptr(), None, user_data),
+            -1
+        );
+        fundot_free(fundot);
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,56 @@
+use fundot::compiler::CompiledProgram;
+use fundot::evaluator::Evaluator;
+use fundot::object::Object;
+
+fn eval(evaluator: &Evaluator, source: &str) -> Object {
+    evaluator.eval_str(source).unwrap()
+}
+
+#[test]
+fn functions_compile_to_bytecode() {
+    let evaluator = Evaluator::new();
+    let listing = eval(&evaluator, "(disassemble (fn (x) (+ x 1)))");
+    let listing = listing.to_string();
+    assert!(listing.contains("GET_LOCAL"));
+    assert!(listing.contains("CALL"));
+    assert!(listing.contains("RETURN"));
+}
+
+#[test]
+fn compiled_functions_close_over_and_recurse() {
+    let evaluator = Evaluator::new();
+    eval(&evaluator, "(set make-adder (fn (n) (fn (x) (+ x n))))");
+    assert_eq!(eval(&evaluator, "((make-adder 2) 40)"), Object::Integer(42));
+    eval(
+        &evaluator,
+        "(set fact (fn (n) (if (< n 2) 1 (* n (fact (- n 1))))))",
+    );
+    assert_eq!(eval(&evaluator, "(fact 10)"), Object::Integer(3_628_800));
+}
+
+#[test]
+fn compiled_loops_and_scopes() {
+    let evaluator = Evaluator::new();
+    let sum = "((fn (n)
+                  (let ((i 0) (total 0))
+                    (while (< i n) (do (set total (+ total i)) (set i (+ i 1))))
+                    total))
+                5)";
+    assert_eq!(eval(&evaluator, sum), Object::Integer(10));
+    assert_eq!(
+        eval(&evaluator, "((fn (x) (quote (a b))) 1)"),
+        "(a b)".parse().unwrap()
+    );
+}
+
+#[test]
+fn compiled_programs_run_every_form_and_survive_bytes() {
+    let evaluator = Evaluator::new();
+    let source = "(set x 20) (set y 1) (+ x y)";
+    let program = evaluator.compile(source).unwrap();
+    assert!(program.matches(source));
+    assert_eq!(evaluator.execute(&program), Ok(Object::Integer(21)));
+    let bytes = program.to_bytes().unwrap();
+    let program = CompiledProgram::from_bytes(&bytes).unwrap();
+    assert_eq!(Evaluator::new().execute(&program), Ok(Object::Integer(21)));
+}
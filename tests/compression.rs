@@ -0,0 +1,48 @@
+#![cfg(any(feature = "gzip", feature = "zstd"))]
+
+use fundot::evaluator::Evaluator;
+use fundot::object::{Bytes, Object};
+
+fn parse(s: &str) -> Object {
+    s.parse().unwrap()
+}
+
+fn len(obj: &Object) -> usize {
+    match obj {
+        Object::Other(other) => other.downcast_ref::<Bytes>().unwrap().0.len(),
+        _ => panic!("{} is not bytes", obj),
+    }
+}
+
+// Sets compressed to a megabyte of text compressed with format, which is
+// a few kilobytes at most.
+fn bomb(format: &str) -> Evaluator {
+    let evaluator = Evaluator::new();
+    let text = "x".repeat(1 << 20);
+    let source = format!("(set compressed ({}-compress \"{}\"))", format, text);
+    evaluator.eval_str(&source).unwrap();
+    evaluator
+}
+
+fn check(format: &str) {
+    let evaluator = bomb(format);
+    assert!(len(&evaluator.get("compressed").unwrap()) < 4096);
+    let decompress = parse(&format!("({}-decompress compressed)", format));
+    assert_eq!(
+        evaluator.eval_with_memory_limit(&decompress, 100_000),
+        Ok(Object::Null)
+    );
+    assert_eq!(len(&evaluator.eval(&decompress)), 1 << 20);
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn gzip_output_past_the_memory_limit_is_null() {
+    check("gzip");
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_output_past_the_memory_limit_is_null() {
+    check("zstd");
+}
@@ -0,0 +1,58 @@
+use fundot::evaluator::Evaluator;
+use fundot::object::Object;
+
+fn eval(evaluator: &Evaluator, source: &str) -> Object {
+    evaluator.eval_str(source).unwrap()
+}
+
+#[test]
+fn shift_captures_up_to_the_nearest_reset() {
+    let evaluator = Evaluator::new();
+    assert_eq!(
+        eval(&evaluator, "(reset (+ 1 (shift k (k (k 10)))))"),
+        Object::Integer(12)
+    );
+    assert_eq!(
+        eval(
+            &evaluator,
+            "(+ 100 (reset (* 2 (shift k (+ (k 1) (k 2))))))"
+        ),
+        Object::Integer(106)
+    );
+    assert_eq!(eval(&evaluator, "(reset 7)"), Object::Integer(7));
+}
+
+#[test]
+fn shift_without_calling_k_escapes() {
+    let evaluator = Evaluator::new();
+    assert_eq!(
+        eval(&evaluator, "(reset (+ 1 (shift k 5)))"),
+        Object::Integer(5)
+    );
+    eval(&evaluator, "(set after false)");
+    assert_eq!(
+        eval(
+            &evaluator,
+            "(reset (do (shift k \"escaped\") (set after true)))"
+        ),
+        Object::from("escaped")
+    );
+    assert_eq!(eval(&evaluator, "after"), Object::Bool(false));
+    // The top level delimits a shift with no reset around it.
+    assert_eq!(eval(&evaluator, "(+ 1 (shift k 1))"), Object::Integer(1));
+}
+
+#[test]
+fn continuations_can_be_kept_and_resumed_later() {
+    let evaluator = Evaluator::new();
+    assert_eq!(
+        eval(&evaluator, "(reset (+ 1 (shift k (do (set saved k) 0))))"),
+        Object::Integer(0)
+    );
+    assert_eq!(eval(&evaluator, "(saved 41)"), Object::Integer(42));
+    assert_eq!(eval(&evaluator, "(saved 1)"), Object::Integer(2));
+    assert_eq!(
+        eval(&evaluator, "((fn () (reset (+ 1 (shift k (k 2))))))"),
+        Object::Integer(3)
+    );
+}
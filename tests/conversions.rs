@@ -0,0 +1,46 @@
+use fundot::error::Error;
+use fundot::evaluator::{EvalError, Evaluator};
+
+#[test]
+fn eval_as_converts_the_last_form() {
+    let evaluator = Evaluator::new();
+    assert_eq!(evaluator.eval_as::<i64>("1 2").unwrap(), 2);
+    assert_eq!(evaluator.eval_as::<i64>("(set x 20) (+ x 1)").unwrap(), 21);
+    assert_eq!(
+        evaluator.eval_as::<Vec<i64>>("[1, 2, 3]").unwrap(),
+        vec![1, 2, 3]
+    );
+}
+
+#[test]
+fn eval_as_reports_every_kind_of_failure() {
+    let evaluator = Evaluator::new();
+    assert!(matches!(
+        evaluator.eval_as::<i64>("1 (2"),
+        Err(Error::Parse(_))
+    ));
+    assert!(matches!(
+        evaluator.eval_as::<i64>("1 (exit 3)"),
+        Err(Error::Eval(EvalError::Exit(3)))
+    ));
+    assert!(matches!(
+        evaluator.eval_as::<i64>("1 \"two\""),
+        Err(Error::Convert(_))
+    ));
+}
+
+#[test]
+fn conversions_do_not_hide_type_errors() {
+    let evaluator = Evaluator::new();
+    assert!(evaluator.eval_as::<bool>("true").unwrap());
+    assert!(matches!(
+        evaluator.eval_as::<bool>("null"),
+        Err(Error::Convert(_))
+    ));
+    assert_eq!(evaluator.eval_as::<Option<bool>>("null").unwrap(), None);
+    assert_eq!(evaluator.eval_as::<String>("\"a\"").unwrap(), "a");
+    assert!(matches!(
+        evaluator.eval_as::<String>("(quote a)"),
+        Err(Error::Convert(_))
+    ));
+}
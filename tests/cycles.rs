@@ -0,0 +1,74 @@
+use fundot::evaluator::Evaluator;
+use fundot::object::Object;
+
+fn eval(evaluator: &Evaluator, source: &str) -> Object {
+    evaluator.eval_str(source).unwrap()
+}
+
+// A generator whose saved stack holds the generator itself.
+const SELF_HOLDING_GENERATOR: &str =
+    "(set g ((fn* () (let ((me g)) (yield 1) (yield me) (yield 3))))) (next g)";
+
+#[test]
+fn reachable_cycles_are_kept() {
+    let evaluator = Evaluator::new();
+    eval(&evaluator, SELF_HOLDING_GENERATOR);
+    eval(&evaluator, "(set h (let ((x g)) (fn () x))) (set g null)");
+    assert_eq!(evaluator.collect_cycles(), 0);
+    eval(&evaluator, "(next (h))");
+    assert_eq!(eval(&evaluator, "(next (h))"), Object::Integer(3));
+}
+
+#[test]
+fn unreachable_generator_cycles_are_freed() {
+    let evaluator = Evaluator::new();
+    eval(&evaluator, SELF_HOLDING_GENERATOR);
+    eval(&evaluator, "(set g null)");
+    assert!(evaluator.collect_cycles() > 0);
+    assert_eq!(evaluator.collect_cycles(), 0);
+}
+
+#[cfg(not(feature = "rc"))]
+#[test]
+fn unreachable_thread_cycles_are_freed() {
+    let evaluator = Evaluator::new();
+    eval(
+        &evaluator,
+        "(set ready (chan))
+         (set t (spawn (fn () (do (recv ready) (let ((me t)) (fn () me))))))
+         (send ready true)
+         (join t)",
+    );
+    assert_eq!(evaluator.collect_cycles(), 0);
+    eval(&evaluator, "(set t null)");
+    assert!(evaluator.collect_cycles() > 0);
+}
+
+#[cfg(not(feature = "rc"))]
+#[test]
+fn channels_holding_themselves_are_freed() {
+    let evaluator = Evaluator::new();
+    eval(&evaluator, "(set c (chan)) (send c c)");
+    assert_eq!(evaluator.collect_cycles(), 0);
+    eval(&evaluator, "(set c null)");
+    assert_eq!(evaluator.collect_cycles(), 1);
+}
+
+#[cfg(not(feature = "rc"))]
+#[test]
+fn channels_still_deliver_in_order() {
+    let evaluator = Evaluator::new();
+    eval(&evaluator, "(set c (chan)) (send c 1) (send c 2)");
+    evaluator.collect_cycles();
+    assert_eq!(eval(&evaluator, "(recv c)"), Object::Integer(1));
+    assert_eq!(eval(&evaluator, "(recv c)"), Object::Integer(2));
+}
+
+#[test]
+fn memoized_functions_caching_themselves_are_freed() {
+    let evaluator = Evaluator::new();
+    eval(&evaluator, "(set m (memoize (fn (x) m))) (m 1)");
+    assert_eq!(evaluator.collect_cycles(), 0);
+    eval(&evaluator, "(set m null)");
+    assert!(evaluator.collect_cycles() > 0);
+}
@@ -0,0 +1,164 @@
+#![cfg(all(feature = "json", not(feature = "rc")))]
+
+use fundot::dap::Adapter;
+use fundot::evaluator::Evaluator;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, BufReader, Cursor, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Output the test can read once the adapter is done with it.
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn frame(messages: &[Value]) -> Vec<u8> {
+    let mut input = Vec::new();
+    for message in messages {
+        let body = message.to_string();
+        write!(input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    }
+    input
+}
+
+fn messages(output: &Output) -> Vec<Value> {
+    let output = output.0.lock().unwrap();
+    let text = String::from_utf8_lossy(&output);
+    text.split("Content-Length: ")
+        .filter_map(|message| message.split_once("\r\n\r\n"))
+        .filter_map(|(_, body)| serde_json::from_str::<Value>(body).ok())
+        .collect()
+}
+
+// The responses the adapter sent, in order.
+fn responses(output: &Output) -> Vec<Value> {
+    messages(output)
+        .into_iter()
+        .filter(|message| message["type"] == "response")
+        .collect()
+}
+
+// Waits for the adapter to send a message matching found.
+fn wait_for(output: &Output, found: impl Fn(&Value) -> bool) -> Value {
+    let start = Instant::now();
+    loop {
+        if let Some(message) = messages(output).into_iter().find(&found) {
+            return message;
+        }
+        assert!(start.elapsed() < Duration::from_secs(10), "no such message");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn request(seq: u64, command: &str, arguments: Value) -> Value {
+    json!({"seq": seq, "type": "request", "command": command, "arguments": arguments})
+}
+
+#[test]
+fn huge_messages_are_refused() {
+    let output = Output::default();
+    let adapter = Adapter::new(Arc::new(Evaluator::new()), output.clone());
+    let input = b"Content-Length: 99999999999\r\n\r\n{}".to_vec();
+    let error = adapter.serve(Cursor::new(input)).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn requests_need_the_token() {
+    let output = Output::default();
+    let adapter = Adapter::new(Arc::new(Evaluator::new()), output.clone()).token("secret");
+    let input = frame(&[
+        request(1, "initialize", json!({})),
+        request(2, "evaluate", json!({"expression": "(+ 1 2)"})),
+        request(3, "evaluate", json!({"expression": "(+ 1 2)"})),
+    ]);
+    adapter.serve(Cursor::new(input)).unwrap();
+    let responses = responses(&output);
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["success"], true);
+    assert_eq!(responses[1]["success"], false);
+    assert_eq!(responses[1]["message"], "unauthorized");
+}
+
+#[test]
+fn launch_with_the_wrong_token_is_refused() {
+    let output = Output::default();
+    let adapter = Adapter::new(Arc::new(Evaluator::new()), output.clone()).token("secret");
+    let input = frame(&[request(
+        1,
+        "launch",
+        json!({"program": "Cargo.toml", "token": "guess"}),
+    )]);
+    adapter.serve(Cursor::new(input)).unwrap();
+    let responses = responses(&output);
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0]["message"], "unauthorized");
+}
+
+#[test]
+fn launch_with_the_token_is_served() {
+    let output = Output::default();
+    let adapter = Adapter::new(Arc::new(Evaluator::new()), output.clone()).token("secret");
+    let input = frame(&[
+        request(
+            1,
+            "launch",
+            json!({"program": "missing.fd", "token": "secret"}),
+        ),
+        request(2, "initialize", json!({})),
+    ]);
+    adapter.serve(Cursor::new(input)).unwrap();
+    let responses = responses(&output);
+    assert_eq!(responses.len(), 2);
+    // Authorized, so the launch fails only for the file it names.
+    assert!(responses[0]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("could not read missing.fd"));
+    assert_eq!(responses[1]["success"], true);
+}
+
+#[test]
+fn programs_stop_evaluate_and_run_to_the_end() {
+    let path = std::env::temp_dir().join(format!("fundot-dap-{}.fd", std::process::id()));
+    fs::write(&path, "(set x 20)\n(set y (+ x 1))\n").unwrap();
+    let output = Output::default();
+    let adapter = Adapter::new(Arc::new(Evaluator::new()), output.clone());
+    let (reader, mut writer) = io::pipe().unwrap();
+    let serving = thread::spawn(move || adapter.serve(BufReader::new(reader)));
+    let launch = json!({"program": path.to_str().unwrap(), "stopOnEntry": true});
+    writer
+        .write_all(&frame(&[
+            request(1, "initialize", json!({})),
+            request(2, "launch", launch),
+            request(3, "configurationDone", json!({})),
+        ]))
+        .unwrap();
+    wait_for(&output, |message| message["event"] == "stopped");
+    let expression = json!({"expression": "(+ 1 2)"});
+    writer
+        .write_all(&frame(&[request(4, "evaluate", expression)]))
+        .unwrap();
+    let evaluated = wait_for(&output, |message| message["request_seq"] == 4);
+    assert_eq!(evaluated["body"]["result"], "3");
+    writer
+        .write_all(&frame(&[request(5, "continue", json!({}))]))
+        .unwrap();
+    let exited = wait_for(&output, |message| message["event"] == "exited");
+    assert_eq!(exited["body"]["exitCode"], 0);
+    drop(writer);
+    serving.join().unwrap().unwrap();
+    fs::remove_file(path).unwrap();
+}
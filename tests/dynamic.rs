@@ -0,0 +1,72 @@
+use fundot::evaluator::Evaluator;
+use fundot::object::Object;
+
+fn eval(evaluator: &Evaluator, source: &str) -> Object {
+    evaluator.eval_str(source).unwrap()
+}
+
+fn evaluator() -> Evaluator {
+    let evaluator = Evaluator::new();
+    eval(
+        &evaluator,
+        "(defdynamic *depth* 0) (set show (fn () *depth*))",
+    );
+    evaluator
+}
+
+#[test]
+fn bindings_reach_the_functions_called_in_them() {
+    let evaluator = evaluator();
+    assert_eq!(eval(&evaluator, "(show)"), Object::Integer(0));
+    assert_eq!(
+        eval(&evaluator, "(binding ((*depth* 1)) (show))"),
+        Object::Integer(1)
+    );
+    assert_eq!(
+        eval(&evaluator, "(binding [*depth*, 4] (show))"),
+        Object::Integer(4)
+    );
+    assert_eq!(
+        eval(
+            &evaluator,
+            "(binding ((*depth* 1)) (binding ((*depth* 2)) (show)))"
+        ),
+        Object::Integer(2)
+    );
+    assert_eq!(eval(&evaluator, "(show)"), Object::Integer(0));
+}
+
+#[test]
+fn set_inside_a_binding_is_undone_with_it() {
+    let evaluator = evaluator();
+    assert_eq!(
+        eval(
+            &evaluator,
+            "(binding ((*depth* 1)) (do (set *depth* 5) (show)))"
+        ),
+        Object::Integer(5)
+    );
+    assert_eq!(eval(&evaluator, "(show)"), Object::Integer(0));
+}
+
+#[test]
+fn bindings_are_undone_when_evaluation_fails() {
+    let evaluator = evaluator();
+    let endless = "(binding ((*depth* 1)) (while true null))".parse().unwrap();
+    assert!(evaluator.eval_with_fuel(&endless, 1000).is_err());
+    assert_eq!(eval(&evaluator, "(show)"), Object::Integer(0));
+}
+
+#[test]
+fn generators_see_the_bindings_they_are_resumed_in() {
+    let evaluator = evaluator();
+    eval(
+        &evaluator,
+        "(set g ((fn* () (yield *depth*) (yield *depth*))))",
+    );
+    assert_eq!(
+        eval(&evaluator, "(binding ((*depth* 9)) (next g))"),
+        Object::Integer(9)
+    );
+    assert_eq!(eval(&evaluator, "(next g)"), Object::Integer(0));
+}
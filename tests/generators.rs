@@ -0,0 +1,51 @@
+use fundot::evaluator::Evaluator;
+use fundot::object::Object;
+
+fn eval(evaluator: &Evaluator, source: &str) -> Object {
+    evaluator.eval_str(source).unwrap()
+}
+
+fn parse(s: &str) -> Object {
+    s.parse().unwrap()
+}
+
+#[test]
+fn generators_yield_in_order_then_finish() {
+    let evaluator = Evaluator::new();
+    eval(&evaluator, "(set g ((fn* (n) (yield 1) (yield n)) 2))");
+    assert_eq!(eval(&evaluator, "(done? g)"), Object::Bool(false));
+    assert_eq!(eval(&evaluator, "(next g)"), Object::Integer(1));
+    assert_eq!(eval(&evaluator, "(next g)"), Object::Integer(2));
+    assert_eq!(eval(&evaluator, "(next g)"), Object::Null);
+    assert_eq!(eval(&evaluator, "(done? g)"), Object::Bool(true));
+    assert_eq!(eval(&evaluator, "(next g)"), Object::Null);
+}
+
+#[test]
+fn endless_generators_are_lazy() {
+    let evaluator = Evaluator::new();
+    eval(
+        &evaluator,
+        "(set naturals (fn* () (let ((i 0)) (while true (do (yield i) (set i (+ i 1)))))))",
+    );
+    assert_eq!(
+        eval(&evaluator, "(take 4 (naturals))"),
+        parse("[0, 1, 2, 3]")
+    );
+    eval(&evaluator, "(set g (naturals)) (take 2 g)");
+    assert_eq!(eval(&evaluator, "(take 2 g)"), parse("[2, 3]"));
+}
+
+#[test]
+fn each_call_makes_its_own_generator() {
+    let evaluator = Evaluator::new();
+    eval(
+        &evaluator,
+        "(set count-to (fn* (n) (let ((i 1)) (while (< i (+ n 1)) (do (yield i) (set i (+ i 1)))))))
+         (set a (count-to 3))
+         (set b (count-to 2))
+         (next a)",
+    );
+    assert_eq!(eval(&evaluator, "(collect b)"), parse("[1, 2]"));
+    assert_eq!(eval(&evaluator, "(collect a)"), parse("[2, 3]"));
+}
@@ -0,0 +1,92 @@
+use fundot::evaluator::{EvalError, Evaluator};
+use fundot::object::Object;
+use std::time::{Duration, Instant};
+
+fn parse(s: &str) -> Object {
+    s.parse().unwrap()
+}
+
+#[test]
+fn strings_count_against_the_memory_limit() {
+    let evaluator = Evaluator::new();
+    let text = "x".repeat(100_000);
+    let form = parse(&format!("(bytes-to-string (bytes \"{}\"))", text));
+    assert_eq!(
+        evaluator.eval_with_memory_limit(&form, 10_000),
+        Err(EvalError::MemoryLimitExceeded)
+    );
+    assert!(evaluator.eval_with_memory_limit(&form, 1_000_000).is_ok());
+}
+
+#[test]
+fn bytes_count_against_the_memory_limit() {
+    let evaluator = Evaluator::new();
+    let text = "x".repeat(100_000);
+    let form = parse(&format!("(bytes \"{}\")", text));
+    assert_eq!(
+        evaluator.eval_with_memory_limit(&form, 10_000),
+        Err(EvalError::MemoryLimitExceeded)
+    );
+    assert!(evaluator.eval_with_memory_limit(&form, 1_000_000).is_ok());
+}
+
+#[test]
+fn fuel_stops_endless_loops() {
+    let evaluator = Evaluator::new();
+    for source in [
+        "(while true null)",
+        "(eval (quote (while true null)))",
+        "((fn (f) (f)) (fn () (while true null)))",
+    ] {
+        assert_eq!(
+            evaluator.eval_with_fuel(&parse(source), 10_000),
+            Err(EvalError::BudgetExceeded),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn fuel_is_counted_per_evaluation() {
+    let evaluator = Evaluator::new();
+    let form = parse("(+ 1 2)");
+    assert_eq!(
+        evaluator.eval_with_fuel(&form, 1),
+        Err(EvalError::BudgetExceeded)
+    );
+    for _ in 0..3 {
+        assert_eq!(evaluator.eval_with_fuel(&form, 100), Ok(Object::Integer(3)));
+    }
+    assert_eq!(evaluator.eval(&form), Object::Integer(3));
+}
+
+#[test]
+fn timeouts_stop_endless_loops() {
+    let evaluator = Evaluator::new();
+    let start = Instant::now();
+    assert_eq!(
+        evaluator.eval_with_timeout(&parse("(while true null)"), Duration::from_millis(50)),
+        Err(EvalError::Timeout)
+    );
+    assert!(start.elapsed() < Duration::from_secs(5));
+    let quick = parse("(+ 1 2)");
+    assert_eq!(
+        evaluator.eval_with_timeout(&quick, Duration::from_secs(5)),
+        Ok(Object::Integer(3))
+    );
+}
+
+#[test]
+fn interrupts_are_taken_by_one_evaluation() {
+    let evaluator = Evaluator::new();
+    evaluator.interrupt_handle().interrupt();
+    assert_eq!(
+        evaluator.try_eval(&parse("(while true null)")),
+        Err(EvalError::Interrupted)
+    );
+    assert_eq!(
+        evaluator.try_eval(&parse("(+ 1 2)")),
+        Ok(Object::Integer(3))
+    );
+}
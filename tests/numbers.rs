@@ -0,0 +1,39 @@
+use fundot::object::Object;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+fn hash(obj: &Object) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    obj.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn integers_equal_only_the_floats_they_are_exactly() {
+    let big = 1i64 << 53;
+    assert_eq!(Object::Integer(big), Object::Float(big as f64));
+    assert_ne!(Object::Integer(big + 1), Object::Float(big as f64));
+    assert_ne!(Object::Float(big as f64), Object::Integer(big + 1));
+    assert_eq!(Object::Integer(i64::MIN), Object::Float(i64::MIN as f64));
+    assert_ne!(Object::Integer(i64::MAX), Object::Float(i64::MAX as f64));
+    assert_ne!(Object::Integer(0), Object::Float(f64::NAN));
+}
+
+#[test]
+fn equal_numbers_hash_alike() {
+    for n in [0, -1, 1 << 53, i64::MIN] {
+        assert_eq!(hash(&Object::Integer(n)), hash(&Object::Float(n as f64)));
+    }
+    assert_eq!(hash(&Object::Integer(0)), hash(&Object::Float(-0.0)));
+}
+
+#[test]
+fn nearby_integer_and_float_keys_stay_apart() {
+    let big = 1i64 << 53;
+    let mut map = HashMap::new();
+    map.insert(Object::Float(big as f64), Object::from("float"));
+    map.insert(Object::Integer(big + 1), Object::from("integer"));
+    assert_eq!(map.len(), 2);
+    assert_eq!(map[&Object::Integer(big)], Object::from("float"));
+}
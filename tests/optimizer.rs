@@ -0,0 +1,64 @@
+use fundot::evaluator::Evaluator;
+use fundot::object::Object;
+
+fn parse(s: &str) -> Object {
+    s.parse().unwrap()
+}
+
+#[test]
+fn constant_calls_and_conditions_fold() {
+    let evaluator = Evaluator::new();
+    assert_eq!(
+        evaluator.optimize(&parse("(+ 1 (* 2 3))")),
+        Object::Integer(7)
+    );
+    assert_eq!(
+        evaluator.optimize(&parse("(if false 1 2)")),
+        Object::Integer(2)
+    );
+    assert_eq!(
+        evaluator.optimize(&parse("(if true (f 1) (g 2))")),
+        parse("(f 1)")
+    );
+    assert_eq!(
+        evaluator.optimize(&parse("(quote (+ 1 2))")),
+        parse("(quote (+ 1 2))")
+    );
+}
+
+#[test]
+fn locals_and_assigned_names_are_not_folded() {
+    let evaluator = Evaluator::new();
+    let shadowed = parse("(let ((+ -)) (+ 5 2))");
+    assert_eq!(
+        evaluator.eval(&evaluator.optimize(&shadowed)),
+        Object::Integer(3)
+    );
+    let assigned = parse("(do (set + -) (+ 5 2))");
+    let optimized = evaluator.optimize(&assigned);
+    assert_eq!(evaluator.eval(&optimized), Object::Integer(3));
+}
+
+#[test]
+fn optimized_forms_evaluate_like_the_originals() {
+    let evaluator = Evaluator::new();
+    evaluator
+        .eval_str("(set fib (fn (n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))))")
+        .unwrap();
+    for source in [
+        "(fib 12)",
+        "(+ 1 \"a\")",
+        "(get [1, 2, 3] (+ 0 1))",
+        "((fn (x) (if (< x 0) (- 0 x) x)) -4)",
+        "(let ((a 2)) (* a (+ 1 2)))",
+    ] {
+        let form = parse(source);
+        let optimized = evaluator.optimize(&form);
+        assert_eq!(
+            evaluator.eval(&optimized),
+            evaluator.eval(&form),
+            "{}",
+            source
+        );
+    }
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "json")]
+
+use fundot::evaluator::Evaluator;
+use fundot::object::Object;
+
+fn parse(s: &str) -> Object {
+    s.parse().unwrap()
+}
+
+fn users() -> Evaluator {
+    let evaluator = Evaluator::new();
+    let json = r#"{"users": [{"name": "ann", "age": 31},
+                              {"name": "bob", "age": 25},
+                              {"name": "cy", "age": 40}]}"#;
+    evaluator
+        .eval_str(&format!("(set data (json-decode {:?}))", json))
+        .unwrap();
+    evaluator
+}
+
+fn query(evaluator: &Evaluator, query: &str) -> Object {
+    evaluator
+        .eval_str(&format!("(query data {:?})", query))
+        .unwrap()
+}
+
+#[test]
+fn paths_and_filters() {
+    let evaluator = users();
+    assert_eq!(
+        query(&evaluator, ".users[] | select(.age > 30) | .name"),
+        parse(r#"["ann", "cy"]"#)
+    );
+    assert_eq!(query(&evaluator, ".users[0].name"), parse(r#"["ann"]"#));
+    assert_eq!(query(&evaluator, ".users[-1].age"), parse("[40]"));
+    assert_eq!(query(&evaluator, ".users[1:3] | length"), parse("[2]"));
+    assert_eq!(
+        query(&evaluator, ".missing // \"default\""),
+        parse(r#"["default"]"#)
+    );
+}
+
+#[test]
+fn functions_and_constructors() {
+    let evaluator = users();
+    assert_eq!(query(&evaluator, ".users | map(.age) | add"), parse("[96]"));
+    assert_eq!(
+        query(&evaluator, ".users | sort_by(.age) | first | .name"),
+        parse(r#"["bob"]"#)
+    );
+    assert_eq!(query(&evaluator, "[.users[] | .age] | max"), parse("[40]"));
+    assert_eq!(
+        query(&evaluator, ".users[] | {n: .name}"),
+        parse(r#"[{n: "ann"}, {n: "bob"}, {n: "cy"}]"#)
+    );
+    assert_eq!(query(&evaluator, "1, 2"), parse("[1, 2]"));
+    assert_eq!(query(&evaluator, "7 / 2"), parse("[3]"));
+}
+
+#[test]
+fn symbol_keys_are_read_like_string_keys() {
+    let evaluator = Evaluator::new();
+    evaluator
+        .eval_str("(set data (quote {port: 8080, hosts: [\"a\", \"b\"]}))")
+        .unwrap();
+    assert_eq!(query(&evaluator, ".port"), parse("[8080]"));
+    assert_eq!(query(&evaluator, ".hosts | length"), parse("[2]"));
+}
+
+#[test]
+fn malformed_queries_give_null() {
+    let evaluator = users();
+    assert_eq!(query(&evaluator, ".users["), Object::Null);
+    assert_eq!(query(&evaluator, "nosuchfunction(1)"), Object::Null);
+}
@@ -0,0 +1,103 @@
+use fundot::evaluator::Evaluator;
+use fundot::object::Object;
+use fundot::schema::{Schema, ValidationError};
+
+fn parse(s: &str) -> Object {
+    s.parse().unwrap()
+}
+
+fn validate(schema: &str, value: &str) -> Result<(), Vec<ValidationError>> {
+    schema.parse::<Schema>().unwrap().validate(&parse(value))
+}
+
+fn messages(schema: &str, value: &str) -> Vec<String> {
+    match validate(schema, value) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors.iter().map(ToString::to_string).collect(),
+    }
+}
+
+const CONFIG: &str = "{port: (int (range 1 65535)), hosts: [string], mode: (one-of fast safe), \
+                      timeout: (optional number), tls: (or null {cert: string, key: string})}";
+
+#[test]
+fn matching_values_have_no_errors() {
+    assert_eq!(
+        validate(
+            CONFIG,
+            "{port: 80, hosts: [\"a\", \"b\"], mode: fast, tls: null}"
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        validate(
+            CONFIG,
+            "{port: 443, hosts: [], mode: safe, timeout: 1.5, \
+             tls: {cert: \"c\", key: \"k\"}}"
+        ),
+        Ok(())
+    );
+    assert_eq!(validate("(string (length 1 3))", "\"abc\""), Ok(()));
+    assert_eq!(validate("(range null 10)", "-5"), Ok(()));
+}
+
+#[test]
+fn errors_give_the_path_and_what_was_expected() {
+    assert_eq!(
+        messages(
+            CONFIG,
+            "{port: \"80\", hosts: [\"a\", 2], mode: slow, tls: {cert: \"c\"}}"
+        ),
+        vec![
+            ".hosts[1]: expected string, got 2",
+            ".mode: expected one of fast, safe, got slow",
+            ".port: expected int, got \"80\"",
+            ".tls: expected null or map, got {cert: \"c\"}",
+        ]
+    );
+    assert_eq!(
+        messages(CONFIG, "{port: 0, hosts: []}"),
+        vec![
+            ".mode: missing, expected one of fast, safe",
+            ".port: expected a number from 1 to 65535, got 0",
+            ".tls: missing, expected null or map",
+        ]
+    );
+    assert_eq!(
+        messages("(string (length 1 3))", "\"abcd\""),
+        vec![".: expected a length from 1 to 3, got \"abcd\""]
+    );
+}
+
+#[test]
+fn string_keys_match_symbol_keys() {
+    assert_eq!(validate("{name: string}", "{\"name\": \"ann\"}"), Ok(()));
+}
+
+#[test]
+fn malformed_schemas_are_refused() {
+    assert!("[int string]".parse::<Schema>().is_err());
+    assert!("(nothing int)".parse::<Schema>().is_err());
+    assert!("(range a 1)".parse::<Schema>().is_err());
+    assert!("(length -1 null)".parse::<Schema>().is_err());
+    assert!("{".parse::<Schema>().is_err());
+}
+
+#[test]
+fn validate_gives_errors_as_data() {
+    let evaluator = Evaluator::new();
+    assert_eq!(
+        evaluator
+            .eval_str("(validate {port: int} {port: \"80\"})")
+            .unwrap(),
+        parse("[{path: [port], message: \"expected int, got \\\"80\\\"\"}]")
+    );
+    assert_eq!(
+        evaluator.eval_str("(validate [int] [1, 2])").unwrap(),
+        parse("[]")
+    );
+    assert_eq!(
+        evaluator.eval_str("(validate [int, string] 1)").unwrap(),
+        Object::Null
+    );
+}
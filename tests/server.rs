@@ -0,0 +1,60 @@
+#![cfg(all(feature = "json", not(feature = "rc")))]
+
+use fundot::evaluator::Evaluator;
+use fundot::server::Server;
+use std::io::{self, Cursor};
+use std::sync::Arc;
+
+fn session(server: &Server, input: &str) -> String {
+    let mut output = Vec::new();
+    server
+        .session(Cursor::new(input.to_string().into_bytes()), &mut output)
+        .unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn sessions_must_give_the_token() {
+    let server = Server::new(Arc::new(Evaluator::new())).token("secret");
+    assert_eq!(
+        session(&server, "(+ 1 2)\n(+ 3 4)\n"),
+        "error: unauthorized\n"
+    );
+    assert_eq!(session(&server, "secret\n(+ 1 2)\n"), "true\n3\n");
+    assert_eq!(
+        session(
+            &server,
+            "{\"id\": 1, \"op\": \"auth\", \"token\": \"secret\"}\n(+ 1 2)\n"
+        ),
+        "{\"id\":1,\"value\":\"true\"}\n3\n"
+    );
+}
+
+#[test]
+fn interrupts_stop_what_was_sent_before_them() {
+    let server = Server::new(Arc::new(Evaluator::new()));
+    let output = session(
+        &server,
+        "(while true null)\n(while true null)\n{\"op\": \"interrupt\"}\n(+ 1 2)\n",
+    );
+    assert_eq!(output, "error: Interrupted\nerror: Interrupted\n3\n");
+}
+
+#[test]
+fn overlong_lines_end_the_session() {
+    let server = Server::new(Arc::new(Evaluator::new())).token("secret");
+    let input = "x".repeat(2 * 1024 * 1024);
+    let mut output = Vec::new();
+    let error = server
+        .session(Cursor::new(input.into_bytes()), &mut output)
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn many_waiting_requests_are_all_answered() {
+    let server = Server::new(Arc::new(Evaluator::new()));
+    let input = "(+ 1 2)\n".repeat(100);
+    assert_eq!(session(&server, &input), "3\n".repeat(100));
+}
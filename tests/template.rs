@@ -0,0 +1,99 @@
+use fundot::evaluator::{EvalError, Evaluator};
+use fundot::object::Object;
+use fundot::template::{Template, TemplateError};
+use fundot::Error;
+
+fn parse(s: &str) -> Object {
+    s.parse().unwrap()
+}
+
+fn render(text: &str, context: &str) -> String {
+    let template: Template = text.parse().unwrap();
+    template.render(&Evaluator::new(), &parse(context)).unwrap()
+}
+
+#[test]
+fn expressions_are_replaced_by_their_values() {
+    assert_eq!(
+        render(
+            "port {{ (+ port 1) }} on {{ host }}{{ missing }}",
+            "{port: 80, host: \"a\", missing: null}"
+        ),
+        "port 81 on a"
+    );
+    assert_eq!(
+        render("{{ hosts }}", "{\"hosts\": [\"a\", b]}"),
+        "[\"a\", b]"
+    );
+    assert_eq!(render("a{# note #}b", "{}"), "ab");
+}
+
+#[test]
+fn loops_repeat_their_body() {
+    assert_eq!(
+        render(
+            "{% for host in hosts %}\nserver {{ host }}\n{% endfor %}\n",
+            "{hosts: [\"a\", \"b\"]}"
+        ),
+        "server a\nserver b\n"
+    );
+    assert_eq!(
+        render(
+            "{% for key, value in env %}{{ key }}={{ value }};{% endfor %}",
+            "{env: {b: 2, a: 1}}"
+        ),
+        "a=1;b=2;"
+    );
+}
+
+#[test]
+fn conditions_keep_the_first_true_branch() {
+    let text = "{% if (> n 10) %}big{% elif (> n 0) %}small{% else %}none{% endif %}";
+    assert_eq!(render(text, "{n: 20}"), "big");
+    assert_eq!(render(text, "{n: 5}"), "small");
+    assert_eq!(render(text, "{n: 0}"), "none");
+}
+
+#[test]
+fn evaluation_errors_are_passed_on() {
+    let template: Template = "a{{ (exit 3) }}".parse().unwrap();
+    assert_eq!(
+        template.render(&Evaluator::new(), &Object::Null),
+        Err(TemplateError::Eval(EvalError::Exit(3)))
+    );
+}
+
+#[test]
+fn malformed_templates_give_the_line() {
+    let error = "a\n{% for x in xs %}\nb\n{% endif %}"
+        .parse::<Template>()
+        .unwrap_err();
+    assert!(matches!(error, TemplateError::Syntax { line: 4, .. }));
+    let error = "a\n{{ (+ 1 }}".parse::<Template>().unwrap_err();
+    assert!(matches!(error, TemplateError::Syntax { line: 2, .. }));
+    assert!("{% for x in xs %}".parse::<Template>().is_err());
+}
+
+#[test]
+fn render_template_uses_the_interpreter() {
+    let evaluator = Evaluator::new();
+    evaluator
+        .eval_str("(set greet (fn (name) (+ \"hi \" name)))")
+        .unwrap();
+    assert_eq!(
+        evaluator
+            .eval_str("(render-template \"{{ (greet who) }}\" {who: \"ann\"})")
+            .unwrap(),
+        Object::from("hi ann")
+    );
+    assert_eq!(
+        evaluator
+            .eval_str("(render-template \"{% if x %}\" {x: true})")
+            .unwrap(),
+        Object::Null
+    );
+    assert!(matches!(
+        evaluator.eval_str("(render-template \"{{ (exit 3) }}\" {})"),
+        Err(Error::Eval(EvalError::Exit(3)))
+    ));
+}
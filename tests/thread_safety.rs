@@ -0,0 +1,99 @@
+#![cfg(not(feature = "rc"))]
+
+use fundot::evaluator::{EvalError, Evaluator};
+use fundot::object::Object;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn parse(s: &str) -> Object {
+    s.parse().unwrap()
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn evaluator_and_object_are_send_and_sync() {
+    assert_send_sync::<Evaluator>();
+    assert_send_sync::<Object>();
+}
+
+#[test]
+fn concurrent_evaluations_share_functions() {
+    let evaluator = Arc::new(Evaluator::new());
+    evaluator.eval(&parse(
+        "(set fib (fn (n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))))",
+    ));
+    let handles: Vec<_> = (0..8)
+        .map(|i| {
+            let evaluator = evaluator.clone();
+            thread::spawn(move || {
+                let obj = parse(&format!("(fib {})", 10 + i));
+                evaluator.try_eval(&obj).unwrap()
+            })
+        })
+        .collect();
+    let results: Vec<Object> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    let expected = [55, 89, 144, 233, 377, 610, 987, 1597];
+    for (result, expected) in results.iter().zip(expected.iter()) {
+        assert_eq!(*result, Object::Integer(*expected));
+    }
+}
+
+#[test]
+fn global_assignments_are_visible_across_threads() {
+    let evaluator = Arc::new(Evaluator::new());
+    let writer = evaluator.clone();
+    thread::spawn(move || writer.eval(&parse("(set answer 42)")))
+        .join()
+        .unwrap();
+    assert_eq!(evaluator.eval(&parse("answer")), Object::Integer(42));
+}
+
+#[test]
+fn concurrent_assignments_do_not_deadlock() {
+    let evaluator = Arc::new(Evaluator::new());
+    evaluator.eval(&parse("(set counter 0)"));
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let evaluator = evaluator.clone();
+            thread::spawn(move || {
+                let obj = parse(
+                    "(let ((i 0)) (while (< i 100) (set counter (+ counter 1)) (set i (+ i 1))))",
+                );
+                evaluator.try_eval(&obj).unwrap();
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    match evaluator.eval(&parse("counter")) {
+        Object::Integer(n) => assert!(n > 0 && n <= 400),
+        other => panic!("unexpected counter {}", other),
+    }
+}
+
+#[test]
+fn per_call_limits_are_independent() {
+    let evaluator = Arc::new(Evaluator::new());
+    let limited = evaluator.clone();
+    let handle = thread::spawn(move || limited.eval_with_fuel(&parse("(while true 1)"), 1000));
+    let obj = parse("(let ((i 0)) (while (< i 10000) (set i (+ i 1))) i)");
+    assert_eq!(evaluator.try_eval(&obj).unwrap(), Object::Integer(10000));
+    assert_eq!(handle.join().unwrap(), Err(EvalError::BudgetExceeded));
+}
+
+#[test]
+fn interrupt_from_another_thread() {
+    let evaluator = Evaluator::new();
+    let handle = evaluator.interrupt_handle();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        handle.interrupt();
+    });
+    assert_eq!(
+        evaluator.try_eval(&parse("(while true 1)")),
+        Err(EvalError::Interrupted)
+    );
+}
@@ -0,0 +1,82 @@
+use fundot::evaluator::Evaluator;
+use fundot::object::Object;
+use fundot::types::check;
+
+fn messages(sources: &[&str]) -> Vec<(usize, usize, String)> {
+    check(sources)
+        .unwrap()
+        .into_iter()
+        .map(|error| (error.file, error.form, error.message))
+        .collect()
+}
+
+fn error(file: usize, form: usize, message: &str) -> (usize, usize, String) {
+    (file, form, message.to_string())
+}
+
+#[test]
+fn annotations_are_ignored_when_running() {
+    let evaluator = Evaluator::new();
+    evaluator
+        .eval_str("(set area (fn (w : float h : float) : float (* w h)))")
+        .unwrap();
+    assert_eq!(
+        evaluator.eval_str("(area 2 3)").unwrap(),
+        Object::Integer(6)
+    );
+    assert_eq!(
+        evaluator.eval_str("(set limit : int 10) limit").unwrap(),
+        Object::Integer(10)
+    );
+    assert_eq!(
+        evaluator
+            .eval_str("(let ((name : string \"x\")) name)")
+            .unwrap(),
+        Object::from("x")
+    );
+}
+
+#[test]
+fn mismatches_are_reported() {
+    assert_eq!(
+        messages(&["(set limit : int \"ten\")"]),
+        vec![error(0, 1, "limit: expected int, got string")]
+    );
+    assert_eq!(
+        messages(&["(set f (fn (x : int) : string (+ x 1)))"]),
+        vec![error(0, 1, "return value: expected string, got int")]
+    );
+    assert_eq!(
+        messages(&["(set f (fn (x : int) : int (+ x 1)))\n(f 2)\n(f \"a\")"]),
+        vec![error(0, 3, "argument 1 of f: expected int, got string")]
+    );
+    assert_eq!(
+        messages(&["(let ((n : int \"x\")) n)"]),
+        vec![error(0, 1, "n: expected int, got string")]
+    );
+    assert_eq!(
+        messages(&["(set xs : [int] [1, 2])\n(set ys : [int] [\"a\"])"]),
+        vec![error(0, 2, "ys: expected [int], got [string]")]
+    );
+}
+
+#[test]
+fn files_are_checked_together() {
+    assert_eq!(
+        messages(&["(set f (fn (x : int) : int (+ x 1)))", "(f 1)\n(f true)"]),
+        vec![error(1, 2, "argument 1 of f: expected int, got bool")]
+    );
+}
+
+#[test]
+fn unannotated_code_is_not_reported() {
+    assert_eq!(
+        messages(&["(set f (fn (x) (+ x 1)))\n(f 2)\n(f \"a\")\n(g 3)"]),
+        vec![]
+    );
+}
+
+#[test]
+fn unparsable_sources_are_errors() {
+    assert!(check(&["(set f (fn ("]).is_err());
+}